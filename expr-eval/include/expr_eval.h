@@ -0,0 +1,31 @@
+/* C interface of expr-eval, in the cdylib built with
+ *
+ *     cargo rustc --release --lib --crate-type cdylib --features ffi
+ */
+#ifndef EXPR_EVAL_H
+#define EXPR_EVAL_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define EXPR_EVAL_OK 0
+/* the source doesn't parse or evaluate, or gives no plain number */
+#define EXPR_EVAL_ERROR 1
+/* a null pointer, or source that isn't utf-8 */
+#define EXPR_EVAL_BAD_ARGUMENT 2
+/* a bug in the evaluator */
+#define EXPR_EVAL_PANIC 3
+
+/* Evaluate the statements of src and store the value of the last one in
+ * *out. On failure *out is left alone and, when err isn't NULL, the message
+ * is written to it, cut to err_len - 1 bytes and NUL-terminated. */
+int expr_eval(const char *src, double *out, char *err, size_t err_len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,707 @@
+use alloc::{
+    boxed::Box,
+    collections::BTreeSet,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::fmt::Display;
+
+use num_traits::{One, Signed};
+use serde_json::{json, Value as Json};
+
+use crate::{
+    apply_function, call_node, check_arity, decimal::Decimal, interval::Interval, lookup_function,
+    options::Budget, placeholders, Assoc, Env, EvalOptions, ExprError, Function, Mode, Quantity,
+    Result, Span, Token, Tokenizer, Value, Visitor,
+};
+
+// parse tree of an expression, spans point back into the source for errors
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ast {
+    Num(Value),
+    Var {
+        name: String,
+        span: Span,
+    },
+    Unary {
+        op: Token,
+        operand: Box<Ast>,
+        span: Span,
+    },
+    BinaryOp {
+        op: Token,
+        lhs: Box<Ast>,
+        rhs: Box<Ast>,
+        span: Span,
+    },
+    // explicit ( ), kept so the tree mirrors the source
+    Paren(Box<Ast>),
+    Call {
+        name: String,
+        args: Vec<Ast>,
+        span: Span,
+    },
+    // cond ? then : otherwise, span is the `?`
+    Cond {
+        cond: Box<Ast>,
+        then: Box<Ast>,
+        otherwise: Box<Ast>,
+        span: Span,
+    },
+}
+
+impl Ast {
+    // evaluate with no variables bound
+    pub fn eval(&self) -> Result<Value> {
+        self.eval_with(&Env::new())
+    }
+
+    pub fn eval_with(&self, env: &Env) -> Result<Value> {
+        self.eval_with_options(env, &EvalOptions::default())
+    }
+
+    /// Evaluate with the placeholders `?1`, `?2`, ... bound to `args` in
+    /// order, so one parsed formula is reused like a prepared statement. A
+    /// placeholder past the end of `args` is an unknown variable.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode, Value};
+    ///
+    /// let ast = Expr::new("?1 * ?2 + 3", Mode::Standard).parse().unwrap();
+    /// assert_eq!(Value::Int(9), ast.eval_args(&[Value::Int(2), Value::Int(3)]).unwrap());
+    /// assert_eq!(Value::Int(23), ast.eval_args(&[Value::Int(4), Value::Int(5)]).unwrap());
+    /// ```
+    pub fn eval_args(&self, args: &[Value]) -> Result<Value> {
+        self.eval_with(&placeholders(args))
+    }
+
+    // same, following the options where the plain evaluation would fail and
+    // within their limits
+    pub fn eval_with_options(&self, env: &Env, options: &EvalOptions) -> Result<Value> {
+        self.eval_within(env, options, &mut Budget::new(options))
+    }
+
+    pub(crate) fn eval_within(
+        &self,
+        env: &Env,
+        options: &EvalOptions,
+        budget: &mut Budget,
+    ) -> Result<Value> {
+        let eval = |ast: &Ast, budget: &mut Budget| ast.eval_within(env, options, budget);
+        match self {
+            Ast::Num(n) => Ok(n.clone()),
+            Ast::Var { name, span } => {
+                env.get(name)
+                    .cloned()
+                    .ok_or_else(|| ExprError::UnknownVariable {
+                        name: name.clone(),
+                        span: *span,
+                    })
+            }
+            Ast::Unary { op, operand, span } => {
+                let v = eval(operand, budget)?;
+                budget.spend(*span)?;
+                options.compute_unary(op, v, *span)
+            }
+            Ast::BinaryOp { .. } => self.eval_chain(env, options, budget),
+            Ast::Paren(inner) => eval(inner, budget),
+            Ast::Call { name, args, span } => {
+                let func = lookup_function(name);
+                let user = match (func, env.get(name)) {
+                    (None, Some(Value::Function(f))) => Some(f),
+                    (None, _) => {
+                        return Err(ExprError::UnknownFunction {
+                            name: name.clone(),
+                            span: *span,
+                        })
+                    }
+                    _ => None,
+                };
+                let args = args
+                    .iter()
+                    .map(|arg| eval(arg, budget))
+                    .collect::<Result<_>>()?;
+                budget.spend(*span)?;
+                match (func, user) {
+                    (Some(func), _) => apply_function(func, args, *span),
+                    (None, Some(f)) => f.call(args, env, options, budget, *span),
+                    (None, None) => unreachable!("unknown functions fail above"),
+                }
+            }
+            // only the branch taken is evaluated
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                span,
+            } => {
+                let cond = eval(cond, budget)?.expect_bool(*span)?;
+                budget.spend(*span)?;
+                if cond {
+                    eval(then, budget)
+                } else {
+                    eval(otherwise, budget)
+                }
+            }
+        }
+    }
+
+    // a binary operation and those of its chain, see Ast::chain
+    fn eval_chain(&self, env: &Env, options: &EvalOptions, budget: &mut Budget) -> Result<Value> {
+        let (bottom, chain) = self.chain();
+        let mut l = bottom.eval_within(env, options, budget)?;
+        for (_, op, rhs, span) in chain {
+            l = if op.is_logical() {
+                // the right side only runs when the left doesn't decide
+                let l = l.expect_bool(span)?;
+                budget.spend(span)?;
+                if l == (*op == Token::Or) {
+                    Value::Bool(l)
+                } else {
+                    Value::Bool(rhs.eval_within(env, options, budget)?.expect_bool(span)?)
+                }
+            } else {
+                let r = rhs.eval_within(env, options, budget)?;
+                budget.spend(span)?;
+                options.compute(op, l, r, span)?
+            };
+        }
+        Ok(l)
+    }
+}
+
+impl Ast {
+    /// A copy of the expression with every use of the variable replaced by
+    /// the other expression, so a template formula can be specialized before
+    /// it is evaluated or differentiated. The tree keeps the grouping, the
+    /// printed form gets the parentheses it needs.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode};
+    ///
+    /// let parse = |src| Expr::new(src, Mode::Standard).parse().unwrap();
+    /// let area = parse("pi * r^2").substitute("r", &parse("d / 2"));
+    /// assert_eq!("pi * (d / 2) ^ 2", area.to_string());
+    /// ```
+    pub fn substitute(&self, var: &str, with: &Ast) -> Ast {
+        let sub = |ast: &Ast| Box::new(ast.substitute(var, with));
+        match self {
+            Ast::Var { name, .. } if name == var => with.clone(),
+            Ast::Num(_) | Ast::Var { .. } => self.clone(),
+            Ast::Unary { op, operand, span } => Ast::Unary {
+                op: op.clone(),
+                operand: sub(operand),
+                span: *span,
+            },
+            Ast::BinaryOp { op, lhs, rhs, span } => Ast::BinaryOp {
+                op: op.clone(),
+                lhs: sub(lhs),
+                rhs: sub(rhs),
+                span: *span,
+            },
+            Ast::Paren(inner) => Ast::Paren(sub(inner)),
+            Ast::Call { name, args, span } => Ast::Call {
+                name: name.clone(),
+                args: args.iter().map(|arg| arg.substitute(var, with)).collect(),
+                span: *span,
+            },
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                span,
+            } => Ast::Cond {
+                cond: sub(cond),
+                then: sub(then),
+                otherwise: sub(otherwise),
+                span: *span,
+            },
+        }
+    }
+
+    /// The names of the variables the expression reads, so a caller can
+    /// find the values it needs, or what to recompute when one changes,
+    /// before evaluating it. Both branches of a conditional count.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode};
+    ///
+    /// let ast = Expr::new("rate * (hours + overtime * 1.5)", Mode::Standard).parse().unwrap();
+    /// let names: Vec<_> = ast.variables().into_iter().collect();
+    /// assert_eq!(vec!["hours", "overtime", "rate"], names);
+    /// ```
+    pub fn variables(&self) -> BTreeSet<String> {
+        struct Names(BTreeSet<String>);
+
+        impl Visitor for Names {
+            fn var(&mut self, name: &str, _: Span, _: usize) {
+                self.0.insert(name.to_string());
+            }
+        }
+
+        let mut names = Names(BTreeSet::new());
+        self.walk(&mut names);
+        names.0
+    }
+}
+
+impl Ast {
+    // frames on the deepest way down from this one of the recursion
+    // evaluating, printing or dropping it, which the parsers keep within
+    // ExprLimits::max_depth; a chain counts like one operation however long
+    // it is, see Ast::chain
+    pub(crate) fn height(&self) -> usize {
+        match self {
+            Ast::Num(_) | Ast::Var { .. } => 1,
+            Ast::Unary { operand, .. } => 1 + operand.height(),
+            Ast::Paren(inner) => 1 + inner.height(),
+            Ast::BinaryOp { .. } => {
+                let (mut lhs, chain) = self.chain();
+                let mut height = lhs.height();
+                for (node, op, rhs, _) in chain {
+                    height = binary_height(op, lhs, height, rhs.height());
+                    lhs = node;
+                }
+                height
+            }
+            Ast::Call { args, .. } => 1 + args.iter().map(Ast::height).max().unwrap_or(0),
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                ..
+            } => 1 + cond.height().max(then.height()).max(otherwise.height()),
+        }
+    }
+
+    // the binary operations down the left operands from this one, the bottom
+    // one first, with the operand under them: a chain like 1 + 2 + 3 is
+    // nested on the left as deep as it is long, and walked in a loop over
+    // these, only the right operands are gone into by recursion
+    pub(crate) fn chain(&self) -> (&Ast, Vec<Link<'_>>) {
+        let mut chain = Vec::new();
+        let mut node = self;
+        while let Ast::BinaryOp { op, lhs, rhs, span } = node {
+            chain.push((node, op, &**rhs, *span));
+            node = lhs;
+            if !in_chain(op, lhs) {
+                break;
+            }
+        }
+        chain.reverse();
+        (node, chain)
+    }
+
+    // how tightly a node holds together when printed, atoms never need ( )
+    pub(crate) fn precedence(&self) -> i32 {
+        match self {
+            Ast::BinaryOp { op, .. } => op.precedence(),
+            // the operand of unary minus is parsed at power precedence
+            Ast::Unary { op, .. } => op.prefix_precedence(),
+            Ast::Paren(inner) => inner.precedence(),
+            Ast::Num(n) if is_negative(n) => Token::Power.precedence(),
+            Ast::Num(Value::Ratio(n)) if !n.denom().is_one() => Token::Divide.precedence(),
+            Ast::Cond { .. } => Token::Question.precedence(),
+            Ast::Num(_) | Ast::Var { .. } | Ast::Call { .. } => i32::MAX,
+        }
+    }
+
+    // print a child, wrapped in ( ) when the parser would group it differently
+    fn fmt_child(&self, f: &mut core::fmt::Formatter<'_>, paren: bool) -> core::fmt::Result {
+        if paren {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+// a binary operation in a chain: the node, its operator, right operand and span
+pub(crate) type Link<'a> = (&'a Ast, &'a Token, &'a Ast, Span);
+
+// whether the left operand of op is gone down in the loop of a chain rather
+// than by recursion: that of a left associative operator, printed without
+// ( ) like the 1 + 2 of 1 + 2 + 3
+fn in_chain(op: &Token, lhs: &Ast) -> bool {
+    op.assoc() == Assoc::Left && lhs.precedence() >= op.precedence()
+}
+
+// the height of lhs op rhs from those of its operands, see Ast::height
+pub(crate) fn binary_height(op: &Token, lhs: &Ast, lhs_height: usize, rhs_height: usize) -> usize {
+    if in_chain(op, lhs) {
+        lhs_height.max(1 + rhs_height)
+    } else {
+        1 + lhs_height.max(rhs_height)
+    }
+}
+
+// the tree in place of ast, leaving a number there; the fields of a node
+// can't be moved out of it since Ast has a Drop
+pub(crate) fn take(ast: &mut Ast) -> Ast {
+    core::mem::replace(ast, Ast::Num(Value::Int(0)))
+}
+
+pub(crate) fn is_negative(n: &Value) -> bool {
+    match n {
+        Value::Int(n) => *n < 0,
+        Value::Big(n) => n.is_negative(),
+        Value::Ratio(n) => n.is_negative(),
+        Value::Dec(n) => n.to_f64() < 0.0,
+        Value::Float(n) => n.is_sign_negative(),
+        Value::Quantity(q) => q.value().is_sign_negative(),
+        Value::Bool(_)
+        | Value::Interval(_)
+        | Value::Text(_)
+        | Value::Vector(_)
+        | Value::Function(_) => false,
+    }
+}
+
+// canonical source form, with only the parentheses precedence requires
+impl Display for Ast {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            // {:?} keeps the dot of whole floats, so 2.0 reads back as a float
+            Ast::Num(Value::Float(n)) => write!(f, "{:?}", n),
+            Ast::Num(n) => write!(f, "{}", n),
+            Ast::Var { name, .. } => write!(f, "{}", name),
+            Ast::Unary { op, operand, .. } => {
+                write!(f, "{}", op)?;
+                // a word operator needs a space before its operand
+                if op.to_string().ends_with(char::is_alphanumeric) {
+                    write!(f, " ")?;
+                }
+                operand.fmt_child(f, operand.precedence() < self.precedence())
+            }
+            Ast::BinaryOp { .. } => {
+                let (lhs, chain) = self.chain();
+                let (_, op, ..) = chain[0];
+                let prec = op.precedence();
+                let lhs_paren = lhs.precedence() < prec
+                    || lhs.precedence() == prec && op.assoc() == Assoc::Right;
+                lhs.fmt_child(f, lhs_paren)?;
+                for (_, op, rhs, _) in chain {
+                    let prec = op.precedence();
+                    let rhs_paren = rhs.precedence() < prec
+                        || rhs.precedence() == prec && op.assoc() == Assoc::Left;
+                    write!(f, " {} ", op)?;
+                    rhs.fmt_child(f, rhs_paren)?;
+                }
+                Ok(())
+            }
+            Ast::Paren(inner) => write!(f, "{}", inner),
+            Ast::Call { name, args, .. } => {
+                let args: Vec<String> = args.iter().map(Ast::to_string).collect();
+                match name.as_str() {
+                    "vec" => write!(f, "{{{}}}", args.join(", ")),
+                    _ => write!(f, "{}({})", name, args.join(", ")),
+                }
+            }
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                ..
+            } => {
+                cond.fmt_child(f, cond.precedence() <= self.precedence())?;
+                write!(f, " ? {} : ", then)?;
+                otherwise.fmt_child(f, otherwise.precedence() < self.precedence())
+            }
+        }
+    }
+}
+
+pub(crate) fn invalid(reason: impl Into<String>) -> ExprError {
+    ExprError::InvalidJson {
+        reason: reason.into(),
+    }
+}
+
+fn field<'a>(node: &'a Json, key: &str) -> Result<&'a Json> {
+    node.get(key)
+        .ok_or_else(|| invalid(format!("missing `{}` in {}", key, node)))
+}
+
+fn str_field<'a>(node: &'a Json, key: &str) -> Result<&'a str> {
+    field(node, key)?
+        .as_str()
+        .ok_or_else(|| invalid(format!("`{}` should be a string in {}", key, node)))
+}
+
+// operator token from its symbol, reusing the tokenizer so both agree
+fn operator(symbol: &str) -> Result<Token> {
+    match Tokenizer::new(symbol, Mode::Standard).next() {
+        Some(Ok((token, span)))
+            if (token.is_operator() || token.is_prefix()) && span.len == symbol.len() =>
+        {
+            Ok(token)
+        }
+        _ => Err(invalid(format!("unknown operator `{}`", symbol))),
+    }
+}
+
+fn value_to_json(n: &Value) -> Json {
+    match n {
+        Value::Int(n) => json!({"type": "num", "kind": "int", "value": n}),
+        Value::Float(n) => json!({"type": "num", "kind": "float", "value": n}),
+        Value::Bool(b) => json!({"type": "num", "kind": "bool", "value": b}),
+        Value::Interval(i) => json!({"type": "num", "kind": "interval", "value": [i.lo(), i.hi()]}),
+        Value::Quantity(q) => json!({
+            "type": "num",
+            "kind": "quantity",
+            "value": q.value(),
+            "units": q.units().collect::<Vec<_>>(),
+        }),
+        // exact kinds travel as strings so no digit is lost
+        Value::Big(n) => json!({"type": "num", "kind": "big", "value": n.to_string()}),
+        Value::Text(s) => json!({"type": "num", "kind": "text", "value": s}),
+        Value::Vector(v) => json!({
+            "type": "num",
+            "kind": "vector",
+            "value": v.iter().map(value_to_json).collect::<Vec<_>>(),
+        }),
+        Value::Ratio(n) => json!({"type": "num", "kind": "ratio", "value": n.to_string()}),
+        Value::Function(f) => json!({
+            "type": "num",
+            "kind": "function",
+            "value": f.body.to_json_value(),
+            "name": f.name,
+            "params": f.params,
+        }),
+        Value::Dec(n) => json!({
+            "type": "num",
+            "kind": "decimal",
+            "value": n.to_string(),
+            "max_scale": n.max_scale(),
+        }),
+    }
+}
+
+fn value_from_json(node: &Json) -> Result<Value> {
+    let value = field(node, "value")?;
+    let bad_value = || invalid(format!("bad number in {}", node));
+    let text = || value.as_str().ok_or_else(bad_value);
+    match str_field(node, "kind")? {
+        "int" => value.as_i64().map(Value::Int).ok_or_else(bad_value),
+        "float" => value.as_f64().map(Value::Float).ok_or_else(bad_value),
+        "bool" => value.as_bool().map(Value::Bool).ok_or_else(bad_value),
+        "interval" => match value.as_array().map(|v| v.as_slice()) {
+            Some([lo, hi]) => lo
+                .as_f64()
+                .zip(hi.as_f64())
+                .and_then(|(lo, hi)| Interval::new(lo, hi))
+                .map(Value::Interval)
+                .ok_or_else(bad_value),
+            _ => Err(bad_value()),
+        },
+        "quantity" => {
+            let units = field(node, "units")?.as_array().ok_or_else(bad_value)?;
+            let units = (units.iter())
+                .map(|unit| match unit.as_array().map(|u| u.as_slice()) {
+                    Some([name, exp]) => name
+                        .as_str()
+                        .zip(exp.as_i64().and_then(|e| i32::try_from(e).ok())),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(bad_value)?;
+            value
+                .as_f64()
+                .and_then(|v| Quantity::new(v, &units))
+                .map(Value::Quantity)
+                .ok_or_else(bad_value)
+        }
+        "big" => text()?.parse().map(Value::Big).map_err(|_| bad_value()),
+        "ratio" => text()?.parse().map(Value::Ratio).map_err(|_| bad_value()),
+        "text" => text().map(|s| Value::Text(s.to_string())),
+        "vector" => match value.as_array() {
+            Some(elems) if !elems.is_empty() => elems
+                .iter()
+                .map(value_from_json)
+                .collect::<Result<_>>()
+                .map(Value::Vector),
+            _ => Err(bad_value()),
+        },
+        "decimal" => {
+            let max_scale = field(node, "max_scale")?
+                .as_u64()
+                .and_then(|n| u32::try_from(n).ok())
+                .ok_or_else(bad_value)?;
+            Decimal::parse(text()?, max_scale)
+                .map(Value::Dec)
+                .ok_or_else(bad_value)
+        }
+        "function" => {
+            let params = (field(node, "params")?.as_array())
+                .and_then(|params| {
+                    params
+                        .iter()
+                        .map(|p| p.as_str().map(String::from))
+                        .collect()
+                })
+                .ok_or_else(bad_value)?;
+            Ok(Value::Function(Arc::new(Function {
+                name: str_field(node, "name")?.to_string(),
+                params,
+                body: Ast::from_json_value(value, true)?,
+            })))
+        }
+        kind => Err(invalid(format!("unknown number kind `{}`", kind))),
+    }
+}
+
+// serialized forms for other tools, spans are not kept
+impl Ast {
+    pub fn to_json(&self) -> String {
+        self.to_json_value().to_string()
+    }
+
+    fn to_json_value(&self) -> Json {
+        match self {
+            Ast::Num(n) => value_to_json(n),
+            Ast::Var { name, .. } => json!({"type": "var", "name": name}),
+            Ast::Unary { op, operand, .. } => json!({
+                "type": "unary",
+                "op": op.to_string(),
+                "operand": operand.to_json_value(),
+            }),
+            Ast::BinaryOp { op, lhs, rhs, .. } => json!({
+                "type": "binary",
+                "op": op.to_string(),
+                "lhs": lhs.to_json_value(),
+                "rhs": rhs.to_json_value(),
+            }),
+            Ast::Paren(inner) => json!({"type": "paren", "inner": inner.to_json_value()}),
+            Ast::Call { name, args, .. } => json!({
+                "type": "call",
+                "name": name,
+                "args": args.iter().map(Ast::to_json_value).collect::<Vec<_>>(),
+            }),
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                ..
+            } => json!({
+                "type": "cond",
+                "cond": cond.to_json_value(),
+                "then": then.to_json_value(),
+                "else": otherwise.to_json_value(),
+            }),
+        }
+    }
+
+    // rebuild a tree from to_json output
+    pub fn from_json(src: &str) -> Result<Ast> {
+        let node = serde_json::from_str(src).map_err(|e| invalid(e.to_string()))?;
+        Self::from_json_value(&node, false)
+    }
+
+    // user_calls lets calls name functions that aren't builtins, the user
+    // functions the body of one may call
+    fn from_json_value(node: &Json, user_calls: bool) -> Result<Ast> {
+        let child = |key| Self::from_json_value(field(node, key)?, user_calls).map(Box::new);
+        let span = Span::default();
+        match str_field(node, "type")? {
+            "num" => value_from_json(node).map(Ast::Num),
+            "var" => Ok(Ast::Var {
+                name: str_field(node, "name")?.to_string(),
+                span,
+            }),
+            "unary" => match operator(str_field(node, "op")?)? {
+                op if op.is_prefix() => Ok(Ast::Unary {
+                    op,
+                    operand: child("operand")?,
+                    span,
+                }),
+                op => Err(invalid(format!("`{}` is not a unary operator", op))),
+            },
+            "binary" => match operator(str_field(node, "op")?)? {
+                op if !op.is_operator() => {
+                    Err(invalid(format!("`{}` is not a binary operator", op)))
+                }
+                op => Ok(Ast::BinaryOp {
+                    op,
+                    lhs: child("lhs")?,
+                    rhs: child("rhs")?,
+                    span,
+                }),
+            },
+            "paren" => Ok(Ast::Paren(child("inner")?)),
+            "call" => {
+                let name = str_field(node, "name")?.to_string();
+                let func = lookup_function(&name);
+                if func.is_none() && !user_calls {
+                    return Err(ExprError::UnknownFunction { name, span });
+                }
+                // trees written before calls took several arguments have one "arg"
+                let args = match node.get("arg") {
+                    Some(_) => vec![*child("arg")?],
+                    None => (field(node, "args")?.as_array())
+                        .ok_or_else(|| invalid(format!("bad arguments in {}", node)))?
+                        .iter()
+                        .map(|arg| Self::from_json_value(arg, user_calls))
+                        .collect::<Result<_>>()?,
+                };
+                let Some(func) = func else {
+                    return Ok(Ast::Call { name, args, span });
+                };
+                check_arity(&name, func, args.len(), span)?;
+                Ok(call_node(func, name, args, span))
+            }
+            "cond" => Ok(Ast::Cond {
+                cond: child("cond")?,
+                then: child("then")?,
+                otherwise: child("else")?,
+                span,
+            }),
+            kind => Err(invalid(format!("unknown node type `{}`", kind))),
+        }
+    }
+
+    // lisp style prefix form, (+ 1 (* 2 3))
+    pub fn to_sexpr(&self) -> String {
+        match self {
+            Ast::Num(Value::Float(n)) => format!("{:?}", n),
+            Ast::Num(n) => n.to_string(),
+            Ast::Var { name, .. } => name.clone(),
+            Ast::Unary { op, operand, .. } => format!("({} {})", op, operand.to_sexpr()),
+            Ast::BinaryOp { op, lhs, rhs, .. } => {
+                format!("({} {} {})", op, lhs.to_sexpr(), rhs.to_sexpr())
+            }
+            Ast::Paren(inner) => inner.to_sexpr(),
+            Ast::Call { name, args, .. } => {
+                let args: Vec<String> = args.iter().map(Ast::to_sexpr).collect();
+                format!("({})", [vec![name.clone()], args].concat().join(" "))
+            }
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                ..
+            } => format!(
+                "(if {} {} {})",
+                cond.to_sexpr(),
+                then.to_sexpr(),
+                otherwise.to_sexpr()
+            ),
+        }
+    }
+}
+
+// the left operands of a chain are taken out and dropped one at a time, see
+// Ast::chain
+impl Drop for Ast {
+    fn drop(&mut self) {
+        let Ast::BinaryOp { lhs, .. } = self else {
+            return;
+        };
+        let mut next = take(lhs);
+        while let Ast::BinaryOp { lhs, .. } = &mut next {
+            next = take(lhs);
+        }
+    }
+}
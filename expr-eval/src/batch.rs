@@ -0,0 +1,87 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+
+use num_traits::ToPrimitive;
+use serde_json::{json, Map as JsonMap, Value as Json};
+
+use crate::{ast::invalid, Env, Expr, Mode, Result, Value};
+
+/// Evaluate a JSON array of `{"expr": ..., "vars": {...}}` objects, giving a
+/// JSON array with a `{"value": ...}` or `{"error": ...}` for each in order.
+/// Variables are numbers, booleans, or expressions in strings; a failing
+/// entry doesn't stop the ones after it.
+///
+/// ```
+/// use expr_eval::{eval_batch, Mode};
+///
+/// let input = r#"[{"expr": "x * 2", "vars": {"x": 21}}, {"expr": "1 / 0"}]"#;
+/// assert_eq!(
+///     r#"[{"value":42},{"error":"division by zero at offset 2"}]"#,
+///     eval_batch(input, Mode::Standard).unwrap()
+/// );
+/// ```
+pub fn eval_batch(input: &str, mode: Mode) -> Result<String> {
+    let input: Json = serde_json::from_str(input).map_err(|e| invalid(e.to_string()))?;
+    let entries = input
+        .as_array()
+        .ok_or_else(|| invalid("expected an array of expressions"))?;
+    let results = entries.iter().map(|entry| match eval_entry(entry, mode) {
+        Ok(value) => json!({ "value": value_to_json(&value) }),
+        Err(err) => json!({ "error": err }),
+    });
+    Ok(Json::Array(results.collect()).to_string())
+}
+
+// one {"expr": ..., "vars": ...} object, errors already in their message
+fn eval_entry(entry: &Json, mode: Mode) -> core::result::Result<Value, String> {
+    let Some(src) = entry.get("expr").and_then(Json::as_str) else {
+        return Err(format!("expected a string `expr` in {}", entry));
+    };
+    let mut env = match entry.get("vars") {
+        None => Env::new(),
+        Some(Json::Object(vars)) => bind_vars(vars, mode)?,
+        Some(vars) => return Err(format!("expected an object of variables, found {}", vars)),
+    };
+    Expr::new(src, mode)
+        .parse_script()
+        .and_then(|script| script.eval(&mut env))
+        .map_err(|err| err.to_string())
+}
+
+// numbers are read as if written in an expression, so they take the mode's
+// kind of number like literals do
+fn bind_vars(vars: &JsonMap<String, Json>, mode: Mode) -> core::result::Result<Env, String> {
+    let mut env = Env::new();
+    for (name, value) in vars {
+        let value = match value {
+            Json::Bool(b) => Ok(Value::Bool(*b)),
+            Json::Number(n) => eval_var(&n.to_string(), mode),
+            Json::String(src) => eval_var(src, mode),
+            _ => return Err(format!("`{}` should be a number, a bool or a string", name)),
+        };
+        let value = value.map_err(|err| format!("in the value of `{}`: {}", name, err))?;
+        env.insert(name.clone(), value);
+    }
+    Ok(env)
+}
+
+fn eval_var(src: &str, mode: Mode) -> Result<Value> {
+    Expr::new(src, mode).parse()?.eval_with(&Env::new())
+}
+
+// numbers json can hold exactly stay numbers, the others are written out
+fn value_to_json(value: &Value) -> Json {
+    match value {
+        Value::Int(n) => json!(n),
+        Value::Float(n) => json!(n),
+        Value::Bool(b) => json!(b),
+        Value::Big(n) if n.to_i64().is_some() => json!(n.to_i64()),
+        Value::Ratio(n) if n.is_integer() && n.numer().to_i64().is_some() => {
+            json!(n.numer().to_i64())
+        }
+        Value::Vector(v) => Json::Array(v.iter().map(value_to_json).collect()),
+        _ => json!(value.to_string()),
+    }
+}
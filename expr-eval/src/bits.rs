@@ -0,0 +1,72 @@
+use alloc::{string::ToString, vec::Vec};
+
+use num_traits::ToPrimitive;
+
+use crate::{ExprError, Result, Span, Value};
+
+// the 64 bits of an integer from i64::MIN to u64::MAX, negative ones in two's
+// complement
+fn word(v: &Value, span: Span) -> Result<u64> {
+    let n = v.expect_integer(span)?;
+    match (n.to_i64(), n.to_u64()) {
+        (Some(n), _) => Ok(n as u64),
+        (_, Some(n)) => Ok(n),
+        _ => Err(ExprError::Type {
+            expected: "a 64-bit integer".into(),
+            found: v.to_string(),
+            operation: None,
+            span,
+        }),
+    }
+}
+
+// bits, or a count of them, in the kind of the argument: the i64 they are
+// in standard mode, unsigned for other kinds
+fn from_word(bits: u64, like: &Value) -> Value {
+    match like {
+        Value::Int(_) => Value::Int(bits as i64),
+        _ => Value::Big(bits.into()),
+    }
+}
+
+pub(crate) fn popcount(args: Vec<Value>, span: Span) -> Result<Value> {
+    Ok(from_word(
+        word(&args[0], span)?.count_ones().into(),
+        &args[0],
+    ))
+}
+
+// leading and trailing zeros of the 64 bits, 64 for 0
+pub(crate) fn clz(args: Vec<Value>, span: Span) -> Result<Value> {
+    Ok(from_word(
+        word(&args[0], span)?.leading_zeros().into(),
+        &args[0],
+    ))
+}
+
+pub(crate) fn ctz(args: Vec<Value>, span: Span) -> Result<Value> {
+    Ok(from_word(
+        word(&args[0], span)?.trailing_zeros().into(),
+        &args[0],
+    ))
+}
+
+// rotl(x, n) turns the bits n places left, what falls off coming back on the
+// right; any n works, taken modulo 64
+pub(crate) fn rotl(args: Vec<Value>, span: Span) -> Result<Value> {
+    let (bits, n) = rotation(&args, span)?;
+    Ok(from_word(bits.rotate_left(n), &args[0]))
+}
+
+pub(crate) fn rotr(args: Vec<Value>, span: Span) -> Result<Value> {
+    let (bits, n) = rotation(&args, span)?;
+    Ok(from_word(bits.rotate_right(n), &args[0]))
+}
+
+fn rotation(args: &[Value], span: Span) -> Result<(u64, u32)> {
+    let n = (args[1].expect_integer(span)? % 64u32)
+        .to_i32()
+        .expect("below 64");
+    let n = n.rem_euclid(64) as u32;
+    Ok((word(&args[0], span)?, n))
+}
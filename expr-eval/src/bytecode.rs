@@ -0,0 +1,340 @@
+use alloc::{format, string::String, vec, vec::Vec};
+
+use crate::{
+    function_at, function_index, function_name, vm::CompiledExpr, vm::Instr, Ast, ExprError,
+    Result, Span,
+};
+
+// every serialized expression starts with the magic bytes and the version of
+// the format, bumped whenever the layout below changes
+const MAGIC: &[u8; 4] = b"EXPR";
+const VERSION: u8 = 1;
+
+// after the header, with every count and number a little endian u32:
+//
+//   names: count, then each one as its length and utf-8 bytes
+//   constants: count, then each one as the json of its number node
+//   code: count, then each instruction as its opcode, its operands and the
+//   offset and length of its span
+//
+// a call names its function rather than numbering it, so bytes written
+// before a native function was registered still call the right one
+
+fn invalid(reason: impl Into<String>) -> ExprError {
+    ExprError::InvalidBytecode {
+        reason: reason.into(),
+    }
+}
+
+impl CompiledExpr {
+    /// The compiled expression as bytes [`CompiledExpr::from_bytes`] reads
+    /// back, to store a formula and evaluate it later without parsing it
+    /// again. Code using an operator registered with
+    /// [`Operators`](crate::Operators) can't be written, its function
+    /// being in no bytes.
+    ///
+    /// ```
+    /// use expr_eval::{CompiledExpr, Expr, Mode, Value};
+    ///
+    /// let ast = Expr::new("max(2, 3) ^ 2", Mode::Standard).parse().unwrap();
+    /// let bytes = CompiledExpr::new(&ast).to_bytes().unwrap();
+    /// let code = CompiledExpr::from_bytes(&bytes).unwrap();
+    /// assert_eq!(Value::Int(9), code.eval().unwrap());
+    /// ```
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        put_len(&mut out, self.names.len());
+        for name in &self.names {
+            put_str(&mut out, name);
+        }
+        put_len(&mut out, self.constants.len());
+        for n in &self.constants {
+            put_str(&mut out, &Ast::Num(n.clone()).to_json());
+        }
+        put_len(&mut out, self.code.len());
+        for (instr, span) in self.code.iter().zip(&self.spans) {
+            match *instr {
+                Instr::Call(i, n) => {
+                    out.push(CALL);
+                    put_str(&mut out, &function_name(i));
+                    put_len(&mut out, n);
+                }
+                Instr::CallUser(i, n) => {
+                    out.push(CALL_USER);
+                    put_len(&mut out, i);
+                    put_len(&mut out, n);
+                }
+                Instr::Custom(i) => {
+                    return Err(ExprError::Unsupported {
+                        what: format!("saving the operator `{}`", self.operators[i]),
+                        span: *span,
+                    })
+                }
+                instr => {
+                    let (op, operand) = opcode(instr);
+                    out.push(op);
+                    if let Some(operand) = operand {
+                        put_len(&mut out, operand);
+                    }
+                }
+            }
+            put_len(&mut out, span.offset);
+            put_len(&mut out, span.len);
+        }
+        Ok(out)
+    }
+
+    /// Read bytes [`CompiledExpr::to_bytes`] wrote, with an InvalidBytecode
+    /// error for ones of another version, or that are cut short or don't
+    /// describe code that can run: every index and jump target is checked
+    /// and the stack each instruction needs worked out before anything runs.
+    pub fn from_bytes(bytes: &[u8]) -> Result<CompiledExpr> {
+        let mut r = Reader { bytes, pos: 0 };
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not compiled expression bytes"));
+        }
+        let version = r.u8()?;
+        if version != VERSION {
+            return Err(invalid(format!(
+                "version {}, expected {}",
+                version, VERSION
+            )));
+        }
+        let names = (0..r.len()?).map(|_| r.string()).collect::<Result<_>>()?;
+        let mut constants = Vec::new();
+        for _ in 0..r.len()? {
+            match &Ast::from_json(&r.string()?) {
+                Ok(Ast::Num(n)) => constants.push(n.clone()),
+                _ => return Err(invalid("a constant that is not a number")),
+            }
+        }
+        let mut code = Vec::new();
+        let mut spans = Vec::new();
+        for _ in 0..r.len()? {
+            let op = r.u8()?;
+            let instr = match op {
+                CALL => {
+                    let name = r.string()?;
+                    let i = function_index(&name)
+                        .ok_or_else(|| invalid(format!("unknown function `{}`", name)))?;
+                    Instr::Call(i, r.len()?)
+                }
+                CALL_USER => Instr::CallUser(r.len()?, r.len()?),
+                _ => instr(op, &mut r)?,
+            };
+            code.push(instr);
+            spans.push(Span::new(r.len()?, r.len()?));
+        }
+        if r.pos != bytes.len() {
+            return Err(invalid("bytes after the code"));
+        }
+        let mut compiled = CompiledExpr {
+            code,
+            spans,
+            constants,
+            names,
+            operators: Vec::new(),
+            max_stack: 0,
+        };
+        compiled.max_stack = compiled.verify()?;
+        Ok(compiled)
+    }
+
+    // the deepest the stack gets, after checking that every instruction finds
+    // the values it takes there and the code leaves one; jumps only go
+    // forward, so one pass sees every way into an instruction before it
+    fn verify(&self) -> Result<usize> {
+        let len = self.code.len();
+        // stack size on the way into each instruction, and past the end
+        let mut depths: Vec<Option<usize>> = vec![None; len + 1];
+        depths[0] = Some(0);
+        let mut deepest = 0;
+        for pc in 0..len {
+            let Some(depth) = depths[pc] else {
+                continue;
+            };
+            let mut reach = |target: usize, depth: usize| -> Result<()> {
+                if target <= pc || target > len {
+                    return Err(invalid(format!("jump from {} to {}", pc, target)));
+                }
+                match depths[target] {
+                    Some(d) if d != depth => Err(invalid(format!(
+                        "stack of {} and {} values at {}",
+                        d, depth, target
+                    ))),
+                    _ => {
+                        depths[target] = Some(depth);
+                        Ok(())
+                    }
+                }
+            };
+            let (takes, after) = match self.code[pc] {
+                Instr::Const(i) if i >= self.constants.len() => {
+                    return Err(invalid(format!("no constant {}", i)))
+                }
+                Instr::Load(i) if i >= self.names.len() => {
+                    return Err(invalid(format!("no variable {}", i)))
+                }
+                Instr::CallUser(i, _) if i >= self.names.len() => {
+                    return Err(invalid(format!("no function {}", i)))
+                }
+                Instr::Const(_) | Instr::Load(_) => (0, depth + 1),
+                Instr::Call(i, n) => {
+                    let arity = function_at(i).arity();
+                    if !arity.accepts(n) {
+                        return Err(invalid(format!("a call with {} arguments", n)));
+                    }
+                    (n, depth + 1 - n.min(depth))
+                }
+                // the arity of a user function is only known when it runs
+                Instr::CallUser(_, n) => (n, depth + 1 - n.min(depth)),
+                Instr::Custom(_) => return Err(invalid("a registered operator")),
+                Instr::JumpIfFalse(target) | Instr::JumpIfTrue(target) => {
+                    reach(target, depth)?;
+                    (1, depth.saturating_sub(1))
+                }
+                Instr::BranchIfFalse(target) => {
+                    reach(target, depth.saturating_sub(1))?;
+                    (1, depth.saturating_sub(1))
+                }
+                Instr::Jump(target) => {
+                    reach(target, depth)?;
+                    continue;
+                }
+                Instr::Neg | Instr::Not | Instr::BitNot | Instr::CheckBool => (1, depth),
+                _ => (2, depth.saturating_sub(1)),
+            };
+            if depth < takes {
+                return Err(invalid(format!("too few values on the stack at {}", pc)));
+            }
+            reach(pc + 1, after)?;
+            deepest = deepest.max(after);
+        }
+        match depths[len] {
+            Some(1) => Ok(deepest),
+            _ => Err(invalid("code that doesn't leave one value")),
+        }
+    }
+}
+
+const CALL: u8 = 0xff;
+// then the index of the function's name among the names, and the count of
+// arguments
+const CALL_USER: u8 = 0xfe;
+
+// the opcode of an instruction and its one operand, for all but the calls
+// and Custom
+fn opcode(instr: Instr) -> (u8, Option<usize>) {
+    match instr {
+        Instr::Const(i) => (0, Some(i)),
+        Instr::Load(i) => (1, Some(i)),
+        Instr::Neg => (2, None),
+        Instr::Not => (3, None),
+        Instr::BitNot => (4, None),
+        Instr::Add => (5, None),
+        Instr::Sub => (6, None),
+        Instr::Mul => (7, None),
+        Instr::Div => (8, None),
+        Instr::Pow => (9, None),
+        Instr::FloorDiv => (10, None),
+        Instr::Mod => (11, None),
+        Instr::Eq => (12, None),
+        Instr::Ne => (13, None),
+        Instr::Lt => (14, None),
+        Instr::Le => (15, None),
+        Instr::Gt => (16, None),
+        Instr::Ge => (17, None),
+        Instr::BitAnd => (18, None),
+        Instr::BitOr => (19, None),
+        Instr::BitXor => (20, None),
+        Instr::Shl => (21, None),
+        Instr::Shr => (22, None),
+        Instr::JumpIfFalse(target) => (23, Some(target)),
+        Instr::JumpIfTrue(target) => (24, Some(target)),
+        Instr::CheckBool => (25, None),
+        Instr::BranchIfFalse(target) => (26, Some(target)),
+        Instr::Jump(target) => (27, Some(target)),
+        Instr::Call(..) | Instr::CallUser(..) | Instr::Custom(_) => {
+            unreachable!("written by to_bytes itself")
+        }
+    }
+}
+
+// the instruction of an opcode, reading its operand
+fn instr(op: u8, r: &mut Reader) -> Result<Instr> {
+    Ok(match op {
+        0 => Instr::Const(r.len()?),
+        1 => Instr::Load(r.len()?),
+        2 => Instr::Neg,
+        3 => Instr::Not,
+        4 => Instr::BitNot,
+        5 => Instr::Add,
+        6 => Instr::Sub,
+        7 => Instr::Mul,
+        8 => Instr::Div,
+        9 => Instr::Pow,
+        10 => Instr::FloorDiv,
+        11 => Instr::Mod,
+        12 => Instr::Eq,
+        13 => Instr::Ne,
+        14 => Instr::Lt,
+        15 => Instr::Le,
+        16 => Instr::Gt,
+        17 => Instr::Ge,
+        18 => Instr::BitAnd,
+        19 => Instr::BitOr,
+        20 => Instr::BitXor,
+        21 => Instr::Shl,
+        22 => Instr::Shr,
+        23 => Instr::JumpIfFalse(r.len()?),
+        24 => Instr::JumpIfTrue(r.len()?),
+        25 => Instr::CheckBool,
+        26 => Instr::BranchIfFalse(r.len()?),
+        27 => Instr::Jump(r.len()?),
+        _ => return Err(invalid(format!("unknown opcode {}", op))),
+    })
+}
+
+fn put_len(out: &mut Vec<u8>, n: usize) {
+    let n = u32::try_from(n).expect("compiled code is smaller than 4 GiB");
+    out.extend(n.to_le_bytes());
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_len(out, s.len());
+    out.extend(s.as_bytes());
+}
+
+// reads the bytes front to back, failing where they end too soon
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len());
+        let end = end.ok_or_else(|| invalid("bytes cut short"))?;
+        let taken = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        let bytes = self.take(4)?.try_into().expect("four bytes");
+        Ok(u32::from_le_bytes(bytes) as usize)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let n = self.len()?;
+        String::from_utf8(self.take(n)?.to_vec()).map_err(|_| invalid("a name that isn't utf-8"))
+    }
+}
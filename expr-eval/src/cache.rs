@@ -0,0 +1,75 @@
+use alloc::{string::String, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{PoisonError, RwLock},
+};
+
+use crate::{vm::CompiledExpr, Env, Expr, Mode, Result, Value};
+
+/// Compiled expressions by their source, for evaluating the same formulas
+/// over and over without parsing them again. It can be shared between
+/// threads, a formula compiled by one of them being there for all; sources
+/// that don't parse are not kept.
+///
+/// ```
+/// use expr_eval::{Env, ExprCache, Mode, Value};
+///
+/// let cache = ExprCache::new(Mode::Standard);
+/// for x in 1..=3 {
+///     let env = Env::from([("x".to_string(), Value::Int(x))]);
+///     assert_eq!(Value::Int(x * 2), cache.eval("x * 2", &env).unwrap());
+/// }
+/// assert_eq!(1, cache.len());
+/// ```
+#[derive(Debug)]
+pub struct ExprCache {
+    mode: Mode,
+    compiled: RwLock<HashMap<String, Arc<CompiledExpr>>>,
+}
+
+impl ExprCache {
+    pub fn new(mode: Mode) -> Self {
+        ExprCache {
+            mode,
+            compiled: RwLock::new(HashMap::new()),
+        }
+    }
+
+    // src compiled, only the first time it is asked for; two threads missing
+    // at once may both compile it, the first one to finish is kept
+    pub fn get(&self, src: &str) -> Result<Arc<CompiledExpr>> {
+        let compiled = self.compiled.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some(code) = compiled.get(src) {
+            return Ok(code.clone());
+        }
+        drop(compiled);
+        let code = Arc::new(CompiledExpr::new(&Expr::new(src, self.mode).parse()?));
+        let mut compiled = self
+            .compiled
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        Ok(compiled.entry(src.into()).or_insert(code).clone())
+    }
+
+    pub fn eval(&self, src: &str, env: &Env) -> Result<Value> {
+        self.get(src)?.eval_with(env)
+    }
+
+    pub fn len(&self) -> usize {
+        self.compiled
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.compiled
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
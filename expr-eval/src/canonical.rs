@@ -0,0 +1,349 @@
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use core::cmp::Reverse;
+
+use num_traits::ToPrimitive;
+
+use crate::{ast::is_negative, Ast, Span, Token, Value};
+
+// most terms a product or power is multiplied out to, past that it is left
+// as written
+const MAX_TERMS: usize = 64;
+
+// coefficient times factors raised to their exponents, 3 * x^2 * y; factors
+// are sorted by their text and no exponent is zero
+#[derive(Clone)]
+struct Term {
+    coeff: Value,
+    factors: Vec<(Ast, i32)>,
+}
+
+impl Term {
+    // the same for like terms, whatever their coefficients
+    fn key(&self) -> String {
+        let factors: Vec<String> = (self.factors.iter())
+            .map(|(base, exp)| format!("{}^{}", base, exp))
+            .collect();
+        factors.join(" * ")
+    }
+
+    fn degree(&self) -> i32 {
+        self.factors.iter().map(|(_, exp)| exp).sum()
+    }
+}
+
+type Sum = Vec<Term>;
+
+fn constant(n: Value) -> Sum {
+    vec![Term {
+        coeff: n,
+        factors: Vec::new(),
+    }]
+}
+
+// something the sum can't look into, ordered inside
+fn atom(ast: &Ast) -> Sum {
+    vec![Term {
+        coeff: Value::Int(1),
+        factors: vec![(ordered(ast), 1)],
+    }]
+}
+
+// kinds of numbers a coefficient can be
+fn is_coefficient(n: &Value) -> bool {
+    matches!(
+        n,
+        Value::Int(_) | Value::Big(_) | Value::Ratio(_) | Value::Dec(_) | Value::Float(_)
+    )
+}
+
+impl Ast {
+    /// A canonical form of the expression: sums and products flattened and
+    /// multiplied out, like terms combined and the terms sorted, highest
+    /// power first. Expressions that differ only in order or grouping give
+    /// the same form.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode};
+    ///
+    /// let canonical = |src| Expr::new(src, Mode::Standard).parse().unwrap().canonicalize();
+    /// assert_eq!("3 * x + 3", canonical("x*2 + 3 + x").to_string());
+    /// assert_eq!("x ^ 2 + 2 * x + 1", canonical("(x + 1)^2").to_string());
+    /// ```
+    pub fn canonicalize(&self) -> Ast {
+        let ast = self.simplify();
+        match to_sum(&ast) {
+            Some(sum) => from_sum(sum),
+            None => ordered(&ast),
+        }
+    }
+}
+
+// the sum of terms ast is, None when a coefficient can't be worked out, like
+// one that overflows
+fn to_sum(ast: &Ast) -> Option<Sum> {
+    let span = Span::default();
+    match ast {
+        Ast::Num(n) if is_coefficient(n) => Some(constant(n.clone())),
+        Ast::Paren(inner) => to_sum(inner),
+        Ast::Unary {
+            op: Token::Minus,
+            operand,
+            ..
+        } => negate(to_sum(operand)?),
+        Ast::BinaryOp { op, lhs, rhs, .. } => match op {
+            Token::Plus => combine([to_sum(lhs)?, to_sum(rhs)?].concat()),
+            Token::Minus => combine([to_sum(lhs)?, negate(to_sum(rhs)?)?].concat()),
+            Token::Multiply => {
+                Some(multiply(&to_sum(lhs)?, &to_sum(rhs)?)?.unwrap_or_else(|| atom(ast)))
+            }
+            // by one term, multiplying by its reciprocal
+            Token::Divide => match to_sum(rhs)?.as_slice() {
+                [term] => {
+                    let reciprocal = Term {
+                        coeff: Token::Divide
+                            .compute(Value::Int(1), term.coeff.clone(), span)
+                            .ok()?,
+                        factors: (term.factors.iter())
+                            .map(|(base, exp)| (base.clone(), -exp))
+                            .collect(),
+                    };
+                    Some(multiply(&to_sum(lhs)?, &[reciprocal])?.unwrap_or_else(|| atom(ast)))
+                }
+                _ => Some(atom(ast)),
+            },
+            Token::Power => match &**rhs {
+                Ast::Num(n) => match n.to_integer().and_then(|n| n.to_i32()) {
+                    Some(n) => Some(power(to_sum(lhs)?, n)?.unwrap_or_else(|| atom(ast))),
+                    None => Some(atom(ast)),
+                },
+                _ => Some(atom(ast)),
+            },
+            _ => Some(atom(ast)),
+        },
+        _ => Some(atom(ast)),
+    }
+}
+
+fn negate(sum: Sum) -> Option<Sum> {
+    (sum.into_iter())
+        .map(|term| {
+            Some(Term {
+                coeff: Token::Minus
+                    .compute_unary(term.coeff, Span::default())
+                    .ok()?,
+                factors: term.factors,
+            })
+        })
+        .collect()
+}
+
+// like terms added up, the ones that cancel dropped
+fn combine(sum: Sum) -> Option<Sum> {
+    let mut combined: Vec<(String, Term)> = Vec::new();
+    for term in sum {
+        let key = term.key();
+        match combined.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, like)) => {
+                like.coeff = Token::Plus
+                    .compute(like.coeff.clone(), term.coeff, Span::default())
+                    .ok()?
+            }
+            None => combined.push((key, term)),
+        }
+    }
+    Some(
+        (combined.into_iter())
+            .map(|(_, term)| term)
+            .filter(|term| term.coeff.as_f64() != 0.0)
+            .collect(),
+    )
+}
+
+// every term of one times every term of the other; Some(None) when that is
+// more terms than MAX_TERMS
+fn multiply(a: &[Term], b: &[Term]) -> Option<Option<Sum>> {
+    if a.len() * b.len() > MAX_TERMS {
+        return Some(None);
+    }
+    let mut product = Vec::with_capacity(a.len() * b.len());
+    for x in a {
+        for y in b {
+            let coeff = Token::Multiply
+                .compute(x.coeff.clone(), y.coeff.clone(), Span::default())
+                .ok()?;
+            let mut factors = x.factors.clone();
+            for (base, exp) in &y.factors {
+                let text = base.to_string();
+                match factors.iter_mut().find(|(b, _)| b.to_string() == text) {
+                    Some((_, e)) => *e += exp,
+                    None => factors.push((base.clone(), *exp)),
+                }
+            }
+            factors.retain(|(_, exp)| *exp != 0);
+            factors.sort_by_cached_key(|(base, _)| base.to_string());
+            product.push(Term { coeff, factors });
+        }
+    }
+    combine(product).map(Some)
+}
+
+// one term to any integer power, a longer sum multiplied out n times
+fn power(sum: Sum, n: i32) -> Option<Option<Sum>> {
+    if let [term] = sum.as_slice() {
+        let coeff = Token::Power
+            .compute(term.coeff.clone(), Value::Int(n.into()), Span::default())
+            .ok()?;
+        let factors = (term.factors.iter())
+            .map(|(base, exp)| (base.clone(), exp * n))
+            .filter(|(_, exp)| *exp != 0)
+            .collect();
+        return Some(Some(vec![Term { coeff, factors }]));
+    }
+    if n < 0 || n as usize > MAX_TERMS {
+        return Some(None);
+    }
+    let mut result = constant(Value::Int(1));
+    for _ in 0..n {
+        match multiply(&result, &sum)? {
+            Some(product) => result = product,
+            None => return Some(None),
+        }
+    }
+    Some(Some(result))
+}
+
+// the sum written out, the highest power first, x^2 before x * y, and the
+// constant after the terms with variables
+fn from_sum(mut sum: Sum) -> Ast {
+    sum.sort_by_cached_key(|term| {
+        let powers: Vec<(String, Reverse<i32>)> = (term.factors.iter())
+            .map(|(base, exp)| (base.to_string(), Reverse(*exp)))
+            .collect();
+        (-term.degree(), powers)
+    });
+    let mut terms = sum.into_iter();
+    let Some(first) = terms.next() else {
+        return Ast::Num(Value::Int(0));
+    };
+    let mut ast = term_ast(first);
+    for term in terms {
+        let (op, term) = match is_negative(&term.coeff) {
+            true => (Token::Minus, negate(vec![term]).unwrap().remove(0)),
+            false => (Token::Plus, term),
+        };
+        ast = binary(op, ast, term_ast(term));
+    }
+    ast
+}
+
+// coefficient first, then the factors, those with negative powers under a
+// fraction bar
+fn term_ast(term: Term) -> Ast {
+    let one = !matches!(term.coeff, Value::Float(_)) && term.coeff.as_f64() == 1.0;
+    let minus_one = !matches!(term.coeff, Value::Float(_)) && term.coeff.as_f64() == -1.0;
+    let factor = |base: &Ast, exp: i32| match exp {
+        1 => base.clone(),
+        _ => binary(Token::Power, base.clone(), Ast::Num(Value::Int(exp.into()))),
+    };
+    let product = |factors: Vec<Ast>| {
+        factors
+            .into_iter()
+            .reduce(|a, b| binary(Token::Multiply, a, b))
+    };
+    let above: Vec<Ast> = (term.factors.iter())
+        .filter(|(_, exp)| *exp > 0)
+        .map(|(base, exp)| factor(base, *exp))
+        .collect();
+    let below: Vec<Ast> = (term.factors.iter())
+        .filter(|(_, exp)| *exp < 0)
+        .map(|(base, exp)| factor(base, -exp))
+        .collect();
+    let numerator = match product(above.clone()) {
+        None => Ast::Num(term.coeff),
+        Some(product) if one => product,
+        Some(product) if minus_one => Ast::Unary {
+            op: Token::Minus,
+            operand: Box::new(product),
+            span: Span::default(),
+        },
+        Some(_) => product([vec![Ast::Num(term.coeff)], above].concat()).unwrap(),
+    };
+    match product(below) {
+        Some(denominator) => binary(Token::Divide, numerator, denominator),
+        None => numerator,
+    }
+}
+
+fn binary(op: Token, lhs: Ast, rhs: Ast) -> Ast {
+    Ast::BinaryOp {
+        op,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+        span: Span::default(),
+    }
+}
+
+// a node the sums don't cover with its operands in canonical form, those of
+// a commutative operator in the order of their text
+fn ordered(ast: &Ast) -> Ast {
+    match ast {
+        Ast::BinaryOp { op, lhs, rhs, span } => {
+            let (mut lhs, mut rhs) = (lhs.canonicalize(), rhs.canonicalize());
+            if is_commutative(op) && lhs.to_string() > rhs.to_string() {
+                core::mem::swap(&mut lhs, &mut rhs);
+            }
+            Ast::BinaryOp {
+                op: op.clone(),
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+                span: *span,
+            }
+        }
+        Ast::Unary { op, operand, span } => Ast::Unary {
+            op: op.clone(),
+            operand: Box::new(operand.canonicalize()),
+            span: *span,
+        },
+        Ast::Paren(inner) => ordered(inner),
+        Ast::Call { name, args, span } => Ast::Call {
+            name: name.clone(),
+            args: args.iter().map(Ast::canonicalize).collect(),
+            span: *span,
+        },
+        Ast::Cond {
+            cond,
+            then,
+            otherwise,
+            span,
+        } => Ast::Cond {
+            cond: Box::new(cond.canonicalize()),
+            then: Box::new(then.canonicalize()),
+            otherwise: Box::new(otherwise.canonicalize()),
+            span: *span,
+        },
+        Ast::Num(_) | Ast::Var { .. } => ast.clone(),
+    }
+}
+
+// a op b is b op a
+fn is_commutative(op: &Token) -> bool {
+    matches!(
+        op,
+        Token::Plus
+            | Token::Multiply
+            | Token::Equal
+            | Token::NotEqual
+            | Token::And
+            | Token::Or
+            | Token::BitAnd
+            | Token::BitOr
+            | Token::BitXor
+    )
+}
@@ -0,0 +1,57 @@
+use alloc::{format, string::String};
+
+use crate::{random, seed_random, vm::CompiledExpr, Ast, Env, ExprError, Result, Value};
+
+// a backend the tree walk is checked against, by what its errors call it
+type Backend = (&'static str, fn(&Ast, &Env) -> Result<Value>);
+
+// a result as the backends are compared on: values by their debug form,
+// which keeps 1 and 1.0 apart and has NaN equal to itself, errors by their
+// message and offset
+fn outcome(result: &Result<Value>) -> String {
+    match result {
+        Ok(value) => format!("{:?}", value),
+        Err(err) => format!("error: {}", err),
+    }
+}
+
+impl Ast {
+    /// Evaluate like [`Ast::eval_with`], and again on the bytecode vm, as
+    /// closures and with [`Ast::eval_memo`], failing with
+    /// [`ExprError::BackendsDisagree`] unless all of them give the same value
+    /// or the same error. A harness for generated input: a bug in one
+    /// backend shows up as a disagreement. Each backend gets the same
+    /// numbers from `rand()`.
+    ///
+    /// ```
+    /// use expr_eval::{Env, Expr, Mode, Value};
+    ///
+    /// let ast = Expr::new("max(x, 2) ^ 2 / (x - 3)", Mode::Standard).parse().unwrap();
+    /// let env = Env::from([("x".to_string(), Value::Int(4))]);
+    /// assert_eq!(Value::Int(16), ast.eval_checked(&env).unwrap());
+    /// let env = Env::from([("x".to_string(), Value::Int(3))]);
+    /// assert_eq!("division by zero at offset 14", ast.eval_checked(&env).unwrap_err().to_string());
+    /// ```
+    pub fn eval_checked(&self, env: &Env) -> Result<Value> {
+        let seed = random::state();
+        let expected = self.eval_with(env);
+        let backends: [Backend; 3] = [
+            ("the vm", |ast, env| CompiledExpr::new(ast).eval_with(env)),
+            ("closures", |ast, env| ast.compile()(env)),
+            ("memoized", Ast::eval_memo),
+        ];
+        for (backend, eval) in backends {
+            seed_random(seed);
+            let found = eval(self, env);
+            let (expected, found) = (outcome(&expected), outcome(&found));
+            if expected != found {
+                return Err(ExprError::BackendsDisagree {
+                    backend,
+                    expected: expected.into(),
+                    found: found.into(),
+                });
+            }
+        }
+        expected
+    }
+}
@@ -0,0 +1,776 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, IsTerminal, Read},
+    time::Instant,
+};
+
+use expr_eval::{
+    eval_batch, load_rates, render, seed_random, Arithmetic, Ast, CompiledExpr, Engineering, Env,
+    EvalOptions, Expr, ExprError, Locale, Mode, Parser, Precision, Promotion, Quantity, Radix,
+    SiPrefix, Token, Tokenizer, Value, ValueFormatter, WordSize, DEFAULT_DECIMAL_SCALE,
+    MAX_DECIMAL_SCALE, PARSERS,
+};
+use serde_json::json;
+
+use crate::{csv, repl};
+
+// what to do with each expression
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    Eval,
+    // print the parse tree instead of the value
+    Json,
+    Sexpr,
+    Latex,
+    // print the parse tree as a graphviz graph
+    Dot,
+    // print the expression in postfix
+    Rpn,
+    // print the expression with constants folded
+    Simplify,
+    // print the derivative with respect to the variable
+    Derive(String),
+    // the input is a json tree from --json, evaluate it
+    FromJson,
+    // report every syntax error without evaluating
+    Check,
+    // evaluate many times and print how long it took
+    Bench,
+    // the input is a json array of expressions and variables
+    Batch,
+    // print each step of the evaluation before the value
+    Trace,
+    // evaluate with every backend, an error where they disagree
+    SelfCheck,
+}
+
+// an expression ready to evaluate with a backend
+type Prepared = Box<dyn Fn(&Env) -> expr_eval::Result<Value>>;
+
+// how a parsed expression gets evaluated
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Backend {
+    // walk the tree
+    Ast,
+    // compile to bytecode and run it on the stack machine
+    Vm,
+    // build nested closures once and call them
+    Closure,
+    // walk the tree, evaluating repeated subtrees once
+    Memo,
+}
+
+impl Backend {
+    // only the tree walk follows the options
+    fn eval(self, ast: &Ast, env: &Env, options: &EvalOptions) -> expr_eval::Result<Value> {
+        match self {
+            Backend::Ast => ast.eval_with_options(env, options),
+            Backend::Vm => CompiledExpr::new(ast).eval_with(env),
+            Backend::Closure => ast.compile()(env),
+            Backend::Memo => ast.eval_memo(env),
+        }
+    }
+
+    // evaluate printing what happens along the way: each instruction and the
+    // stack it leaves on the vm, each reduction for the others
+    fn trace(self, ast: &Ast, env: &Env) -> expr_eval::Result<Value> {
+        match self {
+            Backend::Vm => CompiledExpr::new(ast).eval_debug(env, |step| println!("{}", step)),
+            _ => ast.eval_trace(env, |step| println!("{}", step)),
+        }
+    }
+
+    // the work done once per expression, compiling it, left out of what is
+    // called for each evaluation
+    fn prepare(self, ast: &Ast, options: EvalOptions) -> Prepared {
+        let ast = ast.clone();
+        match self {
+            Backend::Ast => Box::new(move |env| ast.eval_with_options(env, &options)),
+            Backend::Vm => {
+                let code = CompiledExpr::new(&ast);
+                Box::new(move |env| code.eval_with(env))
+            }
+            Backend::Closure => Box::new(ast.compile()),
+            Backend::Memo => Box::new(move |env| ast.eval_memo(env)),
+        }
+    }
+}
+
+// evaluations per expression with --bench unless -n says otherwise
+const DEFAULT_BENCH_RUNS: usize = 100_000;
+
+// the most -n takes, so the runs count in a u32 divisor of the timings
+const MAX_BENCH_RUNS: usize = 100_000_000;
+
+// how numbers in results are written, --notation=eng|si
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Notation {
+    Plain,
+    // 1.5e3, the exponent a multiple of 3
+    Engineering,
+    // 1.5k
+    Si,
+}
+
+// how each line's result or error is printed, --output=plain|json|tsv
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Output {
+    // the value, or the error under a caret on stderr
+    Plain,
+    // {"expr": ..., "value": ...} or {"expr": ..., "error": {...}}
+    Json,
+    // the expression, the value and the error, one of them empty
+    Tsv,
+}
+
+// the settings of the command line, the repl changing the ones it has
+// commands for as it goes
+#[derive(Debug, Clone)]
+pub(crate) struct Options {
+    pub(crate) mode: Mode,
+    action: Action,
+    backend: Backend,
+    // base integer results are printed in
+    pub(crate) radix: Radix,
+    // digits float results are printed with
+    pub(crate) precision: Precision,
+    // bits integer results are cut to, shown as two's complement
+    pub(crate) word: Option<WordSize>,
+    notation: Notation,
+    output: Output,
+    // input is postfix, `3 4 +`
+    rpn: bool,
+    // what infix input is read with instead of the script parser, which
+    // takes statements
+    parser: Option<&'static dyn Parser>,
+    // evaluations of each expression for --bench
+    runs: usize,
+    // currency results with money in them are converted to
+    currency: Option<String>,
+    // wrapping or saturating int arithmetic and promotion, for --backend=ast
+    options: EvalOptions,
+    // separators numbers in the input are written with
+    locale: Locale,
+}
+
+// the rates table --rates reads when it isn't given
+const RATES_VAR: &str = "EXPR_EVAL_RATES";
+
+impl Options {
+    // a result in the --currency one when it has money in it, then in the
+    // output base and precision
+    pub(crate) fn show(&self, value: Value) -> String {
+        let value = match (&value, &self.currency) {
+            (Value::Quantity(q), Some(code)) => q.to_currency(code).map_or(value, Value::Quantity),
+            _ => value,
+        };
+        // --digits counts the digits of the mantissa
+        let digits = match self.precision {
+            Precision::Digits(digits) => digits,
+            _ => Engineering::default().digits,
+        };
+        match (self.notation, self.word) {
+            (Notation::Engineering, _) => Engineering { digits }.format(&value),
+            (Notation::Si, _) => SiPrefix { digits }.format(&value),
+            (Notation::Plain, Some(word)) => value.format_word(self.radix, self.precision, word),
+            (Notation::Plain, None) => value.format(self.radix, self.precision),
+        }
+    }
+
+    // a parser for src in the mode and locale
+    fn expr<'a>(&self, src: &'a str) -> Expr<'a> {
+        Expr::new(src, self.mode).with_locale(self.locale)
+    }
+
+    // parse a single expression in the input notation
+    fn parse(&self, src: &str) -> expr_eval::Result<Ast> {
+        let mut expr = self.expr(src);
+        match self.parser {
+            _ if self.rpn => expr.parse_rpn(),
+            Some(parser) => parser.parse(&mut expr),
+            None => expr.parse(),
+        }
+    }
+
+    // the value of a line with the backend and its options; postfix input
+    // has no statements, just the one expression, and neither has input for
+    // another parser
+    pub(crate) fn eval(&self, src: &str, env: &mut Env) -> expr_eval::Result<Value> {
+        if self.rpn || self.parser.is_some() {
+            return (self.parse(src)).and_then(|ast| self.backend.eval(&ast, env, &self.options));
+        }
+        // earlier lines may have defined functions this one calls
+        self.expr(src)
+            .with_functions(env)
+            .parse_script()
+            .and_then(|script| {
+                script.eval_with(env, |ast, env| self.backend.eval(ast, env, &self.options))
+            })
+    }
+}
+
+// handle one line, printing the output or the error; variables assigned by a
+// script stay in env for the next lines
+fn eval_line(src: &str, opts: &Options, env: &mut Env) -> bool {
+    match opts.action {
+        Action::Check => return check_line(src, opts),
+        Action::Bench => return bench_line(src, opts, env),
+        _ => {}
+    }
+    let output = match &opts.action {
+        Action::Eval => opts.eval(src, env).map(|v| opts.show(v)),
+        Action::Json => opts.parse(src).map(|ast| ast.to_json()),
+        Action::Sexpr => opts.parse(src).map(|ast| ast.to_sexpr()),
+        Action::Latex => opts.parse(src).map(|ast| ast.to_latex()),
+        Action::Dot => opts.parse(src).map(|ast| ast.to_dot()),
+        Action::Rpn => opts.expr(src).to_rpn(),
+        Action::Simplify => opts.parse(src).map(|ast| ast.simplify().to_string()),
+        Action::Derive(var) => opts
+            .parse(src)
+            .and_then(|ast| ast.derivative(var))
+            .map(|ast| ast.to_string()),
+        Action::FromJson => Ast::from_json(src)
+            .and_then(|ast| opts.backend.eval(&ast, env, &opts.options))
+            .map(|v| opts.show(v)),
+        Action::Batch => eval_batch(src, opts.mode),
+        Action::Trace if opts.rpn || opts.parser.is_some() => opts
+            .parse(src)
+            .and_then(|ast| opts.backend.trace(&ast, env))
+            .map(|v| opts.show(v)),
+        Action::Trace => opts
+            .expr(src)
+            .with_functions(env)
+            .parse_script()
+            .and_then(|script| script.eval_with(env, |ast, env| opts.backend.trace(ast, env)))
+            .map(|v| opts.show(v)),
+        Action::SelfCheck => opts
+            .parse(src)
+            .and_then(|ast| ast.eval_checked(env))
+            .map(|v| opts.show(v)),
+        Action::Check | Action::Bench => unreachable!("handled above"),
+    };
+    let ok = output.is_ok();
+    // --batch already answers in json, with an object for each error
+    let format = match opts.action {
+        Action::Batch => Output::Plain,
+        _ => opts.output,
+    };
+    match (format, output) {
+        (Output::Plain, Ok(output)) => println!("{}", output),
+        // json trees carry no source positions to point at, and a
+        // disagreement is about the whole expression
+        (Output::Plain, Err(err))
+            if matches!(opts.action, Action::FromJson | Action::Batch)
+                || matches!(err, ExprError::BackendsDisagree { .. }) =>
+        {
+            eprintln!("error: {}", err)
+        }
+        (Output::Plain, Err(err)) => eprint!("{}", render(src, &err)),
+        (Output::Json, Ok(output)) => println!("{}", json!({ "expr": src, "value": output })),
+        (Output::Json, Err(err)) => {
+            println!("{}", json!({ "expr": src, "error": error_json(&err) }))
+        }
+        (Output::Tsv, Ok(output)) => println!("{}\t{}\t", tsv_field(src), tsv_field(&output)),
+        (Output::Tsv, Err(err)) => {
+            println!("{}\t\t{}", tsv_field(src), tsv_field(&format!("{:#}", err)))
+        }
+    }
+    ok
+}
+
+// an error as the object --output=json prints, its message and where it is
+fn error_json(err: &ExprError) -> serde_json::Value {
+    let span = err.span();
+    json!({ "message": format!("{:#}", err), "offset": span.offset, "length": span.len })
+}
+
+// tabs, line breaks and backslashes escaped, so a field stays one field of
+// one line
+fn tsv_field(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+// print ok, or each syntax error of the line under its own caret
+fn check_line(src: &str, opts: &Options) -> bool {
+    match opts.expr(src).parse_all() {
+        Ok(_) => {
+            println!("ok");
+            true
+        }
+        Err(errors) => {
+            for err in errors {
+                eprint!("{}", render(src, &err));
+            }
+            false
+        }
+    }
+}
+
+// parse once, evaluate opts.runs times with the backend, and print the value,
+// the throughput and the latency percentiles
+fn bench_line(src: &str, opts: &Options, env: &Env) -> bool {
+    let eval = match opts.parse(src) {
+        Ok(ast) => opts.backend.prepare(&ast, opts.options),
+        Err(err) => {
+            eprint!("{}", render(src, &err));
+            return false;
+        }
+    };
+    let mut times = Vec::new();
+    let mut value = None;
+    let start = Instant::now();
+    for _ in 0..opts.runs {
+        let before = Instant::now();
+        let result = eval(env);
+        times.push(before.elapsed());
+        match result {
+            Ok(v) => value = Some(v),
+            Err(err) => {
+                eprint!("{}", render(src, &err));
+                return false;
+            }
+        }
+    }
+    let total = start.elapsed();
+    times.sort();
+    let value = value.expect("at least one run");
+    println!("{}", opts.show(value));
+    println!(
+        "{} evaluations in {:?}, {:.0} per second",
+        opts.runs,
+        total,
+        opts.runs as f64 / total.as_secs_f64()
+    );
+    let [p50, p90, p99, max] = [50, 90, 99, 100].map(|p| times[(times.len() - 1) * p / 100]);
+    println!(
+        "latency p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+        p50, p90, p99, max
+    );
+    // how long the source takes to parse, with each of the infix parsers
+    if !opts.rpn {
+        for parser in PARSERS {
+            let start = Instant::now();
+            for _ in 0..opts.runs {
+                // the ones reading less than the others fail, but as fast
+                let _ = parser.parse(&mut opts.expr(src));
+            }
+            let each = start.elapsed() / u32::try_from(opts.runs).unwrap_or(u32::MAX);
+            println!("parsed in {:?} by {}", each, parser.name());
+        }
+    }
+    true
+}
+
+// handle newline-separated expressions, one output per line
+fn eval_lines(input: impl BufRead, opts: &Options, mut env: Env) -> io::Result<bool> {
+    let mut ok = true;
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        ok &= eval_line(line, opts, &mut env);
+    }
+    Ok(ok)
+}
+
+// bind NAME=VALUE in env, the value an expression that may use the names
+// defined before it; false after printing why it isn't a definition
+fn define(def: &str, mode: Mode, env: &mut Env) -> bool {
+    let Some((name, src)) = def.split_once('=') else {
+        eprintln!("error: expected -D NAME=VALUE, found `{}`", def);
+        return false;
+    };
+    let name = name.trim();
+    let mut tokens = Tokenizer::new(name, mode);
+    if !matches!(
+        (tokens.next(), tokens.next()),
+        (Some(Ok((Token::Ident(_), _))), None)
+    ) {
+        eprintln!("error: `{}` is not a variable name", name);
+        return false;
+    }
+    match Expr::new(src, mode)
+        .parse()
+        .and_then(|ast| ast.eval_with(env))
+    {
+        Ok(value) => {
+            env.insert(name.to_string(), value);
+            true
+        }
+        Err(err) => {
+            let context = format!("error: in the value of `{}`: ", name);
+            eprint!("{}", render(src, &err).replacen("error: ", &context, 1));
+            false
+        }
+    }
+}
+
+// `expr-eval run script.calc`: the statements of the file top to bottom, the
+// value of each one that isn't an assignment printed as it is reached
+fn run_script(path: &str, opts: &Options, mut env: Env) -> i32 {
+    let src = match fs::read_to_string(path) {
+        Ok(src) => src,
+        Err(err) => {
+            eprintln!("error: can't read `{}`: {}", path, err);
+            return 2;
+        }
+    };
+    let result = opts.expr(&src).parse_script().and_then(|script| {
+        script.eval_each(
+            &mut env,
+            |ast, env| opts.backend.eval(ast, env, &opts.options),
+            |value| println!("{}", opts.show(value.clone())),
+        )
+    });
+    match result {
+        Ok(_) => 0,
+        Err(err) => {
+            eprint!("{}", render(&src, &err));
+            1
+        }
+    }
+}
+
+// `expr-eval --csv data.csv "c1 * 2 + c3"`: the file again with a result
+// column, the formula worked out for each row; the columns are variables
+// named by the header and by position, c1 being the first, and cells that
+// aren't numbers in the mode and locale are text
+fn run_csv(path: &str, src: &str, opts: &Options, mut env: Env) -> i32 {
+    let data = match fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("error: can't read `{}`: {}", path, err);
+            return 2;
+        }
+    };
+    let mut rows = match csv::records(&data) {
+        Ok(rows) => rows.into_iter(),
+        Err(line) => {
+            eprintln!(
+                "error: `{}` has a quote on line {} that is never closed",
+                path, line
+            );
+            return 2;
+        }
+    };
+    let ast = match opts.parse(src) {
+        Ok(ast) => ast,
+        Err(err) => {
+            eprint!("{}", render(src, &err));
+            return 2;
+        }
+    };
+    let mut header = rows.next().unwrap_or_default();
+    let names: Vec<String> = (1..=header.len()).map(|i| format!("c{}", i)).collect();
+    header.push("result".into());
+    println!("{}", csv::line(&header));
+    let mut ok = true;
+    for (row, mut cells) in rows.enumerate() {
+        // a short row leaves the rest of its cells empty
+        cells.resize(cells.len().max(names.len()), String::new());
+        for ((position, name), cell) in names.iter().zip(&header).zip(&cells) {
+            let value =
+                (opts.expr(cell).parse_number()).unwrap_or_else(|_| Value::Text(cell.clone()));
+            env.insert(position.clone(), value.clone());
+            env.insert(name.clone(), value);
+        }
+        let result = match opts.backend.eval(&ast, &env, &opts.options) {
+            Ok(value) => opts.show(value),
+            Err(err) => {
+                // rows counted from the first one after the header
+                let context = format!("error: in row {}: ", row + 1);
+                eprint!("{}", render(src, &err).replacen("error: ", &context, 1));
+                ok = false;
+                String::new()
+            }
+        };
+        cells.push(result);
+        println!("{}", csv::line(&cells));
+    }
+    if ok {
+        0
+    } else {
+        1
+    }
+}
+
+// entry of the binary, returns the process exit code
+pub fn run(args: Vec<String>) -> i32 {
+    let mut opts = Options {
+        mode: Mode::Standard,
+        action: Action::Eval,
+        backend: Backend::Ast,
+        radix: Radix::Dec,
+        word: None,
+        notation: Notation::Plain,
+        output: Output::Plain,
+        precision: Precision::Shortest,
+        rpn: false,
+        parser: None,
+        runs: DEFAULT_BENCH_RUNS,
+        currency: None,
+        options: EvalOptions::default(),
+        locale: Locale::Plain,
+    };
+    let mut rates = env::var(RATES_VAR).ok();
+    let mut csv = None;
+    let mut words = Vec::new();
+    let mut defines = Vec::new();
+    // the last flag only the tree walk follows, like --wrapping
+    let mut evaluation = None;
+    let mut args = args.into_iter().peekable();
+    let script = args.next_if(|arg| arg == "run").is_some();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // -D x=3 or -Dx=3 binds x before anything is evaluated
+            "-D" | "--define" => match args.next() {
+                Some(def) => defines.push(def),
+                None => {
+                    eprintln!("error: expected NAME=VALUE after `{}`", arg);
+                    return 2;
+                }
+            },
+            _ if arg.starts_with("-D") => defines.push(arg[2..].to_string()),
+            "--bigint" => opts.mode = Mode::BigInt,
+            "--rational" => opts.mode = Mode::Rational,
+            "--decimal" => opts.mode = Mode::Decimal(DEFAULT_DECIMAL_SCALE),
+            // --decimal=4 keeps 4 fractional digits, at most MAX_DECIMAL_SCALE
+            _ if arg.starts_with("--decimal=") => {
+                match arg["--decimal=".len()..]
+                    .parse()
+                    .ok()
+                    .and_then(Mode::decimal)
+                {
+                    Some(mode) => opts.mode = mode,
+                    None => {
+                        eprintln!(
+                            "error: invalid decimal scale `{}`, expected 0 to {}",
+                            arg, MAX_DECIMAL_SCALE
+                        );
+                        return 2;
+                    }
+                }
+            }
+            "--json" => opts.action = Action::Json,
+            "--sexpr" => opts.action = Action::Sexpr,
+            "--latex" => opts.action = Action::Latex,
+            // expr-eval --dot "1 + 2 * 3" | dot -Tsvg > tree.svg
+            "--dot" => opts.action = Action::Dot,
+            "--to-rpn" => opts.action = Action::Rpn,
+            "--simplify" => opts.action = Action::Simplify,
+            _ if arg.starts_with("--derive=") => {
+                opts.action = Action::Derive(arg["--derive=".len()..].to_string())
+            }
+            "--from-json" => opts.action = Action::FromJson,
+            "--check" => opts.action = Action::Check,
+            "--trace" => opts.action = Action::Trace,
+            // generated expressions, one per line, with --self-check < cases.txt
+            "--self-check" => opts.action = Action::SelfCheck,
+            // --bench -n 1000000 times a million evaluations
+            "--bench" => opts.action = Action::Bench,
+            // [{"expr": "x + 1", "vars": {"x": 2}}, ...] on stdin
+            "--batch" => opts.action = Action::Batch,
+            "-n" => match args.next().map(|n| n.parse()) {
+                Some(Ok(runs)) if (1..=MAX_BENCH_RUNS).contains(&runs) => opts.runs = runs,
+                _ => {
+                    eprintln!(
+                        "error: expected a count of runs from 1 to {} after `-n`",
+                        MAX_BENCH_RUNS
+                    );
+                    return 2;
+                }
+            },
+            // --seed=42 makes rand() and randint() repeat from run to run
+            _ if arg.starts_with("--seed=") => match arg["--seed=".len()..].parse() {
+                Ok(seed) => seed_random(seed),
+                Err(_) => {
+                    eprintln!("error: invalid seed `{}`", arg);
+                    return 2;
+                }
+            },
+            // --rates=rates.toml, exchange rates for 100 USD + 50 EUR
+            _ if arg.starts_with("--rates=") => rates = Some(arg["--rates=".len()..].to_string()),
+            // --currency=EUR prints money in euros
+            _ if arg.starts_with("--currency=") => {
+                opts.currency = Some(arg["--currency=".len()..].to_string())
+            }
+            // 64-bit machine arithmetic, i64::MAX + 1 is i64::MIN or i64::MAX
+            "--wrapping" => {
+                opts.options.arithmetic = Arithmetic::Wrapping;
+                evaluation = Some(arg);
+            }
+            "--saturating" => {
+                opts.options.arithmetic = Arithmetic::Saturating;
+                evaluation = Some(arg);
+            }
+            // ints and floats only mix through int() and float(), and with
+            // error ints don't become floats at all
+            "--promotion=explicit" => {
+                opts.options.promotion = Promotion::Explicit;
+                evaluation = Some(arg);
+            }
+            "--promotion=error" => {
+                opts.options.promotion = Promotion::Error;
+                evaluation = Some(arg);
+            }
+            "--rpn" => opts.rpn = true,
+            // --parser=pratt reads infix input with another parser
+            _ if arg.starts_with("--parser=") => {
+                let name = &arg["--parser=".len()..];
+                match PARSERS.into_iter().find(|parser| parser.name() == name) {
+                    Some(parser) => opts.parser = Some(parser),
+                    None => {
+                        eprintln!(
+                            "error: unknown parser `{}`, expected climbing, pratt or shunting-yard",
+                            arg
+                        );
+                        return 2;
+                    }
+                }
+            }
+            "--backend=ast" => opts.backend = Backend::Ast,
+            "--backend=vm" => opts.backend = Backend::Vm,
+            "--backend=closure" => opts.backend = Backend::Closure,
+            "--backend=memo" => opts.backend = Backend::Memo,
+            // --locale=comma reads 1.234,5 as 1234.5
+            _ if arg.starts_with("--locale=") => match Locale::from_name(&arg["--locale=".len()..])
+            {
+                Some(locale) => opts.locale = locale,
+                None => {
+                    eprintln!(
+                        "error: unknown locale `{}`, expected plain, point, comma or auto",
+                        arg
+                    );
+                    return 2;
+                }
+            },
+            // --word=32 with --out=hex prints -1 as 0xffffffff
+            _ if arg.starts_with("--word=") => match WordSize::from_name(&arg["--word=".len()..]) {
+                Some(word) => opts.word = Some(word),
+                None => {
+                    eprintln!(
+                        "error: unknown word size `{}`, expected 8, 16, 32 or 64",
+                        arg
+                    );
+                    return 2;
+                }
+            },
+            // --out=hex prints integer results as 0x...
+            _ if arg.starts_with("--out=") => match Radix::from_name(&arg["--out=".len()..]) {
+                Some(radix) => opts.radix = radix,
+                None => {
+                    eprintln!(
+                        "error: unknown output base `{}`, expected dec, hex, oct or bin",
+                        arg
+                    );
+                    return 2;
+                }
+            },
+            "--csv" => match args.next() {
+                Some(path) => csv = Some(path),
+                None => {
+                    eprintln!("error: expected --csv FILE");
+                    return 2;
+                }
+            },
+            "--output=plain" => opts.output = Output::Plain,
+            "--output=json" => opts.output = Output::Json,
+            "--output=tsv" => opts.output = Output::Tsv,
+            "--notation=eng" => opts.notation = Notation::Engineering,
+            "--notation=si" => opts.notation = Notation::Si,
+            // --digits=6 significant digits, --places=2 digits after the point
+            _ if arg.starts_with("--digits=") || arg.starts_with("--places=") => {
+                let (name, n) = arg.split_once('=').unwrap();
+                match n.parse() {
+                    Ok(n) if name == "--digits" => opts.precision = Precision::Digits(n),
+                    Ok(n) => opts.precision = Precision::Places(n),
+                    Err(_) => {
+                        eprintln!("error: expected a count of digits in `{}`", arg);
+                        return 2;
+                    }
+                }
+            }
+            _ => words.push(arg),
+        }
+    }
+
+    if let Some(flag) = evaluation.filter(|_| opts.backend != Backend::Ast) {
+        eprintln!("error: {} needs --backend=ast", flag);
+        return 2;
+    }
+
+    if let Some(path) = rates {
+        let loaded = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|src| load_rates(&src).map_err(|e| e.to_string()));
+        if let Err(err) = loaded {
+            eprintln!("error: can't load rates from `{}`: {}", path, err);
+            return 2;
+        }
+    }
+    if let Some(code) = &opts.currency {
+        if Quantity::plain(1.0).to_currency(code).is_none() {
+            eprintln!("error: unknown currency `{}`, load it with --rates", code);
+            return 2;
+        }
+    }
+
+    // in order and once the mode is known, wherever it was given
+    let mut env = Env::new();
+    for def in &defines {
+        if !define(def, opts.mode, &mut env) {
+            return 2;
+        }
+    }
+
+    if let Some(path) = csv {
+        return run_csv(&path, &words.join(" "), &opts, env);
+    }
+
+    if script {
+        return match words.as_slice() {
+            [path] => run_script(path, &opts, env),
+            _ => {
+                eprintln!("error: expected `expr-eval run FILE`");
+                2
+            }
+        };
+    }
+
+    let ok = if !words.is_empty() {
+        // `expr-eval 1 + 2` and `expr-eval "1 + 2"` are the same
+        Ok(eval_line(&words.join(" "), &opts, &mut env))
+    } else if opts.action == Action::Batch {
+        // the array can span many lines, read it whole
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .map(|_| eval_line(&input, &opts, &mut env))
+            .map_err(|e| e.to_string())
+    } else if io::stdin().is_terminal() {
+        // the repl evaluates each line and prints the value plainly
+        if opts.action != Action::Eval || opts.output != Output::Plain {
+            eprintln!("error: the repl only evaluates, give the input as arguments or on stdin");
+            return 2;
+        }
+        repl::run(opts, env)
+            .map(|_| true)
+            .map_err(|e| e.to_string())
+    } else {
+        eval_lines(io::stdin().lock(), &opts, env).map_err(|e| e.to_string())
+    };
+    match ok {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            1
+        }
+    }
+}
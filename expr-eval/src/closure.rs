@@ -0,0 +1,105 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{
+    apply_function, function::call_user, lookup_function, Ast, Env, ExprError, Result, Span, Token,
+    Value,
+};
+
+// an expression turned into nested closures, the tree is matched once here
+// instead of on every evaluation
+type Compiled = Box<dyn Fn(&Env) -> Result<Value>>;
+
+impl Ast {
+    // the tree as a closure, to call with many environments
+    pub fn compile(&self) -> impl Fn(&Env) -> Result<Value> {
+        compile(self)
+    }
+}
+
+fn compile(ast: &Ast) -> Compiled {
+    match ast {
+        Ast::Num(n) => {
+            let n = n.clone();
+            Box::new(move |_| Ok(n.clone()))
+        }
+        Ast::Var { name, span } => {
+            let (name, span) = (name.clone(), *span);
+            Box::new(move |env| {
+                env.get(&name)
+                    .cloned()
+                    .ok_or_else(|| ExprError::UnknownVariable {
+                        name: name.clone(),
+                        span,
+                    })
+            })
+        }
+        Ast::Unary { op, operand, span } => {
+            let (op, span) = (op.clone(), *span);
+            let operand = compile(operand);
+            Box::new(move |env| op.compute_unary(operand(env)?, span))
+        }
+        Ast::BinaryOp { .. } => compile_chain(ast),
+        Ast::Paren(inner) => compile(inner),
+        Ast::Call { name, args, span } => {
+            let span = *span;
+            let args: Vec<Compiled> = args.iter().map(compile).collect();
+            // the parser only builds calls to builtins and user functions
+            let Some(func) = lookup_function(name) else {
+                let name = name.clone();
+                return Box::new(move |env| {
+                    let args = args.iter().map(|arg| arg(env));
+                    call_user(&name, args.collect::<Result<_>>()?, env, span)
+                });
+            };
+            Box::new(move |env| {
+                let args = args.iter().map(|arg| arg(env));
+                apply_function(func, args.collect::<Result<_>>()?, span)
+            })
+        }
+        // only the branch taken is called
+        Ast::Cond {
+            cond,
+            then,
+            otherwise,
+            span,
+        } => {
+            let span = *span;
+            let (cond, then, otherwise) = (compile(cond), compile(then), compile(otherwise));
+            Box::new(move |env| {
+                if cond(env)?.expect_bool(span)? {
+                    then(env)
+                } else {
+                    otherwise(env)
+                }
+            })
+        }
+    }
+}
+
+// a binary operation and those of its chain in one closure, running them in
+// a loop, see Ast::chain
+fn compile_chain(ast: &Ast) -> Compiled {
+    let (bottom, chain) = ast.chain();
+    let bottom = compile(bottom);
+    let chain: Vec<(Token, Compiled, Span)> = chain
+        .into_iter()
+        .map(|(_, op, rhs, span)| (op.clone(), compile(rhs), span))
+        .collect();
+    Box::new(move |env| {
+        let mut l = bottom(env)?;
+        for (op, rhs, span) in &chain {
+            l = if op.is_logical() {
+                // the right side only runs when the left doesn't decide
+                let l = l.expect_bool(*span)?;
+                if l == (*op == Token::Or) {
+                    Value::Bool(l)
+                } else {
+                    Value::Bool(rhs(env)?.expect_bool(*span)?)
+                }
+            } else {
+                op.compute(l, rhs(env)?, *span)?
+            };
+        }
+        Ok(l)
+    })
+}
@@ -0,0 +1,70 @@
+use alloc::{string::ToString, vec};
+
+use crate::{Env, Quantity, Value};
+
+// a constant with units, in the base units of its dimensions
+fn quantity(value: f64, units: &[(&str, i32)]) -> Value {
+    Value::Quantity(Quantity::new(value, units).expect("known units"))
+}
+
+/// Variables holding the constants of a named set, for an environment to
+/// start from: `math` has `pi`, `e`, `tau`, `golden`, `sqrt2` and `ln2`, and
+/// `physics` the CODATA values of `c`, `G`, `h`, `hbar`, `g0`, `m_e`, `m_p`,
+/// `N_A`, `au` and `ly` in meters, kilograms and seconds, so they carry
+/// their units into the arithmetic. `N_A` is a plain number, there being no
+/// unit for amounts of substance. None for an unknown set.
+///
+/// ```
+/// use expr_eval::{constants, Expr, Mode};
+///
+/// let env = constants("physics").unwrap();
+/// // the seconds light takes to cross a light year
+/// let ast = Expr::new("ly / c", Mode::Standard).parse().unwrap();
+/// assert_eq!("31557600 s", ast.eval_with(&env).unwrap().to_string());
+/// ```
+pub fn constants(set: &str) -> Option<Env> {
+    let constants = match set {
+        "math" => vec![
+            ("pi", Value::Float(core::f64::consts::PI)),
+            ("e", Value::Float(core::f64::consts::E)),
+            ("tau", Value::Float(core::f64::consts::TAU)),
+            ("golden", Value::Float(1.618_033_988_749_895)),
+            ("sqrt2", Value::Float(core::f64::consts::SQRT_2)),
+            ("ln2", Value::Float(core::f64::consts::LN_2)),
+        ],
+        "physics" => vec![
+            // speed of light in vacuum
+            ("c", quantity(299_792_458.0, &[("m", 1), ("s", -1)])),
+            // gravitational constant
+            (
+                "G",
+                quantity(6.674_30e-11, &[("m", 3), ("kg", -1), ("s", -2)]),
+            ),
+            // planck constant and the reduced one, in J s
+            (
+                "h",
+                quantity(6.626_070_15e-34, &[("m", 2), ("kg", 1), ("s", -1)]),
+            ),
+            (
+                "hbar",
+                quantity(1.054_571_817e-34, &[("m", 2), ("kg", 1), ("s", -1)]),
+            ),
+            // standard gravity
+            ("g0", quantity(9.806_65, &[("m", 1), ("s", -2)])),
+            // electron and proton mass
+            ("m_e", quantity(9.109_383_701_5e-31, &[("kg", 1)])),
+            ("m_p", quantity(1.672_621_923_69e-27, &[("kg", 1)])),
+            // avogadro constant, per mole
+            ("N_A", Value::Float(6.022_140_76e23)),
+            // astronomical unit and light year
+            ("au", quantity(149_597_870_700.0, &[("m", 1)])),
+            ("ly", quantity(9_460_730_472_580_800.0, &[("m", 1)])),
+        ],
+        _ => return None,
+    };
+    Some(
+        (constants.into_iter())
+            .map(|(name, value)| (name.to_string(), value))
+            .collect(),
+    )
+}
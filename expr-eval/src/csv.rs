@@ -0,0 +1,60 @@
+// just enough csv for --csv: fields split on commas, quoted with "" inside
+// quotes for a quote, and quoted fields may hold commas and line breaks
+
+// the records of src, each a list of its fields; Err is the line a quoted
+// field starts on and never ends
+pub fn records(src: &str) -> Result<Vec<Vec<String>>, usize> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    // line of the quote that opened the field
+    let (mut line, mut opened) = (1, 0);
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.next_if_eq(&'"').is_some() => field.push('"'),
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => {
+                quoted = true;
+                opened = line;
+            }
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+                line += 1;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if quoted {
+        return Err(opened);
+    }
+    // the last line needs no line break
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    // blank lines hold no record
+    records.retain(|record| record.iter().any(|field| !field.is_empty()));
+    Ok(records)
+}
+
+// fields as a csv line, quoting the ones that need it
+pub fn line(fields: &[String]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| match field.contains([',', '"', '\n', '\r']) {
+            true => format!("\"{}\"", field.replace('"', "\"\"")),
+            false => field.clone(),
+        })
+        .collect();
+    fields.join(",")
+}
@@ -0,0 +1,165 @@
+#[cfg(feature = "std")]
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::sync::{PoisonError, RwLock};
+
+#[cfg(feature = "std")]
+use serde_json::Value as Json;
+
+#[cfg(feature = "std")]
+use crate::{units::is_fixed_unit, ExprError, Result};
+
+// currencies of the loaded rates table and their size in its base currency,
+// NaN for one a later table left out; a name is leaked once so quantities can
+// hand it out like the names of the other units
+#[cfg(feature = "std")]
+static CURRENCIES: RwLock<Vec<(&'static str, f64)>> = RwLock::new(Vec::new());
+
+#[cfg(feature = "std")]
+pub(crate) fn currency_index(name: &str) -> Option<usize> {
+    let table = CURRENCIES.read().unwrap_or_else(PoisonError::into_inner);
+    (table.iter()).position(|&(n, size)| n == name && !size.is_nan())
+}
+
+// name and size of a currency currency_index found
+#[cfg(feature = "std")]
+pub(crate) fn currency(i: usize) -> (&'static str, f64) {
+    CURRENCIES.read().unwrap_or_else(PoisonError::into_inner)[i]
+}
+
+// no table to load without std
+#[cfg(not(feature = "std"))]
+pub(crate) fn currency_index(_name: &str) -> Option<usize> {
+    None
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn currency(_i: usize) -> (&'static str, f64) {
+    unreachable!("there are no currencies without std")
+}
+
+#[cfg(feature = "std")]
+fn invalid(reason: impl Into<String>) -> ExprError {
+    ExprError::InvalidRates {
+        reason: reason.into(),
+    }
+}
+
+/// Load the exchange rates currencies are converted with, after which a
+/// number can be followed by a currency code like any other unit. The table
+/// names a `base` currency and how much of each other currency one of it
+/// buys, as json or as toml:
+///
+/// ```toml
+/// base = "USD"
+///
+/// [rates]
+/// EUR = 0.92
+/// GBP = 0.79
+/// ```
+///
+/// A later table replaces the earlier one.
+///
+/// ```
+/// use expr_eval::{load_rates, Expr, Mode};
+///
+/// load_rates(r#"{"base": "USD", "rates": {"EUR": 0.5}}"#).unwrap();
+/// let total = Expr::new("100 USD + 50 EUR", Mode::Standard).eval().unwrap();
+/// assert_eq!("200 USD", total.to_string());
+/// ```
+#[cfg(feature = "std")]
+pub fn load_rates(src: &str) -> Result<()> {
+    let (base, rates) = match src.trim_start().starts_with('{') {
+        true => parse_json(src)?,
+        false => parse_toml(src)?,
+    };
+    let mut sizes = Vec::from([(base, 1.0)]);
+    for (code, rate) in rates {
+        if !rate.is_finite() || rate <= 0.0 {
+            return Err(invalid(format!("rate of `{}` is not positive", code)));
+        }
+        sizes.push((code, 1.0 / rate));
+    }
+    for (i, (code, _)) in sizes.iter().enumerate() {
+        if code.is_empty() || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(invalid(format!("`{}` is not a currency code", code)));
+        }
+        if is_fixed_unit(code) {
+            return Err(invalid(format!("`{}` is already a unit", code)));
+        }
+        if sizes[..i].iter().any(|(c, _)| c == code) {
+            return Err(invalid(format!("`{}` is given twice", code)));
+        }
+    }
+    // a currency keeps its index, quantities already made still refer to it
+    let mut table = CURRENCIES.write().unwrap_or_else(PoisonError::into_inner);
+    for (_, size) in table.iter_mut() {
+        *size = f64::NAN;
+    }
+    for (code, size) in sizes {
+        match table.iter_mut().find(|(c, _)| *c == code) {
+            Some(entry) => entry.1 = size,
+            None => table.push((Box::leak(code.into_boxed_str()), size)),
+        }
+    }
+    Ok(())
+}
+
+// {"base": "USD", "rates": {"EUR": 0.92}}
+#[cfg(feature = "std")]
+fn parse_json(src: &str) -> Result<(String, Vec<(String, f64)>)> {
+    let table: Json = serde_json::from_str(src).map_err(|e| invalid(e.to_string()))?;
+    let base = (table.get("base").and_then(Json::as_str))
+        .ok_or_else(|| invalid("missing `base` currency"))?;
+    let rates = (table.get("rates").and_then(Json::as_object))
+        .ok_or_else(|| invalid("missing `rates` table"))?;
+    let rates = (rates.iter())
+        .map(|(code, rate)| {
+            rate.as_f64()
+                .map(|rate| (code.clone(), rate))
+                .ok_or_else(|| invalid(format!("rate of `{}` is not a number", code)))
+        })
+        .collect::<Result<_>>()?;
+    Ok((base.to_string(), rates))
+}
+
+// the toml of the example above: base = "USD", then CODE = rate lines under
+// [rates]; # starts a comment
+#[cfg(feature = "std")]
+fn parse_toml(src: &str) -> Result<(String, Vec<(String, f64)>)> {
+    let mut base = None;
+    let mut rates = Vec::new();
+    let mut in_rates = false;
+    for (n, line) in src.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == "[rates]" {
+            in_rates = true;
+            continue;
+        }
+        let bad_line = || invalid(format!("can't read line {}: `{}`", n + 1, line));
+        let (key, value) = line.split_once('=').ok_or_else(bad_line)?;
+        let (key, value) = (key.trim(), value.trim());
+        if in_rates {
+            let rate = value.parse().map_err(|_| bad_line())?;
+            rates.push((key.to_string(), rate));
+        } else if key == "base" {
+            let code =
+                (value.strip_prefix('"').and_then(|v| v.strip_suffix('"'))).ok_or_else(bad_line)?;
+            base = Some(code.to_string());
+        } else {
+            return Err(bad_line());
+        }
+    }
+    Ok((
+        base.ok_or_else(|| invalid("missing `base` currency"))?,
+        rates,
+    ))
+}
@@ -0,0 +1,315 @@
+use alloc::{format, string::ToString};
+use core::{cmp::Ordering, fmt::Display};
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::ToPrimitive;
+
+use crate::MAX_DECIMAL_SCALE;
+
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use num_traits::Float;
+
+// fixed-point decimal, value = mantissa / 10^scale
+//
+// results never keep more than max_scale fractional digits, extra digits are
+// dropped with banker's rounding (round half to even)
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+    max_scale: u32,
+}
+
+fn pow10(exp: u32) -> Option<i128> {
+    10i128.checked_pow(exp)
+}
+
+// num / den rounded half to even
+fn div_round(num: i128, den: i128) -> Option<i128> {
+    let (num, den) = if den < 0 {
+        (num.checked_neg()?, den.checked_neg()?)
+    } else {
+        (num, den)
+    };
+    let quot = num.checked_div(den)?;
+    let rem = (num % den).unsigned_abs();
+    let half = den.unsigned_abs() - rem;
+    let away = match rem.cmp(&half) {
+        Ordering::Greater => true,
+        Ordering::Equal => quot % 2 != 0,
+        Ordering::Less => false,
+    };
+    if !away {
+        Some(quot)
+    } else if num < 0 {
+        quot.checked_sub(1)
+    } else {
+        quot.checked_add(1)
+    }
+}
+
+impl Decimal {
+    pub fn from_int(n: i128, max_scale: u32) -> Self {
+        Self {
+            mantissa: n,
+            scale: 0,
+            max_scale,
+        }
+    }
+
+    // parse digits with an optional sign, fraction and exponent like 12.50 or
+    // -1.5e3, None when it doesn't fit
+    pub fn parse(num: &str, max_scale: u32) -> Option<Self> {
+        if max_scale > MAX_DECIMAL_SCALE {
+            return None;
+        }
+        let (negative, num) = match num.strip_prefix('-') {
+            Some(num) => (true, num),
+            None => (false, num),
+        };
+        let (mantissa, exp) = match num.split_once(['e', 'E']) {
+            Some((mantissa, exp)) => (mantissa, exp.parse().ok()?),
+            None => (num, 0i64),
+        };
+        let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let mut mantissa: i128 = format!("{}{}", int, frac).parse().ok()?;
+        if negative {
+            mantissa = -mantissa;
+        }
+        let mut scale = (frac.len() as i64).checked_sub(exp)?;
+        if scale < 0 {
+            mantissa = mantissa.checked_mul(pow10(u32::try_from(-scale).ok()?)?)?;
+            scale = 0;
+        }
+        Self {
+            mantissa,
+            scale: u32::try_from(scale).ok()?,
+            max_scale,
+        }
+        .round(max_scale)
+    }
+
+    // same value with exactly scale fractional digits
+    fn rescale(self, scale: u32) -> Option<Self> {
+        let mantissa = match scale.cmp(&self.scale) {
+            Ordering::Equal => self.mantissa,
+            Ordering::Greater => self.mantissa.checked_mul(pow10(scale - self.scale)?)?,
+            // dropping more digits than an i128 holds leaves nothing
+            Ordering::Less => match pow10(self.scale - scale) {
+                Some(unit) => div_round(self.mantissa, unit)?,
+                None => 0,
+            },
+        };
+        Some(Self {
+            mantissa,
+            scale,
+            ..self
+        })
+    }
+
+    // keep at most scale fractional digits
+    fn round(self, scale: u32) -> Option<Self> {
+        if self.scale > scale {
+            self.rescale(scale)
+        } else {
+            Some(self)
+        }
+    }
+
+    // both operands at the same scale, remembering the wider limit
+    fn align(self, other: Self) -> Option<(Self, Self)> {
+        let scale = self.scale.max(other.scale);
+        let max_scale = self.max_scale.max(other.max_scale);
+        let l = Self { max_scale, ..self }.rescale(scale)?;
+        let r = Self { max_scale, ..other }.rescale(scale)?;
+        Some((l, r))
+    }
+
+    pub fn max_scale(&self) -> u32 {
+        self.max_scale
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa == 0
+    }
+
+    // the integer value, None when there is a fractional part
+    pub fn to_integer(self) -> Option<i128> {
+        let unit = pow10(self.scale)?;
+        (self.mantissa % unit == 0).then(|| self.mantissa / unit)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.mantissa as f64 / 10f64.powi(self.scale as i32)
+    }
+
+    pub fn abs(self) -> Option<Self> {
+        Some(Self {
+            mantissa: self.mantissa.checked_abs()?,
+            ..self
+        })
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        let (l, r) = self.align(other)?;
+        Some(Self {
+            mantissa: l.mantissa.checked_add(r.mantissa)?,
+            ..l
+        })
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        let (l, r) = self.align(other)?;
+        Some(Self {
+            mantissa: l.mantissa.checked_sub(r.mantissa)?,
+            ..l
+        })
+    }
+
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        let max_scale = self.max_scale.max(other.max_scale);
+        Self {
+            mantissa: self.mantissa.checked_mul(other.mantissa)?,
+            scale: self.scale + other.scale,
+            max_scale,
+        }
+        .round(max_scale)
+    }
+
+    // always rounded to max_scale digits, trailing zeros dropped; None on overflow
+    // or a zero divisor
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        if other.is_zero() {
+            return None;
+        }
+        let max_scale = self.max_scale.max(other.max_scale);
+        // mantissa / 10^max_scale = (l / 10^ls) / (r / 10^rs)
+        let exp = max_scale as i64 + other.scale as i64 - self.scale as i64;
+        let (num, den) = if exp >= 0 {
+            (
+                self.mantissa.checked_mul(pow10(exp as u32)?)?,
+                other.mantissa,
+            )
+        } else {
+            (
+                self.mantissa,
+                other.mantissa.checked_mul(pow10(-exp as u32)?)?,
+            )
+        };
+        Some(
+            Self {
+                mantissa: div_round(num, den)?,
+                scale: max_scale,
+                max_scale,
+            }
+            .trim(),
+        )
+    }
+
+    // the quotient rounded down to a whole number and the remainder with the
+    // sign of other; None on overflow or a zero divisor
+    pub fn checked_div_rem_floor(self, other: Self) -> Option<(Self, Self)> {
+        let (l, r) = self.align(other)?;
+        let (mut q, mut m) = (
+            l.mantissa.checked_div(r.mantissa)?,
+            l.mantissa.checked_rem(r.mantissa)?,
+        );
+        if m != 0 && (m < 0) != (r.mantissa < 0) {
+            q -= 1;
+            m += r.mantissa;
+        }
+        let rem = Self { mantissa: m, ..l }.trim();
+        Some((Self::from_int(q, l.max_scale), rem))
+    }
+
+    // rounded to places digits after the point, round taking the value in
+    // units of the last digit kept to a whole number; negative places round to
+    // tens, hundreds and so on
+    pub(crate) fn round_to(
+        self,
+        places: i32,
+        round: impl Fn(&BigRational) -> BigRational,
+    ) -> Option<Self> {
+        if places >= 0 && places as u32 >= self.scale {
+            return Some(self);
+        }
+        let unit = (self.scale as i64 - places as i64) as u32;
+        let value = BigRational::new(self.mantissa.into(), BigInt::from(10).pow(unit));
+        let n = round(&value).to_integer();
+        let (n, scale) = match u32::try_from(places) {
+            Ok(places) => (n, places),
+            Err(_) => (n * BigInt::from(10).pow(places.unsigned_abs()), 0),
+        };
+        Some(
+            Self {
+                mantissa: n.to_i128()?,
+                scale,
+                max_scale: self.max_scale,
+            }
+            .trim(),
+        )
+    }
+
+    // integer powers by squaring, negative ones through division
+    pub fn checked_pow(self, exp: i128) -> Option<Self> {
+        let mut result = Self::from_int(1, self.max_scale);
+        let mut base = self;
+        let mut n = exp.unsigned_abs();
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result.checked_mul(base)?;
+            }
+            n >>= 1;
+            if n > 0 {
+                base = base.checked_mul(base)?;
+            }
+        }
+        if exp < 0 {
+            result = Self::from_int(1, self.max_scale).checked_div(result)?;
+        }
+        Some(result)
+    }
+
+    // drop trailing fractional zeros
+    fn trim(mut self) -> Self {
+        while self.scale > 0 && self.mantissa % 10 == 0 {
+            self.mantissa /= 10;
+            self.scale -= 1;
+        }
+        self
+    }
+}
+
+// equal by value, 0.30 == 0.3
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        match self.align(*other) {
+            Some((l, r)) => l.mantissa == r.mantissa,
+            None => false,
+        }
+    }
+}
+
+// ordered by value too, None when aligning the scales overflows
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let (l, r) = self.align(*other)?;
+        Some(l.mantissa.cmp(&r.mantissa))
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (int, frac) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, int, frac)
+    }
+}
@@ -0,0 +1,188 @@
+use alloc::{boxed::Box, string::ToString, vec};
+
+use crate::{Ast, ExprError, Result, Span, Token, Value};
+
+fn num(n: i64) -> Ast {
+    Ast::Num(Value::Int(n))
+}
+
+fn binary(op: Token, lhs: Ast, rhs: Ast) -> Ast {
+    Ast::BinaryOp {
+        op,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+        span: Span::default(),
+    }
+}
+
+fn is_zero(ast: &Ast) -> bool {
+    matches!(ast, Ast::Num(Value::Int(0)))
+}
+
+// products and quotients of derivative terms, a zero term drops out so
+// constant parts don't leave 0 * x behind
+fn mul(lhs: Ast, rhs: Ast) -> Ast {
+    if is_zero(&lhs) || is_zero(&rhs) {
+        return num(0);
+    }
+    binary(Token::Multiply, lhs, rhs)
+}
+
+fn div(lhs: Ast, rhs: Ast) -> Ast {
+    if is_zero(&lhs) {
+        return num(0);
+    }
+    binary(Token::Divide, lhs, rhs)
+}
+
+fn neg(operand: Ast) -> Ast {
+    Ast::Unary {
+        op: Token::Minus,
+        operand: Box::new(operand),
+        span: Span::default(),
+    }
+}
+
+fn call(name: &str, arg: Ast) -> Ast {
+    Ast::Call {
+        name: name.into(),
+        args: vec![arg],
+        span: Span::default(),
+    }
+}
+
+impl Ast {
+    // whether the variable appears anywhere in the tree
+    fn depends_on(&self, var: &str) -> bool {
+        match self {
+            Ast::Num(_) => false,
+            Ast::Var { name, .. } => name == var,
+            Ast::Unary { operand, .. } => operand.depends_on(var),
+            Ast::BinaryOp { lhs, rhs, .. } => lhs.depends_on(var) || rhs.depends_on(var),
+            Ast::Paren(inner) => inner.depends_on(var),
+            Ast::Call { args, .. } => args.iter().any(|arg| arg.depends_on(var)),
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                ..
+            } => cond.depends_on(var) || then.depends_on(var) || otherwise.depends_on(var),
+        }
+    }
+
+    // symbolic derivative with respect to var, simplified
+    pub fn derivative(&self, var: &str) -> Result<Ast> {
+        Ok(self.derive(var)?.simplify())
+    }
+
+    fn derive(&self, var: &str) -> Result<Ast> {
+        if !self.depends_on(var) {
+            return Ok(num(0));
+        }
+        let d = match self {
+            // constants and other variables were handled above
+            Ast::Num(_) | Ast::Var { .. } => num(1),
+            Ast::Unary {
+                op: Token::Minus,
+                operand,
+                ..
+            } => neg(operand.derive(var)?),
+            Ast::Paren(inner) => inner.derive(var)?,
+            // piecewise, each piece differentiated on its own
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                span,
+            } => Ast::Cond {
+                cond: cond.clone(),
+                then: Box::new(then.derive(var)?),
+                otherwise: Box::new(otherwise.derive(var)?),
+                span: *span,
+            },
+            // conditions jump between true and false
+            Ast::Unary { op, span, .. } => {
+                return Err(ExprError::NotDifferentiable {
+                    name: op.to_string(),
+                    span: *span,
+                })
+            }
+            Ast::BinaryOp { op, span, .. }
+                if op.is_comparison()
+                    || op.is_logical()
+                    || op.is_bitwise()
+                    || matches!(op, Token::FloorDiv | Token::Mod | Token::Custom(_)) =>
+            {
+                return Err(ExprError::NotDifferentiable {
+                    name: op.to_string(),
+                    span: *span,
+                })
+            }
+            Ast::BinaryOp { op, lhs, rhs, .. } => {
+                let (u, v) = (*lhs.clone(), *rhs.clone());
+                let (du, dv) = (lhs.derive(var)?, rhs.derive(var)?);
+                match op {
+                    Token::Plus | Token::Minus => binary(op.clone(), du, dv),
+                    // u'v + uv'
+                    Token::Multiply => binary(Token::Plus, mul(du, v), mul(u, dv)),
+                    // (u'v - uv') / v^2
+                    Token::Divide => div(
+                        binary(Token::Minus, mul(du, v.clone()), mul(u, dv)),
+                        binary(Token::Power, v, num(2)),
+                    ),
+                    // v u^(v-1) u' for a constant exponent
+                    Token::Power if !v.depends_on(var) => mul(
+                        mul(
+                            v.clone(),
+                            binary(Token::Power, u, binary(Token::Minus, v, num(1))),
+                        ),
+                        du,
+                    ),
+                    // u^v (v' ln(u) + v u' / u)
+                    _ => mul(
+                        self.clone(),
+                        binary(
+                            Token::Plus,
+                            mul(dv, call("ln", u.clone())),
+                            div(mul(v, du), u),
+                        ),
+                    ),
+                }
+            }
+            // a vector element by element
+            Ast::Call { name, args, span } if name == "vec" => Ast::Call {
+                name: name.clone(),
+                args: args
+                    .iter()
+                    .map(|arg| arg.derive(var))
+                    .collect::<Result<_>>()?,
+                span: *span,
+            },
+            // chain rule, f'(u) u', functions of several arguments have no rule
+            Ast::Call { name, args, span } => {
+                let not_differentiable = || ExprError::NotDifferentiable {
+                    name: name.clone(),
+                    span: *span,
+                };
+                let [arg] = args.as_slice() else {
+                    return Err(not_differentiable());
+                };
+                let u = arg.clone();
+                let outer = match name.as_str() {
+                    "sqrt" => div(num(1), mul(num(2), call("sqrt", u))),
+                    "sin" => call("cos", u),
+                    "cos" => neg(call("sin", u)),
+                    "tan" => div(num(1), binary(Token::Power, call("cos", u), num(2))),
+                    "ln" => div(num(1), u),
+                    "log" => div(num(1), mul(u, call("ln", num(10)))),
+                    "exp" => call("exp", u),
+                    // the sign of u, undefined at 0
+                    "abs" => div(u.clone(), call("abs", u)),
+                    _ => return Err(not_differentiable()),
+                };
+                mul(outer, arg.derive(var)?)
+            }
+        };
+        Ok(d)
+    }
+}
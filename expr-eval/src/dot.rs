@@ -0,0 +1,87 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use core::fmt::Write;
+
+use crate::{Ast, Value};
+
+impl Ast {
+    /// The parse tree as a Graphviz DOT graph, one node per tree node with
+    /// edges to its operands from left to right, to see how precedence and
+    /// associativity grouped the expression.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode};
+    ///
+    /// let ast = Expr::new("1 - x", Mode::Standard).parse().unwrap();
+    /// assert_eq!(
+    ///     "digraph ast {\n  n0 [label=\"-\"];\n  n1 [label=\"1\"];\n  n0 -> n1;\n  \
+    ///      n2 [label=\"x\"];\n  n0 -> n2;\n}\n",
+    ///     ast.to_dot()
+    /// );
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph ast {\n");
+        let mut next = 0;
+        dot_node(self, &mut out, &mut next);
+        out.push_str("}\n");
+        out
+    }
+}
+
+// write the node and everything under it, numbered in preorder from next;
+// the node's own number is returned for the edge from its parent
+fn dot_node(ast: &Ast, out: &mut String, next: &mut usize) -> usize {
+    let id = *next;
+    *next += 1;
+    let label = match ast {
+        Ast::Num(Value::Float(n)) => format!("{:?}", n),
+        Ast::Num(n) => n.to_string(),
+        Ast::Var { name, .. } => name.clone(),
+        Ast::Unary { op, .. } | Ast::BinaryOp { op, .. } => op.to_string(),
+        Ast::Paren(_) => "( )".to_string(),
+        Ast::Call { name, .. } => format!("{}()", name),
+        Ast::Cond { .. } => "? :".to_string(),
+    };
+    // writing to a String can't fail
+    let _ = writeln!(out, "  n{} [label=\"{}\"];", id, escape(&label));
+    let mut edge = |child: &Ast, out: &mut String, label: Option<&str>| {
+        let child = dot_node(child, out, next);
+        let _ = match label {
+            Some(label) => writeln!(out, "  n{} -> n{} [label=\"{}\"];", id, child, label),
+            None => writeln!(out, "  n{} -> n{};", id, child),
+        };
+    };
+    match ast {
+        Ast::Num(_) | Ast::Var { .. } => {}
+        Ast::Unary { operand, .. } => edge(operand, out, None),
+        Ast::BinaryOp { lhs, rhs, .. } => {
+            edge(lhs, out, None);
+            edge(rhs, out, None);
+        }
+        Ast::Paren(inner) => edge(inner, out, None),
+        Ast::Call { args, .. } => {
+            for arg in args {
+                edge(arg, out, None);
+            }
+        }
+        // the branches are told apart by their edges
+        Ast::Cond {
+            cond,
+            then,
+            otherwise,
+            ..
+        } => {
+            edge(cond, out, Some("if"));
+            edge(then, out, Some("then"));
+            edge(otherwise, out, Some("else"));
+        }
+    }
+    id
+}
+
+// quotes and backslashes inside a quoted dot label
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
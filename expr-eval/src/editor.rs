@@ -0,0 +1,155 @@
+use std::{borrow::Cow, cell::Cell};
+
+use rustyline::{
+    completion::Completer,
+    highlight::{CmdKind, Highlighter},
+    hint::Hinter,
+    validate::Validator,
+    Context, Helper,
+};
+
+use expr_eval::{function_names, Mode, Span, Token, Tokenizer};
+
+// ansi colors of the kinds of tokens
+const NUMBER: &str = "\x1b[33m";
+const FUNCTION: &str = "\x1b[34m";
+const OPERATOR: &str = "\x1b[36m";
+// the bracket under the cursor and the one it pairs with
+const MATCHING: &str = "\x1b[1;7m";
+const RESET: &str = "\x1b[0m";
+
+// what the line editor of the repl knows about the language: it colors the
+// line as it is typed, marks the bracket matching the one at the cursor and
+// completes function and variable names with tab
+pub struct LineHelper {
+    pub mode: Mode,
+    // names the session has assigned, offered along with the functions
+    pub variables: Vec<String>,
+    // off once the line is accepted, so it stays in history unmarked
+    matching: Cell<bool>,
+}
+
+impl LineHelper {
+    pub fn new(mode: Mode) -> Self {
+        LineHelper {
+            mode,
+            variables: Vec::new(),
+            matching: Cell::new(true),
+        }
+    }
+}
+
+impl Highlighter for LineHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        // the line up to the first error is colored, the rest left as typed
+        let tokens: Vec<(Token, Span)> = Tokenizer::new(line, self.mode)
+            .map_while(|token| token.ok())
+            .collect();
+        if tokens.is_empty() {
+            return Cow::Borrowed(line);
+        }
+        let pair = match self.matching.get() {
+            true => matching_pair(&tokens, pos),
+            false => None,
+        };
+        let mut out = String::with_capacity(line.len() * 2);
+        let mut end = 0;
+        for (i, (token, span)) in tokens.iter().enumerate() {
+            out += &line[end..span.offset];
+            let color = match token {
+                _ if pair.is_some_and(|(a, b)| i == a || i == b) => MATCHING,
+                Token::Number(_) => NUMBER,
+                Token::Ident(name) if function_names().any(|f| f == name) => FUNCTION,
+                Token::Ident(_) => "",
+                _ => OPERATOR,
+            };
+            match color {
+                "" => out += span.text(line),
+                _ => out += &format!("{}{}{}", color, span.text(line), RESET),
+            }
+            end = span.end();
+        }
+        out += &line[end..];
+        Cow::Owned(out)
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize, kind: CmdKind) -> bool {
+        self.matching.set(kind != CmdKind::ForcedRefresh);
+        true
+    }
+}
+
+// indices of the bracket at the cursor, or just before it, and its partner
+fn matching_pair(tokens: &[(Token, Span)], pos: usize) -> Option<(usize, usize)> {
+    let at = |pos: usize| tokens.iter().position(|(_, span)| span.offset == pos);
+    let i = at(pos)
+        .filter(|&i| is_bracket(&tokens[i].0))
+        .or_else(|| pos.checked_sub(1).and_then(at))
+        .filter(|&i| is_bracket(&tokens[i].0))?;
+    let (open, close, forward) = match tokens[i].0 {
+        Token::LeftParen => (Token::LeftParen, Token::RightParen, true),
+        Token::RightParen => (Token::LeftParen, Token::RightParen, false),
+        Token::LeftBrace => (Token::LeftBrace, Token::RightBrace, true),
+        _ => (Token::LeftBrace, Token::RightBrace, false),
+    };
+    let mut depth = 0;
+    let candidates: Box<dyn Iterator<Item = usize>> = match forward {
+        true => Box::new(i..tokens.len()),
+        false => Box::new((0..=i).rev()),
+    };
+    for j in candidates {
+        if tokens[j].0 == open {
+            depth += 1;
+        } else if tokens[j].0 == close {
+            depth -= 1;
+        }
+        if depth == 0 {
+            return Some((i, j));
+        }
+    }
+    None
+}
+
+fn is_bracket(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::LeftParen | Token::RightParen | Token::LeftBrace | Token::RightBrace
+    )
+}
+
+impl Completer for LineHelper {
+    type Candidate = String;
+
+    // the name being typed at the cursor, completed to a function or a
+    // variable
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos]
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map_or(0, |i| i + 1);
+        let word = &line[start..pos];
+        if word.is_empty() || word.starts_with(|c: char| c.is_ascii_digit()) {
+            return Ok((pos, Vec::new()));
+        }
+        let mut names: Vec<String> = function_names()
+            .map(|f| format!("{}(", f))
+            .chain(self.variables.iter().cloned())
+            .filter(|name| name.starts_with(word))
+            .collect();
+        names.sort();
+        names.dedup();
+        Ok((start, names))
+    }
+}
+
+impl Hinter for LineHelper {
+    type Hint = String;
+}
+
+impl Validator for LineHelper {}
+
+impl Helper for LineHelper {}
@@ -0,0 +1,79 @@
+use alloc::string::ToString;
+
+use crate::{Ast, Env, Token, Value};
+
+// points the two sides are evaluated at when their forms differ
+const SAMPLES: usize = 32;
+// relative difference still taken as equal, for float rounding
+const TOLERANCE: f64 = 1e-9;
+
+impl Ast {
+    /// Whether the two expressions are algebraically equal, like
+    /// `2 * (x + 1)` and `x + x + 2`. Their canonical forms are compared
+    /// first, and when those differ the two are evaluated at random points: equal at every one is taken as equal.
+    /// The points are the same on every call, so the answer is too.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode};
+    ///
+    /// let parse = |src| Expr::new(src, Mode::Standard).parse().unwrap();
+    /// assert!(parse("(x + 1)^2").equivalent(&parse("x^2 + 2*x + 1")));
+    /// assert!(!parse("(x + 1)^2").equivalent(&parse("x^2 + 1")));
+    /// ```
+    pub fn equivalent(&self, other: &Ast) -> bool {
+        if self.canonicalize().to_string() == other.canonicalize().to_string() {
+            return true;
+        }
+        let mut names = self.variables();
+        names.extend(other.variables());
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        let mut compared = 0;
+        for _ in 0..SAMPLES {
+            let env: Env = (names.iter())
+                .map(|name| (name.clone(), Value::Float(random.next() * 20.0 - 10.0)))
+                .collect();
+            match (self.eval_with(&env), other.eval_with(&env)) {
+                (Ok(a), Ok(b)) if close(&a, &b) => compared += 1,
+                // both undefined there, like 1 / x and 2 / (2 * x) at 0
+                (Err(_), Err(_)) => {}
+                _ => return false,
+            }
+        }
+        compared > 0
+    }
+}
+
+// equal values, numbers up to float rounding
+fn close(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Text(a), Value::Text(b)) => a == b,
+        (Value::Vector(a), Value::Vector(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| close(a, b))
+        }
+        (Value::Bool(_) | Value::Text(_) | Value::Vector(_), _)
+        | (_, Value::Bool(_) | Value::Text(_) | Value::Vector(_)) => false,
+        // the difference also puts quantities in the same units
+        _ => match Token::Minus.compute(a.clone(), b.clone(), Default::default()) {
+            Ok(diff) => {
+                let scale = a.as_f64().abs().max(b.as_f64().abs()).max(1.0);
+                diff.as_f64().abs() <= TOLERANCE * scale
+                    || (a.as_f64().is_nan() && b.as_f64().is_nan())
+            }
+            Err(_) => false,
+        },
+    }
+}
+
+// xorshift, its own generator so rand() is left alone
+struct Random(u64);
+
+impl Random {
+    // uniform in [0, 1)
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,80 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+};
+use core::ffi::{c_char, c_int, CStr};
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{Env, Expr, Mode, Value};
+
+// what expr_eval returns, the ones after OK with a message in err
+const OK: c_int = 0;
+// the source doesn't parse or evaluate, or gives no plain number
+const EVAL_ERROR: c_int = 1;
+// a null pointer, or source that isn't utf-8
+const BAD_ARGUMENT: c_int = 2;
+// a bug in the evaluator, kept from unwinding into the caller
+const PANIC: c_int = 3;
+
+/// Evaluate the statements of a C string in standard mode and store the
+/// value of the last one in `*out`, for C, C++ or Python through ctypes. It
+/// returns 0 on success; otherwise `*out` is left alone and the message of
+/// the error, cut to fit, is written to `err` with a NUL after it, when
+/// `err` isn't null and `err_len` isn't 0. See `include/expr_eval.h`.
+///
+/// # Safety
+///
+/// `src` is null or a NUL-terminated string, `out` is null or valid for a
+/// write, and `err` is null or valid for writes of `err_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn expr_eval(
+    src: *const c_char,
+    out: *mut f64,
+    err: *mut c_char,
+    err_len: usize,
+) -> c_int {
+    let (code, message) = match (src.is_null(), out.is_null()) {
+        (true, _) => (BAD_ARGUMENT, "source is a null pointer".to_string()),
+        (_, true) => (BAD_ARGUMENT, "out is a null pointer".to_string()),
+        _ => match CStr::from_ptr(src).to_str() {
+            Err(_) => (BAD_ARGUMENT, "source is not utf-8".to_string()),
+            Ok(src) => match panic::catch_unwind(AssertUnwindSafe(|| eval_number(src))) {
+                Ok(Ok(value)) => {
+                    *out = value;
+                    return OK;
+                }
+                Ok(Err(message)) => (EVAL_ERROR, message),
+                Err(_) => (PANIC, "internal error".to_string()),
+            },
+        },
+    };
+    write_message(&message, err, err_len);
+    code
+}
+
+// the value of the last statement as a double, only for plain numbers
+fn eval_number(src: &str) -> Result<f64, String> {
+    let value = Expr::new(src, Mode::Standard)
+        .parse_script()
+        .and_then(|script| script.eval(&mut Env::new()))
+        .map_err(|err| err.to_string())?;
+    match value {
+        Value::Int(_) | Value::Big(_) | Value::Ratio(_) | Value::Dec(_) | Value::Float(_) => {
+            Ok(value.as_f64())
+        }
+        _ => Err(format!("the result `{}` is not a plain number", value)),
+    }
+}
+
+// as much of message as fits in err with the NUL, never splitting a character
+unsafe fn write_message(message: &str, err: *mut c_char, err_len: usize) {
+    if err.is_null() || err_len == 0 {
+        return;
+    }
+    let mut n = message.len().min(err_len - 1);
+    while !message.is_char_boundary(n) {
+        n -= 1;
+    }
+    core::ptr::copy_nonoverlapping(message.as_ptr().cast::<c_char>(), err, n);
+    *err.add(n) = 0;
+}
@@ -0,0 +1,133 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{trim_zeros, Value};
+
+/// How results print, for an embedder that wants them some other way than
+/// their `Display`. Only `number` has to be written: `format` hands it every
+/// number of a value, the elements of a vector, the bounds of an interval and
+/// the amount of a quantity, and prints the rest as usual. Any
+/// `Fn(&Value) -> String` is a formatter for its numbers.
+///
+/// ```
+/// use expr_eval::{Engineering, Expr, Mode, SiPrefix, Value, ValueFormatter};
+///
+/// let value = Expr::new("{1500, 0.00025, 42}", Mode::Standard).eval().unwrap();
+/// assert_eq!("{1.5e3, 250e-6, 42}", Engineering::default().format(&value));
+/// assert_eq!("{1.5k, 250µ, 42}", SiPrefix::default().format(&value));
+/// let percent = |n: &Value| format!("{}%", n.as_f64() * 100.0);
+/// assert_eq!("25%", percent.format(&Value::Float(0.25)));
+/// ```
+pub trait ValueFormatter {
+    // a number of any kind, int, big int, fraction, decimal or float
+    fn number(&self, n: &Value) -> String;
+
+    fn format(&self, value: &Value) -> String {
+        match value {
+            Value::Bool(_) | Value::Text(_) | Value::Function(_) => value.to_string(),
+            Value::Interval(i) => format!(
+                "[{}, {}]",
+                self.number(&Value::Float(i.lo())),
+                self.number(&Value::Float(i.hi()))
+            ),
+            Value::Quantity(q) => format!("{} {}", self.number(&Value::Float(q.value())), q.unit()),
+            Value::Vector(v) => {
+                let elems: Vec<String> = v.iter().map(|e| self.format(e)).collect();
+                format!("{{{}}}", elems.join(", "))
+            }
+            _ => self.number(value),
+        }
+    }
+}
+
+impl<F: Fn(&Value) -> String> ValueFormatter for F {
+    fn number(&self, n: &Value) -> String {
+        self(n)
+    }
+}
+
+/// Numbers with an exponent that is a multiple of 3, `1.5e3` and `250e-6`,
+/// rounded to `digits` significant digits. Ones between 1 and 1000 have no
+/// exponent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Engineering {
+    pub digits: usize,
+}
+
+impl Default for Engineering {
+    fn default() -> Self {
+        Self { digits: 6 }
+    }
+}
+
+impl ValueFormatter for Engineering {
+    fn number(&self, n: &Value) -> String {
+        match engineering(n.as_f64(), self.digits) {
+            Some((mantissa, 0)) => mantissa,
+            Some((mantissa, exp)) => format!("{}e{}", mantissa, exp),
+            None => n.to_string(),
+        }
+    }
+}
+
+/// Numbers with the SI prefix of their power of 1000, `1.5k` and `250µ`,
+/// rounded to `digits` significant digits. Ones past the prefixes, from
+/// yocto to yotta, get an exponent as with [`Engineering`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SiPrefix {
+    pub digits: usize,
+}
+
+impl Default for SiPrefix {
+    fn default() -> Self {
+        Self { digits: 6 }
+    }
+}
+
+// the prefixes from 10^-24 up to 10^24
+const PREFIXES: [&str; 17] = [
+    "y", "z", "a", "f", "p", "n", "µ", "m", "", "k", "M", "G", "T", "P", "E", "Z", "Y",
+];
+
+impl ValueFormatter for SiPrefix {
+    fn number(&self, n: &Value) -> String {
+        let Some((mantissa, exp)) = engineering(n.as_f64(), self.digits) else {
+            return n.to_string();
+        };
+        match usize::try_from(exp / 3 + 8)
+            .ok()
+            .and_then(|i| PREFIXES.get(i))
+        {
+            Some(prefix) => format!("{}{}", mantissa, prefix),
+            None => format!("{}e{}", mantissa, exp),
+        }
+    }
+}
+
+// n as a mantissa from 1 up to 1000 and the exponent of 10 it is multiplied
+// by, a multiple of 3; none for infinities and NaN
+fn engineering(n: f64, digits: usize) -> Option<(String, i32)> {
+    if !n.is_finite() {
+        return None;
+    }
+    if n == 0.0 {
+        return Some(("0".into(), 0));
+    }
+    // rounding first, so 999.9996 to 4 digits moves on to 1e3
+    let rounded = format!("{:.*e}", digits.max(1) - 1, n.abs());
+    let (mantissa, exp) = rounded.split_once('e').expect("exponent format");
+    let exp: i32 = exp.parse().expect("exponent format");
+    // the point moves right by the exponent past a multiple of 3
+    let shift = exp.rem_euclid(3) as usize;
+    let mut digits = mantissa.replace('.', "");
+    while digits.len() < shift + 1 {
+        digits.push('0');
+    }
+    let (whole, fraction) = digits.split_at(shift + 1);
+    let mantissa = trim_zeros(&format!("{}.{}", whole, fraction)).to_string();
+    let sign = if n < 0.0 { "-" } else { "" };
+    Some((format!("{}{}", sign, mantissa), exp - shift as i32))
+}
@@ -0,0 +1,72 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt::Display;
+
+use crate::{options::Budget, Arity, Ast, Env, EvalOptions, ExprError, Result, Span, Value};
+
+// calls of user functions nested deeper than this fail with RecursionLimit
+// rather than run out of stack
+pub(crate) const MAX_CALL_DEPTH: usize = 200;
+
+/// A function a script defined with `fn area(r) = pi * r^2`, kept in the
+/// environment under its name like a variable. The body reads its
+/// parameters, and any other name from the environment it is called in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Ast,
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "fn {}({}) = {}",
+            self.name,
+            self.params.join(", "),
+            self.body
+        )
+    }
+}
+
+impl Function {
+    // the body evaluated with the parameters bound to args on top of env
+    pub(crate) fn call(
+        &self,
+        args: Vec<Value>,
+        env: &Env,
+        options: &EvalOptions,
+        budget: &mut Budget,
+        span: Span,
+    ) -> Result<Value> {
+        if args.len() != self.params.len() {
+            return Err(ExprError::WrongArgCount {
+                name: self.name.clone(),
+                expected: Arity::Exactly(self.params.len()),
+                found: args.len(),
+                span,
+            });
+        }
+        budget.enter(&self.name, span)?;
+        let mut local = env.clone();
+        local.extend(self.params.iter().cloned().zip(args));
+        let value = self.body.eval_within(&local, options, budget);
+        budget.leave();
+        value.map_err(|err| err.at(span))
+    }
+}
+
+// a call of the function env holds under name, for the backends that take no
+// options; its body runs on the tree walker
+pub(crate) fn call_user(name: &str, args: Vec<Value>, env: &Env, span: Span) -> Result<Value> {
+    match env.get(name) {
+        Some(Value::Function(f)) => {
+            let options = EvalOptions::default();
+            f.call(args, env, &options, &mut Budget::new(&options), span)
+        }
+        _ => Err(ExprError::UnknownFunction {
+            name: name.into(),
+            span,
+        }),
+    }
+}
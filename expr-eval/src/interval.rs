@@ -0,0 +1,191 @@
+use core::{
+    f64::consts::{FRAC_PI_2, PI, TAU},
+    fmt::Display,
+    ops::{Add, Mul, Sub},
+};
+
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use num_traits::Float;
+
+// closed range of reals [lo, hi], every operation gives a range holding all
+// the results for operands anywhere in theirs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    lo: f64,
+    hi: f64,
+}
+
+// smallest interval holding all the values
+fn hull(values: impl IntoIterator<Item = f64>) -> Interval {
+    values.into_iter().fold(
+        Interval {
+            lo: f64::INFINITY,
+            hi: f64::NEG_INFINITY,
+        },
+        |acc, v| Interval {
+            lo: acc.lo.min(v),
+            hi: acc.hi.max(v),
+        },
+    )
+}
+
+impl Interval {
+    // None unless lo <= hi
+    pub fn new(lo: f64, hi: f64) -> Option<Self> {
+        (lo <= hi).then_some(Self { lo, hi })
+    }
+
+    pub fn point(x: f64) -> Self {
+        Self { lo: x, hi: x }
+    }
+
+    pub fn lo(&self) -> f64 {
+        self.lo
+    }
+
+    pub fn hi(&self) -> f64 {
+        self.hi
+    }
+
+    pub fn mid(&self) -> f64 {
+        self.lo + (self.hi - self.lo) / 2.0
+    }
+
+    pub fn contains(&self, x: f64) -> bool {
+        self.lo <= x && x <= self.hi
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.lo.is_finite() && self.hi.is_finite()
+    }
+
+    // None when the divisor holds 0
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        if other.contains(0.0) {
+            return None;
+        }
+        Some(self * hull([1.0 / other.lo, 1.0 / other.hi]))
+    }
+
+    // None when a negative power divides by a range holding 0; fractional
+    // powers of negative numbers have no real value and give NaN like floats
+    pub fn checked_pow(self, exp: Self) -> Option<Self> {
+        if exp.lo == exp.hi && exp.lo.fract() == 0.0 {
+            let n = exp.lo;
+            if n < 0.0 {
+                return Self::point(1.0).checked_div(self.checked_pow(Self::point(-n))?);
+            }
+            let (lo, hi) = (self.lo.powf(n), self.hi.powf(n));
+            // even powers fold the negative half over
+            if n % 2.0 == 0.0 && self.contains(0.0) {
+                return Some(hull([0.0, lo, hi]));
+            }
+            return Some(hull([lo, hi]));
+        }
+        if self.lo < 0.0 {
+            return Some(Self::point(f64::NAN));
+        }
+        if self.contains(0.0) && exp.lo < 0.0 {
+            return None;
+        }
+        // x^y with x >= 0 is monotonic in each operand, the corners bound it
+        Some(hull([
+            self.lo.powf(exp.lo),
+            self.lo.powf(exp.hi),
+            self.hi.powf(exp.lo),
+            self.hi.powf(exp.hi),
+        ]))
+    }
+
+    // apply a non-decreasing function to the bounds
+    pub fn map_increasing(self, f: impl Fn(f64) -> f64) -> Self {
+        Self {
+            lo: f(self.lo),
+            hi: f(self.hi),
+        }
+    }
+
+    pub fn abs(self) -> Self {
+        if self.contains(0.0) {
+            return Self {
+                lo: 0.0,
+                hi: self.lo.abs().max(self.hi.abs()),
+            };
+        }
+        hull([self.lo.abs(), self.hi.abs()])
+    }
+
+    // whether phase + k * period lies inside for some integer k
+    fn hits(&self, phase: f64, period: f64) -> bool {
+        let k = ((self.lo - phase) / period).ceil();
+        phase + k * period <= self.hi
+    }
+
+    pub fn sin(self) -> Self {
+        if self.hi - self.lo >= TAU {
+            return Self { lo: -1.0, hi: 1.0 };
+        }
+        let mut result = hull([self.lo.sin(), self.hi.sin()]);
+        if self.hits(FRAC_PI_2, TAU) {
+            result.hi = 1.0;
+        }
+        if self.hits(-FRAC_PI_2, TAU) {
+            result.lo = -1.0;
+        }
+        result
+    }
+
+    pub fn cos(self) -> Self {
+        (self + Self::point(FRAC_PI_2)).sin()
+    }
+
+    // None across a pole, where tan runs off to infinity
+    pub fn tan(self) -> Option<Self> {
+        if self.hi - self.lo >= PI || self.hits(FRAC_PI_2, PI) {
+            return None;
+        }
+        Some(self.map_increasing(f64::tan))
+    }
+}
+
+impl Add for Interval {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            lo: self.lo + other.lo,
+            hi: self.hi + other.hi,
+        }
+    }
+}
+
+impl Sub for Interval {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            lo: self.lo - other.hi,
+            hi: self.hi - other.lo,
+        }
+    }
+}
+
+impl Mul for Interval {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        hull([
+            self.lo * other.lo,
+            self.lo * other.hi,
+            self.hi * other.lo,
+            self.hi * other.hi,
+        ])
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "[{}, {}]", self.lo, self.hi)
+    }
+}
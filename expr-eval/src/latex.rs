@@ -0,0 +1,224 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use num_traits::Signed;
+
+use crate::{ast::is_negative, Assoc, Ast, Token, Value};
+
+// names typeset as greek letters, \pi for pi
+const GREEK: &[&str] = &[
+    "alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta", "lambda", "mu", "nu",
+    "xi", "pi", "rho", "sigma", "tau", "phi", "chi", "psi", "omega",
+];
+
+// functions latex has a command for, the others are \operatorname
+const COMMANDS: &[&str] = &["sin", "cos", "tan", "ln", "log", "exp", "max", "min"];
+
+// the elements of {a, b, ...}, written out or already evaluated
+fn elements(ast: &Ast) -> Option<Vec<Ast>> {
+    match ast {
+        Ast::Call { name, args, .. } if name == "vec" => Some(args.clone()),
+        Ast::Num(Value::Vector(v)) => Some(v.iter().cloned().map(Ast::Num).collect()),
+        _ => None,
+    }
+}
+
+// a vector is one row, a vector of vectors a matrix of rows
+fn matrix(ast: &Ast) -> String {
+    let elems = elements(ast).expect("a vector");
+    let row = |elems: &[Ast]| {
+        let cells: Vec<String> = elems.iter().map(Ast::to_latex).collect();
+        cells.join(" & ")
+    };
+    let rows: Option<Vec<String>> = elems.iter().map(|e| elements(e).map(|r| row(&r))).collect();
+    let body = rows.map_or_else(|| row(&elems), |rows| rows.join(r" \\ "));
+    format!(r"\begin{{bmatrix}} {} \end{{bmatrix}}", body)
+}
+
+impl Ast {
+    /// The expression as LaTeX math, fractions as `\frac{a}{b}` and powers
+    /// as `x^{n}`, with parentheses only where the grouping needs them.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode};
+    ///
+    /// let ast = Expr::new("(a + 1) / 2 * x^(n - 1)", Mode::Standard).parse().unwrap();
+    /// assert_eq!(r"\frac{a + 1}{2} \cdot x^{n - 1}", ast.to_latex());
+    /// ```
+    pub fn to_latex(&self) -> String {
+        match self {
+            Ast::Num(Value::Ratio(n)) if !n.is_integer() => {
+                let sign = if n.is_negative() { "-" } else { "" };
+                format!(
+                    r"{}\frac{{{}}}{{{}}}",
+                    sign,
+                    n.numer().magnitude(),
+                    n.denom()
+                )
+            }
+            Ast::Num(Value::Float(n)) => format!("{:?}", n),
+            Ast::Num(Value::Bool(b)) => format!(r"\mathrm{{{}}}", b),
+            Ast::Num(Value::Vector(_)) => matrix(self),
+            Ast::Num(n) => n.to_string(),
+            Ast::Var { name, .. } => variable(name),
+            Ast::Unary { op, operand, .. } => {
+                let symbol = match op {
+                    Token::Minus => "-".to_string(),
+                    Token::Not => r"\neg ".to_string(),
+                    Token::BitNot => r"\sim ".to_string(),
+                    op => format!(r"\operatorname{{{}}}", op),
+                };
+                format!(
+                    "{}{}",
+                    symbol,
+                    operand.latex_child(operand.precedence() < self.precedence())
+                )
+            }
+            // the bar of a fraction groups both sides already
+            Ast::BinaryOp {
+                op: Token::Divide,
+                lhs,
+                rhs,
+                ..
+            } => format!(r"\frac{{{}}}{{{}}}", lhs.to_latex(), rhs.to_latex()),
+            Ast::BinaryOp {
+                op: Token::FloorDiv,
+                lhs,
+                rhs,
+                ..
+            } => format!(
+                r"\left\lfloor\frac{{{}}}{{{}}}\right\rfloor",
+                lhs.to_latex(),
+                rhs.to_latex()
+            ),
+            // so does the raised exponent, only the base may need ( )
+            Ast::BinaryOp {
+                op: Token::Power,
+                lhs,
+                rhs,
+                ..
+            } => format!(
+                "{}^{{{}}}",
+                lhs.latex_child(!lhs.is_latex_atom()),
+                rhs.to_latex()
+            ),
+            Ast::BinaryOp { op, lhs, rhs, .. } => {
+                let prec = op.precedence();
+                let lhs_paren = lhs.latex_precedence() < prec
+                    || lhs.latex_precedence() == prec && op.assoc() == Assoc::Right;
+                let rhs_paren = rhs.latex_precedence() < prec
+                    || rhs.latex_precedence() == prec && op.assoc() == Assoc::Left;
+                format!(
+                    "{} {} {}",
+                    lhs.latex_child(lhs_paren),
+                    operator(op),
+                    rhs.latex_child(rhs_paren)
+                )
+            }
+            Ast::Paren(inner) => inner.to_latex(),
+            Ast::Call { name, args, .. } => {
+                let args: Vec<String> = args.iter().map(Ast::to_latex).collect();
+                let args = args.join(", ");
+                match name.as_str() {
+                    "vec" => matrix(self),
+                    "sqrt" => format!(r"\sqrt{{{}}}", args),
+                    "abs" => format!(r"\left|{}\right|", args),
+                    name if COMMANDS.contains(&name) => format!(r"\{}\left({}\right)", name, args),
+                    name => format!(r"\operatorname{{{}}}\left({}\right)", escape(name), args),
+                }
+            }
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                ..
+            } => format!(
+                r"\begin{{cases}} {} & \text{{if }} {} \\ {} & \text{{otherwise}} \end{{cases}}",
+                then.to_latex(),
+                cond.to_latex(),
+                otherwise.to_latex()
+            ),
+        }
+    }
+
+    // like precedence, but a fraction is set apart by its bar
+    fn latex_precedence(&self) -> i32 {
+        match self {
+            Ast::BinaryOp {
+                op: Token::Divide | Token::FloorDiv,
+                ..
+            } => i32::MAX,
+            Ast::Paren(inner) => inner.latex_precedence(),
+            _ => self.precedence(),
+        }
+    }
+
+    // what can be raised to a power without ( ) around it
+    fn is_latex_atom(&self) -> bool {
+        match self {
+            Ast::Num(Value::Ratio(n)) => n.is_integer() && !n.is_negative(),
+            Ast::Num(n) => !is_negative(n),
+            Ast::Var { .. } | Ast::Call { .. } => true,
+            Ast::Paren(inner) => inner.is_latex_atom(),
+            _ => false,
+        }
+    }
+
+    fn latex_child(&self, paren: bool) -> String {
+        if paren {
+            format!(r"\left({}\right)", self.to_latex())
+        } else {
+            self.to_latex()
+        }
+    }
+}
+
+fn operator(op: &Token) -> String {
+    let symbol = match op {
+        Token::Multiply => r"\cdot",
+        Token::Equal => "=",
+        Token::NotEqual => r"\neq",
+        Token::LessEqual => r"\leq",
+        Token::GreaterEqual => r"\geq",
+        Token::And => r"\land",
+        Token::Or => r"\lor",
+        Token::BitAnd => r"\mathbin{\&}",
+        Token::BitOr => r"\mathbin{|}",
+        Token::BitXor => r"\oplus",
+        Token::ShiftLeft => r"\ll",
+        Token::ShiftRight => r"\gg",
+        Token::Mod => r"\bmod",
+        Token::Custom(op) => return format!(r"\mathbin{{{}}}", escape(op.symbol())),
+        op => return op.to_string(),
+    };
+    symbol.to_string()
+}
+
+// one letter names are set in italics as they are, longer ones upright
+fn variable(name: &str) -> String {
+    if GREEK.contains(&name) {
+        format!(r"\{}", name)
+    } else if name.chars().count() == 1 {
+        name.to_string()
+    } else {
+        format!(r"\mathrm{{{}}}", escape(name))
+    }
+}
+
+// characters with a meaning of their own in latex
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '_' | '&' | '%' | '$' | '#' | '{' | '}' => {
+                out.push('\\');
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,3364 @@
+//! Parse and evaluate arithmetic expressions.
+//!
+//! ```
+//! use expr_eval::{Expr, Mode, Value};
+//!
+//! let value = Expr::new("2 ^ 10 - (1 + 3) * 6", Mode::Standard).eval().unwrap();
+//! assert_eq!(Value::Int(1000), value);
+//!
+//! // exact fractions instead of floats
+//! let value = Expr::new("1/3 + 1/6", Mode::Rational).eval().unwrap();
+//! assert_eq!("1/2", value.to_string());
+//! ```
+//!
+//! Errors carry the span of the source they are about:
+//!
+//! ```
+//! use expr_eval::{caret, Expr, Mode};
+//!
+//! let src = "1 + 4 / (2 - 2)";
+//! let err = Expr::new(src, Mode::Standard).eval().unwrap_err();
+//! assert_eq!("division by zero at offset 6", err.to_string());
+//! assert_eq!("1 + 4 / (2 - 2)\n      ^", caret(src, err.span()));
+//! ```
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+// the test harness runs on std either way
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
+mod ast;
+mod batch;
+mod bits;
+mod bytecode;
+#[cfg(feature = "std")]
+mod cache;
+mod canonical;
+mod check;
+mod closure;
+mod constants;
+mod currency;
+mod decimal;
+mod derivative;
+mod dot;
+mod equivalent;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod formatter;
+mod function;
+mod interval;
+mod latex;
+mod memo;
+mod native;
+mod numeric;
+mod operator;
+mod options;
+mod parser;
+mod pratt;
+mod primes;
+mod random;
+mod report;
+mod rpn;
+mod script;
+mod simplify;
+mod trace;
+mod units;
+mod vector;
+mod visit;
+mod vm;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+use alloc::{
+    boxed::Box,
+    collections::BTreeSet,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::{cmp::Ordering, fmt::Display, iter::Peekable, str::CharIndices};
+
+pub use ast::Ast;
+use ast::{binary_height, take};
+pub use batch::eval_batch;
+#[cfg(feature = "std")]
+pub use cache::ExprCache;
+pub use constants::constants;
+#[cfg(feature = "std")]
+pub use currency::load_rates;
+pub use decimal::Decimal;
+#[cfg(feature = "ffi")]
+pub use ffi::expr_eval;
+pub use formatter::{Engineering, SiPrefix, ValueFormatter};
+pub use function::Function;
+pub use interval::Interval;
+#[cfg(feature = "std")]
+pub use native::register_function;
+pub use native::NativeFn;
+use num_bigint::{BigInt, Sign};
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, Signed, ToPrimitive, Zero};
+// float functions like sqrt come from libm when there is no std to provide them
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use num_traits::Float;
+pub use numeric::Numeric;
+pub use operator::{Assoc, Operator, Operators};
+pub use options::{Arithmetic, EvalOptions, ExprLimits, Promotion, ZeroDivision};
+pub use parser::{Parser, Pratt, PrecedenceClimbing, ShuntingYard, PARSERS};
+pub use random::seed_random;
+pub use report::render;
+pub use script::{Script, Stmt};
+pub use trace::Step;
+use units::is_unit;
+pub use units::Quantity;
+pub use visit::{Fold, Visitor};
+pub use vm::{CompiledExpr, VmStep};
+#[cfg(feature = "wasm")]
+pub use wasm::evaluate;
+
+// type alias reduce Result complexity
+pub type Result<T> = core::result::Result<T, ExprError>;
+
+#[derive(Debug, Clone)]
+pub enum ExprError {
+    // a token that doesn't fit here, `expected` describes what would
+    UnexpectedToken {
+        expected: String,
+        found: String,
+        span: Span,
+    },
+    // a `(` never closed, or a `)` never opened
+    UnbalancedParen {
+        span: Span,
+    },
+    DivisionByZero {
+        span: Span,
+    },
+    Overflow {
+        span: Span,
+    },
+    // a character that starts no token, like `$`
+    InvalidChar {
+        ch: char,
+        span: Span,
+    },
+    // malformed numeric literal
+    InvalidNumber {
+        text: String,
+        span: Span,
+    },
+    // 1,234 or 1.234 with Locale::Auto, a separator that could group
+    // thousands or start the fraction
+    AmbiguousNumber {
+        text: String,
+        span: Span,
+    },
+    UnknownFunction {
+        name: String,
+        span: Span,
+    },
+    // a call with more or fewer arguments than the function takes
+    WrongArgCount {
+        name: String,
+        expected: Arity,
+        found: usize,
+        span: Span,
+    },
+    // a value of the wrong type or out of what it is used for, like the bool
+    // of `true * 3` or the 0 of `factor(0)`; operation is the operator with
+    // the values it was given, boxed to keep errors small, and found the type
+    // of the wrong one there, else found is the value itself
+    Type {
+        expected: String,
+        found: String,
+        operation: Option<Box<str>>,
+        span: Span,
+    },
+    // a function without a derivative rule
+    NotDifferentiable {
+        name: String,
+        span: Span,
+    },
+    // `=` after something that isn't a variable
+    InvalidAssignment {
+        span: Span,
+    },
+    // an operator or function the number type of Ast::eval_as has no meaning for
+    Unsupported {
+        what: String,
+        span: Span,
+    },
+    // a /* with no */
+    UnterminatedComment {
+        span: Span,
+    },
+    // a variable the environment has no value for
+    UnknownVariable {
+        name: String,
+        span: Span,
+    },
+    // quantities that can't be added or compared, like 3 m + 2 s
+    IncompatibleUnits {
+        left: String,
+        right: String,
+        span: Span,
+    },
+    // nesting past the parser's depth limit
+    TooDeep {
+        span: Span,
+    },
+    // a source of more than ExprLimits::max_tokens tokens, span is the first
+    // one past them
+    TooManyTokens {
+        max: usize,
+        span: Span,
+    },
+    // a number literal of more than ExprLimits::max_number_digits digits
+    TooManyDigits {
+        max: usize,
+        span: Span,
+    },
+    // evaluation gave up after the EvalOptions::max_ops operations
+    TooManyOps {
+        max: u64,
+        span: Span,
+    },
+    // or once its EvalOptions::timeout ran out
+    TimedOut {
+        span: Span,
+    },
+    // calls of user functions nested past the limit, like a recursion that
+    // never stops
+    RecursionLimit {
+        name: String,
+        span: Span,
+    },
+    // an exact number an operator would turn into a float, refused under
+    // EvalOptions::promotion
+    ImplicitPromotion {
+        value: String,
+        span: Span,
+    },
+    // a serialized tree that doesn't describe an expression
+    InvalidJson {
+        reason: String,
+    },
+    // an exchange rates table load_rates can't read
+    InvalidRates {
+        reason: String,
+    },
+    // bytes CompiledExpr::from_bytes can't read
+    InvalidBytecode {
+        reason: String,
+    },
+    // a backend of Ast::eval_checked coming to another value or error than
+    // the tree walk, both written out
+    BackendsDisagree {
+        backend: &'static str,
+        expected: Box<str>,
+        found: Box<str>,
+    },
+}
+
+impl ExprError {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::UnexpectedToken { span, .. }
+            | Self::UnbalancedParen { span }
+            | Self::DivisionByZero { span }
+            | Self::Overflow { span }
+            | Self::InvalidChar { span, .. }
+            | Self::InvalidNumber { span, .. }
+            | Self::AmbiguousNumber { span, .. }
+            | Self::UnknownFunction { span, .. }
+            | Self::WrongArgCount { span, .. }
+            | Self::Type { span, .. }
+            | Self::NotDifferentiable { span, .. }
+            | Self::InvalidAssignment { span }
+            | Self::Unsupported { span, .. }
+            | Self::UnterminatedComment { span }
+            | Self::UnknownVariable { span, .. }
+            | Self::IncompatibleUnits { span, .. }
+            | Self::TooDeep { span }
+            | Self::TooManyTokens { span, .. }
+            | Self::TooManyDigits { span, .. }
+            | Self::TooManyOps { span, .. }
+            | Self::TimedOut { span }
+            | Self::RecursionLimit { span, .. }
+            | Self::ImplicitPromotion { span, .. } => *span,
+            Self::InvalidJson { .. }
+            | Self::InvalidRates { .. }
+            | Self::InvalidBytecode { .. }
+            | Self::BackendsDisagree { .. } => Span::default(),
+        }
+    }
+
+    // the same error pointing at span instead, for one raised in the body of
+    // a user function, whose spans are in the source that defined it
+    pub(crate) fn at(mut self, at: Span) -> Self {
+        match &mut self {
+            Self::UnexpectedToken { span, .. }
+            | Self::UnbalancedParen { span }
+            | Self::DivisionByZero { span }
+            | Self::Overflow { span }
+            | Self::InvalidChar { span, .. }
+            | Self::InvalidNumber { span, .. }
+            | Self::AmbiguousNumber { span, .. }
+            | Self::UnknownFunction { span, .. }
+            | Self::WrongArgCount { span, .. }
+            | Self::Type { span, .. }
+            | Self::NotDifferentiable { span, .. }
+            | Self::InvalidAssignment { span }
+            | Self::Unsupported { span, .. }
+            | Self::UnterminatedComment { span }
+            | Self::UnknownVariable { span, .. }
+            | Self::IncompatibleUnits { span, .. }
+            | Self::TooDeep { span }
+            | Self::TooManyTokens { span, .. }
+            | Self::TooManyDigits { span, .. }
+            | Self::TooManyOps { span, .. }
+            | Self::TimedOut { span }
+            | Self::RecursionLimit { span, .. }
+            | Self::ImplicitPromotion { span, .. } => *span = at,
+            Self::InvalidJson { .. }
+            | Self::InvalidRates { .. }
+            | Self::InvalidBytecode { .. }
+            | Self::BackendsDisagree { .. } => {}
+        }
+        self
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ExprError {}
+
+impl Display for ExprError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::UnexpectedToken {
+                expected, found, ..
+            } if found.is_empty() => write!(f, "expected {}, found end of input", expected)?,
+            Self::UnexpectedToken {
+                expected, found, ..
+            } => write!(f, "expected {}, found `{}`", expected, found)?,
+            Self::UnbalancedParen { .. } => write!(f, "unbalanced parenthesis")?,
+            Self::DivisionByZero { .. } => write!(f, "division by zero")?,
+            Self::Overflow { .. } => write!(f, "arithmetic overflow")?,
+            Self::InvalidChar { ch, .. } => write!(f, "unexpected character `{}`", ch)?,
+            Self::InvalidNumber { text, .. } => write!(f, "invalid number `{}`", text)?,
+            Self::AmbiguousNumber { text, .. } => write!(
+                f,
+                "ambiguous number `{}`, the separator could be a thousands or a decimal one",
+                text
+            )?,
+            Self::UnknownFunction { name, .. } => write!(f, "unknown function `{}`", name)?,
+            Self::WrongArgCount {
+                name,
+                expected,
+                found,
+                ..
+            } => write!(f, "`{}` takes {}, found {}", name, expected, found)?,
+            // a type where another one is expected is named bare, a value
+            // that doesn't fit is quoted
+            Self::Type {
+                expected, found, ..
+            } if Value::TYPE_NAMES.contains(&&**expected) => {
+                write!(f, "expected {}, found {}", expected, found)?
+            }
+            Self::Type {
+                expected, found, ..
+            } => write!(f, "expected {}, found `{}`", expected, found)?,
+            Self::NotDifferentiable { name, .. } => write!(f, "no derivative rule for `{}`", name)?,
+            Self::InvalidAssignment { .. } => write!(f, "can only assign to a variable")?,
+            Self::Unsupported { what, .. } => write!(f, "`{}` is not supported here", what)?,
+            Self::UnterminatedComment { .. } => write!(f, "unterminated comment")?,
+            Self::UnknownVariable { name, .. } => write!(f, "unknown variable `{}`", name)?,
+            Self::IncompatibleUnits { left, right, .. } => {
+                write!(f, "incompatible units `{}` and `{}`", left, right)?
+            }
+            Self::TooDeep { .. } => write!(f, "expression nested too deeply")?,
+            Self::TooManyTokens { max, .. } => write!(f, "expression longer than {} tokens", max)?,
+            Self::TooManyDigits { max, .. } => write!(f, "number longer than {} digits", max)?,
+            Self::TooManyOps { max, .. } => {
+                write!(f, "evaluation took more than {} operations", max)?
+            }
+            Self::TimedOut { .. } => write!(f, "evaluation timed out")?,
+            Self::RecursionLimit { name, .. } => write!(
+                f,
+                "calls of `{}` nested more than {} deep",
+                name,
+                function::MAX_CALL_DEPTH
+            )?,
+            Self::ImplicitPromotion { value, .. } => {
+                write!(f, "`{}` would have to become a float", value)?
+            }
+            Self::InvalidJson { reason } => {
+                return write!(f, "invalid expression json: {}", reason)
+            }
+            Self::InvalidRates { reason } => return write!(f, "invalid rates table: {}", reason),
+            Self::InvalidBytecode { reason } => {
+                return write!(f, "invalid compiled expression: {}", reason)
+            }
+            Self::BackendsDisagree {
+                backend,
+                expected,
+                found,
+            } => {
+                return write!(
+                    f,
+                    "{} gives `{}` where the tree walk gives `{}`",
+                    backend, found, expected
+                )
+            }
+        }
+        // {:#} is the message alone, for render to say where
+        if f.alternate() {
+            return Ok(());
+        }
+        write!(f, " at offset {}", self.span().offset)?;
+        if let Self::Type {
+            operation: Some(operation),
+            ..
+        } = self
+        {
+            write!(f, " (`{}`)", operation)?;
+        }
+        Ok(())
+    }
+}
+
+// operand / 100 for the % after it, span being the %; out of parse_operators
+// so the frame of each nested call stays small
+fn percent(operand: Ast, span: Span) -> Ast {
+    Ast::BinaryOp {
+        op: Token::Divide,
+        lhs: Box::new(operand),
+        rhs: Box::new(Ast::Num(Value::Int(100))),
+        span,
+    }
+}
+
+// percent_of, with height going from the one of lhs to the one of the result
+fn combine(op: Token, lhs: Ast, rhs: Ast, is_percent: bool, height: &mut usize, span: Span) -> Ast {
+    let rewritten = is_percent && takes_percent(&op);
+    *height = binary_height(&op, &lhs, *height, rhs.height());
+    let ast = percent_of(op, lhs, rhs, is_percent, span);
+    // a rewritten one is a few levels more, and rare enough to measure
+    if rewritten {
+        *height = ast.height();
+    }
+    ast
+}
+
+// whether a percentage right of op is taken of the left operand, see percent_of
+fn takes_percent(op: &Token) -> bool {
+    matches!(
+        op,
+        Token::Plus | Token::Minus | Token::Multiply | Token::Divide
+    )
+}
+
+// lhs op rhs, with a percentage on the right, which the parser tells by the
+// `%` it read, taken the way a calculator does: 200 + 10% adds 10% of 200
+// and 50 / 10% is 50 / 0.1, worked out as 200 * (100 + 10) / 100 so
+// integers stay exact
+fn percent_of(op: Token, lhs: Ast, rhs: Ast, is_percent: bool, span: Span) -> Ast {
+    let node = |op, lhs, rhs| Ast::BinaryOp {
+        op,
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+        span,
+    };
+    let mut rhs = rhs;
+    let percent = match &mut rhs {
+        Ast::BinaryOp { lhs: percent, .. } if is_percent && takes_percent(&op) => take(percent),
+        _ => return node(op, lhs, rhs),
+    };
+    let hundred = Ast::Num(Value::Int(100));
+    match op {
+        Token::Plus | Token::Minus => {
+            let factor = node(op, hundred.clone(), percent);
+            node(Token::Divide, node(Token::Multiply, lhs, factor), hundred)
+        }
+        Token::Multiply => node(Token::Divide, node(Token::Multiply, lhs, percent), hundred),
+        _ => node(Token::Divide, node(Token::Multiply, lhs, hundred), percent),
+    }
+}
+
+// render the source with a caret line under the span of the error
+pub fn caret(src: &str, span: Span) -> String {
+    let pad = src[..span.offset].chars().count();
+    let width = span.text(src).chars().count().max(1);
+    format!("{}\n{}{}", src, " ".repeat(pad), "^".repeat(width))
+}
+
+/// Read a number literal on its own, the way an expression reads one: digits
+/// with `_` separators, a fraction, an exponent, units after it and a `-` in
+/// front, for other programs to take numbers in the same forms. Anything
+/// before or after the number is an UnexpectedToken error.
+///
+/// ```
+/// use expr_eval::{parse_number, Value};
+///
+/// assert_eq!(Value::Int(1_000_000), parse_number("1_000_000").unwrap());
+/// assert_eq!(Value::Float(-1500.0), parse_number(" -1.5e3 ").unwrap());
+/// assert_eq!("5 km", parse_number("5 km").unwrap().to_string());
+/// assert!(parse_number("1 + 2").is_err());
+/// assert!(parse_number("1__0").is_err());
+/// ```
+pub fn parse_number(src: &str) -> Result<Value> {
+    Expr::new(src, Mode::Standard).parse_number()
+}
+
+// 1,234 or 1.234 but not 0.125, 1234,567 or 1,5
+fn is_ambiguous(num: &str) -> bool {
+    let (whole, fraction) = num.split_once([',', '.']).expect("one separator");
+    (1..=3).contains(&whole.len()) && !whole.starts_with('0') && fraction.len() == 3
+}
+
+// how integer literals and integer arithmetic are represented
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Mode {
+    // i64, overflow is an error
+    #[default]
+    Standard,
+    // arbitrary precision integers
+    BigInt,
+    // exact fractions, even decimal literals
+    Rational,
+    // fixed-point decimals keeping at most this many fractional digits, up to
+    // MAX_DECIMAL_SCALE, see Mode::decimal
+    Decimal(u32),
+}
+
+// separators numbers are read with besides the `_` of source code; a
+// separator only belongs to a number when a digit follows it, so arguments
+// are still told apart in f(1.5, 2) or f(1,5, 2)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Locale {
+    // 1234.56
+    #[default]
+    Plain,
+    // 1,234.56, a comma followed by exactly three digits groups them, so
+    // f(1,234) is f(1234) and f(1,23) has two arguments
+    DecimalPoint,
+    // 1.234,56
+    DecimalComma,
+    // either, the separator that comes last being the decimal one; 1,234 and
+    // 1.234 could be both and are an AmbiguousNumber
+    Auto,
+}
+
+impl Locale {
+    // plain, point, comma or auto
+    pub fn from_name(name: &str) -> Option<Locale> {
+        match name {
+            "plain" => Some(Locale::Plain),
+            "point" => Some(Locale::DecimalPoint),
+            "comma" => Some(Locale::DecimalComma),
+            "auto" => Some(Locale::Auto),
+            _ => None,
+        }
+    }
+}
+
+impl Mode {
+    // standard, bigint, rational or decimal, the last with the default scale
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "standard" => Some(Mode::Standard),
+            "bigint" => Some(Mode::BigInt),
+            "rational" => Some(Mode::Rational),
+            "decimal" => Some(Mode::Decimal(DEFAULT_DECIMAL_SCALE)),
+            _ => None,
+        }
+    }
+
+    // decimal mode keeping scale fractional digits, None past the most a
+    // decimal can keep
+    pub fn decimal(scale: u32) -> Option<Mode> {
+        (scale <= MAX_DECIMAL_SCALE).then_some(Mode::Decimal(scale))
+    }
+}
+
+// base results are written in, integers only; anything else stays decimal
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Radix {
+    #[default]
+    Dec,
+    Hex,
+    Oct,
+    Bin,
+}
+
+impl Radix {
+    // dec, hex, oct or bin
+    pub fn from_name(name: &str) -> Option<Radix> {
+        match name {
+            "dec" => Some(Radix::Dec),
+            "hex" => Some(Radix::Hex),
+            "oct" => Some(Radix::Oct),
+            "bin" => Some(Radix::Bin),
+            _ => None,
+        }
+    }
+}
+
+// the word integers are cut to for a programmer's calculator display
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WordSize {
+    W8,
+    W16,
+    W32,
+    W64,
+}
+
+impl WordSize {
+    // 8, 16, 32 or 64
+    pub fn from_name(name: &str) -> Option<WordSize> {
+        match name {
+            "8" => Some(WordSize::W8),
+            "16" => Some(WordSize::W16),
+            "32" => Some(WordSize::W32),
+            "64" => Some(WordSize::W64),
+            _ => None,
+        }
+    }
+
+    pub fn bits(self) -> u32 {
+        match self {
+            WordSize::W8 => 8,
+            WordSize::W16 => 16,
+            WordSize::W32 => 32,
+            WordSize::W64 => 64,
+        }
+    }
+}
+
+// how float results are written; outside 1e-6 to 1e21 the shortest and the
+// significant digits forms switch to scientific notation
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Precision {
+    // as few digits as read back to the same float
+    #[default]
+    Shortest,
+    // this many significant digits, trailing zeros dropped
+    Digits(usize),
+    // this many digits after the point, always fixed notation
+    Places(usize),
+}
+
+impl Precision {
+    fn format(self, n: f64) -> String {
+        let scientific = n != 0.0 && !(1e-6..1e21).contains(&n.abs());
+        match self {
+            _ if !n.is_finite() => n.to_string(),
+            Precision::Places(places) => format!("{:.*}", places, n),
+            Precision::Shortest if scientific => format!("{:e}", n),
+            Precision::Shortest => n.to_string(),
+            Precision::Digits(digits) => {
+                let digits = digits.max(1);
+                // rounding first, 9.99 to 2 digits is 10 and not 9.99
+                let rounded = format!("{:.*e}", digits - 1, n);
+                let (mantissa, exp) = rounded.split_once('e').unwrap();
+                let exp: i32 = exp.parse().unwrap();
+                if scientific || !(-6..21).contains(&exp) {
+                    return format!("{}e{}", trim_zeros(mantissa), exp);
+                }
+                let places = (digits as i32 - 1 - exp).max(0) as usize;
+                let rounded: f64 = rounded.parse().unwrap();
+                trim_zeros(&format!("{:.*}", places, rounded)).to_string()
+            }
+        }
+    }
+}
+
+// 1.500 to 1.5 and 2.0 to 2, whole numbers stay as they are
+fn trim_zeros(n: &str) -> &str {
+    match n.contains('.') {
+        true => n.trim_end_matches('0').trim_end_matches('.'),
+        false => n,
+    }
+}
+
+// fractional digits kept by Expr::new_decimal
+pub const DEFAULT_DECIMAL_SCALE: u32 = 10;
+
+// the most fractional digits decimal mode keeps, 10^38 being the largest
+// power of ten the i128 mantissa holds; a literal read with more is an
+// overflow
+pub const MAX_DECIMAL_SCALE: u32 = 38;
+
+// how deep Expr parses nested parens, operands and calls before giving up with
+// TooDeep, shallow enough for the recursive parser and evaluators to handle
+// on a thread with a small stack, even in a debug build
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+// value produced by evaluating an expression, int until a float is involved;
+// two numbers meet at the wider of their kinds, int then big then ratio then
+// decimal then float, and a bool or text where a number must be is a
+// Type error
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Big(BigInt),
+    Ratio(BigRational),
+    Dec(Decimal),
+    Float(f64),
+    // result of a comparison
+    Bool(bool),
+    // every value between two bounds, from a [lo, hi] literal
+    Interval(Interval),
+    // a number with units like 5 km, never one without
+    Quantity(Quantity),
+    // words rather than a number, like the factorization from factor()
+    Text(String),
+    // {1, 2, 3}, numbers or, for a matrix, rows of them
+    Vector(Vec<Value>),
+    // what `fn f(x) = ...` defines, called rather than computed with
+    Function(Arc<Function>),
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Int(n) => write!(f, "{}", n),
+            Self::Big(n) => write!(f, "{}", n),
+            // BigRational prints p/q, or just p when q is 1
+            Self::Ratio(n) => write!(f, "{}", n),
+            Self::Dec(n) => write!(f, "{}", n),
+            Self::Float(n) => write!(f, "{}", n),
+            Self::Bool(b) => write!(f, "{}", b),
+            Self::Interval(i) => write!(f, "{}", i),
+            Self::Quantity(q) => write!(f, "{}", q),
+            Self::Text(s) => write!(f, "{}", s),
+            Self::Function(func) => write!(f, "{}", func),
+            Self::Vector(v) => {
+                let elems: Vec<String> = v.iter().map(ToString::to_string).collect();
+                write!(f, "{{{}}}", elems.join(", "))
+            }
+        }
+    }
+}
+
+impl Value {
+    // parse a literal like 42, 3.14 or 1.5e3 the way mode wants it, None when it
+    // doesn't fit
+    fn from_literal(num: &str, mode: Mode) -> Option<Value> {
+        let is_float = num.contains(['.', 'e', 'E']);
+        match mode {
+            Mode::Rational => parse_ratio(num).map(Value::Ratio),
+            Mode::Decimal(scale) => Decimal::parse(num, scale).map(Value::Dec),
+            // 1e400 is too big for a float rather than infinity
+            _ if is_float => num
+                .parse()
+                .ok()
+                .filter(|n: &f64| n.is_finite())
+                .map(Value::Float),
+            Mode::BigInt => num.parse().ok().map(Value::Big),
+            Mode::Standard => num.parse().ok().map(Value::Int),
+        }
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            Self::Int(n) => *n as f64,
+            Self::Big(n) => n.to_f64().unwrap_or(f64::NAN),
+            Self::Ratio(n) => n.to_f64().unwrap_or(f64::NAN),
+            Self::Dec(n) => n.to_f64(),
+            Self::Float(n) => *n,
+            // only reached after the operand was checked to be a number
+            Self::Bool(b) => *b as i32 as f64,
+            Self::Interval(i) => i.mid(),
+            Self::Quantity(q) => q.value(),
+            Self::Text(_) | Self::Vector(_) | Self::Function(_) => f64::NAN,
+        }
+    }
+
+    // a number as a quantity, plain when it has no units
+    fn to_quantity(&self) -> Quantity {
+        match self {
+            Self::Quantity(q) => q.clone(),
+            _ => Quantity::plain(self.as_f64()),
+        }
+    }
+
+    // a quantity whose units all cancelled is a plain float
+    fn from_quantity(q: Quantity) -> Value {
+        match q.is_plain() {
+            true => Value::Float(q.value()),
+            false => Value::Quantity(q),
+        }
+    }
+
+    /// Format an integer result in another base with a `0x`, `0o` or `0b`
+    /// prefix, anything else the same as `to_string`.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode, Radix};
+    ///
+    /// let value = Expr::new("255 + 1", Mode::Standard).eval().unwrap();
+    /// assert_eq!("0x100", value.to_string_radix(Radix::Hex));
+    /// assert_eq!("-0b101", Expr::new("-5", Mode::Standard).eval().unwrap().to_string_radix(Radix::Bin));
+    /// assert_eq!("2.5", Expr::new("5 / 2", Mode::Standard).eval().unwrap().to_string_radix(Radix::Hex));
+    /// ```
+    pub fn to_string_radix(&self, radix: Radix) -> String {
+        let (prefix, base) = match radix {
+            Radix::Dec => return self.to_string(),
+            Radix::Hex => ("0x", 16),
+            Radix::Oct => ("0o", 8),
+            Radix::Bin => ("0b", 2),
+        };
+        match self.to_integer() {
+            Some(n) if n.is_negative() => format!("-{}{}", prefix, (-n).to_str_radix(base)),
+            Some(n) => format!("{}{}", prefix, n.to_str_radix(base)),
+            None => self.to_string(),
+        }
+    }
+
+    /// Format a result with integers in `radix` and floats to `precision`,
+    /// the same as `to_string_radix` for anything else.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode, Precision, Radix};
+    ///
+    /// let eval = |src| Expr::new(src, Mode::Standard).eval().unwrap();
+    /// assert_eq!("0.333", eval("1 / 3").format(Radix::Dec, Precision::Digits(3)));
+    /// assert_eq!("2.50", eval("5 / 2").format(Radix::Hex, Precision::Places(2)));
+    /// assert_eq!("1e30", eval("10.0^30").format(Radix::Dec, Precision::Shortest));
+    /// assert_eq!("0xff", eval("255").format(Radix::Hex, Precision::Digits(3)));
+    /// ```
+    pub fn format(&self, radix: Radix, precision: Precision) -> String {
+        match self {
+            Self::Float(n) => precision.format(*n),
+            Self::Interval(i) => format!(
+                "[{}, {}]",
+                precision.format(i.lo()),
+                precision.format(i.hi())
+            ),
+            Self::Quantity(q) => format!("{} {}", precision.format(q.value()), q.unit()),
+            Self::Vector(v) => {
+                let elems: Vec<String> = v.iter().map(|e| e.format(radix, precision)).collect();
+                format!("{{{}}}", elems.join(", "))
+            }
+            _ => self.to_string_radix(radix),
+        }
+    }
+
+    /// Format a result the same as `format`, with integers cut to a word of
+    /// `word` like a programmer's calculator shows them: in hex, octal or
+    /// binary a negative one as the two's complement pattern of the word, in
+    /// decimal as the signed number the word holds.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode, Precision, Radix, WordSize};
+    ///
+    /// let eval = |src| Expr::new(src, Mode::Standard).eval().unwrap();
+    /// let (short, w8) = (Precision::Shortest, WordSize::W8);
+    /// assert_eq!("0xffffffff", eval("-1").format_word(Radix::Hex, short, WordSize::W32));
+    /// assert_eq!("0b11111110", eval("~1").format_word(Radix::Bin, short, w8));
+    /// assert_eq!("-1", eval("255").format_word(Radix::Dec, short, w8));
+    /// ```
+    pub fn format_word(&self, radix: Radix, precision: Precision, word: WordSize) -> String {
+        match self {
+            Self::Int(_) | Self::Big(_) => {
+                let bits = word.bits();
+                let modulus = BigInt::from(1) << bits;
+                let mut n = self.to_big().expect("an integer") % &modulus;
+                if n.is_negative() {
+                    n += &modulus;
+                }
+                if radix == Radix::Dec && n.bit(u64::from(bits) - 1) {
+                    n -= &modulus;
+                }
+                Value::Big(n).to_string_radix(radix)
+            }
+            Self::Vector(v) => {
+                let elems: Vec<String> = (v.iter())
+                    .map(|e| e.format_word(radix, precision, word))
+                    .collect();
+                format!("{{{}}}", elems.join(", "))
+            }
+            _ => self.format(radix, precision),
+        }
+    }
+
+    // a number as the interval holding just it
+    fn to_interval(&self) -> Interval {
+        match self {
+            Self::Interval(i) => *i,
+            _ => Interval::point(self.as_f64()),
+        }
+    }
+
+    // widen an integer to a big one, None for fractions and floats
+    fn to_big(&self) -> Option<BigInt> {
+        match self {
+            Self::Int(n) => Some(BigInt::from(*n)),
+            Self::Big(n) => Some(n.clone()),
+            _ => None,
+        }
+    }
+
+    // widen an integer or fraction to an exact fraction, None for decimals and floats
+    fn to_ratio(&self) -> Option<BigRational> {
+        match self {
+            Self::Ratio(n) => Some(n.clone()),
+            _ => self.to_big().map(BigRational::from_integer),
+        }
+    }
+
+    // widen an integer to a decimal, None when it doesn't fit or for other kinds
+    fn to_decimal(&self) -> Option<Decimal> {
+        match self {
+            Self::Dec(n) => Some(*n),
+            _ => Some(Decimal::from_int(self.to_big()?.to_i128()?, 0)),
+        }
+    }
+
+    // numeric ordering across kinds, None when a NaN or a boolean is involved
+    fn num_cmp(&self, other: &Value) -> Option<Ordering> {
+        if let (Some(l), Some(r)) = (self.to_big(), other.to_big()) {
+            return Some(l.cmp(&r));
+        }
+        if let (Some(l), Some(r)) = (self.to_ratio(), other.to_ratio()) {
+            return Some(l.cmp(&r));
+        }
+        if let (Some(l), Some(r)) = (self.to_decimal(), other.to_decimal()) {
+            return l.partial_cmp(&r);
+        }
+        if matches!(self, Self::Bool(_)) || matches!(other, Self::Bool(_)) {
+            return None;
+        }
+        self.as_f64().partial_cmp(&other.as_f64())
+    }
+
+    // the integer value of an exact number, None for fractions and floats
+    fn to_integer(&self) -> Option<BigInt> {
+        match self {
+            Self::Int(_) | Self::Big(_) => self.to_big(),
+            Self::Ratio(n) if n.is_integer() => Some(n.to_integer()),
+            Self::Dec(n) => n.to_integer().map(BigInt::from),
+            _ => None,
+        }
+    }
+
+    fn expect_integer(&self, span: Span) -> Result<BigInt> {
+        self.to_integer().ok_or_else(|| ExprError::Type {
+            expected: "an integer".into(),
+            found: self.to_string(),
+            operation: None,
+            span,
+        })
+    }
+
+    // the names of type_name
+    const TYPE_NAMES: [&'static str; 7] = [
+        "number", "bool", "text", "function", "vector", "interval", "quantity",
+    ];
+
+    // the type a Type error names, every kind of number being a number
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "bool",
+            Self::Text(_) => "text",
+            Self::Function(_) => "function",
+            Self::Vector(_) => "vector",
+            Self::Interval(_) => "interval",
+            Self::Quantity(_) => "quantity",
+            _ => "number",
+        }
+    }
+
+    // error for a boolean, text or function operand where only numbers make
+    // sense
+    fn expect_number(&self, span: Span) -> Result<()> {
+        match self {
+            Self::Bool(_) | Self::Text(_) | Self::Function(_) => Err(ExprError::Type {
+                expected: "number".into(),
+                found: self.type_name().into(),
+                operation: None,
+                span,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    // the boolean inside, or an error for a number where a condition is needed;
+    // there is no truthiness, 0 is not false and true is not 1
+    fn expect_bool(&self, span: Span) -> Result<bool> {
+        match self {
+            Self::Bool(b) => Ok(*b),
+            _ => Err(ExprError::Type {
+                expected: "bool".into(),
+                found: self.type_name().into(),
+                operation: None,
+                span,
+            }),
+        }
+    }
+
+    // None when a float ran off to infinity
+    fn finite(self) -> Option<Value> {
+        match self {
+            Self::Float(n) if n.is_infinite() => None,
+            Self::Interval(i) if !i.is_finite() => None,
+            Self::Quantity(q) if q.value().is_infinite() => None,
+            _ => Some(self),
+        }
+    }
+}
+
+// the largest power of ten a rational literal is scaled by either way, past
+// it the big ints would take long to build and are an overflow instead
+const MAX_RATIO_EXPONENT: i64 = 4096;
+
+// exact value of a literal, 1.25e1 is 125 / 10^2 * 10^1
+fn parse_ratio(num: &str) -> Option<BigRational> {
+    let (mantissa, exp) = match num.split_once(['e', 'E']) {
+        Some((mantissa, exp)) => (mantissa, exp.parse::<i64>().ok()?),
+        None => (num, 0),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let numer: BigInt = format!("{}{}", int, frac).parse().ok()?;
+    let exp = exp.checked_sub(frac.len() as i64)?;
+    if exp.abs() > MAX_RATIO_EXPONENT {
+        return None;
+    }
+    let ten = BigRational::from_integer(BigInt::from(10));
+    Some(BigRational::from_integer(numer) * ten.pow(exp as i32))
+}
+
+// names to values, a BTreeMap without std
+#[cfg(feature = "std")]
+type Map<V> = std::collections::HashMap<String, V>;
+#[cfg(not(feature = "std"))]
+type Map<V> = alloc::collections::BTreeMap<String, V>;
+
+// variable values an expression is evaluated against
+pub type Env = Map<Value>;
+
+// how many arguments a builtin takes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+    // from the first to the second, both included
+    Between(usize, usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, n: usize) -> bool {
+        match *self {
+            Arity::Exactly(k) => n == k,
+            Arity::AtLeast(k) => n >= k,
+            Arity::Between(lo, hi) => (lo..=hi).contains(&n),
+        }
+    }
+
+    // the fewest arguments, what a call in reverse Polish notation takes
+    pub fn min(&self) -> usize {
+        match *self {
+            Arity::Exactly(k) | Arity::AtLeast(k) | Arity::Between(k, _) => k,
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let n = match *self {
+            Arity::AtLeast(k) => {
+                write!(f, "at least ")?;
+                k
+            }
+            Arity::Between(lo, hi) if hi == lo + 1 => {
+                write!(f, "{} or ", lo)?;
+                hi
+            }
+            Arity::Between(lo, hi) => {
+                write!(f, "{} to ", lo)?;
+                hi
+            }
+            Arity::Exactly(k) => k,
+        };
+        write!(f, "{} argument{}", n, if n == 1 { "" } else { "s" })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Builtin {
+    // one number without units, None when the result overflows
+    Unary(fn(Value) -> Option<Value>),
+    // numbers already checked to be numbers and as many as the arity allows,
+    // span is the function name
+    Many(Arity, fn(Vec<Value>, Span) -> Result<Value>),
+    // same, drawing from the shared random generator, so a call is never
+    // folded into a constant or evaluated once for several
+    Random(Arity, fn(Vec<Value>, Span) -> Result<Value>),
+    // if(cond, then, else), parsed into a conditional so only the branch
+    // taken is evaluated
+    Cond,
+    // vec(a, b, ...), what {a, b, ...} is parsed into
+    Vector,
+    // the NativeFn registered at this index
+    Native(usize),
+}
+
+impl Builtin {
+    fn arity(&self) -> Arity {
+        match self {
+            Builtin::Unary(_) => Arity::Exactly(1),
+            Builtin::Many(arity, _) | Builtin::Random(arity, _) => *arity,
+            Builtin::Cond => Arity::Exactly(3),
+            Builtin::Vector => Arity::AtLeast(1),
+            Builtin::Native(i) => native::native(*i).arity(),
+        }
+    }
+
+    fn is_pure(&self) -> bool {
+        match self {
+            Builtin::Random(..) => false,
+            Builtin::Native(i) => native::native(*i).is_pure(),
+            _ => true,
+        }
+    }
+}
+
+// builtin function table, name -> implementation
+const FUNCTIONS: &[(&str, Builtin)] = &[
+    ("sqrt", Builtin::Unary(|v| Some(increasing(v, f64::sqrt)))),
+    (
+        "sin",
+        Builtin::Unary(|v| match v {
+            Value::Interval(i) => Some(Value::Interval(i.sin())),
+            _ => Some(Value::Float(v.as_f64().sin())),
+        }),
+    ),
+    (
+        "cos",
+        Builtin::Unary(|v| match v {
+            Value::Interval(i) => Some(Value::Interval(i.cos())),
+            _ => Some(Value::Float(v.as_f64().cos())),
+        }),
+    ),
+    (
+        "tan",
+        Builtin::Unary(|v| match v {
+            Value::Interval(i) => i.tan().map(Value::Interval),
+            _ => Some(Value::Float(v.as_f64().tan())),
+        }),
+    ),
+    ("ln", Builtin::Unary(|v| Some(increasing(v, f64::ln)))),
+    ("log", Builtin::Unary(|v| Some(increasing(v, f64::log10)))),
+    ("exp", Builtin::Unary(|v| increasing(v, f64::exp).finite())),
+    (
+        "abs",
+        Builtin::Unary(|v| match v {
+            Value::Int(n) => n.checked_abs().map(Value::Int),
+            Value::Big(n) => Some(Value::Big(n.abs())),
+            Value::Ratio(n) => Some(Value::Ratio(n.abs())),
+            Value::Dec(n) => n.abs().map(Value::Dec),
+            Value::Float(n) => Some(Value::Float(n.abs())),
+            Value::Interval(i) => Some(Value::Interval(i.abs())),
+            Value::Bool(_)
+            | Value::Quantity(_)
+            | Value::Text(_)
+            | Value::Vector(_)
+            | Value::Function(_) => unreachable!("rejected by apply_function"),
+        }),
+    ),
+    (
+        "max",
+        Builtin::Many(Arity::AtLeast(2), |args, span| {
+            extreme(args, Token::Greater, span)
+        }),
+    ),
+    (
+        "min",
+        Builtin::Many(Arity::AtLeast(2), |args, span| {
+            extreme(args, Token::Less, span)
+        }),
+    ),
+    // clamp(x, lo, hi) is min(max(x, lo), hi)
+    (
+        "clamp",
+        Builtin::Many(Arity::Exactly(3), |args, span| {
+            let [x, lo, hi] = <[Value; 3]>::try_from(args).unwrap();
+            let x = extreme(vec![x, lo], Token::Greater, span)?;
+            extreme(vec![x, hi], Token::Less, span)
+        }),
+    ),
+    (
+        "atan2",
+        Builtin::Many(Arity::Exactly(2), |args, span| {
+            for v in &args {
+                if let Value::Interval(_) | Value::Quantity(_) = v {
+                    return Err(ExprError::Type {
+                        expected: "a single number without units".into(),
+                        found: v.to_string(),
+                        operation: None,
+                        span,
+                    });
+                }
+            }
+            Ok(Value::Float(args[0].as_f64().atan2(args[1].as_f64())))
+        }),
+    ),
+    // round(x) to a whole number, round(x, 2) to two digits after the point
+    (
+        "floor",
+        Builtin::Many(Arity::Between(1, 2), |args, span| {
+            Rounding::Floor.apply(args, span)
+        }),
+    ),
+    (
+        "ceil",
+        Builtin::Many(Arity::Between(1, 2), |args, span| {
+            Rounding::Ceil.apply(args, span)
+        }),
+    ),
+    (
+        "round",
+        Builtin::Many(Arity::Between(1, 2), |args, span| {
+            Rounding::Round.apply(args, span)
+        }),
+    ),
+    (
+        "trunc",
+        Builtin::Many(Arity::Between(1, 2), |args, span| {
+            Rounding::Trunc.apply(args, span)
+        }),
+    ),
+    // int(x) drops the fraction, float(x) is the nearest float, the casts
+    // Promotion::Explicit asks for
+    ("int", Builtin::Many(Arity::Exactly(1), cast_int)),
+    ("float", Builtin::Many(Arity::Exactly(1), cast_float)),
+    // bits of 64-bit integers
+    ("popcount", Builtin::Many(Arity::Exactly(1), bits::popcount)),
+    ("clz", Builtin::Many(Arity::Exactly(1), bits::clz)),
+    ("ctz", Builtin::Many(Arity::Exactly(1), bits::ctz)),
+    ("rotl", Builtin::Many(Arity::Exactly(2), bits::rotl)),
+    ("rotr", Builtin::Many(Arity::Exactly(2), bits::rotr)),
+    ("gcd", Builtin::Many(Arity::AtLeast(2), primes::gcd)),
+    ("lcm", Builtin::Many(Arity::AtLeast(2), primes::lcm)),
+    ("isprime", Builtin::Many(Arity::Exactly(1), primes::isprime)),
+    ("factor", Builtin::Many(Arity::Exactly(1), primes::factor)),
+    // sum(1, 100) adds up every integer from 1 to 100, prod multiplies them
+    ("sum", Builtin::Many(Arity::Exactly(2), sum_range)),
+    ("prod", Builtin::Many(Arity::Exactly(2), prod_range)),
+    ("if", Builtin::Cond),
+    ("vec", Builtin::Vector),
+    // uniform in [0, 1)
+    (
+        "rand",
+        Builtin::Random(Arity::Exactly(0), |_, _| {
+            Ok(Value::Float(random::next_f64()))
+        }),
+    ),
+    // uniform integer from a to b, both included
+    ("randint", Builtin::Random(Arity::Exactly(2), randint)),
+];
+
+// how floor, ceil, round and trunc pick a whole number; round goes half away
+// from zero
+#[derive(Clone, Copy)]
+enum Rounding {
+    Floor,
+    Ceil,
+    Round,
+    Trunc,
+}
+
+// digits of a precision argument beyond this are refused
+const MAX_ROUNDING_DIGITS: u32 = 10_000;
+
+impl Rounding {
+    fn ratio(self, x: &BigRational) -> BigRational {
+        match self {
+            Rounding::Floor => x.floor(),
+            Rounding::Ceil => x.ceil(),
+            Rounding::Round => x.round(),
+            Rounding::Trunc => x.trunc(),
+        }
+    }
+
+    fn float(self, x: f64) -> f64 {
+        match self {
+            Rounding::Floor => x.floor(),
+            Rounding::Ceil => x.ceil(),
+            Rounding::Round => x.round(),
+            Rounding::Trunc => x.trunc(),
+        }
+    }
+
+    // to the digits after the point the second argument gives, none when it is
+    // left out; negative digits round to tens, hundreds and so on. The kind of
+    // number stays the same
+    fn apply(self, args: Vec<Value>, span: Span) -> Result<Value> {
+        let mut args = args.into_iter();
+        let x = args.next().expect("checked against the arity");
+        let digits = match args.next() {
+            Some(digits) => digits
+                .expect_integer(span)?
+                .to_i32()
+                .filter(|d| d.unsigned_abs() <= MAX_ROUNDING_DIGITS)
+                .ok_or(ExprError::Overflow { span })?,
+            None => 0,
+        };
+        let float = |x: f64| {
+            let scale = 10f64.powi(digits);
+            let y = self.float(x * scale) / scale;
+            // the scaled value has no digits left to round
+            if y.is_finite() {
+                y
+            } else {
+                x
+            }
+        };
+        Ok(match x {
+            Value::Int(_) | Value::Big(_) if digits >= 0 => x,
+            Value::Int(_) | Value::Big(_) | Value::Ratio(_) => {
+                let scale = BigRational::from_integer(BigInt::from(10).pow(digits.unsigned_abs()));
+                let scale = if digits < 0 { scale.recip() } else { scale };
+                let r = x.to_ratio().expect("an exact kind");
+                let r = self.ratio(&(r * &scale)) / scale;
+                match x {
+                    Value::Int(_) => {
+                        Value::Int((r.to_integer().to_i64()).ok_or(ExprError::Overflow { span })?)
+                    }
+                    Value::Big(_) => Value::Big(r.to_integer()),
+                    _ => Value::Ratio(r),
+                }
+            }
+            Value::Dec(n) => Value::Dec(
+                n.round_to(digits, |r| self.ratio(r))
+                    .ok_or(ExprError::Overflow { span })?,
+            ),
+            Value::Float(n) => Value::Float(float(n)),
+            Value::Interval(i) => Value::Interval(i.map_increasing(float)),
+            Value::Bool(_)
+            | Value::Quantity(_)
+            | Value::Text(_)
+            | Value::Vector(_)
+            | Value::Function(_) => {
+                return Err(ExprError::Type {
+                    expected: "a number without units".into(),
+                    found: x.to_string(),
+                    operation: None,
+                    span,
+                })
+            }
+        })
+    }
+}
+
+// products longer than this are refused rather than left running
+const MAX_PRODUCT_TERMS: u32 = 100_000;
+
+// sum and prod over their integer bounds, in the kind the bounds are
+fn fold_range(
+    args: Vec<Value>,
+    span: Span,
+    fold: impl Fn(BigInt, BigInt) -> Result<BigInt>,
+) -> Result<Value> {
+    let int = matches!(args[..], [Value::Int(_), Value::Int(_)]);
+    let (lo, hi) = (args[0].expect_integer(span)?, args[1].expect_integer(span)?);
+    narrow(fold(lo, hi)?, int, span)
+}
+
+// an integer result as i64 when the operands were, which it has to fit like
+// any other i64 arithmetic, else as a big int
+fn narrow(n: BigInt, int: bool, span: Span) -> Result<Value> {
+    match n.to_i64() {
+        Some(n) if int => Ok(Value::Int(n)),
+        None if int => Err(ExprError::Overflow { span }),
+        _ => Ok(Value::Big(n)),
+    }
+}
+
+// an empty range, lo > hi, sums to 0
+fn sum_range(args: Vec<Value>, span: Span) -> Result<Value> {
+    fold_range(args, span, |lo, hi| {
+        if lo > hi {
+            return Ok(BigInt::zero());
+        }
+        // count * (first + last) / 2
+        Ok((&hi - &lo + 1) * (lo + hi) / 2)
+    })
+}
+
+// an empty range multiplies to 1
+fn prod_range(args: Vec<Value>, span: Span) -> Result<Value> {
+    // i64 bounds multiply in i64, overflowing at the first term that doesn't
+    // fit rather than after a product thousands of digits long
+    if let [Value::Int(lo), Value::Int(hi)] = args[..] {
+        if lo <= 0 && 0 <= hi {
+            return Ok(Value::Int(0));
+        }
+        let prod = (lo..=hi).try_fold(1i64, |acc, n| acc.checked_mul(n));
+        return prod.map(Value::Int).ok_or(ExprError::Overflow { span });
+    }
+    fold_range(args, span, |lo, hi| {
+        if lo > hi {
+            return Ok(BigInt::from(1));
+        }
+        // a range through 0 needs no multiplying at all
+        if lo.sign() != Sign::Plus && hi.sign() != Sign::Minus {
+            return Ok(BigInt::zero());
+        }
+        let terms = (&hi - &lo).to_u32().filter(|n| *n < MAX_PRODUCT_TERMS);
+        let terms = terms.ok_or(ExprError::Overflow { span })?;
+        Ok((0..=terms).fold(BigInt::from(1), |acc, i| acc * (&lo + i)))
+    })
+}
+
+fn cast_int(args: Vec<Value>, span: Span) -> Result<Value> {
+    let x = expect_plain(args, span)?;
+    match Rounding::Trunc.apply(vec![x], span)? {
+        Value::Float(n) => narrow(
+            BigInt::from_f64(n).ok_or(ExprError::Overflow { span })?,
+            true,
+            span,
+        ),
+        Value::Ratio(n) => narrow(n.to_integer(), false, span),
+        Value::Dec(n) => narrow(
+            BigInt::from(n.to_integer().expect("truncated")),
+            false,
+            span,
+        ),
+        n => Ok(n),
+    }
+}
+
+fn cast_float(args: Vec<Value>, span: Span) -> Result<Value> {
+    Ok(Value::Float(expect_plain(args, span)?.as_f64()))
+}
+
+// the one argument of a cast, a number of any kind without units
+fn expect_plain(args: Vec<Value>, span: Span) -> Result<Value> {
+    match args.into_iter().next().expect("checked against the arity") {
+        x @ (Value::Int(_) | Value::Big(_) | Value::Ratio(_) | Value::Dec(_) | Value::Float(_)) => {
+            Ok(x)
+        }
+        x => Err(ExprError::Type {
+            expected: "a number without units".into(),
+            found: x.to_string(),
+            operation: None,
+            span,
+        }),
+    }
+}
+
+fn randint(args: Vec<Value>, span: Span) -> Result<Value> {
+    let int = matches!(args[..], [Value::Int(_), Value::Int(_)]);
+    let (lo, hi) = (args[0].expect_integer(span)?, args[1].expect_integer(span)?);
+    if lo > hi {
+        return Err(ExprError::Type {
+            expected: format!("an upper bound of at least {}", lo),
+            found: hi.to_string(),
+            operation: None,
+            span,
+        });
+    }
+    // 64 random bits are plenty for any range that fits a machine word
+    let width = &hi - &lo + 1;
+    let n: BigInt = lo + BigInt::from(random::next_u64()) % width;
+    match n.to_i64() {
+        Some(n) if int => Ok(Value::Int(n)),
+        _ => Ok(Value::Big(n)),
+    }
+}
+
+// the argument that compares `keep` to all others, the first of equal ones
+fn extreme(args: Vec<Value>, keep: Token, span: Span) -> Result<Value> {
+    let mut args = args.into_iter();
+    let mut best = args.next().expect("checked against the arity");
+    for v in args {
+        if keep.compare(&v, &best, span)? == Value::Bool(true) {
+            best = v;
+        }
+    }
+    Ok(best)
+}
+
+// a float function that never decreases, so on an interval it maps the bounds
+fn increasing(v: Value, f: fn(f64) -> f64) -> Value {
+    match v {
+        Value::Interval(i) => Value::Interval(i.map_increasing(f)),
+        _ => Value::Float(f(v.as_f64())),
+    }
+}
+
+fn builtin_index(name: &str) -> Option<usize> {
+    FUNCTIONS.iter().position(|(n, _)| *n == name)
+}
+
+// the builtins numbered first, then the registered native functions
+fn function_index(name: &str) -> Option<usize> {
+    builtin_index(name).or_else(|| native::native_index(name).map(|i| FUNCTIONS.len() + i))
+}
+
+fn function_at(i: usize) -> Builtin {
+    match FUNCTIONS.get(i) {
+        Some((_, func)) => *func,
+        None => Builtin::Native(i - FUNCTIONS.len()),
+    }
+}
+
+// the name the function numbered i is called by
+fn function_name(i: usize) -> String {
+    match FUNCTIONS.get(i) {
+        Some((name, _)) => name.to_string(),
+        None => native::native(i - FUNCTIONS.len()).name().to_string(),
+    }
+}
+
+fn lookup_function(name: &str) -> Option<Builtin> {
+    function_index(name).map(function_at)
+}
+
+// names of the builtin functions, for completing them in an editor
+pub fn function_names() -> impl Iterator<Item = &'static str> {
+    FUNCTIONS.iter().map(|(name, _)| *name)
+}
+
+fn check_arity(name: &str, func: Builtin, found: usize, span: Span) -> Result<()> {
+    match func.arity() {
+        arity if arity.accepts(found) => Ok(()),
+        expected => Err(ExprError::WrongArgCount {
+            name: name.to_string(),
+            expected,
+            found,
+            span,
+        }),
+    }
+}
+
+// the node for a call of func, a conditional for if()
+fn call_node(func: Builtin, name: String, args: Vec<Ast>, span: Span) -> Ast {
+    match <[Ast; 3]>::try_from(args) {
+        Ok([cond, then, otherwise]) if matches!(func, Builtin::Cond) => Ast::Cond {
+            cond: Box::new(cond),
+            then: Box::new(then),
+            otherwise: Box::new(otherwise),
+            span,
+        },
+        Ok(args) => Ast::Call {
+            name,
+            args: args.into(),
+            span,
+        },
+        Err(args) => Ast::Call { name, args, span },
+    }
+}
+
+// a one number function, sqrt({1, 4}) applies to every element
+fn apply_unary(f: fn(Value) -> Option<Value>, arg: Value, span: Span) -> Result<Value> {
+    match arg {
+        Value::Vector(v) => v
+            .into_iter()
+            .map(|x| apply_unary(f, x, span))
+            .collect::<Result<_>>()
+            .map(Value::Vector),
+        Value::Quantity(_) => Err(ExprError::Type {
+            expected: "a number without units".into(),
+            found: arg.to_string(),
+            operation: None,
+            span,
+        }),
+        _ => f(arg).ok_or(ExprError::Overflow { span }),
+    }
+}
+
+// call a builtin with as many arguments as its arity allows, span is the
+// function name for error reporting
+fn apply_function(func: Builtin, args: Vec<Value>, span: Span) -> Result<Value> {
+    // the condition of if() is a bool and its branches can be anything, a
+    // native function takes whatever it is given
+    if !matches!(func, Builtin::Cond | Builtin::Native(_)) {
+        for arg in &args {
+            arg.expect_number(span)?;
+        }
+    }
+    match func {
+        Builtin::Unary(f) => {
+            let arg = args.into_iter().next().expect("checked against the arity");
+            apply_unary(f, arg, span)
+        }
+        Builtin::Many(_, f) | Builtin::Random(_, f) => f(args, span),
+        Builtin::Vector => vector::vector(args, span),
+        Builtin::Native(i) => native::native(i).call(&args, span),
+        // only reached with both branches already evaluated
+        Builtin::Cond => {
+            let [cond, then, otherwise] =
+                <[Value; 3]>::try_from(args).expect("checked against the arity");
+            Ok(if cond.expect_bool(span)? {
+                then
+            } else {
+                otherwise
+            })
+        }
+    }
+}
+
+// Token enum to sign number, operator, ( )
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Number(Value),
+    Ident(String),
+    Plus,         // +
+    Minus,        // -
+    Multiply,     // *
+    Divide,       // /
+    Power,        // ^
+    FloorDiv,     // //, rounds toward negative infinity
+    Mod,          // %, takes the sign of the divisor; with no operand after it a percentage
+    Equal,        // ==
+    NotEqual,     // !=
+    Less,         // <
+    LessEqual,    // <=
+    Greater,      // >
+    GreaterEqual, // >=
+    And,          // &&
+    Or,           // ||
+    Not,          // !
+    BitAnd,       // &
+    BitOr,        // |
+    BitXor,       // ~^, ^ is already power
+    ShiftLeft,    // <<
+    ShiftRight,   // >>
+    BitNot,       // ~
+    Root,         // √, the square root of what follows
+    Question,     // ?
+    Colon,        // :
+    Assign,       // =
+    // +=, -=, *= or /=, assigning the variable the operator applied to it
+    CompoundAssign(Box<Token>),
+    Semicolon, // ; or a newline, ends a statement
+    // .. and ..=, between the bounds of a for loop, the second taking in the
+    // end as well
+    Range,
+    RangeInclusive,
+    LeftParen,  // (
+    RightParen, // )
+    LeftBrace,  // {, starts a vector
+    RightBrace, // }
+    Comma,      // , between function arguments
+    // one of the Operators given to Expr::with_operators
+    Custom(Arc<Operator>),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Number(n) => n.to_string(),
+                Self::Ident(name) => name.clone(),
+                Self::Plus => "+".to_string(),
+                Self::Minus => "-".to_string(),
+                Self::Multiply => "*".to_string(),
+                Self::Divide => "/".to_string(),
+                Self::Power => "^".to_string(),
+                Self::FloorDiv => "//".to_string(),
+                Self::Mod => "%".to_string(),
+                Self::Equal => "==".to_string(),
+                Self::NotEqual => "!=".to_string(),
+                Self::Less => "<".to_string(),
+                Self::LessEqual => "<=".to_string(),
+                Self::Greater => ">".to_string(),
+                Self::GreaterEqual => ">=".to_string(),
+                Self::And => "&&".to_string(),
+                Self::Or => "||".to_string(),
+                Self::Not => "!".to_string(),
+                Self::BitAnd => "&".to_string(),
+                Self::BitOr => "|".to_string(),
+                Self::BitXor => "~^".to_string(),
+                Self::ShiftLeft => "<<".to_string(),
+                Self::ShiftRight => ">>".to_string(),
+                Self::BitNot => "~".to_string(),
+                Self::Root => "√".to_string(),
+                Self::Question => "?".to_string(),
+                Self::Custom(op) => op.symbol().to_string(),
+                Self::Colon => ":".to_string(),
+                Self::Assign => "=".to_string(),
+                Self::CompoundAssign(op) => format!("{}=", op),
+                Self::Semicolon => ";".to_string(),
+                Self::Range => "..".to_string(),
+                Self::RangeInclusive => "..=".to_string(),
+                Self::LeftParen => "(".to_string(),
+                Self::RightParen => ")".to_string(),
+                Self::LeftBrace => "{".to_string(),
+                Self::RightBrace => "}".to_string(),
+                Self::Comma => ",".to_string(),
+            }
+        )
+    }
+}
+
+impl Token {
+    // what an operand can begin with: a number, a name, `(`, `{` or a prefix
+    // operator
+    fn starts_operand(&self) -> bool {
+        matches!(
+            self,
+            Token::Number(_) | Token::Ident(_) | Token::LeftParen | Token::LeftBrace | Token::Root
+        ) || self.is_prefix()
+    }
+
+    // binary operators, the ones parse_expr combines two sides with
+    pub fn is_operator(&self) -> bool {
+        if let Token::Custom(op) = self {
+            return !op.is_prefix();
+        }
+        matches!(
+            self,
+            Token::Plus
+                | Token::Minus
+                | Token::Multiply
+                | Token::Divide
+                | Token::Power
+                | Token::FloorDiv
+                | Token::Mod
+        ) || self.is_comparison()
+            || self.is_logical()
+            || self.is_bitwise()
+    }
+
+    // operators that only make sense on integers
+    pub fn is_bitwise(&self) -> bool {
+        matches!(
+            self,
+            Token::BitAnd | Token::BitOr | Token::BitXor | Token::ShiftLeft | Token::ShiftRight
+        )
+    }
+
+    // operators written before their operand
+    pub fn is_prefix(&self) -> bool {
+        if let Token::Custom(op) = self {
+            return op.is_prefix();
+        }
+        matches!(self, Token::Minus | Token::Not | Token::BitNot)
+    }
+
+    // && and ||, which skip the right side when the left decides
+    pub fn is_logical(&self) -> bool {
+        matches!(self, Token::And | Token::Or)
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Token::Equal
+                | Token::NotEqual
+                | Token::Less
+                | Token::LessEqual
+                | Token::Greater
+                | Token::GreaterEqual
+        )
+    }
+
+    // how tightly a binary operator binds, from 1 for `?` to 11 for `^`
+    pub fn precedence(&self) -> i32 {
+        match self {
+            Token::Question => 1,
+            Token::Or => 2,
+            Token::And => 3,
+            _ if self.is_comparison() => 4,
+            // tighter than comparisons, so x & 1 == 0 tests a bit
+            Token::BitOr => 5,
+            Token::BitXor => 6,
+            Token::BitAnd => 7,
+            Token::ShiftLeft | Token::ShiftRight => 8,
+            Token::Plus | Token::Minus => 9,
+            Token::Multiply | Token::Divide | Token::FloorDiv | Token::Mod => 10,
+            Token::Power => 11,
+            Token::Custom(op) => op.precedence(),
+            _ => 0,
+        }
+    }
+
+    // the precedence the operand of a prefix operator is read at, the built-in
+    // ones bind tighter than * but looser than ^
+    fn prefix_precedence(&self) -> i32 {
+        match self {
+            Token::Custom(op) => op.precedence(),
+            _ => Token::Power.precedence(),
+        }
+    }
+
+    pub fn assoc(&self) -> Assoc {
+        match self {
+            Token::Power | Token::Question => Assoc::Right,
+            Token::Custom(op) => op.assoc(),
+            _ => Assoc::Left,
+        }
+    }
+
+    // compute based on opearator, span is the operator for error reporting
+    // operands are widened to the narrowest kind holding both: int, big int, fraction,
+    // decimal, float
+    fn compute(&self, l: Value, r: Value, span: Span) -> Result<Value> {
+        if let Token::Custom(op) = self {
+            return op.apply_binary(l, r, span);
+        }
+        let operands = [&l, &r];
+        if self.is_logical() {
+            let (Value::Bool(l), Value::Bool(r)) = (&l, &r) else {
+                return Err(self.type_error("bool", &operands, span));
+            };
+            return Ok(Value::Bool(if *self == Token::And {
+                *l && *r
+            } else {
+                *l || *r
+            }));
+        }
+        let numbers = |v: &&Value| v.expect_number(span).is_ok();
+        // booleans are only compared for (in)equality
+        let equality = matches!(self, Token::Equal | Token::NotEqual)
+            && matches!((&l, &r), (Value::Bool(_), Value::Bool(_)));
+        if !equality && !operands.iter().all(numbers) {
+            return Err(self.type_error("number", &operands, span));
+        }
+        if self.is_comparison() {
+            return self.compare(&l, &r, span);
+        }
+        if matches!(l, Value::Vector(_)) || matches!(r, Value::Vector(_)) {
+            return vector::compute_vector(self, l, r, span);
+        }
+        if self.is_bitwise() {
+            return self.compute_bits(&l, &r, span);
+        }
+        if matches!(l, Value::Quantity(_)) || matches!(r, Value::Quantity(_)) {
+            return self.compute_quantity(&l, &r, span);
+        }
+        if matches!(l, Value::Interval(_)) || matches!(r, Value::Interval(_)) {
+            return self.compute_interval(l.to_interval(), r.to_interval(), span);
+        }
+        if let (Value::Int(l), Value::Int(r)) = (&l, &r) {
+            return self.compute_int(*l, *r, span);
+        }
+        if let (Some(l), Some(r)) = (l.to_big(), r.to_big()) {
+            return self.compute_big(l, r, span);
+        }
+        if let (Some(l), Some(r)) = (l.to_ratio(), r.to_ratio()) {
+            return self.compute_ratio(l, r, span);
+        }
+        if let (Some(l), Some(r)) = (l.to_decimal(), r.to_decimal()) {
+            return self.compute_decimal(l, r, span);
+        }
+        self.compute_float(l.as_f64(), r.as_f64(), span)
+    }
+
+    // a Type error for the first of the operands that isn't of the expected
+    // type, showing the whole operation
+    fn type_error(&self, expected: &'static str, operands: &[&Value], span: Span) -> ExprError {
+        let wrong = match expected {
+            "bool" => operands.iter().find(|v| !matches!(v, Value::Bool(_))),
+            _ => operands.iter().find(|v| v.expect_number(span).is_err()),
+        };
+        let operation = match operands {
+            [v] => format!("{}{}", self, v),
+            [l, r] => format!("{} {} {}", l, self, r),
+            _ => unreachable!("operators take one or two operands"),
+        };
+        ExprError::Type {
+            expected: expected.into(),
+            found: wrong.map_or("number", |v| v.type_name()).into(),
+            operation: Some(operation.into()),
+            span,
+        }
+    }
+
+    // prefix operator applied to its operand
+    fn compute_unary(&self, v: Value, span: Span) -> Result<Value> {
+        match self {
+            Token::Custom(op) => op.apply_prefix(v, span),
+            Token::Not => match v {
+                Value::Bool(b) => Ok(Value::Bool(!b)),
+                _ => Err(self.type_error("bool", &[&v], span)),
+            },
+            _ if v.expect_number(span).is_err() => Err(self.type_error("number", &[&v], span)),
+            Token::Minus if matches!(v, Value::Quantity(_)) => {
+                Ok(Value::from_quantity(-v.to_quantity()))
+            }
+            Token::BitNot => match v {
+                Value::Int(n) => Ok(Value::Int(!n)),
+                _ => Ok(Value::Big(!v.expect_integer(span)?)),
+            },
+            _ => self.compute(Value::Int(0), v, span),
+        }
+    }
+
+    // integer bit operations, i64 stays i64 and anything else works on big ints
+    fn compute_bits(&self, l: &Value, r: &Value, span: Span) -> Result<Value> {
+        let int = matches!((l, r), (Value::Int(_), Value::Int(_)));
+        let (l, r) = (l.expect_integer(span)?, r.expect_integer(span)?);
+        let shift = || {
+            r.to_usize().ok_or_else(|| ExprError::Type {
+                expected: "a non-negative shift".into(),
+                found: r.to_string(),
+                operation: None,
+                span,
+            })
+        };
+        let result = match self {
+            Token::BitAnd => &l & &r,
+            Token::BitOr => &l | &r,
+            Token::BitXor => &l ^ &r,
+            Token::ShiftLeft => {
+                let shift = shift()?;
+                // a shift this long only fits when nothing is shifted
+                if shift > u32::MAX as usize && !l.is_zero() {
+                    return Err(ExprError::Overflow { span });
+                }
+                &l << shift
+            }
+            _ => &l >> shift()?,
+        };
+        if int {
+            return result
+                .to_i64()
+                .map(Value::Int)
+                .ok_or(ExprError::Overflow { span });
+        }
+        Ok(Value::Big(result))
+    }
+
+    // numbers compare by value across kinds, booleans only for (in)equality
+    fn compare(&self, l: &Value, r: &Value, span: Span) -> Result<Value> {
+        let ordering = match (l, r) {
+            (Value::Bool(l), Value::Bool(r)) if matches!(self, Token::Equal | Token::NotEqual) => {
+                Some(l.cmp(r))
+            }
+            (Value::Vector(_), _) | (_, Value::Vector(_))
+                if matches!(self, Token::Equal | Token::NotEqual) =>
+            {
+                let equal = vector::vectors_equal(l, r, span)?;
+                return Ok(Value::Bool(equal == (*self == Token::Equal)));
+            }
+            _ => {
+                for v in [l, r] {
+                    v.expect_number(span)?;
+                    // overlapping ranges and vectors have no order
+                    if let Value::Interval(_) | Value::Vector(_) = v {
+                        return Err(ExprError::Type {
+                            expected: "a single number".into(),
+                            found: v.to_string(),
+                            operation: None,
+                            span,
+                        });
+                    }
+                }
+                // quantities compare in the same units, 1 km > 300 m
+                if matches!(l, Value::Quantity(_)) || matches!(r, Value::Quantity(_)) {
+                    let (l, r) = (l.to_quantity(), r.to_quantity());
+                    let diff = l
+                        .checked_sub(&r)
+                        .ok_or_else(|| incompatible(&l, &r, span))?;
+                    diff.value().partial_cmp(&0.0)
+                } else {
+                    l.num_cmp(r)
+                }
+            }
+        };
+        let result = match self {
+            Token::Equal => ordering == Some(Ordering::Equal),
+            Token::NotEqual => ordering != Some(Ordering::Equal),
+            Token::Less => ordering == Some(Ordering::Less),
+            Token::LessEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Token::Greater => ordering == Some(Ordering::Greater),
+            _ => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        };
+        Ok(Value::Bool(result))
+    }
+
+    // int op int stays int, unless the result is fractional
+    fn compute_int(&self, l: i64, r: i64, span: Span) -> Result<Value> {
+        let result = match self {
+            Token::Plus => l.checked_add(r),
+            Token::Minus => l.checked_sub(r),
+            Token::Multiply => l.checked_mul(r),
+            Token::Divide if r != 0 && l.checked_rem(r) == Some(0) => l.checked_div(r),
+            Token::Power if r >= 0 => int_pow(l, r),
+            Token::FloorDiv | Token::Mod if r == 0 => {
+                return Err(ExprError::DivisionByZero { span })
+            }
+            // only i64::MIN // -1 doesn't fit
+            Token::FloorDiv => l.checked_div(r).map(|q| {
+                if l % r != 0 && (l < 0) != (r < 0) {
+                    q - 1
+                } else {
+                    q
+                }
+            }),
+            Token::Mod => {
+                let m = l.wrapping_rem(r);
+                Some(if m != 0 && (m < 0) != (r < 0) {
+                    m + r
+                } else {
+                    m
+                })
+            }
+            _ => return self.compute_float(l as f64, r as f64, span),
+        };
+        result.map(Value::Int).ok_or(ExprError::Overflow { span })
+    }
+
+    // big int op big int, exact unless the result is fractional
+    fn compute_big(&self, l: BigInt, r: BigInt, span: Span) -> Result<Value> {
+        let result = match self {
+            Token::Plus => l + r,
+            Token::Minus => l - r,
+            Token::Multiply => l * r,
+            Token::Divide if !r.is_zero() && (&l % &r).is_zero() => l / r,
+            Token::Power if r.sign() != Sign::Minus => match r.to_u32() {
+                Some(exp) => l.pow(exp),
+                None => match l.to_i64().and_then(|l| unit_pow(l, r.bit(0))) {
+                    Some(n) => BigInt::from(n),
+                    None => return Err(ExprError::Overflow { span }),
+                },
+            },
+            Token::FloorDiv | Token::Mod if r.is_zero() => {
+                return Err(ExprError::DivisionByZero { span })
+            }
+            Token::FloorDiv => floor_div_rem(l, r).0,
+            Token::Mod => floor_div_rem(l, r).1,
+            _ => {
+                let (l, r) = (Value::Big(l), Value::Big(r));
+                return self.compute_float(l.as_f64(), r.as_f64(), span);
+            }
+        };
+        Ok(Value::Big(result))
+    }
+
+    // fraction op fraction, always exact except for fractional powers
+    fn compute_ratio(&self, l: BigRational, r: BigRational, span: Span) -> Result<Value> {
+        let result = match self {
+            Token::Plus => l + r,
+            Token::Minus => l - r,
+            Token::Multiply => l * r,
+            Token::Divide if r.is_zero() => return Err(ExprError::DivisionByZero { span }),
+            Token::Divide => l / r,
+            Token::FloorDiv | Token::Mod if r.is_zero() => {
+                return Err(ExprError::DivisionByZero { span })
+            }
+            Token::FloorDiv => (l / r).floor(),
+            Token::Mod => &l - &r * (&l / &r).floor(),
+            Token::Power if r.is_integer() => match r.to_integer().to_i32() {
+                Some(exp) if exp < 0 && l.is_zero() => {
+                    return Err(ExprError::DivisionByZero { span })
+                }
+                Some(exp) => l.pow(exp),
+                None => return Err(ExprError::Overflow { span }),
+            },
+            _ => {
+                let (l, r) = (Value::Ratio(l), Value::Ratio(r));
+                return self.compute_float(l.as_f64(), r.as_f64(), span);
+            }
+        };
+        Ok(Value::Ratio(result))
+    }
+
+    // decimal op decimal, rounded to the decimal scale
+    fn compute_decimal(&self, l: Decimal, r: Decimal, span: Span) -> Result<Value> {
+        let result = match self {
+            Token::Plus => l.checked_add(r),
+            Token::Minus => l.checked_sub(r),
+            Token::Multiply => l.checked_mul(r),
+            Token::Divide if r.is_zero() => return Err(ExprError::DivisionByZero { span }),
+            Token::Divide => l.checked_div(r),
+            Token::FloorDiv | Token::Mod if r.is_zero() => {
+                return Err(ExprError::DivisionByZero { span })
+            }
+            Token::FloorDiv => l.checked_div_rem_floor(r).map(|(q, _)| q),
+            Token::Mod => l.checked_div_rem_floor(r).map(|(_, m)| m),
+            Token::Power if r.to_integer().is_some() => {
+                if l.is_zero() && r.to_integer() < Some(0) {
+                    return Err(ExprError::DivisionByZero { span });
+                }
+                l.checked_pow(r.to_integer().unwrap())
+            }
+            _ => return self.compute_float(l.to_f64(), r.to_f64(), span),
+        };
+        result.map(Value::Dec).ok_or(ExprError::Overflow { span })
+    }
+
+    // quantity op quantity, a plain number being one without units; sums need
+    // the same dimensions and are given in the units of the left side
+    fn compute_quantity(&self, l: &Value, r: &Value, span: Span) -> Result<Value> {
+        for v in [l, r] {
+            if let Value::Interval(_) = v {
+                return Err(ExprError::Type {
+                    expected: "a single number".into(),
+                    found: v.to_string(),
+                    operation: None,
+                    span,
+                });
+            }
+        }
+        let (l, r) = (l.to_quantity(), r.to_quantity());
+        let result = match self {
+            Token::Plus => l
+                .checked_add(&r)
+                .ok_or_else(|| incompatible(&l, &r, span))?,
+            Token::Minus => l
+                .checked_sub(&r)
+                .ok_or_else(|| incompatible(&l, &r, span))?,
+            Token::Multiply => l * r,
+            Token::Divide if r.value() == 0.0 => return Err(ExprError::DivisionByZero { span }),
+            Token::Divide => l / r,
+            Token::FloorDiv | Token::Mod => {
+                return Err(ExprError::Type {
+                    expected: "numbers without units".into(),
+                    found: format!(
+                        "{} {} {}",
+                        Value::from_quantity(l),
+                        self,
+                        Value::from_quantity(r)
+                    ),
+                    operation: None,
+                    span,
+                })
+            }
+            // only whole powers keep the units whole, m^2 but not m^0.5
+            _ if !r.is_plain() || r.value().fract() != 0.0 => {
+                return Err(ExprError::Type {
+                    expected: "an integer without units".into(),
+                    found: Value::from_quantity(r).to_string(),
+                    operation: None,
+                    span,
+                })
+            }
+            _ => l.powi(r.value() as i32),
+        };
+        Value::from_quantity(result)
+            .finite()
+            .ok_or(ExprError::Overflow { span })
+    }
+
+    // interval op interval, the result holds every value op can give
+    fn compute_interval(&self, l: Interval, r: Interval, span: Span) -> Result<Value> {
+        let result = match self {
+            Token::Plus => l + r,
+            Token::Minus => l - r,
+            Token::Multiply => l * r,
+            Token::Divide => l.checked_div(r).ok_or(ExprError::DivisionByZero { span })?,
+            Token::Power => l.checked_pow(r).ok_or(ExprError::DivisionByZero { span })?,
+            // a range of quotients rounded down isn't one interval
+            Token::FloorDiv | Token::Mod => {
+                return Err(ExprError::Type {
+                    expected: "a single number".into(),
+                    found: format!("{} {} {}", l, self, r),
+                    operation: None,
+                    span,
+                })
+            }
+            _ => return self.compute_float(l.mid(), r.mid(), span),
+        };
+        if !result.is_finite() && l.is_finite() && r.is_finite() {
+            return Err(ExprError::Overflow { span });
+        }
+        Ok(Value::Interval(result))
+    }
+
+    fn compute_float(&self, l: f64, r: f64, span: Span) -> Result<Value> {
+        let result = match self {
+            Token::Plus => l + r,
+            Token::Minus => l - r,
+            Token::Multiply => l * r,
+            Token::Divide if r == 0.0 => return Err(ExprError::DivisionByZero { span }),
+            Token::Divide => l / r,
+            Token::Power => l.powf(r),
+            Token::FloorDiv | Token::Mod if r == 0.0 => {
+                return Err(ExprError::DivisionByZero { span })
+            }
+            Token::FloorDiv => (l / r).floor(),
+            Token::Mod => {
+                let m = l % r;
+                if m != 0.0 && (m < 0.0) != (r < 0.0) {
+                    m + r
+                } else {
+                    m
+                }
+            }
+            _ => {
+                return Err(ExprError::UnexpectedToken {
+                    expected: "an operator".into(),
+                    found: self.to_string(),
+                    span,
+                })
+            }
+        };
+        // finite operands running off to infinity
+        if result.is_infinite() && l.is_finite() && r.is_finite() {
+            return Err(ExprError::Overflow { span });
+        }
+        Ok(Value::Float(result))
+    }
+}
+
+// l ^ exp of a non-negative exp, None when it doesn't fit
+pub(crate) fn int_pow(l: i64, exp: i64) -> Option<i64> {
+    match u32::try_from(exp) {
+        Ok(exp) => l.checked_pow(exp),
+        Err(_) => unit_pow(l, exp % 2 == 1),
+    }
+}
+
+// l ^ exp for an exp past u32::MAX, which only 0, 1 and -1 stay in range
+// of; the sign of -1 goes by whether exp is odd
+fn unit_pow(l: i64, odd: bool) -> Option<i64> {
+    match l {
+        0 | 1 => Some(l),
+        -1 if odd => Some(-1),
+        -1 => Some(1),
+        _ => None,
+    }
+}
+
+// the quotient rounded toward negative infinity and the remainder with the
+// sign of the divisor, l == q * r + m
+fn floor_div_rem(l: BigInt, r: BigInt) -> (BigInt, BigInt) {
+    let (q, m) = (&l / &r, &l % &r);
+    if !m.is_zero() && m.sign() != r.sign() {
+        (q - 1, m + r)
+    } else {
+        (q, m)
+    }
+}
+
+// position of a token in the source, in bytes; trees not parsed from source
+// use the default empty span
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl Span {
+    pub fn new(offset: usize, len: usize) -> Self {
+        Self { offset, len }
+    }
+
+    // offset just past the last byte
+    pub fn end(&self) -> usize {
+        self.offset + self.len
+    }
+
+    // the source text this span covers
+    pub fn text<'s>(&self, src: &'s str) -> &'s str {
+        &src[self.offset..self.end()]
+    }
+}
+
+// error for two quantities measuring different things
+fn incompatible(l: &Quantity, r: &Quantity, span: Span) -> ExprError {
+    ExprError::IncompatibleUnits {
+        left: l.unit(),
+        right: r.unit(),
+        span,
+    }
+}
+
+/// Lexer over one source string, yielding each token with the [`Span`] of
+/// source it came from. Whitespace and comments are skipped, newlines come out
+/// as [`Token::Semicolon`]. A comment is `# ...` to the end of the line or
+/// `/* ... */`, `//` being floor division. A bad token is an `Err` and the
+/// tokens after it still follow, so a highlighter can mark it and go on.
+///
+/// ```
+/// use expr_eval::{Mode, Token, Tokenizer};
+///
+/// let src = "rate * (1 + 2.5) # tax";
+/// let tokens: Vec<_> = Tokenizer::new(src, Mode::Standard)
+///     .map(|t| t.map(|(token, span)| (token, span.text(src))))
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+/// assert_eq!((Token::Ident("rate".into()), "rate"), tokens[0]);
+/// assert_eq!((Token::Multiply, "*"), tokens[1]);
+/// assert_eq!("2.5", tokens[5].1);
+/// assert_eq!(7, tokens.len());
+/// ```
+#[derive(Clone)]
+pub struct Tokenizer<'a> {
+    src: &'a str,
+    tokens: Peekable<CharIndices<'a>>,
+    mode: Mode,
+    operators: Operators,
+    locale: Locale,
+    limits: ExprLimits,
+    // tokens read so far, for limits.max_tokens
+    count: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(expr: &'a str, mode: Mode) -> Self {
+        Self {
+            src: expr,
+            tokens: expr.char_indices().peekable(),
+            mode,
+            operators: Operators::new(),
+            locale: Locale::Plain,
+            limits: ExprLimits::default(),
+            count: 0,
+        }
+    }
+
+    // stop at the token and number lengths of limits
+    pub fn with_limits(mut self, limits: ExprLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    // also read the symbols of ops, as Token::Custom
+    pub fn with_operators(mut self, ops: &Operators) -> Self {
+        self.operators = ops.clone();
+        self
+    }
+
+    // read numbers with the separators of the locale
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    // a registered operator starting at the next char, which comments give
+    // way to when its symbol is at least as long as their marker
+    fn custom_at(&mut self, marker_len: usize) -> Option<Arc<Operator>> {
+        let rest = &self.src[self.offset()..];
+        (self.operators.longest_at(rest))
+            .filter(|op| op.symbol().len() >= marker_len)
+            .cloned()
+    }
+
+    // byte offset of the next unconsumed char
+    fn offset(&mut self) -> usize {
+        self.tokens.peek().map_or(self.src.len(), |&(i, _)| i)
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        self.tokens.peek().map(|&(_, c)| c)
+    }
+
+    // newlines are kept, they separate statements
+    fn consume_whitespace(&mut self) {
+        while let Some(c) = self.peek_char() {
+            if c.is_whitespace() && c != '\n' {
+                self.tokens.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    // skip whitespace and comments; `# ...` ends at the newline,
+    // which still separates statements, `/* ... */` can span lines, and `//`
+    // is floor division
+    fn skip_trivia(&mut self) -> Result<()> {
+        loop {
+            self.consume_whitespace();
+            let start = self.offset();
+            let rest = &self.src[start..];
+            let marker = [("#", 1), ("/*", 2)]
+                .into_iter()
+                .find(|(marker, _)| rest.starts_with(marker));
+            if marker.is_some_and(|(_, len)| self.custom_at(len).is_some()) {
+                return Ok(());
+            }
+            if rest.starts_with('#') {
+                while self.tokens.next_if(|&(_, c)| c != '\n').is_some() {}
+            } else if let Some(body) = rest.strip_prefix("/*") {
+                let end = match body.find("*/") {
+                    Some(end) => start + 2 + end + 2,
+                    None => {
+                        // nothing after it can be read
+                        self.tokens.by_ref().for_each(drop);
+                        return Err(ExprError::UnterminatedComment {
+                            span: Span::new(start, 2),
+                        });
+                    }
+                };
+                while self.offset() < end {
+                    self.tokens.next();
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    // interval literal [lo, hi], the bounds are plain numbers
+    fn scan_interval(&mut self, start: usize) -> Result<Token> {
+        let mut text = String::new();
+        while let Some((_, c)) = self.tokens.next_if(|&(_, c)| c != ']' && c != '\n') {
+            text.push(c);
+        }
+        let closed = self.eat(']');
+        let bound = |s: &str| s.trim().replace('_', "").parse::<f64>().ok();
+        let interval = match text[1..].split_once(',') {
+            Some((lo, hi)) if closed => {
+                Interval::new(bound(lo).unwrap_or(f64::NAN), bound(hi).unwrap_or(f64::NAN))
+            }
+            _ => None,
+        };
+        if closed {
+            text.push(']');
+        }
+        interval
+            .map(|i| Token::Number(Value::Interval(i)))
+            .ok_or_else(|| ExprError::InvalidNumber {
+                span: Span::new(start, text.len()),
+                text,
+            })
+    }
+
+    // digits and `_` separators, checked later by scan_number
+    fn scan_digits(&mut self, num: &mut String) {
+        while let Some(c) = self.peek_char() {
+            if c.is_ascii_digit() || c == '_' {
+                num.push(c);
+                self.tokens.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn scan_number(&mut self, start: usize) -> Result<Token> {
+        let mut num = String::new();
+        self.scan_digits(&mut num);
+        if self.locale != Locale::Plain {
+            num = self.scan_separated(num, start)?;
+        }
+        // decimal literal like 3.14, but not the 1 of a range like 1..10
+        if self.peek_char() == Some('.') && !self.range_ahead() {
+            num.push('.');
+            self.tokens.next();
+            self.scan_digits(&mut num);
+        }
+        // a second dot like 1.2.3, swallow the rest of it for the error
+        if self.peek_char() == Some('.') && !self.range_ahead() {
+            while let Some(c) = self.peek_char() {
+                if c.is_ascii_digit() || c == '.' {
+                    self.tokens.next();
+                } else {
+                    break;
+                }
+            }
+            let span = Span::new(start, self.offset() - start);
+            return Err(ExprError::InvalidNumber {
+                text: span.text(self.src).to_string(),
+                span,
+            });
+        }
+        // exponent like 1.5e3 or 2E-4, only when digits follow the e
+        if let Some(e @ ('e' | 'E')) = self.peek_char() {
+            let mut ahead = self.tokens.clone();
+            ahead.next();
+            let sign = match ahead.peek() {
+                Some(&(_, c @ ('+' | '-'))) => {
+                    ahead.next();
+                    Some(c)
+                }
+                _ => None,
+            };
+            if ahead.peek().is_some_and(|&(_, c)| c.is_ascii_digit()) {
+                self.tokens = ahead;
+                num.push(e);
+                num.extend(sign);
+                self.scan_digits(&mut num);
+            }
+        }
+        let span = Span::new(start, self.offset() - start);
+        // separators only between two digits: 1_000 but not 1_, 1__0 or 1_.5
+        let text = num.as_bytes();
+        let digit_at = |i: usize| text.get(i).is_some_and(u8::is_ascii_digit);
+        let misplaced = (0..text.len())
+            .any(|i| text[i] == b'_' && !(i > 0 && digit_at(i - 1) && digit_at(i + 1)));
+        if misplaced {
+            return Err(ExprError::InvalidNumber { text: num, span });
+        }
+        num.retain(|c| c != '_');
+        if num.bytes().filter(u8::is_ascii_digit).count() > self.limits.max_number_digits {
+            let max = self.limits.max_number_digits;
+            return Err(ExprError::TooManyDigits { max, span });
+        }
+        // only fails when the literal doesn't fit
+        let value = Value::from_literal(&num, self.mode).ok_or(ExprError::Overflow { span })?;
+        // units after the number make it a quantity, 5 km
+        let units = self.scan_units();
+        if units.is_empty() {
+            return Ok(Token::Number(value));
+        }
+        let units: Vec<_> = units
+            .iter()
+            .map(|(name, exp)| (name.as_str(), *exp))
+            .collect();
+        let quantity = Quantity::new(value.as_f64(), &units).expect("scan_units only takes units");
+        Ok(Token::Number(Value::Quantity(quantity)))
+    }
+
+    // the rest of a number with the separators of the locale after its first
+    // digits, rewritten to 1234.56
+    fn scan_separated(&mut self, mut num: String, start: usize) -> Result<String> {
+        loop {
+            let mut ahead = self.tokens.clone();
+            let Some((_, sep @ (',' | '.'))) = ahead.next() else {
+                break;
+            };
+            let mut digits = String::new();
+            while let Some((_, c)) = ahead.next_if(|&(_, c)| c.is_ascii_digit() || c == '_') {
+                digits.push(c);
+            }
+            let joins = match (self.locale, sep) {
+                (Locale::DecimalPoint, ',') => digits.len() == 3 && !num.contains('.'),
+                _ => !digits.is_empty(),
+            };
+            if !joins {
+                break;
+            }
+            num.push(sep);
+            num += &digits;
+            self.tokens = ahead;
+        }
+        let span = Span::new(start, self.offset() - start);
+        let (commas, points) = (num.matches(',').count(), num.matches('.').count());
+        let decimal = match self.locale {
+            Locale::DecimalComma => ',',
+            Locale::Auto if commas + points == 0 => return Ok(num),
+            // one separator alone with three digits after it, and before it
+            // digits that could be a first group
+            Locale::Auto if commas + points == 1 && is_ambiguous(&num) => {
+                return Err(ExprError::AmbiguousNumber { text: num, span })
+            }
+            // the last one, when it is the only one of its kind
+            Locale::Auto => match num.rfind([',', '.']).map(|i| num.as_bytes()[i]) {
+                Some(b',') if commas == 1 => ',',
+                Some(b'.') if points == 1 => '.',
+                // 1,234,567 has no fraction
+                Some(b',') => '.',
+                _ => ',',
+            },
+            _ => '.',
+        };
+        let group = if decimal == ',' { '.' } else { ',' };
+        let (whole, fraction) = match num.split_once(decimal) {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (num.as_str(), None),
+        };
+        // groups of three after the first, nothing grouped after the point
+        let mut groups = whole.split(group);
+        let first = groups.next().unwrap_or_default();
+        let grouped = (1..=3).contains(&first.len()) || !whole.contains(group);
+        let valid = grouped
+            && groups.all(|g| g.len() == 3)
+            && fraction.is_none_or(|f| !f.contains([',', '.']));
+        if !valid {
+            return Err(ExprError::InvalidNumber { text: num, span });
+        }
+        let mut plain = whole.replace(group, "");
+        if let Some(fraction) = fraction {
+            plain.push('.');
+            plain += fraction;
+        }
+        Ok(plain)
+    }
+
+    // unit names after a number, `km`, `m^2` or `km/h`, with their exponents; a
+    // name that isn't a unit is left alone
+    fn scan_units(&mut self) -> Vec<(String, i32)> {
+        let mut units = Vec::new();
+        let mut ahead = self.tokens.clone();
+        let mut sign = 1;
+        loop {
+            while ahead.next_if(|&(_, c)| c == ' ' || c == '\t').is_some() {}
+            let mut name = String::new();
+            while let Some((_, c)) = ahead.next_if(|&(_, c)| c.is_alphanumeric() || c == '_') {
+                name.push(c);
+            }
+            if !is_unit(&name) {
+                break;
+            }
+            // m^2 or s^-1, the power written right after the unit, or m**2
+            let mut exp = 1;
+            let mut power = ahead.clone();
+            let caret = power.next_if(|&(_, c)| c == '^').is_some()
+                || (power.next_if(|&(_, c)| c == '*').is_some()
+                    && power.next_if(|&(_, c)| c == '*').is_some());
+            if caret {
+                let mut digits = String::new();
+                if power.next_if(|&(_, c)| c == '-').is_some() {
+                    digits.push('-');
+                }
+                while let Some((_, c)) = power.next_if(|&(_, c)| c.is_ascii_digit()) {
+                    digits.push(c);
+                }
+                if let Ok(n) = digits.parse() {
+                    exp = n;
+                    ahead = power;
+                }
+            }
+            units.push((name, sign * exp));
+            self.tokens = ahead.clone();
+            while ahead.next_if(|&(_, c)| c == ' ' || c == '\t').is_some() {}
+            sign = match ahead.next_if(|&(_, c)| c == '*' || c == '/') {
+                Some((_, '/')) => -1,
+                Some(_) => 1,
+                None => break,
+            };
+        }
+        units
+    }
+
+    // a name, or one of the boolean literals
+    fn scan_ident(&mut self) -> Token {
+        let mut name = String::new();
+        while let Some(c) = self.peek_char() {
+            if c.is_alphanumeric() || c == '_' {
+                name.push(c);
+                self.tokens.next();
+            } else {
+                break;
+            }
+        }
+        match name.as_str() {
+            "true" => Token::Number(Value::Bool(true)),
+            "false" => Token::Number(Value::Bool(false)),
+            _ => Token::Ident(name),
+        }
+    }
+
+    // whether the next two characters are the `..` of a range
+    fn range_ahead(&self) -> bool {
+        let mut ahead = self.tokens.clone();
+        ahead.next_if(|&(_, c)| c == '.').is_some() && ahead.peek().is_some_and(|&(_, c)| c == '.')
+    }
+
+    // consume c if it comes next
+    fn eat(&mut self, c: char) -> bool {
+        self.tokens.next_if(|&(_, next)| next == c).is_some()
+    }
+
+    fn scan_operator(&mut self) -> Option<Token> {
+        match self.tokens.next().map(|(_, c)| c) {
+            Some(c @ ('+' | '-' | '*' | '/')) if self.peek_char() == Some('=') => {
+                self.tokens.next();
+                let op = match c {
+                    '+' => Token::Plus,
+                    '-' => Token::Minus,
+                    '*' => Token::Multiply,
+                    _ => Token::Divide,
+                };
+                Some(Token::CompoundAssign(Box::new(op)))
+            }
+            Some('+') => Some(Token::Plus),
+            Some('-') => Some(Token::Minus),
+            // the signs of printed math, as pasted from documents
+            Some('−') => Some(Token::Minus),
+            Some('×') => Some(Token::Multiply),
+            Some('÷') => Some(Token::Divide),
+            Some('√') => Some(Token::Root),
+            // ** is another way to write ^
+            Some('*') if self.eat('*') => Some(Token::Power),
+            Some('*') => Some(Token::Multiply),
+            Some('/') if self.eat('/') => Some(Token::FloorDiv),
+            Some('/') => Some(Token::Divide),
+            Some('%') => Some(Token::Mod),
+            Some('^') => Some(Token::Power),
+            Some('(') => Some(Token::LeftParen),
+            Some(')') => Some(Token::RightParen),
+            Some('{') => Some(Token::LeftBrace),
+            Some('}') => Some(Token::RightBrace),
+            Some(',') => Some(Token::Comma),
+            Some('?') => Some(Token::Question),
+            Some(':') => Some(Token::Colon),
+            Some('=') if self.eat('=') => Some(Token::Equal),
+            Some('=') => Some(Token::Assign),
+            Some(';' | '\n') => Some(Token::Semicolon),
+            Some('.') if self.eat('.') => match self.eat('=') {
+                true => Some(Token::RangeInclusive),
+                false => Some(Token::Range),
+            },
+            Some('!') if self.eat('=') => Some(Token::NotEqual),
+            Some('!') => Some(Token::Not),
+            Some('&') if self.eat('&') => Some(Token::And),
+            Some('&') => Some(Token::BitAnd),
+            Some('|') if self.eat('|') => Some(Token::Or),
+            Some('|') => Some(Token::BitOr),
+            Some('~') if self.eat('^') => Some(Token::BitXor),
+            Some('~') => Some(Token::BitNot),
+            Some('<') if self.eat('=') => Some(Token::LessEqual),
+            Some('<') if self.eat('<') => Some(Token::ShiftLeft),
+            Some('<') => Some(Token::Less),
+            Some('>') if self.eat('=') => Some(Token::GreaterEqual),
+            Some('>') if self.eat('>') => Some(Token::ShiftRight),
+            Some('>') => Some(Token::Greater),
+            _ => None,
+        }
+    }
+}
+
+// achieve iterator trait to tokenizer, every token comes with its span
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<(Token, Span)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(err) = self.skip_trivia() {
+            return Some(Err(err));
+        }
+        let start = self.offset();
+        if self.count == self.limits.max_tokens && self.peek_char().is_some() {
+            // nothing past the limit is read, not even for more errors
+            self.tokens.by_ref().for_each(drop);
+            let span = Span::new(start, self.offset() - start);
+            let max = self.limits.max_tokens;
+            return Some(Err(ExprError::TooManyTokens { max, span }));
+        }
+        self.count += 1;
+        if let Some(op) = self.custom_at(0) {
+            while self.offset() < start + op.symbol().len() {
+                self.tokens.next();
+            }
+            let span = Span::new(start, op.symbol().len());
+            return Some(Ok((Token::Custom(op), span)));
+        }
+        let token = match self.peek_char() {
+            Some(c) if c.is_ascii_digit() => self.scan_number(start),
+            Some('[') => self.scan_interval(start),
+            Some(c) if c.is_alphabetic() || c == '_' => Ok(self.scan_ident()),
+            Some(ch) => self.scan_operator().ok_or(ExprError::InvalidChar {
+                ch,
+                span: Span::new(start, ch.len_utf8()),
+            }),
+            None => return None,
+        };
+        Some(token.map(|token| (token, Span::new(start, self.offset() - start))))
+    }
+}
+
+// the variables placeholders `?1`, `?2`, ... read, bound to args in order
+pub(crate) fn placeholders(args: &[Value]) -> Env {
+    (args.iter().enumerate())
+        .map(|(i, value)| (format!("?{}", i + 1), value.clone()))
+        .collect()
+}
+
+/// Parser over one source string, evaluate it directly or parse it into an
+/// [`Ast`] first.
+///
+/// ```
+/// use expr_eval::{Expr, Mode};
+///
+/// let ast = Expr::new("(1 + 2) * -x", Mode::Standard).parse().unwrap();
+/// assert_eq!("(* (+ 1 2) (- x))", ast.to_sexpr());
+/// assert_eq!("3 * -x", ast.simplify().to_string());
+/// ```
+pub struct Expr<'a> {
+    src: &'a str,
+    mode: Mode,
+    iter: Peekable<Tokenizer<'a>>,
+    // what the tokenizer is built with, kept so one setting doesn't undo another
+    operators: Operators,
+    locale: Locale,
+    // syntax errors parsed past so far, None unless parse_all collects them
+    errors: Option<Vec<ExprError>>,
+    // parse_expr calls in progress, at most limits.max_depth
+    depth: usize,
+    // also handed to the tokenizer, for its token and digit counts
+    limits: ExprLimits,
+    // names of user functions calls are made to, the ones of with_functions
+    // and of the `fn` statements read so far
+    functions: BTreeSet<String>,
+    // whether the tree parse_expr last gave is a postfix percentage, for the
+    // operator it is the right operand of
+    percent: bool,
+    // whether the next parse_expr reads the right operand of + or -, which a
+    // percentage ends
+    percent_ends: bool,
+}
+
+impl<'a> Expr<'a> {
+    pub fn new(src: &'a str, mode: Mode) -> Self {
+        Self {
+            src,
+            mode,
+            iter: Tokenizer::new(src, mode).peekable(),
+            operators: Operators::new(),
+            locale: Locale::Plain,
+            errors: None,
+            depth: 0,
+            limits: ExprLimits::default(),
+            functions: BTreeSet::new(),
+            percent: false,
+            percent_ends: false,
+        }
+    }
+
+    // nesting allowed before parsing fails with TooDeep instead of running
+    // out of stack, in the source or in the tree it makes
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.limits.max_depth = max_depth;
+        self
+    }
+
+    // also parse calls of the user functions env holds, which a script
+    // defined earlier
+    pub fn with_functions(mut self, env: &Env) -> Self {
+        let functions = env.iter().filter(|(_, v)| matches!(v, Value::Function(_)));
+        self.functions
+            .extend(functions.map(|(name, _)| name.clone()));
+        self
+    }
+
+    // also parse the operators registered in ops, before anything is read
+    pub fn with_operators(mut self, ops: &Operators) -> Self {
+        self.operators = ops.clone();
+        self.retokenize();
+        self
+    }
+
+    /// Read numbers with the separators of the locale, before anything is
+    /// read.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Locale, Mode};
+    ///
+    /// let total = |src| Expr::new(src, Mode::Standard).with_locale(Locale::Auto).eval();
+    /// assert_eq!("1234.56", total("1.234,56").unwrap().to_string());
+    /// assert_eq!("1234.56", total("1,234.56").unwrap().to_string());
+    /// assert!(total("1,234").is_err());
+    /// ```
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self.retokenize();
+        self
+    }
+
+    // read at most the tokens, nesting and digits of limits, before anything
+    // is read
+    pub fn with_limits(mut self, limits: ExprLimits) -> Self {
+        self.limits = limits;
+        self.retokenize();
+        self
+    }
+
+    fn retokenize(&mut self) {
+        self.iter = Tokenizer::new(self.src, self.mode)
+            .with_operators(&self.operators)
+            .with_locale(self.locale)
+            .with_limits(self.limits)
+            .peekable();
+    }
+
+    // decimal mode with the default scale, for money math
+    pub fn new_decimal(src: &'a str) -> Self {
+        Self::new(src, Mode::Decimal(DEFAULT_DECIMAL_SCALE))
+    }
+
+    pub fn eval(&mut self) -> Result<Value> {
+        self.parse()?.eval()
+    }
+
+    /// Read the source as a number on its own like [`parse_number`], in the
+    /// mode and locale of the expression: `0.1` is exact in decimal mode,
+    /// `1,5` is one and a half with the comma locale, and a fraction like
+    /// `1/3`, the way a rational prints, is read in rational mode.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Locale, Mode};
+    ///
+    /// let number = |src, mode| Expr::new(src, mode).with_locale(Locale::DecimalComma).parse_number();
+    /// assert_eq!("0.1", number("0,1", Mode::Decimal(2)).unwrap().to_string());
+    /// assert_eq!("-1/3", number("-1/3", Mode::Rational).unwrap().to_string());
+    /// assert!(number("1/3", Mode::Standard).is_err());
+    /// ```
+    pub fn parse_number(&mut self) -> Result<Value> {
+        let src = self.src;
+        let tokens = self.iter.by_ref().collect::<Result<Vec<_>>>()?;
+        let unexpected = |found: Option<&(Token, Span)>| {
+            let span = found.map_or(Span::new(src.len(), 0), |(_, span)| *span);
+            ExprError::UnexpectedToken {
+                expected: "a number".into(),
+                found: span.text(src).into(),
+                span,
+            }
+        };
+        let (sign, rest) = match &tokens[..] {
+            [(Token::Minus, span), rest @ ..] => (Some(*span), rest),
+            rest => (None, rest),
+        };
+        let value = match rest {
+            [(Token::Number(n), _)] => n.clone(),
+            [(Token::Number(n), _), (Token::Divide, span), (Token::Number(d), _)]
+                if self.mode == Mode::Rational =>
+            {
+                Token::Divide.compute(n.clone(), d.clone(), *span)?
+            }
+            [(Token::Number(_), _), extra, ..] => return Err(unexpected(Some(extra))),
+            rest => return Err(unexpected(rest.first())),
+        };
+        match sign {
+            Some(span) => Token::Minus.compute_unary(value, span),
+            None => Ok(value),
+        }
+    }
+
+    /// Parse once into a closure that can be called with many environments.
+    ///
+    /// ```
+    /// use expr_eval::{Env, Expr, Mode, Value};
+    ///
+    /// let area = Expr::new("w * h", Mode::Standard).compile().unwrap();
+    /// let mut env = Env::new();
+    /// env.insert("w".into(), Value::Int(3));
+    /// for (h, expected) in [(1, 3), (4, 12)] {
+    ///     env.insert("h".into(), Value::Int(h));
+    ///     assert_eq!(Value::Int(expected), area(&env).unwrap());
+    /// }
+    /// ```
+    pub fn compile(&mut self) -> Result<impl Fn(&Env) -> Result<Value>> {
+        Ok(self.parse()?.compile())
+    }
+
+    /// Check that the source is a well-formed expression without computing
+    /// anything, so variables need no values and `1 / 0` passes. Meant for
+    /// editors and formulas stored in config files, every syntax error is
+    /// reported like [`Expr::parse_all`] does.
+    ///
+    /// ```
+    /// use expr_eval::Expr;
+    ///
+    /// assert!(Expr::validate("max(rate, 0.5) * hours").is_ok());
+    /// let errors = Expr::validate("(1 + ").unwrap_err();
+    /// assert_eq!("expected a number, a name or `(`, found end of input at offset 5", errors[0].to_string());
+    /// ```
+    pub fn validate(src: &str) -> core::result::Result<(), Vec<ExprError>> {
+        Expr::new(src, Mode::default()).parse_all().map(drop)
+    }
+
+    // parse the whole source into a tree without evaluating it
+    pub fn parse(&mut self) -> Result<Ast> {
+        let ast = self.parse_expr(1)?;
+        // only goes round when collecting errors
+        loop {
+            let err = match self.peek()? {
+                None => return Ok(ast),
+                Some((Token::RightParen, span)) => ExprError::UnbalancedParen { span: *span },
+                Some(_) => self.unexpected("an operator or end of input"),
+            };
+            self.report(err)?;
+            // go on from the stray token, or past it when no expression starts there
+            match self.peek()? {
+                Some((token, _)) if token.starts_operand() => {
+                    self.parse_expr(1)?;
+                }
+                _ => {
+                    self.iter.next();
+                }
+            }
+        }
+    }
+
+    /// Parse the whole source like [`Expr::parse`], but skip past each syntax
+    /// error to the next operator or paren and go on, so a long formula has
+    /// all of its errors reported in one pass.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode};
+    ///
+    /// let errors = Expr::new("(1 + ) * $ - 2 3", Mode::Standard).parse_all().unwrap_err();
+    /// let offsets: Vec<usize> = errors.iter().map(|e| e.span().offset).collect();
+    /// assert_eq!(vec![5, 9, 15], offsets);
+    /// ```
+    pub fn parse_all(&mut self) -> core::result::Result<Ast, Vec<ExprError>> {
+        self.errors = Some(Vec::new());
+        let ast = self.parse();
+        let mut errors = self.errors.take().unwrap();
+        match ast {
+            Ok(ast) if errors.is_empty() => Ok(ast),
+            Ok(_) => Err(errors),
+            Err(err) => {
+                errors.push(err);
+                Err(errors)
+            }
+        }
+    }
+
+    // note the error and go on when collecting them, else fail with it
+    fn report(&mut self, err: ExprError) -> Result<()> {
+        match &mut self.errors {
+            Some(errors) => {
+                errors.push(err);
+                Ok(())
+            }
+            None => Err(err),
+        }
+    }
+
+    // after an error, drop tokens up to where parsing can pick up again: an
+    // operator, a `)`, `,`, `?` or `:`, or the end
+    fn skip_to_boundary(&mut self) -> Result<()> {
+        while let Some((token, _)) = self.peek()? {
+            if token.is_operator()
+                || matches!(
+                    token,
+                    Token::RightParen | Token::Comma | Token::Question | Token::Colon
+                )
+            {
+                break;
+            }
+            self.iter.next();
+        }
+        Ok(())
+    }
+
+    // peek the next token, a lexing error surfaces once it is reached
+    fn peek(&mut self) -> Result<Option<&(Token, Span)>> {
+        while let Some(Err(_)) = self.iter.peek() {
+            let err = self.iter.next().unwrap().unwrap_err();
+            self.report(err)?;
+        }
+        Ok(self.iter.peek().map(|t| t.as_ref().unwrap()))
+    }
+
+    // span of the next token, or the empty span at the end of input
+    fn peek_span(&mut self) -> Span {
+        match self.iter.peek() {
+            Some(Ok((_, span))) => *span,
+            _ => Span::new(self.src.len(), 0),
+        }
+    }
+
+    // error pointing at the next token, or the end of input
+    fn unexpected(&mut self, expected: &str) -> ExprError {
+        let span = self.peek_span();
+        ExprError::UnexpectedToken {
+            expected: expected.into(),
+            found: span.text(self.src).to_string(),
+            span,
+        }
+    }
+
+    // consume the expected token or fail pointing at what was found instead
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.peek()? {
+            Some((token, _)) if *token == expected => {
+                self.iter.next();
+                Ok(())
+            }
+            _ => Err(self.unexpected(&format!("`{}`", expected))),
+        }
+    }
+
+    // consume the `)` closing the `(` at open, when collecting errors
+    // anything before it is dropped
+    fn expect_close(&mut self, open: Span) -> Result<()> {
+        match self.peek()? {
+            None => self.report(ExprError::UnbalancedParen { span: open }),
+            Some((Token::RightParen, _)) => {
+                self.iter.next();
+                Ok(())
+            }
+            Some(_) => {
+                let err = self.unexpected("`)`");
+                self.report(err)?;
+                let mut depth = 0;
+                while let Some((token, _)) = self.peek()? {
+                    match token {
+                        Token::LeftParen => depth += 1,
+                        Token::RightParen if depth == 0 => {
+                            self.iter.next();
+                            break;
+                        }
+                        Token::RightParen => depth -= 1,
+                        _ => {}
+                    }
+                    self.iter.next();
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // {a, b, ...}, a vector of what is between the braces
+    fn parse_vector(&mut self) -> Result<Ast> {
+        let span = self.peek_span();
+        self.iter.next();
+        let mut elems = vec![self.parse_expr(1)?];
+        while let Some((Token::Comma, _)) = self.peek()? {
+            self.iter.next();
+            elems.push(self.parse_expr(1)?);
+        }
+        if let Err(err) = self.expect(Token::RightBrace) {
+            self.report(err)?;
+        }
+        Ok(call_node(Builtin::Vector, "vec".into(), elems, span))
+    }
+
+    // parse single token or sub-expr
+    fn parse_atom(&mut self) -> Result<Ast> {
+        let reported = self.errors.as_ref().map_or(0, Vec::len);
+        match self.peek()? {
+            // number, direct return
+            Some((Token::Number(n), _)) => {
+                let val = n.clone();
+                self.iter.next();
+                Ok(Ast::Num(val))
+            }
+            // √x is sqrt(x), its operand read like the one of unary minus
+            Some((Token::Root, span)) => {
+                let span = *span;
+                self.iter.next();
+                let operand = self.parse_expr(Token::Root.prefix_precedence())?;
+                let sqrt = lookup_function("sqrt").expect("a builtin");
+                Ok(call_node(sqrt, "sqrt".into(), vec![operand], span))
+            }
+            // prefix operator, binds tighter than * but looser than ^
+            Some((op, span)) if op.is_prefix() => {
+                let (op, span) = (op.clone(), *span);
+                self.iter.next();
+                let operand = self.parse_expr(op.prefix_precedence())?;
+                Ok(Ast::Unary {
+                    op,
+                    operand: Box::new(operand),
+                    span,
+                })
+            }
+            // function call ident ( expr, ... ), or a variable
+            Some((Token::Ident(name), span)) => {
+                let (name, span) = (name.clone(), *span);
+                self.iter.next();
+                if !matches!(self.peek()?, Some((Token::LeftParen, _))) {
+                    return Ok(Ast::Var { name, span });
+                }
+                self.parse_call(name, span)
+            }
+            Some((Token::LeftBrace, _)) => self.parse_vector(),
+            // ?1, the first value bound with eval_args
+            Some((Token::Question, span)) => {
+                let span = *span;
+                self.iter.next();
+                self.parse_placeholder(span)
+            }
+            // (, recursively parse the expr in ()
+            Some((Token::LeftParen, span)) => {
+                let open = *span;
+                self.iter.next();
+                let inner = self.parse_expr(1)?;
+                self.expect_close(open)?;
+                Ok(Ast::Paren(Box::new(inner)))
+            }
+            // a stand-in for what is missing, parse_all never returns it; a bad
+            // character just reported already marks the spot
+            _ => {
+                if self.errors.as_ref().map_or(0, Vec::len) == reported {
+                    let err = self.unexpected("a number, a name or `(`");
+                    self.report(err)?;
+                }
+                self.skip_to_boundary()?;
+                Ok(Ast::Num(Value::Int(0)))
+            }
+        }
+    }
+
+    // name ( expr, ... ), the name read already
+    fn parse_call(&mut self, name: String, span: Span) -> Result<Ast> {
+        let func = lookup_function(&name);
+        let user = func.is_none() && self.functions.contains(&name);
+        if func.is_none() && !user {
+            let name = name.clone();
+            self.report(ExprError::UnknownFunction { name, span })?;
+        }
+        let open = self.peek_span();
+        self.expect(Token::LeftParen)?;
+        // f() has no arguments, f(a, b) two
+        let mut args = Vec::new();
+        if !matches!(self.peek()?, Some((Token::RightParen, _))) {
+            args.push(self.parse_expr(1)?);
+            while let Some((Token::Comma, _)) = self.peek()? {
+                self.iter.next();
+                args.push(self.parse_expr(1)?);
+            }
+        }
+        self.expect_close(open)?;
+        // a user function is looked up, and its arguments counted, when
+        // it is called
+        let Some(func) = func else {
+            return Ok(match user {
+                true => Ast::Call { name, args, span },
+                false => Ast::Num(Value::Int(0)),
+            });
+        };
+        if let Err(err) = check_arity(&name, func, args.len(), span) {
+            self.report(err)?;
+        }
+        Ok(call_node(func, name, args, span))
+    }
+
+    // the position right after a `?` in operand position, a variable named
+    // after it; a stray digit reads as the start of a placeholder rather
+    // than anything else
+    fn parse_placeholder(&mut self, question: Span) -> Result<Ast> {
+        match self.peek()? {
+            Some((Token::Number(Value::Int(n)), span))
+                if *n >= 1 && span.offset == question.offset + question.len =>
+            {
+                let span = Span::new(question.offset, question.len + span.len);
+                let name = format!("?{}", n);
+                self.iter.next();
+                Ok(Ast::Var { name, span })
+            }
+            _ => {
+                let err = self.unexpected("a placeholder number after `?`, like `?1`");
+                self.report(err)?;
+                Ok(Ast::Num(Value::Int(0)))
+            }
+        }
+    }
+
+    // each level of nesting goes through here, so this is where depth is kept
+    fn parse_expr(&mut self, min_prec: i32) -> Result<Ast> {
+        if self.depth == self.limits.max_depth {
+            let span = self.peek_span();
+            return Err(ExprError::TooDeep { span });
+        }
+        self.depth += 1;
+        let ast = self.parse_operators(min_prec);
+        self.depth -= 1;
+        ast
+    }
+
+    fn parse_operators(&mut self, min_prec: i32) -> Result<Ast> {
+        let ends = core::mem::take(&mut self.percent_ends);
+        let mut atom_lhs = self.parse_atom()?;
+        let mut is_percent = false;
+        // of the tree so far, a chain like 1 + 1 + 1 nests without recursing
+        // here, but not when it is evaluated
+        let mut height = atom_lhs.height();
+        let span = self.peek_span();
+        self.check_height(height, span)?;
+
+        loop {
+            let cur_token = self.peek()?;
+            if cur_token.is_none() {
+                break;
+            }
+            let (token, span) = cur_token.unwrap().clone();
+            // cond ? a : b
+            if token == Token::Question && token.precedence() >= min_prec {
+                self.iter.next();
+                atom_lhs = self.parse_cond(atom_lhs, &mut height, span)?;
+                is_percent = false;
+                continue;
+            }
+            // 10%, the operand it follows in hundredths, binding like `*`;
+            // it ends the right operand of + or -, 100 + 10% * 2 doubles 110
+            if self.postfix_percent(ends) && token.precedence() >= min_prec {
+                self.iter.next();
+                height += 1;
+                self.check_height(height, span)?;
+                atom_lhs = percent(atom_lhs, span);
+                is_percent = true;
+                if ends {
+                    break;
+                }
+                continue;
+            }
+            if !token.is_operator() || token.precedence() < min_prec {
+                break;
+            }
+
+            self.iter.next();
+            atom_lhs = self.parse_rhs(token, atom_lhs, &mut height, span)?;
+            is_percent = false;
+        }
+        self.percent = is_percent;
+        Ok(atom_lhs)
+    }
+
+    // lhs op and the right operand after it, out of parse_operators so the
+    // frame of each nested call stays small; height goes from the one of lhs
+    // to the one of the result
+    fn parse_rhs(&mut self, op: Token, lhs: Ast, height: &mut usize, span: Span) -> Result<Ast> {
+        let mut next_prec = op.precedence();
+        if op.assoc() == Assoc::Left {
+            next_prec += 1;
+        }
+        self.percent_ends = matches!(op, Token::Plus | Token::Minus);
+        let rhs = self.parse_expr(next_prec)?;
+        let (rhs, is_percent) = self.percent_operand(&op, rhs)?;
+        let ast = combine(op, lhs, rhs, is_percent, height, span);
+        self.check_height(*height, span)?;
+        Ok(ast)
+    }
+
+    // cond ? a : b, the `?` read already; the middle is enclosed so it can be
+    // anything
+    fn parse_cond(&mut self, cond: Ast, height: &mut usize, span: Span) -> Result<Ast> {
+        let then = self.parse_expr(1)?;
+        // right associative, a ? b : c ? d : e nests to the right;
+        // without the `:` there is no else branch to look for
+        let otherwise = match self.expect(Token::Colon) {
+            Ok(()) => self.parse_expr(Token::Question.precedence())?,
+            Err(err) => {
+                self.report(err)?;
+                Ast::Num(Value::Int(0))
+            }
+        };
+        *height = 1 + (*height).max(then.height()).max(otherwise.height());
+        self.check_height(*height, span)?;
+        Ok(Ast::Cond {
+            cond: Box::new(cond),
+            then: Box::new(then),
+            otherwise: Box::new(otherwise),
+            span,
+        })
+    }
+
+    // TooDeep once a tree gets higher than the nesting allowed
+    fn check_height(&self, height: usize, span: Span) -> Result<()> {
+        match height > self.limits.max_depth {
+            true => Err(ExprError::TooDeep { span }),
+            false => Ok(()),
+        }
+    }
+
+    // the right operand of op and whether it is a percentage; the `%` of
+    // 50 / 10% binds tighter than `/` does and is read here, the one of
+    // 200 + 10% was read with the operand
+    fn percent_operand(&mut self, op: &Token, rhs: Ast) -> Result<(Ast, bool)> {
+        let is_percent = core::mem::take(&mut self.percent);
+        if is_percent || !takes_percent(op) || !self.postfix_percent(true) {
+            return Ok((rhs, is_percent));
+        }
+        let span = self.peek_span();
+        self.iter.next();
+        Ok((percent(rhs, span), true))
+    }
+
+    // whether the next token is a `%` that no operand follows, a percentage;
+    // after the right operand of an operator taking one, a sign after it is
+    // the next operator, so 200 + 10% - 5 takes 5 from 220
+    fn postfix_percent(&mut self, taken: bool) -> bool {
+        if !matches!(self.iter.peek(), Some(Ok((Token::Mod, _)))) {
+            return false;
+        }
+        let mut ahead = self.iter.clone();
+        ahead.next();
+        match ahead.next() {
+            Some(Ok((Token::Plus | Token::Minus, _))) => taken,
+            Some(Ok((token, _))) => !token.starts_operand(),
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test;
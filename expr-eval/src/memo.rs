@@ -0,0 +1,184 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{
+    apply_function, function::call_user, lookup_function, Ast, Env, ExprError, Result, Token, Value,
+};
+
+// what makes two nodes the same computation: their own content and the ids of
+// their children, so equal subtrees get one id without comparing them whole
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Key {
+    // the debug form keeps 1 and 1.0 apart
+    Num(String),
+    Var(String),
+    Unary(String, usize),
+    Binary(String, usize, usize),
+    Call(String, Vec<usize>),
+    Cond(usize, usize, usize),
+    // a call whose value differs each time, like rand(), is only itself
+    Impure(*const Ast),
+}
+
+// ids of distinct subtrees by node address, and the values worked out so far
+struct Memo<'e> {
+    env: &'e Env,
+    keys: BTreeMap<Key, usize>,
+    ids: BTreeMap<*const Ast, usize>,
+    values: BTreeMap<usize, Value>,
+}
+
+impl Ast {
+    /// Evaluate like [`Ast::eval_with`], working out each distinct subtree
+    /// once: repeated subtrees, common in machine-generated input, reuse the
+    /// first result instead of being evaluated again.
+    ///
+    /// ```
+    /// use expr_eval::{Env, Expr, Mode, Value};
+    ///
+    /// let ast = Expr::new("(x * x + 1) / (x * x + 1) + x * x", Mode::Standard).parse().unwrap();
+    /// let env = Env::from([("x".to_string(), Value::Int(3))]);
+    /// assert_eq!(Value::Int(10), ast.eval_memo(&env).unwrap());
+    /// ```
+    pub fn eval_memo(&self, env: &Env) -> Result<Value> {
+        let mut memo = Memo {
+            env,
+            keys: BTreeMap::new(),
+            ids: BTreeMap::new(),
+            values: BTreeMap::new(),
+        };
+        memo.intern(self);
+        memo.eval(self)
+    }
+}
+
+impl Memo<'_> {
+    // number every node, children first; parens are their inner node
+    fn intern(&mut self, ast: &Ast) -> usize {
+        let key = match ast {
+            Ast::Num(n) => Key::Num(format!("{:?}", n)),
+            Ast::Var { name, .. } => Key::Var(name.clone()),
+            Ast::Unary { op, operand, .. } => Key::Unary(op.to_string(), self.intern(operand)),
+            Ast::BinaryOp { .. } => return self.intern_chain(ast),
+            Ast::Paren(inner) => {
+                let id = self.intern(inner);
+                self.ids.insert(ast, id);
+                return id;
+            }
+            // user functions read the environment beyond their arguments
+            Ast::Call { name, args, .. } if lookup_function(name).is_none_or(|f| !f.is_pure()) => {
+                for arg in args {
+                    self.intern(arg);
+                }
+                Key::Impure(ast)
+            }
+            Ast::Call { name, args, .. } => Key::Call(
+                name.clone(),
+                args.iter().map(|arg| self.intern(arg)).collect(),
+            ),
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                ..
+            } => Key::Cond(self.intern(cond), self.intern(then), self.intern(otherwise)),
+        };
+        self.number(ast, key)
+    }
+
+    // a binary operation and those of its chain, from the bottom up in a
+    // loop, see Ast::chain
+    fn intern_chain(&mut self, ast: &Ast) -> usize {
+        let (bottom, chain) = ast.chain();
+        let mut lhs = self.intern(bottom);
+        for (node, op, rhs, _) in chain {
+            let key = Key::Binary(op.to_string(), lhs, self.intern(rhs));
+            lhs = self.number(node, key);
+        }
+        lhs
+    }
+
+    // the id intern gave a node
+    fn id(&self, ast: &Ast) -> usize {
+        self.ids[&(ast as *const Ast)]
+    }
+
+    // the id of a node with its key, a new one for a key not seen before
+    fn number(&mut self, ast: &Ast, key: Key) -> usize {
+        let next = self.keys.len();
+        let id = *self.keys.entry(key).or_insert(next);
+        self.ids.insert(ast, id);
+        id
+    }
+
+    // same rules as eval_with; errors end the evaluation so only values are kept
+    fn eval(&mut self, ast: &Ast) -> Result<Value> {
+        let id = self.id(ast);
+        if let Some(value) = self.values.get(&id) {
+            return Ok(value.clone());
+        }
+        let value = match ast {
+            Ast::Num(n) => n.clone(),
+            Ast::Var { name, span } => {
+                (self.env.get(name).cloned()).ok_or_else(|| ExprError::UnknownVariable {
+                    name: name.clone(),
+                    span: *span,
+                })?
+            }
+            Ast::Unary { op, operand, span } => op.compute_unary(self.eval(operand)?, *span)?,
+            Ast::BinaryOp { .. } => return self.eval_chain(ast),
+            Ast::Paren(inner) => self.eval(inner)?,
+            Ast::Call { name, args, span } => {
+                let args = args.iter().map(|arg| self.eval(arg));
+                let args = args.collect::<Result<_>>()?;
+                match lookup_function(name) {
+                    Some(func) => apply_function(func, args, *span)?,
+                    None => call_user(name, args, self.env, *span)?,
+                }
+            }
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                span,
+            } => {
+                if self.eval(cond)?.expect_bool(*span)? {
+                    self.eval(then)?
+                } else {
+                    self.eval(otherwise)?
+                }
+            }
+        };
+        self.values.insert(id, value.clone());
+        Ok(value)
+    }
+
+    // a binary operation and those of its chain, from the last one worked out
+    // up in a loop, see Ast::chain
+    fn eval_chain(&mut self, ast: &Ast) -> Result<Value> {
+        let (bottom, chain) = ast.chain();
+        let known = (chain.iter()).rposition(|(node, ..)| self.values.contains_key(&self.id(node)));
+        let (mut l, chain) = match known {
+            Some(i) => (self.values[&self.id(chain[i].0)].clone(), &chain[i + 1..]),
+            None => (self.eval(bottom)?, &chain[..]),
+        };
+        for &(node, op, rhs, span) in chain {
+            l = if op.is_logical() {
+                let l = l.expect_bool(span)?;
+                if l == (*op == Token::Or) {
+                    Value::Bool(l)
+                } else {
+                    Value::Bool(self.eval(rhs)?.expect_bool(span)?)
+                }
+            } else {
+                op.compute(l, self.eval(rhs)?, span)?
+            };
+            self.values.insert(self.id(node), l.clone());
+        }
+        Ok(l)
+    }
+}
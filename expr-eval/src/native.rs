@@ -0,0 +1,105 @@
+#[cfg(feature = "std")]
+use alloc::{sync::Arc, vec::Vec};
+#[cfg(feature = "std")]
+use std::sync::{PoisonError, RwLock};
+
+use crate::{Arity, Result, Span, Value};
+
+/// A function written in Rust that expressions call by name like a builtin,
+/// once it is given to [`register_function`]. The parser checks calls
+/// against its arity, so `call` gets as many arguments as that allows, as
+/// they are rather than checked to be numbers.
+///
+// registering needs std, the example only runs with it
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use expr_eval::{register_function, Arity, Expr, Mode, NativeFn, Result, Span, Value};
+///
+/// struct Hypot;
+///
+/// impl NativeFn for Hypot {
+///     fn name(&self) -> &str {
+///         "hypot"
+///     }
+///
+///     fn arity(&self) -> Arity {
+///         Arity::Exactly(2)
+///     }
+///
+///     fn call(&self, args: &[Value], _span: Span) -> Result<Value> {
+///         Ok(Value::Float(args[0].as_f64().hypot(args[1].as_f64())))
+///     }
+/// }
+///
+/// register_function(Hypot);
+/// let value = Expr::new("hypot(3, 4) + 1", Mode::Standard).eval().unwrap();
+/// assert_eq!(Value::Float(6.0), value);
+/// ```
+pub trait NativeFn: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn arity(&self) -> Arity;
+
+    // span is the function name in the call, for errors
+    fn call(&self, args: &[Value], span: Span) -> Result<Value>;
+
+    // false for one that can give a different value for the same arguments,
+    // so a call is never folded into a constant or evaluated once for several
+    fn is_pure(&self) -> bool {
+        true
+    }
+}
+
+// registered functions in the order they came, a name registered again
+// replacing the function in its place so compiled code calling it by index
+// calls the new one
+#[cfg(feature = "std")]
+static NATIVES: RwLock<Vec<Arc<dyn NativeFn>>> = RwLock::new(Vec::new());
+
+/// Make `f` callable from every expression parsed after this, replacing a
+/// function registered before under the same name. Panics when the name is
+/// not an identifier or is the name of a builtin.
+#[cfg(feature = "std")]
+pub fn register_function(f: impl NativeFn + 'static) {
+    let name = f.name();
+    let mut chars = name.chars();
+    assert!(
+        chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || c == '_'),
+        "function name `{}` is not an identifier",
+        name
+    );
+    assert!(
+        crate::builtin_index(name).is_none(),
+        "function name `{}` is taken by a builtin",
+        name
+    );
+    let mut natives = NATIVES.write().unwrap_or_else(PoisonError::into_inner);
+    match natives.iter().position(|g| g.name() == name) {
+        Some(i) => natives[i] = Arc::new(f),
+        None => natives.push(Arc::new(f)),
+    }
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn native_index(name: &str) -> Option<usize> {
+    let natives = NATIVES.read().unwrap_or_else(PoisonError::into_inner);
+    natives.iter().position(|f| f.name() == name)
+}
+
+// the function native_index found
+#[cfg(feature = "std")]
+pub(crate) fn native(i: usize) -> Arc<dyn NativeFn> {
+    NATIVES.read().unwrap_or_else(PoisonError::into_inner)[i].clone()
+}
+
+// nowhere to register one without std
+#[cfg(not(feature = "std"))]
+pub(crate) fn native_index(_name: &str) -> Option<usize> {
+    None
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn native(_i: usize) -> alloc::sync::Arc<dyn NativeFn> {
+    unreachable!("there are no native functions without std")
+}
@@ -0,0 +1,163 @@
+use alloc::string::ToString;
+use core::str::FromStr;
+
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+
+use crate::{int_pow, Ast, Expr, ExprError, Map, Result, Span, Token};
+
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use num_traits::Float;
+
+// a number type the tree can be evaluated in directly, instead of the
+// widening Value kinds; the checked operations return None when the result
+// doesn't fit the type
+pub trait Numeric: Clone + FromStr + Zero {
+    fn checked_add(&self, rhs: &Self) -> Option<Self>;
+    fn checked_sub(&self, rhs: &Self) -> Option<Self>;
+    fn checked_mul(&self, rhs: &Self) -> Option<Self>;
+    // never called with a zero divisor
+    fn checked_div(&self, rhs: &Self) -> Option<Self>;
+    fn checked_pow(&self, rhs: &Self) -> Option<Self>;
+}
+
+// integer division truncates, negative powers don't fit
+impl Numeric for i64 {
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        i64::checked_add(*self, *rhs)
+    }
+
+    fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        i64::checked_sub(*self, *rhs)
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        i64::checked_mul(*self, *rhs)
+    }
+
+    fn checked_div(&self, rhs: &Self) -> Option<Self> {
+        i64::checked_div(*self, *rhs)
+    }
+
+    fn checked_pow(&self, rhs: &Self) -> Option<Self> {
+        match *rhs >= 0 {
+            true => int_pow(*self, *rhs),
+            false => None,
+        }
+    }
+}
+
+// finite operands running off to infinity don't fit
+impl Numeric for f64 {
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        finite(self + rhs, *self, *rhs)
+    }
+
+    fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        finite(self - rhs, *self, *rhs)
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        finite(self * rhs, *self, *rhs)
+    }
+
+    fn checked_div(&self, rhs: &Self) -> Option<Self> {
+        finite(self / rhs, *self, *rhs)
+    }
+
+    fn checked_pow(&self, rhs: &Self) -> Option<Self> {
+        finite(self.powf(*rhs), *self, *rhs)
+    }
+}
+
+fn finite(result: f64, l: f64, r: f64) -> Option<f64> {
+    (!result.is_infinite() || !l.is_finite() || !r.is_finite()).then_some(result)
+}
+
+// never overflows, division truncates like i64
+impl Numeric for BigInt {
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        Some(self + rhs)
+    }
+
+    fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        Some(self - rhs)
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        Some(self * rhs)
+    }
+
+    fn checked_div(&self, rhs: &Self) -> Option<Self> {
+        Some(self / rhs)
+    }
+
+    fn checked_pow(&self, rhs: &Self) -> Option<Self> {
+        Some(self.pow(rhs.to_u32()?))
+    }
+}
+
+impl Ast {
+    // evaluate in T, with variables from env; only the arithmetic operators
+    // are available, literals are read back from their text
+    pub fn eval_as<T: Numeric>(&self, env: &Map<T>) -> Result<T> {
+        match self {
+            Ast::Num(n) => {
+                let text = n.to_string();
+                text.parse().map_err(|_| ExprError::InvalidNumber {
+                    text,
+                    span: Span::default(),
+                })
+            }
+            Ast::Var { name, span } => {
+                env.get(name)
+                    .cloned()
+                    .ok_or_else(|| ExprError::UnknownVariable {
+                        name: name.clone(),
+                        span: *span,
+                    })
+            }
+            Ast::Unary {
+                op: Token::Minus,
+                operand,
+                span,
+            } => arith(&Token::Minus, T::zero(), operand.eval_as(env)?, *span),
+            Ast::BinaryOp { op, lhs, rhs, span } => {
+                arith(op, lhs.eval_as(env)?, rhs.eval_as(env)?, *span)
+            }
+            Ast::Paren(inner) => inner.eval_as(env),
+            Ast::Unary { op, span, .. } => Err(unsupported(op, *span)),
+            Ast::Call { name, span, .. } => Err(unsupported(name, *span)),
+            Ast::Cond { span, .. } => Err(unsupported(&Token::Question, *span)),
+        }
+    }
+}
+
+impl<'a> Expr<'a> {
+    // parse and evaluate in T, like `Expr::new("2 ^ 40", Mode::BigInt).eval_as::<i64>()`;
+    // BigInt mode reads integer literals of any size
+    pub fn eval_as<T: Numeric>(&mut self) -> Result<T> {
+        self.parse()?.eval_as(&Map::new())
+    }
+}
+
+fn arith<T: Numeric>(op: &Token, l: T, r: T, span: Span) -> Result<T> {
+    let result = match op {
+        Token::Plus => l.checked_add(&r),
+        Token::Minus => l.checked_sub(&r),
+        Token::Multiply => l.checked_mul(&r),
+        Token::Divide if r.is_zero() => return Err(ExprError::DivisionByZero { span }),
+        Token::Divide => l.checked_div(&r),
+        Token::Power => l.checked_pow(&r),
+        _ => return Err(unsupported(op, span)),
+    };
+    result.ok_or(ExprError::Overflow { span })
+}
+
+fn unsupported(what: &impl ToString, span: Span) -> ExprError {
+    ExprError::Unsupported {
+        what: what.to_string(),
+        span,
+    }
+}
@@ -0,0 +1,165 @@
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use core::fmt::Debug;
+
+use crate::{Result, Span, Value};
+
+// which way a chain of the same operator groups, a - b - c is (a - b) - c
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+}
+
+type BinaryFn = dyn Fn(Value, Value, Span) -> Result<Value> + Send + Sync;
+type PrefixFn = dyn Fn(Value, Span) -> Result<Value> + Send + Sync;
+
+enum Apply {
+    Binary(Box<BinaryFn>),
+    Prefix(Box<PrefixFn>),
+}
+
+// an operator registered with Operators, the span given to its callback is
+// the operator for error reporting
+pub struct Operator {
+    symbol: String,
+    precedence: i32,
+    assoc: Assoc,
+    apply: Apply,
+}
+
+impl Operator {
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn precedence(&self) -> i32 {
+        self.precedence
+    }
+
+    pub fn assoc(&self) -> Assoc {
+        self.assoc
+    }
+
+    pub fn is_prefix(&self) -> bool {
+        matches!(self.apply, Apply::Prefix(_))
+    }
+
+    pub(crate) fn apply_binary(&self, l: Value, r: Value, span: Span) -> Result<Value> {
+        match &self.apply {
+            Apply::Binary(f) => f(l, r, span),
+            Apply::Prefix(_) => {
+                unreachable!("the parser only puts binary operators between two sides")
+            }
+        }
+    }
+
+    pub(crate) fn apply_prefix(&self, v: Value, span: Span) -> Result<Value> {
+        match &self.apply {
+            Apply::Prefix(f) => f(v, span),
+            Apply::Binary(_) => {
+                unreachable!("the parser only puts prefix operators before an operand")
+            }
+        }
+    }
+}
+
+// the same registration, not just the same symbol
+impl PartialEq for Operator {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(self, other)
+    }
+}
+
+impl Debug for Operator {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Operator")
+            .field("symbol", &self.symbol)
+            .field("precedence", &self.precedence)
+            .field("assoc", &self.assoc)
+            .field("prefix", &self.is_prefix())
+            .finish()
+    }
+}
+
+/// Operators added on top of the built-in ones, given to
+/// [`Expr::with_operators`](crate::Expr::with_operators). Precedences are on
+/// the scale of [`Token::precedence`](crate::Token::precedence), from 1 for
+/// `?` up to 11 for `^`; a registered symbol is matched before the built-in
+/// ones, longest first, and a symbol made of letters only where a whole word
+/// matches.
+///
+/// ```
+/// use expr_eval::{Assoc, Expr, Mode, Operators, Token, Value};
+///
+/// let mut ops = Operators::new();
+/// ops.binary("%%", Token::Multiply.precedence(), Assoc::Left, |l, r, _| {
+///     Ok(Value::Float(l.as_f64().rem_euclid(r.as_f64())))
+/// });
+/// let value = Expr::new("1 + -7 %% 3", Mode::Standard).with_operators(&ops).eval();
+/// assert_eq!(Value::Float(3.0), value.unwrap());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Operators {
+    ops: Vec<Arc<Operator>>,
+}
+
+impl Operators {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // lhs symbol rhs, replacing an operator registered before with the symbol
+    pub fn binary(
+        &mut self,
+        symbol: &str,
+        precedence: i32,
+        assoc: Assoc,
+        f: impl Fn(Value, Value, Span) -> Result<Value> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.register(symbol, precedence, assoc, Apply::Binary(Box::new(f)))
+    }
+
+    // symbol operand, the operand is read at the given precedence like the
+    // built-in `-` reads it at that of `^`
+    pub fn prefix(
+        &mut self,
+        symbol: &str,
+        precedence: i32,
+        f: impl Fn(Value, Span) -> Result<Value> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.register(symbol, precedence, Assoc::Right, Apply::Prefix(Box::new(f)))
+    }
+
+    fn register(&mut self, symbol: &str, precedence: i32, assoc: Assoc, apply: Apply) -> &mut Self {
+        assert!(
+            !symbol.is_empty() && !symbol.contains(char::is_whitespace),
+            "operator symbol `{}` is empty or has whitespace",
+            symbol
+        );
+        self.ops.retain(|op| op.symbol != symbol);
+        self.ops.push(Arc::new(Operator {
+            symbol: symbol.to_string(),
+            precedence,
+            assoc,
+            apply,
+        }));
+        self
+    }
+
+    // the longest symbol src starts with, words only when one ends there
+    pub(crate) fn longest_at(&self, src: &str) -> Option<&Arc<Operator>> {
+        (self.ops.iter())
+            .filter(|op| src.starts_with(&op.symbol))
+            .filter(|op| {
+                let word = op.symbol.chars().all(char::is_alphanumeric);
+                let next = src[op.symbol.len()..].chars().next();
+                !word || !next.is_some_and(|c| c.is_alphanumeric() || c == '_')
+            })
+            .max_by_key(|op| op.symbol.len())
+    }
+}
@@ -0,0 +1,356 @@
+use alloc::{format, string::ToString};
+#[cfg(feature = "std")]
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use crate::{
+    function::MAX_CALL_DEPTH, Env, Expr, ExprError, Result, Span, Token, Value, DEFAULT_MAX_DEPTH,
+};
+
+/// How much of a source [`Expr::with_limits`] reads before giving up, for
+/// input from anyone: tokens before TooManyTokens, nesting before TooDeep and
+/// digits of one number literal before TooManyDigits, so a huge input fails
+/// early instead of taking long to tokenize, parse or turn into a big int.
+/// The nesting is the one of the tree, as deep as evaluating it recurses:
+/// parentheses, prefix operators, `^` and calls count a level each, a chain
+/// like `1 + 1 + 1` counts once however long it is.
+/// The default only limits the nesting, the way [`Expr::new`] does.
+///
+/// ```
+/// use expr_eval::{Expr, ExprError, ExprLimits, Mode};
+///
+/// let limits = ExprLimits {
+///     max_tokens: 5,
+///     ..Default::default()
+/// };
+/// let parse = |src| Expr::new(src, Mode::Standard).with_limits(limits).parse();
+/// assert!(parse("1 + 2 * 3").is_ok());
+/// assert!(matches!(parse("1 + 2 * 3 - 4"), Err(ExprError::TooManyTokens { .. })));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExprLimits {
+    pub max_tokens: usize,
+    pub max_depth: usize,
+    pub max_number_digits: usize,
+}
+
+impl Default for ExprLimits {
+    fn default() -> Self {
+        Self {
+            max_tokens: usize::MAX,
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_number_digits: usize::MAX,
+        }
+    }
+}
+
+/// How [`Expr::eval_with_options`] and [`Ast::eval_with_options`] treat what
+/// a plain evaluation stops at with an error. The default is that plain
+/// evaluation. `max_ops` and `timeout` bound the work an untrusted
+/// expression can make, counted and checked between operations, and
+/// `promotion` keeps integers and floats from mixing silently.
+///
+/// ```
+/// use expr_eval::{Arithmetic, EvalOptions, Expr, Mode, Promotion, Value, ZeroDivision};
+///
+/// let options = EvalOptions {
+///     division_by_zero: ZeroDivision::Infinity,
+///     ..Default::default()
+/// };
+/// let value = Expr::new("1 / 0", Mode::Standard).eval_with_options(&options);
+/// assert_eq!(Value::Float(f64::INFINITY), value.unwrap());
+///
+/// let options = EvalOptions {
+///     arithmetic: Arithmetic::Wrapping,
+///     ..Default::default()
+/// };
+/// let value = Expr::new("9223372036854775807 + 1", Mode::Standard).eval_with_options(&options);
+/// assert_eq!(Value::Int(i64::MIN), value.unwrap());
+///
+/// let options = EvalOptions {
+///     max_ops: Some(50),
+///     ..Default::default()
+/// };
+/// let src = vec!["1"; 100].join(" + ");
+/// assert!(Expr::new(&src, Mode::Standard).eval_with_options(&options).is_err());
+///
+/// let options = EvalOptions {
+///     promotion: Promotion::Explicit,
+///     ..Default::default()
+/// };
+/// assert!(Expr::new("2 * 1.5", Mode::Standard).eval_with_options(&options).is_err());
+/// let value = Expr::new("float(2) * 1.5", Mode::Standard).eval_with_options(&options);
+/// assert_eq!(Value::Float(3.0), value.unwrap());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EvalOptions {
+    pub division_by_zero: ZeroDivision,
+    pub arithmetic: Arithmetic,
+    pub promotion: Promotion,
+    // operators applied and functions called before giving up with
+    // TooManyOps, None for no limit
+    pub max_ops: Option<u64>,
+    // time before giving up with TimedOut; a single operation, like a power
+    // of a huge big int, runs to its end before it is checked
+    #[cfg(feature = "std")]
+    pub timeout: Option<Duration>,
+}
+
+// what an operator gives when its result doesn't fit the i64 of its int
+// operands, like a machine word would; functions like prod() and other kinds
+// of numbers are always checked
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Arithmetic {
+    // an Overflow error
+    #[default]
+    Checked,
+    // the low 64 bits of the result, two's complement
+    Wrapping,
+    // i64::MAX or i64::MIN, whichever is on the side of the result
+    Saturating,
+}
+
+// what an operator does with an exact number, an integer, a fraction or a
+// decimal, where the result is a float; functions like sqrt() always give
+// their float
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Promotion {
+    // the exact operand becomes a float, 2 * 1.5 is 3.0
+    #[default]
+    Auto,
+    // an exact operand and a float can't be mixed without int() or float()
+    // making them one kind; an ImplicitPromotion error otherwise
+    Explicit,
+    // nor can exact operands give a float, so 5 / 2 is an error as well
+    Error,
+}
+
+// what x / 0, x // 0 and x % 0 give when x and 0 are plain numbers; a zero
+// inside an interval, a vector or a quantity is always an error
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ZeroDivision {
+    // a DivisionByZero error
+    #[default]
+    Error,
+    // the float ieee 754 gives, inf or -inf, and nan for 0 / 0 and x % 0
+    Infinity,
+    // the largest value of the sign the quotient would have, i64::MAX for an
+    // int and f64::MAX for other kinds; 0 / 0 is 0 and x % 0 is x
+    Saturate,
+}
+
+impl EvalOptions {
+    // l op r, following the options where op.compute alone would fail
+    pub(crate) fn compute(&self, op: &Token, l: Value, r: Value, span: Span) -> Result<Value> {
+        if self.promotion != Promotion::Auto {
+            return self.compute_exact(op, l, r, span);
+        }
+        if self.division_by_zero == ZeroDivision::Error && self.arithmetic == Arithmetic::Checked {
+            return op.compute(l, r, span);
+        }
+        let divides = matches!(op, Token::Divide | Token::FloorDiv | Token::Mod);
+        match op.compute(l.clone(), r.clone(), span) {
+            Err(ExprError::DivisionByZero { .. })
+                if divides
+                    && self.division_by_zero != ZeroDivision::Error
+                    && is_scalar(&l)
+                    && is_scalar(&r) =>
+            {
+                Ok(self.division_by_zero.divide(op, l, &r))
+            }
+            Err(ExprError::Overflow { span }) => match (l, r) {
+                (Value::Int(l), Value::Int(r)) => (self.arithmetic.int(op, l, r))
+                    .map(Value::Int)
+                    .ok_or(ExprError::Overflow { span }),
+                _ => Err(ExprError::Overflow { span }),
+            },
+            result => result,
+        }
+    }
+
+    // l op r refusing the promotions the options don't allow, then as usual
+    fn compute_exact(&self, op: &Token, l: Value, r: Value, span: Span) -> Result<Value> {
+        let refuse = |value: &Value| ExprError::ImplicitPromotion {
+            value: value.to_string(),
+            span,
+        };
+        match (is_exact(&l), is_exact(&r)) {
+            (true, false) if matches!(r, Value::Float(_)) => return Err(refuse(&l)),
+            (false, true) if matches!(l, Value::Float(_)) => return Err(refuse(&r)),
+            _ => {}
+        }
+        let exact = is_exact(&l) && is_exact(&r);
+        let display = format!("{} {} {}", l, op, r);
+        let options = EvalOptions {
+            promotion: Promotion::Auto,
+            ..*self
+        };
+        match options.compute(op, l, r, span)? {
+            Value::Float(_) if exact && self.promotion == Promotion::Error => {
+                Err(ExprError::ImplicitPromotion {
+                    value: display,
+                    span,
+                })
+            }
+            value => Ok(value),
+        }
+    }
+
+    // op v, -i64::MIN being the one that can overflow
+    pub(crate) fn compute_unary(&self, op: &Token, v: Value, span: Span) -> Result<Value> {
+        match (op, &v) {
+            (Token::Minus, Value::Int(_)) => self.compute(op, Value::Int(0), v, span),
+            _ => op.compute_unary(v, span),
+        }
+    }
+}
+
+// what is left of the limits of the options while a tree is evaluated
+pub(crate) struct Budget {
+    ops: u64,
+    // calls of user functions in progress
+    calls: usize,
+    max_ops: Option<u64>,
+    #[cfg(feature = "std")]
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    pub(crate) fn new(options: &EvalOptions) -> Self {
+        Budget {
+            ops: 0,
+            calls: 0,
+            max_ops: options.max_ops,
+            #[cfg(feature = "std")]
+            deadline: options.timeout.map(|timeout| Instant::now() + timeout),
+        }
+    }
+
+    // one more operation, the one at span
+    pub(crate) fn spend(&mut self, span: Span) -> Result<()> {
+        self.ops += 1;
+        if let Some(max) = self.max_ops.filter(|max| self.ops > *max) {
+            return Err(ExprError::TooManyOps { max, span });
+        }
+        #[cfg(feature = "std")]
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(ExprError::TimedOut { span });
+        }
+        Ok(())
+    }
+
+    // one more call of the user function name in progress, the one at span
+    pub(crate) fn enter(&mut self, name: &str, span: Span) -> Result<()> {
+        if self.calls == MAX_CALL_DEPTH {
+            return Err(ExprError::RecursionLimit {
+                name: name.to_string(),
+                span,
+            });
+        }
+        self.calls += 1;
+        Ok(())
+    }
+
+    pub(crate) fn leave(&mut self) {
+        self.calls -= 1;
+    }
+}
+
+impl Arithmetic {
+    // l op r for one that overflowed, None for checked arithmetic or an
+    // operator that has no wrapped or saturated result
+    fn int(self, op: &Token, l: i64, r: i64) -> Option<i64> {
+        match self {
+            Arithmetic::Checked => None,
+            Arithmetic::Wrapping => match op {
+                Token::Plus => Some(l.wrapping_add(r)),
+                Token::Minus => Some(l.wrapping_sub(r)),
+                Token::Multiply => Some(l.wrapping_mul(r)),
+                // only i64::MIN / -1, which wraps back to i64::MIN
+                Token::Divide | Token::FloorDiv => Some(l.wrapping_div(r)),
+                Token::Power => u64::try_from(r).ok().map(|exp| wrapping_pow(l, exp)),
+                // the bits shifted past the top are lost
+                Token::ShiftLeft if r >= 64 => Some(0),
+                Token::ShiftLeft => Some(((l as u64) << r) as i64),
+                _ => None,
+            },
+            Arithmetic::Saturating => match op {
+                Token::Plus => Some(l.saturating_add(r)),
+                Token::Minus => Some(l.saturating_sub(r)),
+                Token::Multiply => Some(l.saturating_mul(r)),
+                Token::Divide | Token::FloorDiv => Some(l.saturating_div(r)),
+                // an odd power of a negative number is negative
+                Token::Power => u64::try_from(r)
+                    .ok()
+                    .map(|exp| match l < 0 && exp % 2 == 1 {
+                        true => i64::MIN,
+                        false => i64::MAX,
+                    }),
+                Token::ShiftLeft if l < 0 => Some(i64::MIN),
+                Token::ShiftLeft => Some(i64::MAX),
+                _ => None,
+            },
+        }
+    }
+}
+
+// l^exp modulo 2^64, by squaring
+fn wrapping_pow(mut l: i64, mut exp: u64) -> i64 {
+    let mut result: i64 = 1;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result.wrapping_mul(l);
+        }
+        l = l.wrapping_mul(l);
+        exp >>= 1;
+    }
+    result
+}
+
+impl ZeroDivision {
+    // l op r for an r of zero
+    fn divide(self, op: &Token, l: Value, r: &Value) -> Value {
+        let x = l.as_f64();
+        match self {
+            ZeroDivision::Error => unreachable!("the error is kept"),
+            ZeroDivision::Infinity if *op == Token::Mod => Value::Float(f64::NAN),
+            // a signed zero divisor keeps its sign, 1 / -0.0 is -inf
+            ZeroDivision::Infinity => Value::Float(x / r.as_f64()),
+            ZeroDivision::Saturate if *op == Token::Mod => l,
+            ZeroDivision::Saturate => match l {
+                Value::Int(_) if x == 0.0 => Value::Int(0),
+                Value::Int(_) if x > 0.0 => Value::Int(i64::MAX),
+                Value::Int(_) => Value::Int(i64::MIN),
+                _ if x == 0.0 => Value::Float(0.0),
+                _ => Value::Float(f64::MAX.copysign(x)),
+            },
+        }
+    }
+}
+
+// a number held exactly, of the kinds an operator may promote to a float
+fn is_exact(v: &Value) -> bool {
+    matches!(
+        v,
+        Value::Int(_) | Value::Big(_) | Value::Ratio(_) | Value::Dec(_)
+    )
+}
+
+// a number the policies know the largest value of the kind of
+fn is_scalar(v: &Value) -> bool {
+    matches!(
+        v,
+        Value::Int(_) | Value::Big(_) | Value::Ratio(_) | Value::Dec(_) | Value::Float(_)
+    )
+}
+
+impl<'a> Expr<'a> {
+    // evaluate with no variables bound, following the options
+    pub fn eval_with_options(&mut self, options: &EvalOptions) -> Result<Value> {
+        self.parse()?.eval_with_options(&Env::new(), options)
+    }
+}
@@ -0,0 +1,68 @@
+use core::fmt::Debug;
+
+use crate::{Ast, Expr, Result};
+
+/// A way of reading infix source into a tree. The three of [`PARSERS`] give
+/// the same tree for what they all read, so each can be checked and timed
+/// against the others on the same inputs.
+///
+/// ```
+/// use expr_eval::{Expr, Mode, PARSERS};
+///
+/// for parser in PARSERS {
+///     let ast = parser.parse(&mut Expr::new("1 + 2 * 3 ^ 2", Mode::Standard)).unwrap();
+///     assert_eq!("1 + 2 * 3 ^ 2", ast.to_string(), "{}", parser.name());
+/// }
+/// ```
+pub trait Parser: Debug + Sync {
+    // what the cli's --parser calls it
+    fn name(&self) -> &'static str;
+
+    // the whole source of expr, read with its mode, limits and functions
+    fn parse(&self, expr: &mut Expr) -> Result<Ast>;
+}
+
+/// [`Expr::parse`], precedence climbing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PrecedenceClimbing;
+
+impl Parser for PrecedenceClimbing {
+    fn name(&self) -> &'static str {
+        "climbing"
+    }
+
+    fn parse(&self, expr: &mut Expr) -> Result<Ast> {
+        expr.parse()
+    }
+}
+
+/// [`Expr::parse_pratt`], top down operator precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Pratt;
+
+impl Parser for Pratt {
+    fn name(&self) -> &'static str {
+        "pratt"
+    }
+
+    fn parse(&self, expr: &mut Expr) -> Result<Ast> {
+        expr.parse_pratt()
+    }
+}
+
+/// [`Expr::parse_shunting_yard`], an operator stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ShuntingYard;
+
+impl Parser for ShuntingYard {
+    fn name(&self) -> &'static str {
+        "shunting-yard"
+    }
+
+    fn parse(&self, expr: &mut Expr) -> Result<Ast> {
+        expr.parse_shunting_yard()
+    }
+}
+
+/// Every parser, [`Expr::parse`]'s first.
+pub const PARSERS: [&dyn Parser; 3] = [&PrecedenceClimbing, &Pratt, &ShuntingYard];
@@ -0,0 +1,210 @@
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+
+use crate::{
+    call_node, check_arity, combine, lookup_function, percent, Assoc, Ast, Builtin, Expr,
+    ExprError, Result, Span, Token,
+};
+
+// the binding powers of a binary operator on its left and right; a left
+// associative one binds tighter on the right so the next one at its level
+// doesn't get pulled in, a right associative one the other way round
+fn binding_power(op: &Token) -> (i32, i32) {
+    let power = 2 * op.precedence();
+    match op.assoc() {
+        Assoc::Left => (power, power + 1),
+        _ => (power + 1, power),
+    }
+}
+
+// the power of `?`, the loosest operator, everything binds at the top
+const LOWEST: i32 = 2;
+
+// how tightly a prefix operator holds its operand, so -2 ^ 2 is -(2 ^ 2)
+fn prefix_power(op: &Token) -> i32 {
+    2 * op.prefix_precedence()
+}
+
+impl<'a> Expr<'a> {
+    /// Parse the whole source like [`Expr::parse`], with a Pratt parser: each
+    /// token knows how it starts an operand and how it continues one, and an
+    /// operator takes the one before it when it binds tighter than what
+    /// came before. The tree is the same one [`Expr::parse`] gives, but the
+    /// first error ends parsing.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode, Value};
+    ///
+    /// let ast = Expr::new("2 ^ 3 ^ 2 - 1", Mode::Standard).parse_pratt().unwrap();
+    /// assert_eq!("2 ^ 3 ^ 2 - 1", ast.to_string());
+    /// assert_eq!(Value::Int(511), ast.eval().unwrap());
+    /// ```
+    pub fn parse_pratt(&mut self) -> Result<Ast> {
+        let ast = self.pratt_expr(LOWEST)?;
+        match self.peek()? {
+            None => Ok(ast),
+            Some((Token::RightParen, span)) => Err(ExprError::UnbalancedParen { span: *span }),
+            Some(_) => Err(self.unexpected("an operator or end of input")),
+        }
+    }
+
+    // an operand and the operators after it that bind at least min_power
+    fn pratt_expr(&mut self, min_power: i32) -> Result<Ast> {
+        if self.depth == self.limits.max_depth {
+            let span = self.peek_span();
+            return Err(ExprError::TooDeep { span });
+        }
+        self.depth += 1;
+        let ast = self.pratt_operators(min_power);
+        self.depth -= 1;
+        ast
+    }
+
+    fn pratt_operators(&mut self, min_power: i32) -> Result<Ast> {
+        let ends = core::mem::take(&mut self.percent_ends);
+        let mut lhs = self.pratt_prefix()?;
+        let mut is_percent = false;
+        // of the tree so far, as in parse_operators
+        let mut height = lhs.height();
+        let span = self.peek_span();
+        self.check_height(height, span)?;
+        while let Some((token, span)) = self.peek()?.cloned() {
+            if !token.is_operator() && token != Token::Question {
+                break;
+            }
+            let (left, right) = binding_power(&token);
+            if left < min_power {
+                break;
+            }
+            // 10%, postfix when no operand follows, binds like `*` and ends
+            // the right operand of + or -, as in parse_operators
+            is_percent = self.postfix_percent(ends);
+            self.iter.next();
+            if is_percent {
+                height += 1;
+                self.check_height(height, span)?;
+                lhs = percent(lhs, span);
+                if ends {
+                    break;
+                }
+            } else {
+                lhs = self.pratt_infix(token, lhs, right, &mut height, span)?;
+            }
+        }
+        self.percent = is_percent;
+        Ok(lhs)
+    }
+
+    // what an operator makes of the operand before it, height going from the
+    // one of lhs to the one of the result
+    fn pratt_infix(
+        &mut self,
+        op: Token,
+        lhs: Ast,
+        right: i32,
+        height: &mut usize,
+        span: Span,
+    ) -> Result<Ast> {
+        if op != Token::Question {
+            self.percent_ends = matches!(op, Token::Plus | Token::Minus);
+            let rhs = self.pratt_expr(right)?;
+            let (rhs, is_percent) = self.percent_operand(&op, rhs)?;
+            let ast = combine(op, lhs, rhs, is_percent, height, span);
+            self.check_height(*height, span)?;
+            return Ok(ast);
+        }
+        // the middle of cond ? a : b is enclosed, anything goes there
+        let then = self.pratt_expr(LOWEST)?;
+        self.expect(Token::Colon)?;
+        let otherwise = self.pratt_expr(right)?;
+        *height = 1 + (*height).max(then.height()).max(otherwise.height());
+        self.check_height(*height, span)?;
+        Ok(Ast::Cond {
+            cond: Box::new(lhs),
+            then: Box::new(then),
+            otherwise: Box::new(otherwise),
+            span,
+        })
+    }
+
+    // what a token makes of itself at the start of an operand
+    fn pratt_prefix(&mut self) -> Result<Ast> {
+        let Some((token, span)) = self.peek()?.cloned() else {
+            return Err(self.unexpected("a number, a name or `(`"));
+        };
+        match token {
+            Token::Number(n) => {
+                self.iter.next();
+                Ok(Ast::Num(n))
+            }
+            Token::Root => {
+                self.iter.next();
+                let operand = self.pratt_expr(prefix_power(&Token::Root))?;
+                let sqrt = lookup_function("sqrt").expect("a builtin");
+                Ok(call_node(sqrt, "sqrt".into(), vec![operand], span))
+            }
+            op if op.is_prefix() => {
+                self.iter.next();
+                let operand = self.pratt_expr(prefix_power(&op))?;
+                Ok(Ast::Unary {
+                    op,
+                    operand: Box::new(operand),
+                    span,
+                })
+            }
+            Token::Ident(name) => {
+                self.iter.next();
+                match self.peek()? {
+                    Some((Token::LeftParen, _)) => self.pratt_call(name, span),
+                    _ => Ok(Ast::Var { name, span }),
+                }
+            }
+            Token::LeftBrace => {
+                self.iter.next();
+                let elems = self.pratt_list()?;
+                self.expect(Token::RightBrace)?;
+                Ok(call_node(Builtin::Vector, "vec".into(), elems, span))
+            }
+            Token::Question => {
+                self.iter.next();
+                self.parse_placeholder(span)
+            }
+            Token::LeftParen => {
+                self.iter.next();
+                let inner = self.pratt_expr(LOWEST)?;
+                self.expect_close(span)?;
+                Ok(Ast::Paren(Box::new(inner)))
+            }
+            _ => Err(self.unexpected("a number, a name or `(`")),
+        }
+    }
+
+    // name ( a, b, ... ), of a builtin or of a user function
+    fn pratt_call(&mut self, name: String, span: Span) -> Result<Ast> {
+        let func = lookup_function(&name);
+        if func.is_none() && !self.functions.contains(&name) {
+            return Err(ExprError::UnknownFunction { name, span });
+        }
+        let open = self.peek_span();
+        self.iter.next();
+        let args = match self.peek()? {
+            Some((Token::RightParen, _)) => Vec::new(),
+            _ => self.pratt_list()?,
+        };
+        self.expect_close(open)?;
+        let Some(func) = func else {
+            return Ok(Ast::Call { name, args, span });
+        };
+        check_arity(&name, func, args.len(), span)?;
+        Ok(call_node(func, name, args, span))
+    }
+
+    // a, b, ... up to the closing token
+    fn pratt_list(&mut self) -> Result<Vec<Ast>> {
+        let mut elems = vec![self.pratt_expr(LOWEST)?];
+        while let Some((Token::Comma, _)) = self.peek()? {
+            self.iter.next();
+            elems.push(self.pratt_expr(LOWEST)?);
+        }
+        Ok(elems)
+    }
+}
@@ -0,0 +1,176 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use num_bigint::BigInt;
+use num_traits::{One, Signed, Zero};
+
+use crate::{narrow, ExprError, Result, Span, Value};
+
+// the first twelve primes, trial divisors and Miller-Rabin bases; with all of
+// them as bases the test is certain below 3 * 10^23
+const SMALL_PRIMES: [u32; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+// factors below this are found by trial division, larger ones by Pollard's rho
+const TRIAL_LIMIT: u32 = 1000;
+
+// rho steps before a factor counts as out of reach
+const MAX_RHO_STEPS: u32 = 1 << 20;
+
+fn gcd2(mut a: BigInt, mut b: BigInt) -> BigInt {
+    while !b.is_zero() {
+        let r = &a % &b;
+        a = b;
+        b = r;
+    }
+    a.abs()
+}
+
+// gcd(a, b, ...), 0 only when every argument is
+pub(crate) fn gcd(args: Vec<Value>, span: Span) -> Result<Value> {
+    let int = args.iter().all(|v| matches!(v, Value::Int(_)));
+    let mut n = BigInt::zero();
+    for arg in &args {
+        n = gcd2(n, arg.expect_integer(span)?);
+    }
+    narrow(n, int, span)
+}
+
+// lcm(a, b, ...), 0 when any argument is
+pub(crate) fn lcm(args: Vec<Value>, span: Span) -> Result<Value> {
+    let int = args.iter().all(|v| matches!(v, Value::Int(_)));
+    let mut n = BigInt::one();
+    for arg in &args {
+        let m = arg.expect_integer(span)?;
+        n = match n.is_zero() || m.is_zero() {
+            true => BigInt::zero(),
+            false => (&n * &m).abs() / gcd2(n, m),
+        };
+    }
+    narrow(n, int, span)
+}
+
+pub(crate) fn isprime(args: Vec<Value>, span: Span) -> Result<Value> {
+    Ok(Value::Bool(is_prime(&args[0].expect_integer(span)?)))
+}
+
+// the factorization as text, like 2^3 * 3^2 * 5 for 360
+pub(crate) fn factor(args: Vec<Value>, span: Span) -> Result<Value> {
+    let n = args[0].expect_integer(span)?;
+    if n.is_zero() {
+        return Err(ExprError::Type {
+            expected: "a non-zero integer".into(),
+            found: args[0].to_string(),
+            operation: None,
+            span,
+        });
+    }
+    let sign = if n.is_negative() { "-" } else { "" };
+    let factors = prime_factors(n.abs()).ok_or(ExprError::Overflow { span })?;
+    if factors.is_empty() {
+        return Ok(Value::Text(format!("{}1", sign)));
+    }
+    // runs of the same prime become powers
+    let mut powers: Vec<(BigInt, u32)> = Vec::new();
+    for p in factors {
+        match powers.last_mut() {
+            Some((q, k)) if *q == p => *k += 1,
+            _ => powers.push((p, 1)),
+        }
+    }
+    let terms: Vec<String> = (powers.iter())
+        .map(|(p, k)| match k {
+            1 => format!("{}", p),
+            k => format!("{}^{}", p, k),
+        })
+        .collect();
+    Ok(Value::Text(format!("{}{}", sign, terms.join(" * "))))
+}
+
+// Miller-Rabin with the small primes as bases
+fn is_prime(n: &BigInt) -> bool {
+    if *n < BigInt::from(2) {
+        return false;
+    }
+    for p in SMALL_PRIMES {
+        if *n == BigInt::from(p) {
+            return true;
+        }
+        if (n % p).is_zero() {
+            return false;
+        }
+    }
+    // n - 1 = d * 2^s with d odd
+    let n1: BigInt = n - 1;
+    let s = n1.trailing_zeros().expect("n - 1 is not zero");
+    let d = &n1 >> s;
+    'bases: for a in SMALL_PRIMES {
+        let mut x = BigInt::from(a).modpow(&d, n);
+        if x.is_one() || x == n1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = x.modpow(&BigInt::from(2), n);
+            if x == n1 {
+                continue 'bases;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+// prime factors of n >= 1 in increasing order with repeats, None when one is
+// too large to find
+fn prime_factors(mut n: BigInt) -> Option<Vec<BigInt>> {
+    let mut factors = Vec::new();
+    // composite divisors never divide what their primes left
+    for p in 2..TRIAL_LIMIT {
+        while (&n % p).is_zero() {
+            factors.push(BigInt::from(p));
+            n /= p;
+        }
+    }
+    let mut pending = vec![n];
+    while let Some(n) = pending.pop() {
+        if n.is_one() {
+            continue;
+        }
+        if is_prime(&n) {
+            factors.push(n);
+            continue;
+        }
+        let d = rho(&n)?;
+        pending.push(&n / &d);
+        pending.push(d);
+    }
+    factors.sort();
+    Some(factors)
+}
+
+// a divisor of the composite n other than 1 and n, Pollard's rho with Floyd's
+// cycle finding, trying x^2 + c for a few c
+fn rho(n: &BigInt) -> Option<BigInt> {
+    let mut steps = 0;
+    for c in 1u32.. {
+        let f = |x: &BigInt| (x * x + c) % n;
+        let (mut x, mut y) = (BigInt::from(2), BigInt::from(2));
+        let mut d = BigInt::one();
+        while d.is_one() {
+            steps += 1;
+            if steps > MAX_RHO_STEPS {
+                return None;
+            }
+            x = f(&x);
+            y = f(&f(&y));
+            d = gcd2(&x - &y, n.clone());
+        }
+        if d != *n {
+            return Some(d);
+        }
+    }
+    None
+}
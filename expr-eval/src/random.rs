@@ -0,0 +1,49 @@
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// splitmix64 state behind rand and randint, shared by every expression
+static STATE: AtomicU64 = AtomicU64::new(0);
+static SEEDED: AtomicBool = AtomicBool::new(false);
+
+/// Restart the numbers `rand()` and `randint(a, b)` give from `seed`, the
+/// same seed always gives the same sequence. Without it the sequence starts
+/// from the clock, or from a fixed seed without std.
+pub fn seed_random(seed: u64) {
+    STATE.store(seed, Ordering::Relaxed);
+    SEEDED.store(true, Ordering::Relaxed);
+}
+
+#[cfg(feature = "std")]
+fn default_seed() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    (SystemTime::now().duration_since(UNIX_EPOCH)).map_or(0, |d| d.as_nanos() as u64)
+}
+
+#[cfg(not(feature = "std"))]
+fn default_seed() -> u64 {
+    0x853c_49e6_748f_ea9b
+}
+
+// the state the next number comes from, seeded if nothing has drawn one
+// yet; seed_random with it has the same numbers come again
+pub(crate) fn state() -> u64 {
+    if !SEEDED.swap(true, Ordering::Relaxed) {
+        STATE.store(default_seed(), Ordering::Relaxed);
+    }
+    STATE.load(Ordering::Relaxed)
+}
+
+pub(crate) fn next_u64() -> u64 {
+    if !SEEDED.swap(true, Ordering::Relaxed) {
+        STATE.store(default_seed(), Ordering::Relaxed);
+    }
+    let mut z = (STATE.fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed))
+        .wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// uniform in [0, 1), the top 53 bits are as many as an f64 holds
+pub(crate) fn next_f64() -> f64 {
+    (next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
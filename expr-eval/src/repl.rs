@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+
+use rustyline::{error::ReadlineError, history::DefaultHistory, Editor};
+
+use expr_eval::{constants, render, Env, Mode, Precision, Radix, WordSize};
+
+use crate::{
+    cli::Options,
+    editor::LineHelper,
+    session::{self, Session},
+};
+
+const PROMPT: &str = ">> ";
+
+const HELP: &str = "\
+:vars              list the variables and their values
+:history           list the results with the lines that gave them
+:clear             forget every variable and result
+:constants SET     define the constants of math or physics as variables
+:save FILE         keep the variables and the history in FILE
+:load FILE         go back to the session saved in FILE
+:mode [NAME]       show the number mode, or switch to standard, bigint,
+                   rational or decimal [SCALE]
+:precision         show how results are printed
+:digits [N]        print floats to N significant digits, none for shortest
+:places [N]        print floats to N digits after the point
+:out BASE          print integers in dec, hex, oct or bin
+:word [BITS]       cut integers to words of 8, 16, 32 or 64 bits, showing
+                   negative ones as two's complement, none for no limit
+:help              this list
+:quit              leave, like Ctrl-D";
+
+// interactive loop: read a line, evaluate, print, until Ctrl-D or :quit;
+// variables live until the session ends
+//
+// every result is kept as `ans` and numbered as `_1`, `_2`, ... so later lines
+// can build on it, `@1` being another way to write `_1`
+//
+// a line starting with `:` is a command rather than an expression, HELP lists
+// them
+//
+// each line is read and evaluated the way opts asks for on the command line,
+// with rpn as one postfix expression, `ans 2 *`
+//
+// the line is colored as it is typed, the bracket matching the one at the
+// cursor is marked and tab completes function and variable names
+pub fn run(mut opts: Options, mut env: Env) -> rustyline::Result<()> {
+    let mut editor: Editor<LineHelper, DefaultHistory> = Editor::new()?;
+    let mut helper = LineHelper::new(opts.mode);
+    helper.variables = env.keys().cloned().collect();
+    editor.set_helper(Some(helper));
+    let mut results = 0;
+    // the line each numbered result came from, for :history
+    let mut lines: HashMap<usize, String> = HashMap::new();
+    loop {
+        match editor.readline(PROMPT) {
+            Ok(line) => {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                editor.add_history_entry(line)?;
+                if let Some(command) = line.strip_prefix(':') {
+                    let (name, arg) = command
+                        .split_once(char::is_whitespace)
+                        .map_or((command, ""), |(name, arg)| (name, arg.trim()));
+                    match name {
+                        "out" => match Radix::from_name(arg) {
+                            Some(r) => opts.radix = r,
+                            None => eprintln!(
+                                "error: expected `:out dec`, `:out hex`, `:out oct` or `:out bin`"
+                            ),
+                        },
+                        "word" if arg.is_empty() => opts.word = None,
+                        "word" => match WordSize::from_name(arg) {
+                            Some(word) => opts.word = Some(word),
+                            None => eprintln!("error: expected `:word 8`, `16`, `32` or `64`"),
+                        },
+                        "digits" | "places" => match arg.parse() {
+                            Ok(n) if name == "digits" => opts.precision = Precision::Digits(n),
+                            Ok(n) => opts.precision = Precision::Places(n),
+                            Err(_) if arg.is_empty() => opts.precision = Precision::Shortest,
+                            Err(_) => {
+                                eprintln!("error: expected `:{} N` with N a count of digits", name)
+                            }
+                        },
+                        "precision" => println!("{}", describe_precision(&opts)),
+                        "mode" if arg.is_empty() => println!("{}", describe_mode(opts.mode)),
+                        "mode" => match parse_mode(arg) {
+                            Some(m) => opts.mode = m,
+                            None => eprintln!(
+                                "error: expected `:mode standard`, `:mode bigint`, \
+                                 `:mode rational` or `:mode decimal [SCALE]`"
+                            ),
+                        },
+                        "vars" => {
+                            let mut vars: Vec<_> = env.iter().collect();
+                            vars.sort_by(|a, b| a.0.cmp(b.0));
+                            for (name, value) in vars {
+                                println!("{} = {}", name, opts.show(value.clone()));
+                            }
+                        }
+                        "history" => {
+                            for n in 1..=results {
+                                let Some(value) = env.get(&format!("_{}", n)) else {
+                                    continue;
+                                };
+                                let value = opts.show(value.clone());
+                                match lines.get(&n) {
+                                    Some(line) => println!("@{}  {} = {}", n, line, value),
+                                    None => println!("@{}  = {}", n, value),
+                                }
+                            }
+                        }
+                        "clear" => {
+                            env.clear();
+                            lines.clear();
+                            results = 0;
+                        }
+                        "constants" => match constants(arg) {
+                            Some(set) => env.extend(set),
+                            None => eprintln!(
+                                "error: expected `:constants math` or `:constants physics`"
+                            ),
+                        },
+                        "save" | "load" if arg.is_empty() => {
+                            eprintln!("error: expected `:{} FILE`", name)
+                        }
+                        "save" => {
+                            let session = Session {
+                                env: env.clone(),
+                                history: editor.history().iter().cloned().collect(),
+                            };
+                            if let Err(err) = session::save(arg, &session) {
+                                eprintln!("error: can't save to `{}`: {}", arg, err);
+                            }
+                        }
+                        "load" => match session::load(arg) {
+                            Ok(session) => {
+                                env = session.env;
+                                lines.clear();
+                                // numbering goes on after the last result loaded
+                                results = (env.keys())
+                                    .filter_map(|name| name.strip_prefix('_')?.parse().ok())
+                                    .max()
+                                    .unwrap_or(0);
+                                editor.clear_history()?;
+                                for line in &session.history {
+                                    editor.add_history_entry(line)?;
+                                }
+                            }
+                            Err(err) => eprintln!("error: can't load `{}`: {}", arg, err),
+                        },
+                        "help" => println!("{}", HELP),
+                        "quit" | "q" => break,
+                        _ => eprintln!("error: unknown command `:{}`, `:help` lists them", name),
+                    }
+                    if let Some(helper) = editor.helper_mut() {
+                        helper.mode = opts.mode;
+                        helper.variables = env.keys().cloned().collect();
+                    }
+                    continue;
+                }
+                let src = match result_refs(line, &env) {
+                    Ok(src) => src,
+                    Err(n) => {
+                        eprintln!("error: there is no result @{}, `:history` lists them", n);
+                        continue;
+                    }
+                };
+                match opts.eval(&src, &mut env) {
+                    Ok(value) => {
+                        results += 1;
+                        println!("{}", opts.show(value.clone()));
+                        lines.insert(results, line.to_string());
+                        env.insert(format!("_{}", results), value.clone());
+                        env.insert("ans".into(), value);
+                        if let Some(helper) = editor.helper_mut() {
+                            helper.variables = env.keys().cloned().collect();
+                        }
+                    }
+                    Err(err) => {
+                        eprint!("{}", render(line, &err));
+                    }
+                }
+            }
+            // Ctrl-C drops the current line, Ctrl-D quits
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+// the line with each `@N` written as the `_N` holding that result, the same
+// length so error spans still point into the line as typed; Err(N) for a
+// result there isn't
+fn result_refs(line: &str, env: &Env) -> Result<String, usize> {
+    let mut src = String::with_capacity(line.len());
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '@' || !chars.peek().is_some_and(|(_, next)| next.is_ascii_digit()) {
+            src.push(c);
+            continue;
+        }
+        let digits = &line[i + 1..];
+        let end = digits
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(digits.len());
+        let name = format!("_{}", &digits[..end]);
+        if !env.contains_key(&name) {
+            return Err(digits[..end].parse().unwrap_or(usize::MAX));
+        }
+        src.push('_');
+    }
+    Ok(src)
+}
+
+// `decimal 4` keeps 4 fractional digits, the other modes take no scale
+fn parse_mode(arg: &str) -> Option<Mode> {
+    match arg.split_once(char::is_whitespace) {
+        Some(("decimal", scale)) => scale.trim().parse().ok().and_then(Mode::decimal),
+        Some(_) => None,
+        None => Mode::from_name(arg),
+    }
+}
+
+fn describe_mode(mode: Mode) -> String {
+    match mode {
+        Mode::Standard => "standard, 64-bit integers".into(),
+        Mode::BigInt => "bigint, integers of any size".into(),
+        Mode::Rational => "rational, exact fractions".into(),
+        Mode::Decimal(scale) => format!("decimal, {} digits after the point", scale),
+    }
+}
+
+fn describe_precision(opts: &Options) -> String {
+    let (precision, radix, word) = (opts.precision, opts.radix, opts.word);
+    let floats = match precision {
+        Precision::Shortest => "floats in the shortest form that reads back the same".into(),
+        Precision::Digits(n) => format!("floats to {} significant digits", n),
+        Precision::Places(n) => format!("floats to {} digits after the point", n),
+    };
+    let base = match radix {
+        Radix::Dec => "decimal",
+        Radix::Hex => "hex",
+        Radix::Oct => "octal",
+        Radix::Bin => "binary",
+    };
+    match word {
+        Some(word) => format!(
+            "{}, integers in {} as {}-bit words",
+            floats,
+            base,
+            word.bits()
+        ),
+        None => format!("{}, integers in {}", floats, base),
+    }
+}
@@ -0,0 +1,149 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{ExprError, Mode, Token, Tokenizer, FUNCTIONS};
+
+/// Render an error the way a compiler would: the message, where it is, the
+/// source line with a caret under the span and a hint on what to do about it
+/// when there is one. Errors about json or rates tables have no place in the
+/// source and get the message alone.
+///
+/// ```
+/// use expr_eval::{render, Expr, Mode};
+///
+/// let src = "2 * (1 +)";
+/// let err = Expr::new(src, Mode::Standard).parse().unwrap_err();
+/// let expected = [
+///     "error: expected a number, a name or `(`, found `)`",
+///     " --> 1:9",
+///     "  |",
+///     "1 | 2 * (1 +)",
+///     "  |         ^ expected a number, a name or `(` after `+`",
+/// ];
+/// assert_eq!(expected.join("\n") + "\n", render(src, &err));
+/// ```
+pub fn render(src: &str, err: &ExprError) -> String {
+    if matches!(
+        err,
+        ExprError::InvalidJson { .. }
+            | ExprError::InvalidRates { .. }
+            | ExprError::InvalidBytecode { .. }
+    ) {
+        return format!("error: {}\n", err);
+    }
+    let span = err.span();
+    // the line the span starts on, 1-based like the column
+    let start = src[..span.offset].rfind('\n').map_or(0, |i| i + 1);
+    let end = src[start..].find('\n').map_or(src.len(), |i| start + i);
+    let line = &src[start..end];
+    let number = src[..start].matches('\n').count() + 1;
+    let column = src[start..span.offset].chars().count() + 1;
+    // a span running past the line is cut at its end
+    let width = src[span.offset..(span.offset + span.len).min(end)]
+        .chars()
+        .count()
+        .max(1);
+    let gutter = " ".repeat(number.to_string().len());
+    let mut out = format!("error: {:#}\n", err);
+    out += &format!("{}--> {}:{}\n", gutter, number, column);
+    out += &format!("{} |\n", gutter);
+    out += &format!("{} | {}\n", number, line);
+    let marker = format!("{}{}", " ".repeat(column - 1), "^".repeat(width));
+    match hint(src, err) {
+        Some(hint) => out += &format!("{} | {} {}\n", gutter, marker, hint),
+        None => out += &format!("{} | {}\n", gutter, marker),
+    }
+    out
+}
+
+// what to do about the error, from what it is and the source around it
+fn hint(src: &str, err: &ExprError) -> Option<String> {
+    match err {
+        ExprError::UnexpectedToken {
+            expected, found, ..
+        } => {
+            let before = token_before(src, err.span().offset);
+            if expected.starts_with("a number") {
+                return Some(match before {
+                    Some(before) => format!("expected {} after `{}`", expected, before),
+                    None => format!("an expression starts with {}", expected),
+                });
+            }
+            // two operands in a row, 2 x or 3 (4)
+            let operand = Tokenizer::new(found, Mode::Standard)
+                .next()
+                .and_then(|token| token.ok())
+                .is_some_and(|(token, _)| token.starts_operand());
+            match before {
+                Some(before) if operand => Some(format!(
+                    "an operator is missing between `{}` and `{}`",
+                    before, found
+                )),
+                _ => None,
+            }
+        }
+        ExprError::UnbalancedParen { span } => match span.text(src) {
+            "(" => Some("this `(` is never closed".into()),
+            _ => Some("no `(` opens this".into()),
+        },
+        ExprError::UnknownFunction { name, .. } => {
+            closest_function(name).map(|f| format!("did you mean `{}`?", f))
+        }
+        ExprError::UnknownVariable { name, .. } => {
+            Some(format!("give it a value first, like `{} = 1`", name))
+        }
+        ExprError::UnterminatedComment { .. } => Some("close it with `*/`".into()),
+        ExprError::AmbiguousNumber { text, .. } => Some(format!(
+            "write `{}` or `{}`",
+            text.replace([',', '.'], ""),
+            text.replace(',', ".")
+        )),
+        ExprError::DivisionByZero { .. } => Some("the divisor is zero".into()),
+        ExprError::ImplicitPromotion { .. } => {
+            Some("convert it first with `float()`, or the float with `int()`".into())
+        }
+        ExprError::Type { expected, .. } if expected == "bool" => {
+            Some("a number is not a condition, compare it like `x != 0`".into())
+        }
+        _ => None,
+    }
+}
+
+// the last token that ends at or before offset
+fn token_before(src: &str, offset: usize) -> Option<String> {
+    let tokens = Tokenizer::new(src, Mode::Standard).map_while(|token| token.ok());
+    let before: Vec<(Token, _)> = tokens
+        .take_while(|(_, span)| span.offset + span.len <= offset)
+        .collect();
+    before.last().map(|(_, span)| span.text(src).to_string())
+}
+
+// the builtin a misspelled name most likely meant, at most two edits away
+fn closest_function(name: &str) -> Option<&'static str> {
+    (FUNCTIONS.iter())
+        .map(|(f, _)| (edit_distance(name, f), *f))
+        .filter(|(distance, _)| *distance <= 2)
+        .min()
+        .map(|(_, f)| f)
+}
+
+// levenshtein distance, the insertions, deletions and substitutions between
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (diagonal + (ca != *cb) as usize)
+                .min(above + 1)
+                .min(row[j] + 1);
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
@@ -0,0 +1,438 @@
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use crate::{
+    ast::binary_height, call_node, check_arity, lookup_function, Arity, Assoc, Ast, Builtin, Expr,
+    ExprError, Result, Span, Token, Value,
+};
+
+// entries of the shunting-yard operator stack
+enum Pending {
+    Op(Token, Span),
+    // prefix operator, binds like the operand parse_atom reads after it
+    Prefix(Token, Span),
+    Paren(Span),
+    // function waiting for its ( ... ) to close, with the arguments so far
+    Call(String, Span, usize),
+    // a `?` before its `:`, and after it
+    Then(Span),
+    Else(Span),
+}
+
+// what the shunting yard puts out, in postfix order
+enum Postfix {
+    Num(Value, Span),
+    Var(String, Span),
+    Op(Token, Span),
+    Prefix(Token, Span),
+    // a call of as many arguments as came before
+    Call(String, Span, usize),
+    // cond ? a : b, the span of the `?`
+    Cond(Span),
+    // ( ) around the operand before it, nothing in postfix; the span of `(`
+    Paren(Span),
+}
+
+impl Postfix {
+    // how it is written in the notation parse_rpn reads
+    fn rpn(self) -> Result<Option<String>> {
+        Ok(Some(match self {
+            Postfix::Num(n, _) => n.to_string(),
+            Postfix::Var(name, _) => name,
+            Postfix::Op(op, _) => op.to_string(),
+            Postfix::Prefix(Token::Minus, _) => "neg".to_string(),
+            Postfix::Prefix(Token::Root, _) => "sqrt".to_string(),
+            Postfix::Prefix(op, _) => op.to_string(),
+            // a function taking a variable count is applied to the fewest it
+            // takes again and again, max(a, b, c) is a b c max max
+            Postfix::Call(name, span, args) => {
+                let Some(func) = lookup_function(&name) else {
+                    return Err(ExprError::Unsupported {
+                        what: format!("calling the user function `{}`", name),
+                        span,
+                    });
+                };
+                // postfix calls take the fewest arguments, round(x, 2) and
+                // vec(a, b) can't be written
+                let fixed =
+                    matches!(func.arity(), Arity::Between(..)) || matches!(func, Builtin::Vector);
+                if fixed && args != func.arity().min() {
+                    return Err(ExprError::Unsupported {
+                        what: format!("{}({})", name, vec!["_"; args].join(", ")),
+                        span,
+                    });
+                }
+                vec![name; args + 1 - func.arity().min()].join(" ")
+            }
+            Postfix::Cond(_) => "?".to_string(),
+            Postfix::Paren(_) => return Ok(None),
+        }))
+    }
+}
+
+impl Pending {
+    // precedence on the stack, None for the ones only their closer pops
+    fn precedence(&self) -> Option<i32> {
+        match self {
+            Pending::Op(op, _) => Some(op.precedence()),
+            Pending::Prefix(op, _) => Some(op.prefix_precedence()),
+            Pending::Else(_) => Some(Token::Question.precedence()),
+            _ => None,
+        }
+    }
+
+    // what it puts out once popped, parens and `?` leave nothing
+    fn output(self) -> Option<Postfix> {
+        match self {
+            Pending::Op(op, span) => Some(Postfix::Op(op, span)),
+            Pending::Prefix(op, span) => Some(Postfix::Prefix(op, span)),
+            Pending::Call(name, span, args) => Some(Postfix::Call(name, span, args)),
+            Pending::Else(span) => Some(Postfix::Cond(span)),
+            Pending::Paren(_) | Pending::Then(_) => None,
+        }
+    }
+}
+
+impl<'a> Expr<'a> {
+    /// Parse the whole source in reverse Polish notation, operands first and
+    /// then the operator applied to them. `-` always subtracts and `neg`
+    /// negates, `!` and `~` take one operand, `?` takes a condition and two
+    /// branches, and a function name is applied to as many values before it
+    /// as it takes, the fewest for `max` and `min`. A tree deeper than
+    /// [`ExprLimits::max_depth`](crate::ExprLimits) is TooDeep, like nesting
+    /// in infix.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode, Value};
+    ///
+    /// let ast = Expr::new("3 4 + 5 *", Mode::Standard).parse_rpn().unwrap();
+    /// assert_eq!("(3 + 4) * 5", ast.to_string());
+    /// assert_eq!(Value::Int(35), ast.eval().unwrap());
+    /// ```
+    pub fn parse_rpn(&mut self) -> Result<Ast> {
+        // the trees so far with their heights, see Ast::height
+        let mut stack: Vec<(Ast, usize)> = Vec::new();
+        while let Some((token, span)) = self.peek()?.cloned() {
+            // the operands, last one on top, and their heights
+            let mut heights = Vec::new();
+            let mut pop = |n: usize| {
+                if stack.len() < n {
+                    return Err(ExprError::UnexpectedToken {
+                        expected: "an operand".into(),
+                        found: span.text(self.src).to_string(),
+                        span,
+                    });
+                }
+                let operands = stack.split_off(stack.len() - n);
+                heights.extend(operands.iter().map(|(_, h)| *h));
+                Ok(operands.into_iter().map(|(ast, _)| ast).collect::<Vec<_>>())
+            };
+            let node = match token {
+                Token::Number(n) => Ast::Num(n),
+                Token::Ident(name) if name == "neg" => Ast::Unary {
+                    op: Token::Minus,
+                    operand: Box::new(pop(1)?.pop().unwrap()),
+                    span,
+                },
+                Token::Ident(name) if lookup_function(&name).is_some() => {
+                    let func = lookup_function(&name).unwrap();
+                    let args = pop(func.arity().min())?;
+                    call_node(func, name, args, span)
+                }
+                Token::Ident(name) => Ast::Var { name, span },
+                Token::Root => {
+                    let sqrt = lookup_function("sqrt").unwrap();
+                    call_node(sqrt, "sqrt".into(), pop(1)?, span)
+                }
+                Token::Question => {
+                    let [cond, then, otherwise] = <[Ast; 3]>::try_from(pop(3)?).unwrap();
+                    Ast::Cond {
+                        cond: Box::new(cond),
+                        then: Box::new(then),
+                        otherwise: Box::new(otherwise),
+                        span,
+                    }
+                }
+                op if op.is_operator() => {
+                    let [lhs, rhs] = <[Ast; 2]>::try_from(pop(2)?).unwrap();
+                    Ast::BinaryOp {
+                        op,
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                        span,
+                    }
+                }
+                op if op.is_prefix() => Ast::Unary {
+                    op,
+                    operand: Box::new(pop(1)?.pop().unwrap()),
+                    span,
+                },
+                _ => return Err(self.unexpected("a number, a name or an operator")),
+            };
+            // nothing recurses while reading, but evaluating the tree does
+            let height = node_height(&node, &heights);
+            if height > self.limits.max_depth {
+                return Err(ExprError::TooDeep { span });
+            }
+            stack.push((node, height));
+            self.iter.next();
+        }
+        match stack.len() {
+            0 => Err(self.unexpected("an expression")),
+            1 => Ok(stack.pop().unwrap().0),
+            // operands left over with nothing to combine them
+            _ => Err(self.unexpected("an operator")),
+        }
+    }
+
+    /// Convert infix source to postfix with the shunting-yard algorithm, the
+    /// tokens separated by spaces in the notation [`Expr::parse_rpn`] reads.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode};
+    ///
+    /// let rpn = Expr::new("(1 + 2) * -x ^ 2", Mode::Standard).to_rpn().unwrap();
+    /// assert_eq!("1 2 + x 2 ^ neg *", rpn);
+    /// ```
+    pub fn to_rpn(&mut self) -> Result<String> {
+        let mut out = Vec::new();
+        for item in self.shunting_yard()? {
+            out.extend(item.rpn()?);
+        }
+        Ok(out.join(" "))
+    }
+
+    /// Parse the whole source like [`Expr::parse`], with the shunting-yard
+    /// algorithm [`Expr::to_rpn`] uses: operators wait on a stack until one
+    /// binding looser comes along, and the postfix order they leave in is
+    /// built into the tree. Vectors and percentages aren't read, and the
+    /// first error ends parsing. Nothing recurses while reading, the limit
+    /// on nesting holds for the tree as it is built, like in
+    /// [`Expr::parse_rpn`].
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode, Value};
+    ///
+    /// let ast = Expr::new("max(1, 2, 3) * -(4 - 6)", Mode::Standard)
+    ///     .parse_shunting_yard()
+    ///     .unwrap();
+    /// assert_eq!("max(1, 2, 3) * -(4 - 6)", ast.to_string());
+    /// assert_eq!(Value::Int(6), ast.eval().unwrap());
+    /// ```
+    pub fn parse_shunting_yard(&mut self) -> Result<Ast> {
+        // the trees so far with their heights, as in parse_rpn
+        let mut stack = Vec::new();
+        for item in self.shunting_yard()? {
+            let (node, height, span) = tree_node(item, &mut stack);
+            if height > self.limits.max_depth {
+                return Err(ExprError::TooDeep { span });
+            }
+            stack.push((node, height));
+        }
+        Ok(stack.pop().expect("a well formed postfix").0)
+    }
+
+    // the source in postfix order
+    fn shunting_yard(&mut self) -> Result<Vec<Postfix>> {
+        let mut out = Vec::new();
+        let mut stack = Vec::new();
+        // whether an operand comes next, else an operator
+        let mut operand = true;
+        while let Some((token, span)) = self.peek()?.cloned() {
+            match token {
+                Token::Number(_) | Token::Ident(_) if !operand => {
+                    return Err(self.unexpected("an operator or end of input"))
+                }
+                Token::Number(n) => {
+                    out.push(Postfix::Num(n, span));
+                    operand = false;
+                }
+                Token::Ident(name) => {
+                    self.iter.next();
+                    if let Some((Token::LeftParen, _)) = self.peek()? {
+                        if lookup_function(&name).is_none() && !self.functions.contains(&name) {
+                            return Err(ExprError::UnknownFunction { name, span });
+                        }
+                        stack.push(Pending::Call(name, span, 1));
+                    } else {
+                        out.push(Postfix::Var(name, span));
+                        operand = false;
+                    }
+                    continue;
+                }
+                op if operand && (op.is_prefix() || op == Token::Root) => {
+                    stack.push(Pending::Prefix(op, span))
+                }
+                Token::LeftParen if operand => stack.push(Pending::Paren(span)),
+                Token::LeftBrace => {
+                    return Err(ExprError::Unsupported {
+                        what: "{ }".into(),
+                        span,
+                    })
+                }
+                // f(), a call without arguments
+                Token::RightParen
+                    if operand
+                        && matches!(stack[..], [.., Pending::Call(..), Pending::Paren(_)]) =>
+                {
+                    stack.pop();
+                    if let Some(Pending::Call(name, call, args)) = stack.last_mut() {
+                        *args = 0;
+                        if let Some(func) = lookup_function(name) {
+                            check_arity(name, func, 0, *call)?;
+                        }
+                    }
+                    out.extend(stack.pop().and_then(Pending::output));
+                    operand = false;
+                }
+                Token::RightParen if !operand => {
+                    let open = loop {
+                        match stack.pop() {
+                            Some(Pending::Paren(open)) => break open,
+                            None | Some(Pending::Then(_)) => {
+                                return Err(ExprError::UnbalancedParen { span })
+                            }
+                            Some(pending) => out.extend(pending.output()),
+                        }
+                    };
+                    match stack.last() {
+                        Some(Pending::Call(name, call, args)) => {
+                            if let Some(func) = lookup_function(name) {
+                                check_arity(name, func, *args, *call)?;
+                            }
+                            out.extend(stack.pop().and_then(Pending::output));
+                        }
+                        _ => out.push(Postfix::Paren(open)),
+                    }
+                }
+                // the argument before it is complete, the call's paren stays
+                Token::Comma if !operand => {
+                    loop {
+                        match stack.last() {
+                            Some(Pending::Paren(_)) => break,
+                            None | Some(Pending::Then(_)) => {
+                                return Err(self.unexpected("an operator or end of input"))
+                            }
+                            Some(_) => out.extend(stack.pop().and_then(Pending::output)),
+                        }
+                    }
+                    match stack.len().checked_sub(2).map(|i| &mut stack[i]) {
+                        Some(Pending::Call(_, _, args)) => *args += 1,
+                        _ => return Err(self.unexpected("an operator or `)`")),
+                    }
+                    operand = true;
+                }
+                // everything since the `?` is its then branch
+                Token::Colon if !operand => {
+                    loop {
+                        match stack.pop() {
+                            Some(Pending::Then(question)) => {
+                                stack.push(Pending::Else(question));
+                                break;
+                            }
+                            None | Some(Pending::Paren(_)) => {
+                                return Err(self.unexpected("an operator or end of input"))
+                            }
+                            Some(pending) => out.extend(pending.output()),
+                        }
+                    }
+                    operand = true;
+                }
+                op if !operand && (op.is_operator() || op == Token::Question) => {
+                    let prec = op.precedence();
+                    while let Some(p) = stack.last().and_then(Pending::precedence) {
+                        if p < prec || (p == prec && op.assoc() != Assoc::Left) {
+                            break;
+                        }
+                        out.extend(stack.pop().and_then(Pending::output));
+                    }
+                    stack.push(match op {
+                        Token::Question => Pending::Then(span),
+                        op => Pending::Op(op, span),
+                    });
+                    operand = true;
+                }
+                _ if operand => return Err(self.unexpected("a number, a name or `(`")),
+                _ => return Err(self.unexpected("an operator or end of input")),
+            }
+            self.iter.next();
+        }
+        if operand {
+            return Err(self.unexpected("a number, a name or `(`"));
+        }
+        while let Some(pending) = stack.pop() {
+            match pending {
+                Pending::Paren(span) => return Err(ExprError::UnbalancedParen { span }),
+                Pending::Then(_) => return Err(self.unexpected("`:`")),
+                pending => out.extend(pending.output()),
+            }
+        }
+        Ok(out)
+    }
+}
+
+// the node for the next postfix item with its height and span, its operands
+// taken off the top of stack
+fn tree_node(item: Postfix, stack: &mut Vec<(Ast, usize)>) -> (Ast, usize, Span) {
+    let mut heights = Vec::new();
+    let mut pop = |n: usize| {
+        let operands = stack.split_off(stack.len() - n);
+        heights.extend(operands.iter().map(|(_, h)| *h));
+        operands.into_iter().map(|(ast, _)| ast).collect::<Vec<_>>()
+    };
+    let (node, span) = match item {
+        Postfix::Num(n, span) => (Ast::Num(n), span),
+        Postfix::Var(name, span) => (Ast::Var { name, span }, span),
+        Postfix::Paren(span) => (Ast::Paren(Box::new(pop(1).remove(0))), span),
+        Postfix::Prefix(Token::Root, span) => {
+            let sqrt = lookup_function("sqrt").expect("a builtin");
+            (call_node(sqrt, "sqrt".into(), pop(1), span), span)
+        }
+        Postfix::Prefix(op, span) => {
+            let operand = Box::new(pop(1).remove(0));
+            (Ast::Unary { op, operand, span }, span)
+        }
+        Postfix::Op(op, span) => {
+            let [lhs, rhs] = <[Ast; 2]>::try_from(pop(2)).unwrap();
+            let node = Ast::BinaryOp {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+                span,
+            };
+            (node, span)
+        }
+        Postfix::Call(name, span, args) => {
+            let args = pop(args);
+            match lookup_function(&name) {
+                Some(func) => (call_node(func, name, args, span), span),
+                None => (Ast::Call { name, args, span }, span),
+            }
+        }
+        Postfix::Cond(span) => {
+            let [cond, then, otherwise] = <[Ast; 3]>::try_from(pop(3)).unwrap();
+            let node = Ast::Cond {
+                cond: Box::new(cond),
+                then: Box::new(then),
+                otherwise: Box::new(otherwise),
+                span,
+            };
+            (node, span)
+        }
+    };
+    let height = node_height(&node, &heights);
+    (node, height, span)
+}
+
+// the height of a node from those of its operands, see Ast::height
+fn node_height(node: &Ast, operands: &[usize]) -> usize {
+    match node {
+        Ast::BinaryOp { op, lhs, .. } => binary_height(op, lhs, operands[0], operands[1]),
+        _ => 1 + operands.iter().max().unwrap_or(&0),
+    }
+}
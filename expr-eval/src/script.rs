@@ -0,0 +1,307 @@
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec::Vec};
+
+use crate::{lookup_function, Ast, Env, Expr, ExprError, Function, Result, Span, Token, Value};
+
+// one statement of a script
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    // name = value, span is the `=`; `x += 1` is read as `x = x + 1`
+    Assign {
+        name: String,
+        value: Ast,
+        span: Span,
+    },
+    Expr(Ast),
+    // for var in from..to { body }, span is the `..`; the body runs with var
+    // set to each integer from `from` up to, or with ..= through, `to`
+    For {
+        var: String,
+        from: Ast,
+        to: Ast,
+        inclusive: bool,
+        body: Vec<Stmt>,
+        span: Span,
+    },
+    // fn name(params) = body, stored in the environment under its name
+    Def(Arc<Function>),
+}
+
+// statements separated by `;` or newlines, like `a = 3; b = a * 2; a + b`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    stmts: Vec<Stmt>,
+}
+
+impl Script {
+    // run the statements in order, assignments update env; the value is the
+    // one of the last statement
+    pub fn eval(&self, env: &mut Env) -> Result<Value> {
+        self.eval_with(env, Ast::eval_with)
+    }
+
+    // same, evaluating each expression with the given backend
+    pub fn eval_with(
+        &self,
+        env: &mut Env,
+        eval: impl Fn(&Ast, &Env) -> Result<Value>,
+    ) -> Result<Value> {
+        self.eval_each(env, eval, |_| {})
+    }
+
+    // same, also handing the value of every statement that isn't an
+    // assignment to each as soon as it is known, the way a script file prints
+    // its results
+    pub fn eval_each(
+        &self,
+        env: &mut Env,
+        eval: impl Fn(&Ast, &Env) -> Result<Value>,
+        mut each: impl FnMut(&Value),
+    ) -> Result<Value> {
+        if let Some(value) = run(&self.stmts, env, &eval, &mut each)? {
+            return Ok(value);
+        }
+        // only a loop gives no value, when its range is empty
+        match self.stmts.last() {
+            Some(Stmt::For {
+                from,
+                to,
+                inclusive,
+                span,
+                ..
+            }) => Err(ExprError::Type {
+                expected: "a loop that runs at least once".into(),
+                found: format!("{}{}{}", from, if *inclusive { "..=" } else { ".." }, to),
+                operation: None,
+                span: *span,
+            }),
+            _ => unreachable!("the parser never builds an empty script"),
+        }
+    }
+}
+
+// the statements in order, giving the value of the last one; a loop gives the
+// one of its body the last time round, none when it doesn't run
+fn run(
+    stmts: &[Stmt],
+    env: &mut Env,
+    eval: &impl Fn(&Ast, &Env) -> Result<Value>,
+    each: &mut impl FnMut(&Value),
+) -> Result<Option<Value>> {
+    let mut last = None;
+    for stmt in stmts {
+        let value = match stmt {
+            Stmt::Assign { name, value, .. } => {
+                let value = eval(value, env)?;
+                env.insert(name.clone(), value.clone());
+                Some(value)
+            }
+            Stmt::Expr(ast) => {
+                let value = eval(ast, env)?;
+                each(&value);
+                Some(value)
+            }
+            Stmt::For {
+                var,
+                from,
+                to,
+                inclusive,
+                body,
+                span,
+            } => {
+                let (mut i, to) = (eval(from, env)?, eval(to, env)?);
+                i.expect_integer(*span)?;
+                to.expect_integer(*span)?;
+                let past = if *inclusive {
+                    Token::Greater
+                } else {
+                    Token::GreaterEqual
+                };
+                let mut value = None;
+                while !past
+                    .compute(i.clone(), to.clone(), *span)?
+                    .expect_bool(*span)?
+                {
+                    env.insert(var.clone(), i.clone());
+                    value = run(body, env, eval, each)?.or(value);
+                    i = Token::Plus.compute(i, Value::Int(1), *span)?;
+                }
+                value
+            }
+            Stmt::Def(function) => {
+                let value = Value::Function(function.clone());
+                env.insert(function.name.clone(), value.clone());
+                Some(value)
+            }
+        };
+        last = value.or(last);
+    }
+    Ok(last)
+}
+
+impl<'a> Expr<'a> {
+    /// Parse the whole source as a script.
+    ///
+    /// ```
+    /// use expr_eval::{Env, Expr, Mode, Value};
+    ///
+    /// let script = Expr::new("a = 3; b = a * 2\na + b", Mode::Standard)
+    ///     .parse_script()
+    ///     .unwrap();
+    /// let mut env = Env::new();
+    /// assert_eq!(Value::Int(9), script.eval(&mut env).unwrap());
+    /// assert_eq!(Some(&Value::Int(6)), env.get("b"));
+    /// ```
+    pub fn parse_script(&mut self) -> Result<Script> {
+        let mut stmts = Vec::new();
+        loop {
+            // empty statements are fine, `a = 1;;` or blank lines
+            while let Some((Token::Semicolon, _)) = self.peek()? {
+                self.iter.next();
+            }
+            if self.peek()?.is_none() {
+                break;
+            }
+            stmts.push(self.parse_stmt()?);
+            match self.peek()? {
+                None | Some((Token::Semicolon, _)) => {}
+                Some((Token::RightParen, span)) => {
+                    return Err(ExprError::UnbalancedParen { span: *span })
+                }
+                Some(_) => return Err(self.unexpected("an operator, `;` or end of input")),
+            }
+        }
+        if stmts.is_empty() {
+            return Err(self.unexpected("an expression"));
+        }
+        Ok(Script { stmts })
+    }
+
+    // an expression, or one that turns out to be the target of an assignment
+    fn parse_stmt(&mut self) -> Result<Stmt> {
+        if self.keyword_ahead("for")? {
+            return self.parse_for();
+        }
+        if self.keyword_ahead("fn")? {
+            return self.parse_def();
+        }
+        let ast = self.parse_expr(1)?;
+        let (op, span) = match self.peek()? {
+            Some((Token::Assign, span)) => (None, *span),
+            Some((Token::CompoundAssign(op), span)) => (Some((**op).clone()), *span),
+            _ => return Ok(Stmt::Expr(ast)),
+        };
+        let Ast::Var { name, .. } = &ast else {
+            return Err(ExprError::InvalidAssignment { span });
+        };
+        let name = name.clone();
+        self.iter.next();
+        let mut value = self.parse_expr(1)?;
+        // the operator takes the whole right side, x *= 2 + 3 is x = x * (2 + 3)
+        if let Some(op) = op {
+            value = Ast::BinaryOp {
+                op,
+                lhs: Box::new(ast),
+                rhs: Box::new(value),
+                span,
+            };
+        }
+        Ok(Stmt::Assign { name, value, span })
+    }
+
+    // `for` and `fn` start a statement when a name follows them, else they
+    // are variables
+    fn keyword_ahead(&mut self, keyword: &str) -> Result<bool> {
+        if !matches!(self.peek()?, Some((Token::Ident(word), _)) if word == keyword) {
+            return Ok(false);
+        }
+        let mut ahead = self.iter.clone();
+        ahead.next();
+        Ok(matches!(ahead.next(), Some(Ok((Token::Ident(_), _)))))
+    }
+
+    // for NAME in FROM..TO { STMTS }, with ..= to take in TO as well
+    fn parse_for(&mut self) -> Result<Stmt> {
+        self.iter.next();
+        let Some((Token::Ident(var), _)) = self.peek()? else {
+            unreachable!("checked by keyword_ahead");
+        };
+        let var = var.clone();
+        self.iter.next();
+        match self.peek()? {
+            Some((Token::Ident(word), _)) if word == "in" => self.iter.next(),
+            _ => return Err(self.unexpected("`in`")),
+        };
+        let from = self.parse_expr(1)?;
+        let (inclusive, span) = match self.peek()? {
+            Some((Token::Range, span)) => (false, *span),
+            Some((Token::RangeInclusive, span)) => (true, *span),
+            _ => return Err(self.unexpected("`..` or `..=`")),
+        };
+        self.iter.next();
+        let to = self.parse_expr(1)?;
+        let body = self.parse_block()?;
+        Ok(Stmt::For {
+            var,
+            from,
+            to,
+            inclusive,
+            body,
+            span,
+        })
+    }
+
+    // fn NAME(PARAMS) = EXPR, where the body may call NAME itself
+    fn parse_def(&mut self) -> Result<Stmt> {
+        self.iter.next();
+        let Some((Token::Ident(name), span)) = self.peek()? else {
+            unreachable!("checked by keyword_ahead");
+        };
+        let (name, span) = (name.clone(), *span);
+        if lookup_function(&name).is_some() {
+            return Err(ExprError::Unsupported {
+                what: format!("redefining the builtin `{}`", name),
+                span,
+            });
+        }
+        self.iter.next();
+        self.expect(Token::LeftParen)?;
+        let mut params = Vec::new();
+        while let Some((Token::Ident(param), _)) = self.peek()? {
+            params.push(param.clone());
+            self.iter.next();
+            if let Some((Token::Comma, _)) = self.peek()? {
+                self.iter.next();
+            } else {
+                break;
+            }
+        }
+        self.expect(Token::RightParen)?;
+        self.expect(Token::Assign)?;
+        self.functions.insert(name.clone());
+        let body = self.parse_expr(1)?;
+        Ok(Stmt::Def(Arc::new(Function { name, params, body })))
+    }
+
+    // { STMT; STMT }, the statements of a loop, on one line or several
+    fn parse_block(&mut self) -> Result<Vec<Stmt>> {
+        self.expect(Token::LeftBrace)?;
+        let mut stmts = Vec::new();
+        loop {
+            while let Some((Token::Semicolon, _)) = self.peek()? {
+                self.iter.next();
+            }
+            if let Some((Token::RightBrace, _)) = self.peek()? {
+                self.iter.next();
+                return Ok(stmts);
+            }
+            if self.peek()?.is_none() {
+                return Err(self.unexpected("`}`"));
+            }
+            stmts.push(self.parse_stmt()?);
+            match self.peek()? {
+                Some((Token::Semicolon | Token::RightBrace, _)) => {}
+                _ => return Err(self.unexpected("an operator, `;` or `}`")),
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use mini_bitcask_rs::bitcask::MiniBitcask;
+
+use expr_eval::{Ast, Env};
+
+// keys of the store: var:NAME for each variable, its value as a json number
+// node, and history:000042 for the lines typed, numbered so a scan reads them
+// back in order
+const VAR: &str = "var:";
+const HISTORY: &str = "history:";
+
+// a repl session as a bitcask file, what `:save` writes and `:load` reads
+pub struct Session {
+    pub env: Env,
+    pub history: Vec<String>,
+}
+
+// replace whatever the file held with the session, then compact it so the
+// file doesn't grow with every save
+pub fn save(path: &str, session: &Session) -> io::Result<()> {
+    let mut store = MiniBitcask::new(PathBuf::from(path))?;
+    let old: Vec<Vec<u8>> = (store.scan(..))
+        .map(|entry| entry.map(|(key, _)| key))
+        .collect::<io::Result<_>>()?;
+    for key in old {
+        store.delete(&key)?;
+    }
+    for (name, value) in &session.env {
+        let json = Ast::Num(value.clone()).to_json();
+        store.set(format!("{}{}", VAR, name).as_bytes(), json.into_bytes())?;
+    }
+    for (i, line) in session.history.iter().enumerate() {
+        let key = format!("{}{:06}", HISTORY, i);
+        store.set(key.as_bytes(), line.clone().into_bytes())?;
+    }
+    store.merge()
+}
+
+// the session saved in path; a missing file is an error rather than an empty
+// session
+pub fn load(path: &str) -> io::Result<Session> {
+    if !Path::new(path).exists() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+    }
+    let mut store = MiniBitcask::new(PathBuf::from(path))?;
+    let invalid = |what: String| io::Error::new(io::ErrorKind::InvalidData, what);
+    let mut env = Env::new();
+    for entry in store.scan_prefix(VAR.as_bytes()) {
+        let (key, value) = entry?;
+        let name = String::from_utf8_lossy(&key[VAR.len()..]).into_owned();
+        let json = String::from_utf8_lossy(&value);
+        match &Ast::from_json(&json) {
+            Ok(Ast::Num(value)) => {
+                env.insert(name, value.clone());
+            }
+            _ => return Err(invalid(format!("bad value of `{}`", name))),
+        }
+    }
+    let history = (store.scan_prefix(HISTORY.as_bytes()))
+        .map(|entry| entry.map(|(_, line)| String::from_utf8_lossy(&line).into_owned()))
+        .collect::<io::Result<_>>()?;
+    Ok(Session { env, history })
+}
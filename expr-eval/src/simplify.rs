@@ -0,0 +1,101 @@
+use alloc::boxed::Box;
+
+use crate::{ast::take, lookup_function, Ast, Token, Value};
+
+impl Ast {
+    // fold constant subtrees and drop operations that change nothing,
+    // 2 * 3 + x * 1 becomes 6 + x
+    pub fn simplify(&self) -> Ast {
+        match self {
+            Ast::Num(_) | Ast::Var { .. } => self.clone(),
+            // the printer puts back the parentheses that matter
+            Ast::Paren(inner) => inner.simplify(),
+            Ast::Unary { op, operand, span } => match operand.simplify() {
+                // --x is x, registered operators may not undo themselves
+                Ast::Unary {
+                    op: ref inner,
+                    ref mut operand,
+                    ..
+                } if *inner == *op && !matches!(op, Token::Custom(_)) => take(operand),
+                operand => fold(Ast::Unary {
+                    op: op.clone(),
+                    operand: Box::new(operand),
+                    span: *span,
+                }),
+            },
+            Ast::BinaryOp { op, lhs, rhs, span } => {
+                let (lhs, rhs) = (lhs.simplify(), rhs.simplify());
+                match op {
+                    Token::Plus if is_exactly(&lhs, 0) => rhs,
+                    Token::Plus | Token::Minus if is_exactly(&rhs, 0) => lhs,
+                    Token::Minus if is_exactly(&lhs, 0) => Ast::Unary {
+                        op: Token::Minus,
+                        operand: Box::new(rhs),
+                        span: *span,
+                    }
+                    .simplify(),
+                    Token::Multiply if is_exactly(&lhs, 1) => rhs,
+                    Token::Multiply | Token::Divide | Token::Power if is_exactly(&rhs, 1) => lhs,
+                    _ => fold(Ast::BinaryOp {
+                        op: op.clone(),
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                        span: *span,
+                    }),
+                }
+            }
+            Ast::Call { name, args, span } => fold(Ast::Call {
+                name: name.clone(),
+                args: args.iter().map(Ast::simplify).collect(),
+                span: *span,
+            }),
+            // a known condition leaves just the branch it picks
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                span,
+            } => match cond.simplify() {
+                Ast::Num(Value::Bool(true)) => then.simplify(),
+                Ast::Num(Value::Bool(false)) => otherwise.simplify(),
+                cond => Ast::Cond {
+                    cond: Box::new(cond),
+                    then: Box::new(then.simplify()),
+                    otherwise: Box::new(otherwise.simplify()),
+                    span: *span,
+                },
+            },
+        }
+    }
+}
+
+// an exact literal equal to n, floats are left alone so 1.0 * x stays a float
+fn is_exactly(ast: &Ast, n: i32) -> bool {
+    match ast {
+        Ast::Num(Value::Float(_)) => false,
+        Ast::Num(v) => v.as_f64() == n as f64,
+        _ => false,
+    }
+}
+
+// replace a node whose operands are all numbers by its value, unless
+// evaluating it fails, so 1 / 0 is still reported when the result is used
+fn fold(ast: Ast) -> Ast {
+    let constant = match &ast {
+        Ast::Unary { operand, .. } => matches!(**operand, Ast::Num(_)),
+        Ast::BinaryOp { lhs, rhs, .. } => {
+            matches!(**lhs, Ast::Num(_)) && matches!(**rhs, Ast::Num(_))
+        }
+        Ast::Call { name, args, .. } => {
+            lookup_function(name).is_some_and(|f| f.is_pure())
+                && args.iter().all(|arg| matches!(arg, Ast::Num(_)))
+        }
+        _ => false,
+    };
+    match constant.then(|| ast.eval()) {
+        // text has no literal to print it back as
+        Some(Ok(Value::Text(_))) => ast,
+        Some(Ok(value)) => Ast::Num(value),
+        _ => ast,
+    }
+}
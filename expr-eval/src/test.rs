@@ -0,0 +1,3089 @@
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        boxed::Box,
+        format,
+        string::{String, ToString},
+        vec,
+        vec::Vec,
+    };
+    use core::sync::atomic::{AtomicI64, Ordering};
+    use std::{num::ParseIntError, ops::Add, str::FromStr};
+
+    use num_bigint::BigInt;
+    use num_traits::Zero;
+
+    use crate::{
+        caret, constants, eval_batch, parse_number, render, vm::CompiledExpr, Arithmetic, Assoc,
+        Ast, Engineering, Env, EvalOptions, Expr, ExprError, ExprLimits, Interval, Locale, Mode,
+        Numeric, Operators, Parser, Pratt, Precision, Promotion, Radix, Result, SiPrefix, Span,
+        Token, Tokenizer, Value, ValueFormatter, WordSize, ZeroDivision, DEFAULT_DECIMAL_SCALE,
+        MAX_DECIMAL_SCALE, PARSERS,
+    };
+    #[cfg(feature = "std")]
+    use crate::{load_rates, register_function, Arity, ExprCache, NativeFn};
+
+    fn eval(src: &str) -> Result<Value> {
+        Expr::new(src, Mode::Standard).eval()
+    }
+
+    #[test]
+    fn test_int_expr() -> Result<()> {
+        let result = eval("92 + 5 + 5 * 27 - (92 - 12) / 4 + 26")?;
+        assert_eq!(Value::Int(238), result);
+        assert_eq!(Value::Int(512), eval("2 ^ 3 ^ 2")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_float_expr() -> Result<()> {
+        assert_eq!(Value::Float(2.75), eval("2.75")?);
+        assert_eq!(Value::Float(1.0 / 3.0 * 2.5), eval("1/3 * 2.5")?);
+        assert_eq!(Value::Float(0.5), eval("2 ^ (0 - 1)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_functions() -> Result<()> {
+        assert_eq!(Value::Float(7.0), eval("sqrt(16) + abs(-3)")?);
+        assert_eq!(Value::Int(3), eval("abs(1 - 4)")?);
+        assert_eq!(Value::Float(0.0), eval("sin(0) * 2")?);
+        assert_eq!(Value::Int(-4), eval("-2 ^ 2")?);
+        assert!(eval("foo(1)").is_err());
+        assert!(eval("sqrt 4").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_span() {
+        let err = eval("1 + (2 * 3").unwrap_err();
+        assert_eq!(Span::new(4, 1), err.span());
+
+        let src = "12 * foo(3)";
+        let err = eval(src).unwrap_err();
+        assert_eq!(Span::new(5, 3), err.span());
+        assert_eq!("12 * foo(3)\n     ^^^", caret(src, err.span()));
+        assert_eq!("unknown function `foo` at offset 5", err.to_string());
+
+        // used to stop reading at the `$` and give 2
+        let err = eval("2 $ 3").unwrap_err();
+        assert_eq!(Span::new(2, 1), err.span());
+        assert_eq!("unexpected character `$` at offset 2", err.to_string());
+        let err = eval("1 + €").unwrap_err();
+        assert_eq!(Span::new(4, 3), err.span());
+        assert!(matches!(
+            Expr::new("a = 1; b = a @ 2", Mode::Standard).parse_script(),
+            Err(ExprError::InvalidChar { ch: '@', .. })
+        ));
+    }
+
+    #[test]
+    fn test_error_kind() {
+        assert!(matches!(
+            eval("1 + * 2"),
+            Err(ExprError::UnexpectedToken { .. })
+        ));
+        assert!(matches!(
+            eval("(1 + 2"),
+            Err(ExprError::UnbalancedParen { .. })
+        ));
+        assert!(matches!(
+            eval("1 + 2)"),
+            Err(ExprError::UnbalancedParen { .. })
+        ));
+        assert!(matches!(
+            eval("4 / (2 - 2)"),
+            Err(ExprError::DivisionByZero { .. })
+        ));
+        assert!(matches!(
+            eval("10.0 ^ 400"),
+            Err(ExprError::Overflow { .. })
+        ));
+        assert!(matches!(
+            eval("99999999999999999999"),
+            Err(ExprError::Overflow { .. })
+        ));
+        assert!(matches!(
+            eval("1.2.3 + 1"),
+            Err(ExprError::InvalidNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn test_overflow() -> Result<()> {
+        assert_eq!(Value::Int(i64::MAX), eval("2 ^ 62 - 1 + 2 ^ 62")?);
+        assert_eq!(Value::Int(10_000_000_000), eval("100000 * 100000")?);
+        for src in [
+            "2 ^ 63",
+            "4294967296 * 4294967296",
+            "0 - 9223372036854775807 - 2",
+            "abs(0 - 2 ^ 62 - 2 ^ 62)",
+            "exp(1000)",
+        ] {
+            assert!(
+                matches!(eval(src), Err(ExprError::Overflow { .. })),
+                "{}",
+                src
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bigint_mode() -> Result<()> {
+        let eval = |src| Expr::new(src, Mode::BigInt).eval();
+        assert_eq!(Some(Mode::BigInt), Mode::from_name("bigint"));
+        assert_eq!(None, Mode::from_name("big"));
+        let expected = "1606938044258990275541962092341162602522202993782792835301376";
+        assert_eq!(expected, eval("2 ^ 200")?.to_string());
+        assert_eq!(
+            "9999999999800000000001",
+            eval("99999999999 * 99999999999")?.to_string()
+        );
+        assert_eq!("-3", eval("-(2^100) / 2^100 * abs(-3)")?.to_string());
+        assert_eq!(Value::Float(0.5), eval("1 / 2")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rational_mode() -> Result<()> {
+        let eval = |src| Expr::new(src, Mode::Rational).eval();
+        assert_eq!("1/2", eval("1/3 + 1/6")?.to_string());
+        assert_eq!("1", eval("1/3 * 3")?.to_string());
+        assert_eq!("3/10", eval("0.1 + 0.2")?.to_string());
+        assert_eq!("-8/27", eval("(-2/3) ^ 3")?.to_string());
+        assert_eq!("9/4", eval("(2/3) ^ -2")?.to_string());
+        assert_eq!(Value::Float(2f64.sqrt()), eval("2 ^ (1/2)")?);
+        assert!(matches!(
+            eval("1 / (1/2 - 1/2)"),
+            Err(ExprError::DivisionByZero { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_decimal_mode() -> Result<()> {
+        assert_eq!("0.3", Expr::new_decimal("0.1 + 0.2").eval()?.to_string());
+        assert_eq!(
+            Expr::new_decimal("0.3").eval()?,
+            Expr::new_decimal("0.1 + 0.2").eval()?
+        );
+        assert_eq!("19.990", Expr::new_decimal("9.995 * 2").eval()?.to_string());
+        assert_eq!(
+            "0.3333333333",
+            Expr::new_decimal("1 / 3").eval()?.to_string()
+        );
+
+        // banker's rounding at scale 2
+        let eval = |src| Expr::new(src, Mode::Decimal(2)).eval();
+        assert_eq!("0.12", eval("0.125")?.to_string());
+        assert_eq!("0.14", eval("0.135")?.to_string());
+        assert_eq!("-0.12", eval("-0.125")?.to_string());
+        assert_eq!("0.67", eval("2 / 3")?.to_string());
+        assert_eq!("1.21", eval("1.1 ^ 2")?.to_string());
+        assert!(matches!(
+            eval("1 / 0.00"),
+            Err(ExprError::DivisionByZero { .. })
+        ));
+
+        // past 38 digits even 1 / 3 would overflow the mantissa
+        let widest = Mode::decimal(MAX_DECIMAL_SCALE).unwrap();
+        assert_eq!("0.5", Expr::new("1 / 2", widest).eval()?.to_string());
+        assert_eq!(None, Mode::decimal(MAX_DECIMAL_SCALE + 1));
+        assert!(matches!(
+            Expr::new("1", Mode::Decimal(40)).eval(),
+            Err(ExprError::Overflow { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_scientific_literal() -> Result<()> {
+        assert_eq!(Value::Float(1500.0), eval("1.5e3")?);
+        assert_eq!(Value::Float(0.0002), eval("2E-4")?);
+        assert_eq!(Value::Float(2000.0), eval("2e+3")?);
+        assert_eq!(Value::Float(3.5), eval("1e0 + 2.5")?);
+        assert_eq!(
+            "3/2000",
+            Expr::new("1.5e-3", Mode::Rational).eval()?.to_string()
+        );
+        assert_eq!(
+            "1250",
+            Expr::new("1.25e3", Mode::Decimal(2)).eval()?.to_string()
+        );
+        assert_eq!(
+            "0.00",
+            Expr::new("1e-50", Mode::Decimal(2)).eval()?.to_string()
+        );
+        // no digits after the e, so it is not an exponent
+        assert!(matches!(eval("2e"), Err(ExprError::UnexpectedToken { .. })));
+        // exponents too big to work with are an overflow, not a hang or inf
+        for (src, mode) in [
+            ("1e400", Mode::Standard),
+            ("1e400", Mode::BigInt),
+            ("1e999999999", Mode::Rational),
+            ("1e-2147483648", Mode::Rational),
+            ("1e-9223372036854775808", Mode::Decimal(2)),
+        ] {
+            let err = Expr::new(src, mode).eval().unwrap_err();
+            assert!(matches!(err, ExprError::Overflow { .. }), "{}", src);
+        }
+        assert_eq!(Value::Float(0.0), eval("1e-400")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_digit_separator() -> Result<()> {
+        assert_eq!(Value::Int(3_000_000), eval("1_000_000 * 3")?);
+        assert_eq!(Value::Float(1234.5), eval("1_234.5")?);
+        assert_eq!(Value::Float(1e10), eval("1e1_0")?);
+        for src in ["1_ + 2", "1__000", "1_.5", "1._5", "2_e3"] {
+            assert!(
+                matches!(eval(src), Err(ExprError::InvalidNumber { .. })),
+                "{}",
+                src
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_ast() -> Result<()> {
+        let ast = Expr::new("1 + 2 * 3", Mode::Standard).parse()?;
+        let Ast::BinaryOp { op, lhs, rhs, .. } = &ast else {
+            panic!("expected a binary op, got {:?}", ast);
+        };
+        assert_eq!(Token::Plus, *op);
+        assert_eq!(Ast::Num(Value::Int(1)), **lhs);
+        assert!(matches!(
+            **rhs,
+            Ast::BinaryOp {
+                op: Token::Multiply,
+                ..
+            }
+        ));
+
+        let ast = Expr::new("-(4)", Mode::Standard).parse()?;
+        let Ast::Unary { operand, .. } = &ast else {
+            panic!("expected a unary op, got {:?}", ast);
+        };
+        assert_eq!(Ast::Paren(Box::new(Ast::Num(Value::Int(4)))), **operand);
+
+        // parsing alone never divides
+        let ast = Expr::new("1 / 0", Mode::Standard).parse()?;
+        assert!(matches!(ast.eval(), Err(ExprError::DivisionByZero { .. })));
+        let ast = Expr::new("2 * (3 + 4)", Mode::Standard).parse()?;
+        assert_eq!(Value::Int(14), ast.eval()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pretty_print() -> Result<()> {
+        fn print(src: &str) -> Result<String> {
+            Expr::new(src, Mode::Standard)
+                .parse()
+                .map(|ast| ast.to_string())
+        }
+        assert_eq!("(1 + 2) * 3", print("((1 + 2)) * 3")?);
+        assert_eq!("1 + 2 * 3", print("1+(2*3)")?);
+        assert_eq!("1 - 2 - 3", print("(1 - 2) - 3")?);
+        assert_eq!("1 - (2 - 3)", print("1 - (2 - 3)")?);
+        assert_eq!("(2 ^ 3) ^ 2", print("(2 ^ 3) ^ 2")?);
+        assert_eq!("2 ^ 3 ^ 2", print("2 ^ (3 ^ 2)")?);
+        assert_eq!("(-2) ^ 2", print("(-2) ^ 2")?);
+        assert_eq!("-2 ^ 2", print("-(2 ^ 2)")?);
+        assert_eq!("-(1 + 2) * 2.0", print("(-(1 + 2)) * 2.0")?);
+        assert_eq!("sqrt(16) / abs(-3)", print("sqrt((16)) / abs(-3)")?);
+
+        // printing then parsing again gives the same tree shape back
+        for src in [
+            "1 - (2 + 3) * 4 ^ -1",
+            "2 ^ -(1 - 3) / 7",
+            "(1.5 / 2) ^ (3 - 1)",
+        ] {
+            let once = print(src)?;
+            assert_eq!(once, print(&once)?);
+            assert_eq!(
+                Expr::new(src, Mode::Standard).eval()?,
+                Expr::new(&once, Mode::Standard).eval()?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_ast() -> Result<()> {
+        let ast = Expr::new("1 + 2 * -(3.5)", Mode::Standard).parse()?;
+        assert_eq!("(+ 1 (* 2 (- 3.5)))", ast.to_sexpr());
+
+        let json = ast.to_json();
+        assert!(json.contains(r#""type":"binary""#), "{}", json);
+        let back = Ast::from_json(&json)?;
+        assert_eq!(ast.to_string(), back.to_string());
+        assert_eq!(ast.eval()?, back.eval()?);
+
+        // exact kinds survive the trip
+        for (src, mode) in [
+            ("2 ^ 100 + 99999999999", Mode::BigInt),
+            ("1/3 + 0.25", Mode::Rational),
+            ("0.125 - 1", Mode::Decimal(2)),
+        ] {
+            let ast = Expr::new(src, mode).parse()?;
+            let back = Ast::from_json(&ast.to_json())?;
+            assert_eq!(ast.eval()?, back.eval()?, "{}", src);
+        }
+
+        for json in [
+            "{",
+            r#"{"type": "num"}"#,
+            r#"{"type": "binary", "op": "(", "lhs": 1, "rhs": 2}"#,
+            r#"{"type": "call", "name": "nope", "arg": {"type": "num", "kind": "int", "value": 1}}"#,
+        ] {
+            assert!(Ast::from_json(json).is_err(), "{}", json);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_vm_matches_tree() -> Result<()> {
+        for (src, mode) in [
+            ("92 + 5 + 5 * 27 - (92 - 12) / 4 + 26", Mode::Standard),
+            ("-2 ^ 2 * sqrt(16) + abs(-3) / 2", Mode::Standard),
+            ("2 ^ 3 ^ 2 - 1.5e3", Mode::Standard),
+            ("2 ^ 100 * -3", Mode::BigInt),
+            ("1/3 + 1/6", Mode::Rational),
+        ] {
+            let ast = Expr::new(src, mode).parse()?;
+            let compiled = CompiledExpr::new(&ast);
+            assert_eq!(ast.eval()?, compiled.eval()?, "{}", src);
+            // evaluating again reuses the same program
+            assert_eq!(ast.eval()?, compiled.eval()?, "{}", src);
+        }
+
+        let ast = Expr::new("1 + 4 / (2 - 2)", Mode::Standard).parse()?;
+        let err = CompiledExpr::new(&ast).eval().unwrap_err();
+        assert!(matches!(err, ExprError::DivisionByZero { .. }));
+        assert_eq!(Span::new(6, 1), err.span());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_closure() -> Result<()> {
+        let area = Expr::new("pi * r ^ 2", Mode::Standard).compile()?;
+        let mut env = Env::new();
+        env.insert("pi".into(), Value::Float(2.75));
+        for (r, expected) in [(1, 2.75), (2, 11.0), (3, 24.75)] {
+            env.insert("r".into(), Value::Int(r));
+            assert_eq!(Value::Float(expected), area(&env)?);
+        }
+
+        env.remove("r");
+        let err = area(&env).unwrap_err();
+        assert!(matches!(&err, ExprError::UnknownVariable { name, .. } if name == "r"));
+        assert_eq!(Span::new(5, 1), err.span());
+
+        // the other backends read the same environment
+        let ast = Expr::new("x * 2 + abs(y)", Mode::Standard).parse()?;
+        let env = Env::from([("x".into(), Value::Int(4)), ("y".into(), Value::Int(-1))]);
+        assert_eq!(Value::Int(9), ast.eval_with(&env)?);
+        assert_eq!(Value::Int(9), CompiledExpr::new(&ast).eval_with(&env)?);
+        assert_eq!("x * 2 + abs(y)", ast.to_string());
+        let back = Ast::from_json(&ast.to_json())?;
+        assert_eq!(ast.eval_with(&env)?, back.eval_with(&env)?);
+        assert!(matches!(
+            eval("foo(1)"),
+            Err(ExprError::UnknownFunction { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_simplify() -> Result<()> {
+        for (src, expected) in [
+            ("2 * 3 + x", "6 + x"),
+            ("x * 1 + 0", "x"),
+            ("0 + 1 * (x / 1) ^ 1 - 0", "x"),
+            ("- -x", "x"),
+            ("-(-(x + 1))", "x + 1"),
+            ("0 - x", "-x"),
+            ("-3 * x", "-3 * x"),
+            ("abs(-4) * x + sqrt(2 + 2)", "4 * x + 2.0"),
+            ("1.0 * x", "1.0 * x"),
+            // failures are kept so evaluating still reports them
+            ("x + 1 / 0", "x + 1 / 0"),
+        ] {
+            let ast = Expr::new(src, Mode::Standard).parse()?;
+            let simple = ast.simplify();
+            assert_eq!(expected, simple.to_string(), "{}", src);
+            let env = Env::from([("x".into(), Value::Int(5))]);
+            assert_eq!(
+                ast.eval_with(&env).ok(),
+                simple.eval_with(&env).ok(),
+                "{}",
+                src
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_derivative() -> Result<()> {
+        for (src, expected) in [
+            ("x ^ 3 + 2 * x", "3 * x ^ 2 + 2"),
+            ("sin(x) * x", "cos(x) * x + sin(x)"),
+            ("exp(2 * x)", "exp(2 * x) * 2"),
+            ("y * x + y", "y"),
+            ("ln(x)", "1 / x"),
+        ] {
+            let ast = Expr::new(src, Mode::Standard).parse()?;
+            assert_eq!(expected, ast.derivative("x")?.to_string(), "{}", src);
+        }
+
+        // compare against a central difference at x = 1.5
+        let env = |x: f64| Env::from([("x".into(), Value::Float(x))]);
+        for src in [
+            "x / (x + 1)",
+            "sqrt(x ^ 2 + 1)",
+            "2 ^ x",
+            "x ^ x",
+            "tan(x) - cos(x)",
+            "log(3 * x) - -x",
+        ] {
+            let ast = Expr::new(src, Mode::Standard).parse()?;
+            let d = ast.derivative("x")?.eval_with(&env(1.5))?.as_f64();
+            let h = 1e-6;
+            let f = |x| ast.eval_with(&env(x)).map(|v| v.as_f64());
+            let numeric = (f(1.5 + h)? - f(1.5 - h)?) / (2.0 * h);
+            assert!((d - numeric).abs() < 1e-4, "{}: {} vs {}", src, d, numeric);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_comparison() -> Result<()> {
+        assert_eq!(Value::Bool(true), eval("(3 + 4) > 6")?);
+        assert_eq!(Value::Bool(false), eval("3 + 4 < 6")?);
+        assert_eq!(Value::Bool(true), eval("1 == 1.0")?);
+        assert_eq!(Value::Bool(true), eval("2 ^ 3 != 9")?);
+        assert_eq!(Value::Bool(true), eval("1 <= 1 == true")?);
+        assert_eq!(Value::Bool(false), eval("sqrt(-1) >= 0")?);
+        let rational = Expr::new("1/3 < 0.3334", Mode::Rational).eval()?;
+        assert_eq!(Value::Bool(true), rational);
+        let decimal = Expr::new("0.1 + 0.2 == 0.3", Mode::Decimal(2)).eval()?;
+        assert_eq!(Value::Bool(true), decimal);
+
+        let err = eval("1 + (2 > 1)").unwrap_err();
+        assert!(matches!(err, ExprError::Type { .. }));
+        assert_eq!(Span::new(2, 1), err.span());
+        assert!(eval("true < false").is_err());
+        assert!(eval("abs(1 > 0)").is_err());
+
+        let ast = Expr::new("(1 + 2 > 3) == false", Mode::Standard).parse()?;
+        assert_eq!("1 + 2 > 3 == false", ast.to_string());
+        assert_eq!(ast.eval()?, CompiledExpr::new(&ast).eval()?);
+        assert_eq!(ast.eval()?, Ast::from_json(&ast.to_json())?.eval()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_logical() -> Result<()> {
+        assert_eq!(Value::Bool(true), eval("1 < 2 && 3 > 2")?);
+        assert_eq!(Value::Bool(true), eval("1 > 2 || !(2 > 3)")?);
+        // && binds tighter than ||
+        assert_eq!(Value::Bool(true), eval("true || false && false")?);
+        assert!(matches!(eval("!2"), Err(ExprError::Type { .. })));
+        assert!(matches!(eval("true && 2"), Err(ExprError::Type { .. })));
+
+        // the side that isn't needed is never evaluated, on every backend
+        let env = Env::new();
+        for (src, expected) in [
+            ("false && 1 / 0 > 0", false),
+            ("1 == 1 || missing", true),
+            ("(true || 1 / 0) && !false", true),
+        ] {
+            let ast = Expr::new(src, Mode::Standard).parse()?;
+            assert_eq!(Value::Bool(expected), ast.eval()?, "{}", src);
+            assert_eq!(
+                Value::Bool(expected),
+                CompiledExpr::new(&ast).eval()?,
+                "{}",
+                src
+            );
+            let compiled = Expr::new(src, Mode::Standard).compile()?;
+            assert_eq!(Value::Bool(expected), compiled(&env)?, "{}", src);
+        }
+        let ast = Expr::new("true && 1 / 0 > 0", Mode::Standard).parse()?;
+        let err = CompiledExpr::new(&ast).eval().unwrap_err();
+        assert!(matches!(err, ExprError::DivisionByZero { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ternary() -> Result<()> {
+        assert_eq!(Value::Int(2), eval("false ? 1 : true ? 2 : 3")?);
+        assert_eq!(Value::Int(7), eval("1 + (2 > 1 ? 3 : 4) * 2")?);
+        assert!(matches!(eval("1 ? 2 : 3"), Err(ExprError::Type { .. })));
+        assert!(matches!(
+            eval("true ? 1"),
+            Err(ExprError::UnexpectedToken { .. })
+        ));
+
+        // the branch not taken is never evaluated, on every backend
+        let env = Env::from([("x".into(), Value::Int(-3))]);
+        for (src, expected) in [
+            ("x < 0 ? -x : 1 / 0", 3),
+            ("x > 0 ? missing : x * 2", -6),
+            ("(x < 0 ? x > -5 : false) ? 1 : 2", 1),
+        ] {
+            let ast = Expr::new(src, Mode::Standard).parse()?;
+            assert_eq!(Value::Int(expected), ast.eval_with(&env)?, "{}", src);
+            let vm = CompiledExpr::new(&ast).eval_with(&env)?;
+            assert_eq!(Value::Int(expected), vm, "{}", src);
+            let compiled = Expr::new(src, Mode::Standard).compile()?;
+            assert_eq!(Value::Int(expected), compiled(&env)?, "{}", src);
+            let back = Ast::from_json(&ast.to_json())?;
+            assert_eq!(Value::Int(expected), back.eval_with(&env)?, "{}", src);
+        }
+
+        let ast = Expr::new("(a ? b : c) ? d : e ? f : g", Mode::Standard).parse()?;
+        assert_eq!("(a ? b : c) ? d : e ? f : g", ast.to_string());
+        assert_eq!("(if (if a b c) d (if e f g))", ast.to_sexpr());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bitwise() -> Result<()> {
+        assert_eq!(Value::Int(2), eval("6 & 3")?);
+        assert_eq!(Value::Int(7), eval("6 | 3")?);
+        assert_eq!(Value::Int(5), eval("6 ~^ 3")?);
+        assert_eq!(Value::Int(-6), eval("~5")?);
+        assert_eq!(Value::Int(-4), eval("-16 >> 2")?);
+        // ^ is still power, shifts bind looser than + and bits tighter than ==
+        assert_eq!(Value::Int(8), eval("2 ^ 3")?);
+        assert_eq!(Value::Int(12), eval("1 + 2 << 2")?);
+        assert_eq!(Value::Bool(true), eval("5 & 1 == 1")?);
+
+        assert!(matches!(eval("1 << 63"), Err(ExprError::Overflow { .. })));
+        assert!(matches!(eval("1 << -1"), Err(ExprError::Type { .. })));
+        assert!(matches!(eval("1.5 | 0"), Err(ExprError::Type { .. })));
+        let big = Expr::new("1 << 100 >> 98", Mode::BigInt).eval()?;
+        assert_eq!(Value::Big(4.into()), big);
+        assert_eq!("2", Expr::new("6 & 3", Mode::Rational).eval()?.to_string());
+
+        let ast = Expr::new("~a & b | c ~^ d << 2", Mode::Standard).parse()?;
+        assert_eq!("(| (& (~ a) b) (~^ c (<< d 2)))", ast.to_sexpr());
+        let env = Env::from([
+            ("a".into(), Value::Int(12)),
+            ("b".into(), Value::Int(7)),
+            ("c".into(), Value::Int(1)),
+            ("d".into(), Value::Int(3)),
+        ]);
+        let value = ast.eval_with(&env)?;
+        assert_eq!(Value::Int(3 | (1 ^ 12)), value);
+        assert_eq!(value, CompiledExpr::new(&ast).eval_with(&env)?);
+        assert_eq!(value, Ast::from_json(&ast.to_json())?.eval_with(&env)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_script() -> Result<()> {
+        let mut env = Env::new();
+        let script = Expr::new("a = 3; b = a * 2; a + b", Mode::Standard).parse_script()?;
+        assert_eq!(Value::Int(9), script.eval(&mut env)?);
+        assert_eq!(Some(&Value::Int(6)), env.get("b"));
+
+        // newlines separate too, blank statements are skipped and the
+        // environment carries over to the next script
+        let src = "\n  r = a + 1;;\n\n  area = r ^ 2\n";
+        let script = Expr::new(src, Mode::Standard).parse_script()?;
+        assert_eq!(Value::Int(16), script.eval(&mut env)?);
+        let ast = Expr::new("area - r", Mode::Standard).parse()?;
+        assert_eq!(Value::Int(12), CompiledExpr::new(&ast).eval_with(&env)?);
+
+        let script = |src| Expr::new(src, Mode::Standard).parse_script();
+        let err = script("1 + 2 = 3").unwrap_err();
+        assert!(matches!(err, ExprError::InvalidAssignment { .. }));
+        assert_eq!(Span::new(6, 1), err.span());
+        assert!(matches!(
+            script(" ; "),
+            Err(ExprError::UnexpectedToken { .. })
+        ));
+        assert!(matches!(
+            script("a = 1 2"),
+            Err(ExprError::UnexpectedToken { .. })
+        ));
+        assert!(matches!(
+            script("x = (1; 2)"),
+            Err(ExprError::UnexpectedToken { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_comments() -> Result<()> {
+        assert_eq!(Value::Int(3), eval("1 + /* two */ 2 # the sum")?);
+        assert_eq!(Value::Int(4), eval("8 / /* not a comment */ 2 # halved")?);
+        assert_eq!(Value::Int(2), eval("/**/ 2 /* * 3 */")?);
+        // `//` is floor division, not a comment
+        assert_eq!(Value::Int(0), eval("2 // 3")?);
+        assert_eq!(Value::Int(3), eval("7 // 2 # halved")?);
+        assert!(matches!(
+            eval("1 // nothing"),
+            Err(ExprError::UnknownVariable { .. })
+        ));
+
+        let src = "# radius\nr = 2 # meters\n/* area,\n   roughly */ 3 * r ^ 2";
+        let script = Expr::new(src, Mode::Standard).parse_script()?;
+        assert_eq!(Value::Int(12), script.eval(&mut Env::new())?);
+
+        let err = eval("1 + /* 2").unwrap_err();
+        assert!(matches!(err, ExprError::UnterminatedComment { .. }));
+        assert_eq!(Span::new(4, 2), err.span());
+        assert!(matches!(
+            eval("# nothing"),
+            Err(ExprError::UnexpectedToken { .. })
+        ));
+        Ok(())
+    }
+
+    // integers mod 7, a number type the crate knows nothing about
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Mod7(u8);
+
+    impl FromStr for Mod7 {
+        type Err = ParseIntError;
+
+        fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+            Ok(Mod7((s.parse::<u64>()? % 7) as u8))
+        }
+    }
+
+    impl Add for Mod7 {
+        type Output = Mod7;
+
+        fn add(self, rhs: Self) -> Self {
+            Mod7((self.0 + rhs.0) % 7)
+        }
+    }
+
+    impl Zero for Mod7 {
+        fn zero() -> Self {
+            Mod7(0)
+        }
+
+        fn is_zero(&self) -> bool {
+            self.0 == 0
+        }
+    }
+
+    impl Numeric for Mod7 {
+        fn checked_add(&self, rhs: &Self) -> Option<Self> {
+            Some(*self + *rhs)
+        }
+
+        fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+            Some(Mod7((self.0 + 7 - rhs.0) % 7))
+        }
+
+        fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+            Some(Mod7(self.0 * rhs.0 % 7))
+        }
+
+        // times the inverse, x^5 since x^6 = 1
+        fn checked_div(&self, rhs: &Self) -> Option<Self> {
+            self.checked_mul(&rhs.checked_pow(&Mod7(5))?)
+        }
+
+        fn checked_pow(&self, rhs: &Self) -> Option<Self> {
+            (0..rhs.0).try_fold(Mod7(1), |acc, _| acc.checked_mul(self))
+        }
+    }
+
+    #[test]
+    fn test_numeric_types() -> Result<()> {
+        let expr = |src| Expr::new(src, Mode::BigInt);
+        assert_eq!(3, expr("7 / 2").eval_as::<i64>()?);
+        assert_eq!(1 << 40, expr("2 ^ 40").eval_as::<i64>()?);
+        assert_eq!(3.5, expr("7 / 2").eval_as::<f64>()?);
+        let big = expr("2 ^ 100 - 1").eval_as::<BigInt>()?;
+        assert_eq!("1267650600228229401496703205375", big.to_string());
+        assert_eq!(Mod7(1), expr("3 * 5").eval_as::<Mod7>()?);
+        assert_eq!(Mod7(4), expr("1 / 2").eval_as::<Mod7>()?);
+
+        assert!(matches!(
+            expr("2 ^ 63").eval_as::<i64>(),
+            Err(ExprError::Overflow { .. })
+        ));
+        assert!(matches!(
+            expr("1 / 0").eval_as::<f64>(),
+            Err(ExprError::DivisionByZero { .. })
+        ));
+        assert!(matches!(
+            expr("1.5").eval_as::<i64>(),
+            Err(ExprError::InvalidNumber { .. })
+        ));
+        let err = expr("1 < 2").eval_as::<i64>().unwrap_err();
+        assert!(matches!(err, ExprError::Unsupported { .. }));
+        assert_eq!(Span::new(2, 1), err.span());
+
+        let ast = expr("x * x - 1").parse()?;
+        let env = [("x".to_string(), 1e10)].into_iter().collect();
+        assert_eq!(1e20 - 1.0, ast.eval_as(&env)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_interval() -> Result<()> {
+        let eval = |src| Expr::new(src, Mode::Standard).eval().map(|v| v.to_string());
+        assert_eq!("[4, 6]", eval("[1,2] + [3,4]")?);
+        assert_eq!("[-3, -1]", eval("[1, 2] - [3, 4]")?);
+        assert_eq!("[-8, 4]", eval("[-1, 2] * [-4, 2]")?);
+        assert_eq!("[0.25, 1]", eval("[1, 2] / [2, 4]")?);
+        assert_eq!("[0, 4]", eval("[-1, 2] ^ 2")?);
+        assert_eq!("[2, 3]", eval("1 + [1, 2]")?);
+        assert_eq!("[-1, 1]", eval("sin([0, 5])")?);
+        assert_eq!("[1, 2]", eval("abs([-2, -1])")?);
+
+        assert!(matches!(
+            eval("1 / [-1, 1]"),
+            Err(ExprError::DivisionByZero { .. })
+        ));
+        assert!(matches!(eval("[1, 2] < 3"), Err(ExprError::Type { .. })));
+        assert!(matches!(
+            eval("[2, 1] + 1"),
+            Err(ExprError::InvalidNumber { .. })
+        ));
+        assert!(matches!(
+            eval("[1, 2"),
+            Err(ExprError::InvalidNumber { .. })
+        ));
+
+        let ast = Expr::new("[1, 2] * x - [0, 1]", Mode::Standard).parse()?;
+        let env = Env::from([("x".into(), Value::Int(3))]);
+        let value = ast.eval_with(&env)?;
+        assert_eq!(value, CompiledExpr::new(&ast).eval_with(&env)?);
+        assert_eq!(value, Ast::from_json(&ast.to_json())?.eval_with(&env)?);
+        assert_eq!("[2, 6]", value.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_units() -> Result<()> {
+        let eval = |src| Expr::new(src, Mode::Standard).eval().map(|v| v.to_string());
+        assert_eq!("5.3 km", eval("5 km + 300 m")?);
+        assert_eq!("5300 m", eval("300 m + 5 km")?);
+        assert_eq!("45 km/h", eval("90 km / 2 h")?);
+        assert_eq!("90 km", eval("45 km/h * 120 min")?);
+        assert_eq!("6 m^2", eval("2 m * 3 m")?);
+        assert_eq!("9 m^2", eval("(3 m) ^ 2")?);
+        assert_eq!("20 kg*m/s^2", eval("10 kg * 2 m/s^2")?);
+        assert_eq!("0.5 s^-1", eval("1 / 2 s")?);
+        assert_eq!("-3 m", eval("-(3 m)")?);
+        assert_eq!("1000", eval("1 km / 1 m")?);
+        assert_eq!("true", eval("1 km > 300 m")?);
+
+        let err = Expr::new("3 m + 2 s", Mode::Standard).eval().unwrap_err();
+        assert_eq!(
+            "incompatible units `m` and `s` at offset 4",
+            err.to_string()
+        );
+        assert!(matches!(
+            eval("2 m + 1"),
+            Err(ExprError::IncompatibleUnits { .. })
+        ));
+        assert!(matches!(eval("sqrt(4 m)"), Err(ExprError::Type { .. })));
+        assert!(matches!(eval("(2 m) ^ 0.5"), Err(ExprError::Type { .. })));
+
+        // the printed value reads back the same, as does its json
+        let ast = Expr::new("2 kg * 3 m / s^2", Mode::Standard).parse()?;
+        let value = ast.eval()?;
+        let text = value.to_string();
+        assert_eq!(value, Expr::new(&text, Mode::Standard).eval()?);
+        let folded = ast.simplify();
+        assert_eq!(value, Ast::from_json(&folded.to_json())?.eval()?);
+        assert_eq!(value, CompiledExpr::new(&ast).eval()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_radix() -> Result<()> {
+        let out = |src, mode, radix| {
+            Expr::new(src, mode)
+                .eval()
+                .map(|v| v.to_string_radix(radix))
+        };
+        assert_eq!("0x100", out("255 + 1", Mode::Standard, Radix::Hex)?);
+        assert_eq!("0o17", out("15", Mode::Standard, Radix::Oct)?);
+        assert_eq!("-0b110", out("-6", Mode::Standard, Radix::Bin)?);
+        assert_eq!("256", out("255 + 1", Mode::Standard, Radix::Dec)?);
+        assert_eq!(
+            "0x10000000000000000",
+            out("2 ^ 64", Mode::BigInt, Radix::Hex)?
+        );
+        assert_eq!("0x2", out("4/2", Mode::Rational, Radix::Hex)?);
+        assert_eq!("0xa", out("2.5 * 4", Mode::Decimal(2), Radix::Hex)?);
+        // only integers change
+        assert_eq!("1/3", out("1/3", Mode::Rational, Radix::Hex)?);
+        assert_eq!("true", out("1 < 2", Mode::Standard, Radix::Bin)?);
+        assert_eq!(Some(Radix::Oct), Radix::from_name("oct"));
+        assert_eq!(None, Radix::from_name("base7"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rpn() -> Result<()> {
+        let rpn = |src| Expr::new(src, Mode::Standard).parse_rpn();
+        assert_eq!(Value::Int(35), rpn("3 4 + 5 *")?.eval()?);
+        assert_eq!("2 ^ 3 ^ 2", rpn("2 3 2 ^ ^")?.to_string());
+        assert_eq!("(2 ^ 3) ^ 2", rpn("2 3 ^ 2 ^")?.to_string());
+        assert_eq!("10 - 4 - 3", rpn("10 4 - 3 -")?.to_string());
+        assert_eq!("sqrt(x * x)", rpn("x x * sqrt")?.to_string());
+        assert_eq!("!(1 < 2)", rpn("1 2 < !")?.to_string());
+        assert_eq!("x > 0 ? x : 0 - x", rpn("x 0 > x 0 x - ?")?.to_string());
+        assert_eq!("(1 + 2 * 3) / 4", rpn("1 2 3 * + 4 /")?.to_string());
+
+        let err = rpn("1 +").unwrap_err();
+        assert_eq!(
+            "expected an operand, found `+` at offset 2",
+            err.to_string()
+        );
+        let err = rpn("1 2 3 +").unwrap_err();
+        assert_eq!(
+            "expected an operator, found end of input at offset 7",
+            err.to_string()
+        );
+        assert!(matches!(
+            rpn("1 ( 2 +"),
+            Err(ExprError::UnexpectedToken { .. })
+        ));
+        assert!(matches!(rpn(""), Err(ExprError::UnexpectedToken { .. })));
+
+        // as deep as the infix parsers allow, checked while building
+        let negs = |n| format!("1{}", " neg".repeat(n));
+        let rpn = |src: &str| Expr::new(src, Mode::Standard).parse_rpn();
+        assert_eq!(Value::Int(1), rpn(&negs(100))?.eval()?);
+        let err = rpn(&negs(100_000)).unwrap_err();
+        assert!(matches!(err, ExprError::TooDeep { .. }));
+        assert_eq!(
+            "expression nested too deeply at offset 510",
+            err.to_string()
+        );
+        // a chain on the left doesn't count, see test_chain_height
+        let chain = format!("1{}", " 1 +".repeat(10_000));
+        assert_eq!(Value::Int(10_001), rpn(&chain)?.eval()?);
+        let chain = format!("1{}", " 1 ^".repeat(10_000));
+        assert!(matches!(rpn(&chain), Err(ExprError::TooDeep { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_rpn() -> Result<()> {
+        let to_rpn = |src| Expr::new(src, Mode::Standard).to_rpn();
+        assert_eq!("3 4 + 5 *", to_rpn("(3 + 4) * 5")?);
+        assert_eq!("1 2 3 * +", to_rpn("1 + 2 * 3")?);
+        assert_eq!("10 4 - 3 -", to_rpn("10 - 4 - 3")?);
+        assert_eq!("2 3 2 ^ ^", to_rpn("2 ^ 3 ^ 2")?);
+        assert_eq!("2 2 ^ neg", to_rpn("-2 ^ 2")?);
+        assert_eq!("2 neg 3 *", to_rpn("-2 * 3")?);
+        assert_eq!("x sqrt 1 + sin", to_rpn("sin(sqrt(x) + 1)")?);
+        assert_eq!("a b c d e ? ?", to_rpn("a ? b : c ? d : e")?);
+        assert_eq!("x 1 & 0 == y ! ||", to_rpn("x & 1 == 0 || !y")?);
+
+        // parse_rpn reads it back to the same tree
+        for src in [
+            "1 - (2 - 3) * -x",
+            "x > 0 && y > 0 ? sqrt(x * y) : -abs(x)",
+            "2 ^ -x ^ 2 / (1 + 5 km / 2 h)",
+        ] {
+            let infix = Expr::new(src, Mode::Standard).parse()?;
+            let postfix = Expr::new(&to_rpn(src)?, Mode::Standard).parse_rpn()?;
+            assert_eq!(infix.to_string(), postfix.to_string());
+        }
+
+        assert!(matches!(
+            to_rpn("(1 + 2"),
+            Err(ExprError::UnbalancedParen { .. })
+        ));
+        assert!(matches!(
+            to_rpn("1 + 2)"),
+            Err(ExprError::UnbalancedParen { .. })
+        ));
+        assert!(matches!(
+            to_rpn("1 2"),
+            Err(ExprError::UnexpectedToken { .. })
+        ));
+        assert!(matches!(
+            to_rpn("1 +"),
+            Err(ExprError::UnexpectedToken { .. })
+        ));
+        assert!(matches!(
+            to_rpn("a ? b"),
+            Err(ExprError::UnexpectedToken { .. })
+        ));
+        assert!(matches!(
+            to_rpn("f(1)"),
+            Err(ExprError::UnknownFunction { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tokenizer() {
+        let src = "a = 0x # hex\nb $ [1, 2] /* c */ 5 km";
+        let tokens: Vec<_> = Tokenizer::new(src, Mode::Standard)
+            .map(|t| t.map(|(token, span)| (token.to_string(), span.text(src))))
+            .collect();
+        let texts: Vec<_> = tokens
+            .iter()
+            .map(|t| t.as_ref().map_or("error", |(_, text)| *text))
+            .collect();
+        assert_eq!(
+            vec!["a", "=", "0", "x", "\n", "b", "error", "[1, 2]", "5 km"],
+            texts
+        );
+        assert!(matches!(&tokens[8], Ok((_, "5 km"))));
+        assert!(matches!(
+            tokens[6],
+            Err(ExprError::InvalidChar { ch: '$', .. })
+        ));
+
+        let tokens = Tokenizer::new("x<<2 <= y", Mode::Standard).collect::<Result<Vec<_>>>();
+        let tokens: Vec<_> = tokens
+            .unwrap()
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect();
+        assert!(tokens[1].is_bitwise() && tokens[3].is_comparison());
+        assert!(Token::Minus.is_operator() && Token::Minus.is_prefix());
+        assert!(!Token::Not.is_operator() && Token::And.is_logical());
+        assert_eq!(7, Span::new(3, 4).end());
+    }
+
+    #[test]
+    fn test_custom_operators() -> Result<()> {
+        let ints = |l, r, span| match (l, r) {
+            (_, Value::Int(0)) => Err(ExprError::DivisionByZero { span }),
+            (Value::Int(l), Value::Int(r)) => Ok((l, r)),
+            (l, _) => Err(ExprError::Type {
+                expected: "an integer".into(),
+                found: l.to_string(),
+                operation: None,
+                span,
+            }),
+        };
+        let mut ops = Operators::new();
+        // floor division, registered over the builtin one
+        ops.binary(
+            "//",
+            Token::Divide.precedence(),
+            Assoc::Left,
+            move |l, r, span| {
+                let (l, r) = ints(l, r, span)?;
+                Ok(Value::Int(l.div_euclid(r)))
+            },
+        )
+        .binary(
+            "mod",
+            Token::Divide.precedence(),
+            Assoc::Left,
+            move |l, r, span| {
+                let (l, r) = ints(l, r, span)?;
+                Ok(Value::Int(l.rem_euclid(r)))
+            },
+        )
+        // implication, right associative like ^
+        .binary("->", 1, Assoc::Right, |l, r, span| {
+            Ok(Value::Bool(!l.expect_bool(span)? || r.expect_bool(span)?))
+        })
+        .prefix("√", Token::Power.precedence(), |v, _| {
+            Ok(Value::Float(v.as_f64().sqrt()))
+        });
+
+        let parse = |src| Expr::new(src, Mode::Standard).with_operators(&ops).parse();
+        let eval = |src| parse(src)?.eval();
+        assert_eq!(Value::Int(3), eval("7 // 2")?);
+        assert_eq!(Value::Int(-4), eval("-7 // 2")?);
+        assert_eq!(Value::Int(4), eval("1 + 12 // 2 // 2")?);
+        assert_eq!(Value::Int(2), eval("-10 mod 3")?);
+        assert_eq!(Value::Float(6.0), eval("2 * √9")?);
+        assert_eq!(Value::Float(3.0), eval("√(4 + 5)")?);
+        assert_eq!(Value::Bool(true), eval("false -> false -> false")?);
+        // comments the operators don't cover still work
+        assert_eq!(Value::Int(2), eval("1 + 1 # two")?);
+        // a word symbol only matches a whole word
+        assert!(parse("10 model").is_err());
+        assert!(matches!(
+            eval("1 // 0"),
+            Err(ExprError::DivisionByZero { .. })
+        ));
+
+        let src = "a mod 4 // -b > 1 -> √a > 2";
+        let ast = parse(src)?;
+        assert_eq!(src, ast.to_string());
+        let env = Env::from([
+            ("a".to_string(), Value::Int(7)),
+            ("b".to_string(), Value::Int(-2)),
+        ]);
+        let value = ast.eval_with(&env)?;
+        assert_eq!(Value::Bool(true), value);
+        assert_eq!(value, CompiledExpr::new(&ast).eval_with(&env)?);
+        assert_eq!(value, ast.compile()(&env)?);
+
+        let rpn = Expr::new(src, Mode::Standard)
+            .with_operators(&ops)
+            .to_rpn()?;
+        assert_eq!("a 4 mod b neg // 1 > a √ 2 > ->", rpn);
+        let back = Expr::new(&rpn, Mode::Standard)
+            .with_operators(&ops)
+            .parse_rpn()?;
+        assert_eq!(src, back.to_string());
+
+        assert_eq!("√√x", parse("√√x")?.simplify().to_string());
+        assert_eq!("2 + √x", parse("5 // 2 + √x")?.simplify().to_string());
+        assert!(matches!(
+            parse("x // 2")?.derivative("x"),
+            Err(ExprError::NotDifferentiable { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_arg_functions() -> Result<()> {
+        let eval = |src| Expr::new(src, Mode::Standard).eval();
+        assert_eq!(Value::Int(3), eval("max(1, 2, 3)")?);
+        assert_eq!(Value::Float(-1.5), eval("min(2, -1.5)")?);
+        assert_eq!(Value::Int(10), eval("clamp(12, 0, 10)")?);
+        assert_eq!(Value::Int(0), eval("clamp(-3, 0, 2 * 5)")?);
+        assert_eq!(
+            Value::Float(std::f64::consts::FRAC_PI_4),
+            eval("atan2(1, 1)")?
+        );
+        assert_eq!(Value::Bool(true), eval("max(1 km, 300 m) == 1000 m")?);
+
+        let err = eval("clamp(1, 2)").unwrap_err();
+        assert!(matches!(
+            err,
+            ExprError::WrongArgCount {
+                found: 2,
+                span: Span { offset: 0, len: 5 },
+                ..
+            }
+        ));
+        assert_eq!(
+            "`clamp` takes 3 arguments, found 2 at offset 0",
+            err.to_string()
+        );
+        assert_eq!(
+            "`max` takes at least 2 arguments, found 1 at offset 0",
+            eval("max(4)").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "`sqrt` takes 1 argument, found 2 at offset 0",
+            eval("sqrt(4, 9)").unwrap_err().to_string()
+        );
+        assert!(eval("1, 2").is_err());
+
+        let src = "clamp(x, 0, max(y, 1)) + sqrt(4)";
+        let ast = Expr::new(src, Mode::Standard).parse()?;
+        assert_eq!(src, ast.to_string());
+        assert_eq!("(+ (clamp x 0 (max y 1)) (sqrt 4))", ast.to_sexpr());
+        assert_eq!(src, Ast::from_json(&ast.to_json())?.to_string());
+        let env = Env::from([
+            ("x".to_string(), Value::Int(7)),
+            ("y".to_string(), Value::Int(5)),
+        ]);
+        let value = ast.eval_with(&env)?;
+        assert_eq!(Value::Float(7.0), value);
+        assert_eq!(value, CompiledExpr::new(&ast).eval_with(&env)?);
+        assert_eq!(value, ast.compile()(&env)?);
+        assert_eq!("clamp(x, 0, max(y, 1)) + 2.0", ast.simplify().to_string());
+
+        let rpn = Expr::new("max(1, x, 3) - clamp(x, 0, 1)", Mode::Standard).to_rpn()?;
+        assert_eq!("1 x 3 max max x 0 1 clamp -", rpn);
+        let back = Expr::new(&rpn, Mode::Standard).parse_rpn()?;
+        assert_eq!("max(1, max(x, 3)) - clamp(x, 0, 1)", back.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Expr::validate("x / 0 + clamp(y, 0, z)").is_ok());
+        assert!(Expr::validate("undefined_var ? 1 : 2").is_ok());
+        for src in ["", "1 +", "(1 + 2", "2 * $", "nope(1)", "max(1)", "1 2"] {
+            let errors = Expr::validate(src).unwrap_err();
+            assert_eq!(1, errors.len(), "{}", src);
+        }
+        assert!(matches!(
+            Expr::validate("(1 + 2").unwrap_err()[..],
+            [ExprError::UnbalancedParen { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_parse_all_errors() -> Result<()> {
+        let errors = |src| Expr::new(src, Mode::Standard).parse_all().unwrap_err();
+        let offsets = |src| -> Vec<usize> { errors(src).iter().map(|e| e.span().offset).collect() };
+        assert_eq!(vec![4, 13], offsets("1 + * 2 + 3 +"));
+        assert_eq!(vec![2, 4, 10], offsets("1 $ 2 + 3 @"));
+        assert_eq!(vec![3, 8], offsets("(1 2) * max(1) + nope"));
+        assert_eq!(vec![0, 10, 19], offsets("nope(1) + max(1) + clamp(1, 2)"));
+        assert_eq!(vec![6, 6, 8], offsets("a ? b ) c"));
+        assert_eq!(vec![0], offsets("(1 + 2"));
+
+        let all = errors("foo(1, 2 +) * (3 # unclosed");
+        assert!(matches!(
+            all[..],
+            [
+                ExprError::UnknownFunction { .. },
+                ExprError::UnexpectedToken { .. },
+                ExprError::UnbalancedParen { .. }
+            ]
+        ));
+        assert_eq!(4, Expr::validate("1 + + ; 2 ) ,").unwrap_err().len());
+
+        // a clean parse gives the same tree either way
+        let src = "max(1, x) * -(2 + y) ? 1 : 0";
+        let ast = Expr::new(src, Mode::Standard).parse_all().unwrap();
+        assert_eq!(Expr::new(src, Mode::Standard).parse()?, ast);
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_memo() -> Result<()> {
+        // each level doubles the tree, 2^14 leaves but two distinct subtrees a level
+        let mut src = "x".to_string();
+        for _ in 0..14 {
+            src = format!("({} + {}) / 2", src, src);
+        }
+        let ast = Expr::new(&src, Mode::Standard).parse()?;
+        let env = Env::from([("x".to_string(), Value::Float(1.5))]);
+        assert_eq!(Value::Float(1.5), ast.eval_memo(&env)?);
+
+        let env = Env::from([
+            ("a".to_string(), Value::Int(4)),
+            ("b".to_string(), Value::Int(0)),
+        ]);
+        for src in [
+            "a * a - (a * a) + max(a, b)",
+            "b != 0 && a / b > 1 || a / 2 == 2",
+            "b == 0 ? a : a / b",
+            "1 + 1.0 + (1 + 1)",
+        ] {
+            let ast = Expr::new(src, Mode::Standard).parse()?;
+            assert_eq!(ast.eval_with(&env)?, ast.eval_memo(&env)?, "{}", src);
+        }
+        assert!(matches!(
+            Expr::new("a / b + a / b", Mode::Standard)
+                .parse()?
+                .eval_memo(&env),
+            Err(ExprError::DivisionByZero {
+                span: Span { offset: 2, .. }
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_depth_limit() -> Result<()> {
+        let nested = |n| format!("{}1{}", "(".repeat(n), ")".repeat(n));
+        let src = nested(100_000);
+        let err = Expr::new(&src, Mode::Standard).eval().unwrap_err();
+        assert!(matches!(
+            err,
+            ExprError::TooDeep {
+                span: Span { offset: 128, .. }
+            }
+        ));
+        assert_eq!(
+            "expression nested too deeply at offset 128",
+            err.to_string()
+        );
+        assert!(matches!(
+            Expr::new(&format!("{}1", "-".repeat(100_000)), Mode::Standard).parse(),
+            Err(ExprError::TooDeep { .. })
+        ));
+        assert!(matches!(
+            Expr::new(&src, Mode::Standard).parse_all().unwrap_err()[..],
+            [ExprError::TooDeep { .. }]
+        ));
+
+        assert_eq!(
+            Value::Int(1),
+            Expr::new(&nested(100), Mode::Standard).eval()?
+        );
+        let expr = |src| Expr::new(src, Mode::Standard).with_max_depth(3);
+        assert_eq!(Value::Int(3), expr("max(1, (3))").eval()?);
+        assert!(matches!(
+            expr("max(1, ((3)))").eval(),
+            Err(ExprError::TooDeep { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_chain_height() -> Result<()> {
+        // flat in the source, nested on the left once parsed, which is gone
+        // down in a loop and so doesn't count towards the depth
+        let chain = |n| vec!["1"; n].join(" + ");
+        let ast = Expr::new(&chain(100), Mode::Standard).parse()?;
+        assert_eq!(Value::Int(100), ast.eval_checked(&Env::new())?);
+        let src = chain(10_000);
+        for parser in PARSERS {
+            let ast = parser.parse(&mut Expr::new(&src, Mode::Standard))?;
+            let value = ast.eval_checked(&Env::new())?;
+            assert_eq!(Value::Int(10_000), value, "{}", parser.name());
+            assert_eq!(src, ast.to_string(), "{}", parser.name());
+        }
+        let src = format!("{} > 0 ? 1 : 2", chain(10_000));
+        assert_eq!(Value::Int(1), Expr::new(&src, Mode::Standard).eval()?);
+        let src = format!(
+            "{} - 10% == 9000 || false",
+            vec!["x"; 10_000].join(" * 1 + ")
+        );
+        let env = Env::from([("x".to_string(), Value::Int(1))]);
+        let ast = Expr::new(&src, Mode::Standard).parse()?;
+        assert_eq!(Value::Bool(true), ast.eval_checked(&env)?);
+
+        // real nesting still counts
+        let nested = format!("{}{}{}", "(".repeat(200), chain(3), ")".repeat(200));
+        assert!(matches!(
+            Expr::new(&nested, Mode::Standard).parse(),
+            Err(ExprError::TooDeep { .. })
+        ));
+        let expr = |src| Expr::new(src, Mode::Standard).with_max_depth(3);
+        assert_eq!(Value::Int(4), expr("1 + 1 + 1 + 1").eval()?);
+        assert_eq!(Value::Int(5), expr("1 + 2 * 2").eval()?);
+        assert_eq!(Value::Float(4.4), expr("1 + 1 + 1 + 1 + 10%").eval()?);
+        for src in ["1 + (1 + 1)", "2 ^ 2 ^ 2 ^ 2", "1 + 2 * 3 ^ 4"] {
+            assert!(
+                matches!(expr(src).eval(), Err(ExprError::TooDeep { .. })),
+                "{}",
+                src
+            );
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_wasm_evaluate() {
+        assert_eq!(
+            Ok("25".to_string()),
+            crate::evaluate("x = 3; y = 4; x^2 + y^2")
+        );
+        assert_eq!(
+            Err("1 / 0\n  ^\ndivision by zero at offset 2".to_string()),
+            crate::evaluate("1 / 0")
+        );
+    }
+
+    #[test]
+    fn test_bool_literals() -> Result<()> {
+        let tokens: Vec<_> = Tokenizer::new("true falsey false", Mode::Standard)
+            .map(|t| t.map(|(token, _)| token))
+            .collect::<Result<_>>()?;
+        assert_eq!(
+            vec![
+                Token::Number(Value::Bool(true)),
+                Token::Ident("falsey".into()),
+                Token::Number(Value::Bool(false)),
+            ],
+            tokens
+        );
+
+        let eval = |src| Expr::new(src, Mode::Standard).eval();
+        assert_eq!(Value::Bool(true), eval("!false && (true || x)")?);
+        assert_eq!(Value::Int(2), eval("false ? 1 : 2")?);
+        // booleans and numbers never stand in for each other
+        for src in [
+            "true + 1",
+            "1 && true",
+            "0 ? 1 : 2",
+            "true == 1",
+            "-true",
+            "!0",
+        ] {
+            assert!(matches!(eval(src), Err(ExprError::Type { .. })), "{}", src);
+        }
+        assert!(matches!(
+            Expr::new("true = 1", Mode::Standard).parse_script(),
+            Err(ExprError::InvalidAssignment { .. })
+        ));
+        assert_eq!(
+            "true false ||",
+            Expr::new("true || false", Mode::Standard).to_rpn()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_random() -> Result<()> {
+        let eval = |src| Expr::new(src, Mode::Standard).eval();
+        let draws = |src| -> Result<Vec<Value>> { (0..20).map(|_| eval(src)).collect() };
+        crate::seed_random(7);
+        let first = draws("rand() + randint(1, 6)")?;
+        crate::seed_random(7);
+        assert_eq!(first, draws("rand() + randint(1, 6)")?);
+
+        for v in draws("rand()")? {
+            assert!(matches!(v, Value::Float(x) if (0.0..1.0).contains(&x)));
+        }
+        let dice = draws("randint(1, 6)")?;
+        assert!(dice.iter().all(|v| matches!(v, Value::Int(1..=6))));
+        assert!(dice.iter().any(|v| *v != dice[0]));
+        assert_eq!(Value::Int(-3), eval("randint(-3, -3)")?);
+        assert!(matches!(eval("randint(2, 1)"), Err(ExprError::Type { .. })));
+        assert!(matches!(
+            eval("randint(1.5, 2)"),
+            Err(ExprError::Type { .. })
+        ));
+        assert!(matches!(
+            eval("rand(1)"),
+            Err(ExprError::WrongArgCount { .. })
+        ));
+        assert!(matches!(
+            eval("sqrt()"),
+            Err(ExprError::WrongArgCount { found: 0, .. })
+        ));
+
+        // two draws are two numbers, not one reused or folded away
+        let ast = Expr::new("rand() - rand()", Mode::Standard).parse()?;
+        assert_eq!("rand() - rand()", ast.simplify().to_string());
+        assert_eq!("(- (rand) (rand))", ast.to_sexpr());
+        assert_ne!(Value::Float(0.0), ast.eval_memo(&Env::new())?);
+        assert_eq!(
+            "rand 1 10 randint +",
+            Expr::new("rand() + randint(1, 10)", Mode::Standard).to_rpn()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sum_prod() -> Result<()> {
+        let eval = |src, mode| Expr::new(src, mode).eval();
+        assert_eq!(Value::Int(2525), eval("sum(1, 100) / 2", Mode::Standard)?);
+        assert_eq!(Value::Int(3628800), eval("prod(1, 10)", Mode::Standard)?);
+        assert_eq!(Value::Int(-5), eval("sum(-5, 4)", Mode::Standard)?);
+        assert_eq!(Value::Int(0), eval("prod(-3, 3)", Mode::Standard)?);
+        assert_eq!(Value::Int(-6), eval("prod(-3, -1)", Mode::Standard)?);
+        // empty ranges
+        assert_eq!(Value::Int(0), eval("sum(5, 1)", Mode::Standard)?);
+        assert_eq!(Value::Int(1), eval("prod(5, 1)", Mode::Standard)?);
+
+        // an i64 product fails at its first overflow, however long the range
+        for src in [
+            "prod(1, 21)",
+            "prod(1, 99999)",
+            "prod(2, 9223372036854775807)",
+        ] {
+            assert!(
+                matches!(eval(src, Mode::Standard), Err(ExprError::Overflow { .. })),
+                "{}",
+                src
+            );
+        }
+        assert_eq!(
+            Value::Int(0),
+            eval(
+                "prod(-9223372036854775807, 9223372036854775807)",
+                Mode::Standard
+            )?
+        );
+        assert_eq!(
+            "2432902008176640000",
+            eval("prod(1, 20)", Mode::BigInt)?.to_string()
+        );
+        assert_eq!(
+            "500000000500000000",
+            eval("sum(1, 1000000000)", Mode::BigInt)?.to_string()
+        );
+        assert!(matches!(
+            eval("prod(2, 1000000000)", Mode::BigInt),
+            Err(ExprError::Overflow { .. })
+        ));
+        assert!(matches!(
+            eval("sum(1, 2.5)", Mode::Standard),
+            Err(ExprError::Type { .. })
+        ));
+        assert_eq!(
+            "sum(1, n) + 5050",
+            Expr::new("sum(1, n) + sum(1, 100)", Mode::Standard)
+                .parse()?
+                .simplify()
+                .to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_if() -> Result<()> {
+        let src = "if(x != 0, 10 / x, 0)";
+        let ast = Expr::new(src, Mode::Standard).parse()?;
+        let env = Env::from([("x".to_string(), Value::Int(0))]);
+        // the division is never evaluated, in any backend
+        assert_eq!(Value::Int(0), ast.eval_with(&env)?);
+        assert_eq!(Value::Int(0), ast.compile()(&env)?);
+        assert_eq!(Value::Int(0), CompiledExpr::new(&ast).eval_with(&env)?);
+        assert_eq!(Value::Int(0), ast.eval_memo(&env)?);
+        let env = Env::from([("x".to_string(), Value::Int(5))]);
+        assert_eq!(Value::Int(2), ast.eval_with(&env)?);
+
+        let rpn = Expr::new(src, Mode::Standard).to_rpn()?;
+        assert_eq!("x 0 != 10 x / 0 if", rpn);
+        let ast = Expr::new(&rpn, Mode::Standard).parse_rpn()?;
+        assert_eq!(
+            Value::Int(0),
+            ast.eval_with(&Env::from([("x".to_string(), Value::Int(0))]))?
+        );
+
+        assert!(matches!(
+            Expr::new("if(1, 2, 3)", Mode::Standard).eval(),
+            Err(ExprError::Type { .. })
+        ));
+        assert!(matches!(
+            Expr::new("if(true, 2)", Mode::Standard).eval(),
+            Err(ExprError::WrongArgCount { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_batch() -> Result<()> {
+        let input = r#"[
+            {"expr": "a = x * 2; a + y", "vars": {"x": 20, "y": "1 + 1"}},
+            {"expr": "x > 1 && flag", "vars": {"x": 2.5, "flag": true}},
+            {"expr": "x / 3", "vars": {"x": 1}},
+            {"expr": "1 +"},
+            {"expr": "x", "vars": {"x": "1 / 0"}},
+            {"vars": {}}
+        ]"#;
+        assert_eq!(
+            concat!(
+                r#"[{"value":42},{"value":true},{"value":"1/3"},"#,
+                r#"{"error":"expected a number, a name or `(`, found end of input at offset 3"},"#,
+                r#"{"error":"in the value of `x`: division by zero at offset 2"},"#,
+                r#"{"error":"expected a string `expr` in {\"vars\":{}}"}]"#
+            ),
+            eval_batch(input, Mode::Rational)?
+        );
+        assert!(matches!(
+            eval_batch("{}", Mode::Standard),
+            Err(ExprError::InvalidJson { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_trace() -> Result<()> {
+        let trace = |src| -> Result<Vec<String>> {
+            let env = Env::from([("x".to_string(), Value::Int(0))]);
+            let mut steps = Vec::new();
+            Expr::new(src, Mode::Standard)
+                .parse()?
+                .eval_trace(&env, |step| steps.push(step.to_string()))?;
+            Ok(steps)
+        };
+        assert_eq!(
+            vec![
+                "92 + 5 → 97",
+                "-3 → -3",
+                "97 * -3 → -291",
+                "2 ^ 2 → 4",
+                "-291 - 4 → -295"
+            ],
+            trace("(92 + 5) * -3 - 2^2")?
+        );
+        // steps not taken are not shown
+        assert_eq!(
+            vec!["0 != 0 → false", "false && 10 / x > 1 → false"],
+            trace("x != 0 && 10 / x > 1")?
+        );
+        assert_eq!(
+            vec!["0 == 0 → true", "-1 → -1", "max(1, -1) → 1"],
+            trace("if(x == 0, max(1, -1), 1 / x)")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_latex() -> Result<()> {
+        let latex = |src| {
+            Expr::new(src, Mode::Standard)
+                .parse()
+                .map(|ast| ast.to_latex())
+        };
+        assert_eq!(r"\frac{1}{\frac{x}{2} + 1}", latex("1 / (x / 2 + 1)")?);
+        assert_eq!(r"a \cdot \frac{b}{c}", latex("a * (b / c)")?);
+        assert_eq!(
+            r"\left(a - b\right) \cdot \left(-c\right)^{2}",
+            latex("(a - b) * (-c)^2")?
+        );
+        assert_eq!(r"\left(2^{3}\right)^{2}", latex("(2^3)^2")?);
+        assert_eq!(
+            r"\sqrt{x^{2} + 1} \leq \left|\pi\right|",
+            latex("sqrt(x^2 + 1) <= abs(pi)")?
+        );
+        assert_eq!(
+            r"\max\left(1, \mathrm{rate\_2}\right)",
+            latex("max(1, rate_2)")?
+        );
+        assert_eq!(
+            r"\begin{cases} 1 & \text{if } x \neq 0 \\ 2 & \text{otherwise} \end{cases}",
+            latex("if(x != 0, 1, 2)")?
+        );
+        let ast = Expr::new("3/4 - x", Mode::Rational).parse()?.simplify();
+        assert_eq!(r"\frac{3}{4} - x", ast.to_latex());
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_dot() -> Result<()> {
+        let ast = Expr::new("x ? (2 ^ 3) ^ 2 : max(1, y)", Mode::Standard).parse()?;
+        let expected = [
+            "digraph ast {",
+            r#"  n0 [label="? :"];"#,
+            r#"  n1 [label="x"];"#,
+            r#"  n0 -> n1 [label="if"];"#,
+            r#"  n2 [label="^"];"#,
+            r#"  n3 [label="( )"];"#,
+            r#"  n4 [label="^"];"#,
+            r#"  n5 [label="2"];"#,
+            "  n4 -> n5;",
+            r#"  n6 [label="3"];"#,
+            "  n4 -> n6;",
+            "  n3 -> n4;",
+            "  n2 -> n3;",
+            r#"  n7 [label="2"];"#,
+            "  n2 -> n7;",
+            r#"  n0 -> n2 [label="then"];"#,
+            r#"  n8 [label="max()"];"#,
+            r#"  n9 [label="1"];"#,
+            "  n8 -> n9;",
+            r#"  n10 [label="y"];"#,
+            "  n8 -> n10;",
+            r#"  n0 -> n8 [label="else"];"#,
+            "}",
+        ];
+        assert_eq!(expected.join("\n") + "\n", ast.to_dot());
+        Ok(())
+    }
+
+    #[test]
+    fn test_floor_div_mod() -> Result<()> {
+        let eval = |src, mode| Expr::new(src, mode).eval();
+        for mode in [
+            Mode::Standard,
+            Mode::BigInt,
+            Mode::Rational,
+            Mode::Decimal(4),
+        ] {
+            for (src, value) in [
+                ("7 // 2", "3"),
+                ("-7 // 2", "-4"),
+                ("7 // -2", "-4"),
+                ("7 % 3", "1"),
+                ("-7 % 3", "2"),
+                ("7 % -3", "-2"),
+                ("-7 % -3", "-1"),
+                ("1 + 17 // 5 * 2 % 4", "3"),
+            ] {
+                assert_eq!(value, eval(src, mode)?.to_string(), "{} in {:?}", src, mode);
+            }
+            assert!(matches!(
+                eval("1 % 0", mode),
+                Err(ExprError::DivisionByZero { .. })
+            ));
+        }
+        assert_eq!(Value::Float(2.0), eval("7.5 // 3", Mode::Standard)?);
+        assert_eq!(Value::Float(1.5), eval("-7.5 % 3", Mode::Standard)?);
+        assert_eq!("1/2", eval("7/2 % 1", Mode::Rational)?.to_string());
+        assert_eq!("0.5", eval("-2.5 % 1.5", Mode::Decimal(4))?.to_string());
+        assert!(matches!(
+            eval("(-9223372036854775807 - 1) // -1", Mode::Standard),
+            Err(ExprError::Overflow { .. })
+        ));
+        assert_eq!(
+            Value::Int(0),
+            eval("(-2147483647 - 1) % -1", Mode::Standard)?
+        );
+        assert!(matches!(
+            eval("5 m % 2", Mode::Standard),
+            Err(ExprError::Type { .. })
+        ));
+
+        let ast = Expr::new("(i + 1) % n // 2", Mode::Standard).parse()?;
+        assert_eq!("(i + 1) % n // 2", ast.to_string());
+        let env = Env::from([
+            ("i".to_string(), Value::Int(-6)),
+            ("n".to_string(), Value::Int(4)),
+        ]);
+        assert_eq!(Value::Int(1), ast.eval_with(&env)?);
+        assert_eq!(Value::Int(1), CompiledExpr::new(&ast).eval_with(&env)?);
+        assert_eq!(Value::Int(1), ast.compile()(&env)?);
+        assert_eq!(
+            "i 1 + n % 2 //",
+            Expr::new("(i + 1) % n // 2", Mode::Standard).to_rpn()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rounding() -> Result<()> {
+        let eval = |src, mode| Expr::new(src, mode).eval().map(|v| v.to_string());
+        for (src, decimal, rational) in [
+            ("floor(-2.5)", "-3", "-3"),
+            ("ceil(-2.5)", "-2", "-2"),
+            ("round(-2.5)", "-3", "-3"),
+            ("trunc(-2.5)", "-2", "-2"),
+            ("round(2.345, 2)", "2.35", "47/20"),
+            ("floor(2.345, 1)", "2.3", "23/10"),
+            ("round(1250, -2)", "1300", "1300"),
+            ("trunc(-1299, -2)", "-1200", "-1200"),
+            ("ceil(7)", "7", "7"),
+        ] {
+            assert_eq!(decimal, eval(src, Mode::Decimal(6))?, "{}", src);
+            assert_eq!(rational, eval(src, Mode::Rational)?, "{}", src);
+        }
+        assert_eq!(
+            Value::Float(3.0),
+            Expr::new("round(2.5)", Mode::Standard).eval()?
+        );
+        assert_eq!(
+            Value::Float(1.235),
+            Expr::new("round(1.23456, 3)", Mode::Standard).eval()?
+        );
+        assert_eq!(
+            Value::Int(1300),
+            Expr::new("round(1250, -2)", Mode::Standard).eval()?
+        );
+        assert_eq!("7/2", eval("round(10 / 3, 0) + 1/2", Mode::Rational)?);
+        assert_eq!("[1, 3]", eval("floor([1.5, 3.7])", Mode::Standard)?);
+        assert!(matches!(
+            Expr::new("round(9223372036854775807, -1)", Mode::Standard).eval(),
+            Err(ExprError::Overflow { .. })
+        ));
+        assert!(matches!(
+            Expr::new("round(1.5, 0.5)", Mode::Standard).eval(),
+            Err(ExprError::Type { .. })
+        ));
+        let err = Expr::new("floor(1, 2, 3)", Mode::Standard)
+            .eval()
+            .unwrap_err();
+        assert_eq!(
+            "`floor` takes 1 or 2 arguments, found 3 at offset 0",
+            err.to_string()
+        );
+
+        assert_eq!("x floor", Expr::new("floor(x)", Mode::Standard).to_rpn()?);
+        assert!(matches!(
+            Expr::new("round(x, 2)", Mode::Standard).to_rpn(),
+            Err(ExprError::Unsupported { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_number_theory() -> Result<()> {
+        let eval = |src| Expr::new(src, Mode::Standard).eval().map(|v| v.to_string());
+        assert_eq!("6", eval("gcd(54, -24)")?);
+        assert_eq!("4", eval("gcd(12, 8, 20)")?);
+        assert_eq!("0", eval("gcd(0, 0)")?);
+        assert_eq!("72", eval("lcm(8, 9, 12)")?);
+        assert_eq!("0", eval("lcm(0, 5)")?);
+        assert_eq!("true", eval("isprime(97) && !isprime(91) && !isprime(1)")?);
+        assert_eq!("2^3 * 3^2 * 5", eval("factor(360)")?);
+        assert_eq!("-7", eval("factor(-7)")?);
+        assert_eq!("1", eval("factor(1)")?);
+        assert!(matches!(
+            Expr::new("lcm(4294967296, 4294967297)", Mode::Standard).eval(),
+            Err(ExprError::Overflow { .. })
+        ));
+
+        let eval = |src| Expr::new(src, Mode::BigInt).eval().map(|v| v.to_string());
+        assert_eq!("4295032832", eval("lcm(65536, 65537)")?);
+        // a Mersenne prime, and a product of two primes rho has to split
+        assert_eq!("true", eval("isprime(2^61 - 1)")?);
+        assert_eq!("1000003 * 1000033", eval("factor(1000003 * 1000033)")?);
+        assert_eq!(
+            "3 * 5 * 17 * 257 * 641 * 65537 * 6700417",
+            eval("factor(2^64 - 1)")?
+        );
+
+        assert!(matches!(
+            Expr::new("factor(360) + 1", Mode::Standard).eval(),
+            Err(ExprError::Type { found, .. }) if found == "text"
+        ));
+        assert!(matches!(
+            Expr::new("gcd(1.5, 3)", Mode::Standard).eval(),
+            Err(ExprError::Type { .. })
+        ));
+        let ast = Expr::new("factor(12) ", Mode::Standard).parse()?;
+        assert_eq!("factor(12)", ast.simplify().to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bit_builtins() -> Result<()> {
+        let eval = |src, mode| {
+            Expr::new(src, mode)
+                .eval()
+                .map(|v| v.to_string_radix(Radix::Hex))
+        };
+        assert_eq!("0x10", eval("popcount(16711935)", Mode::Standard)?);
+        assert_eq!("0x40", eval("popcount(-1)", Mode::Standard)?);
+        assert_eq!("0x3f", eval("clz(1)", Mode::Standard)?);
+        assert_eq!("0x80", eval("clz(0) + ctz(0)", Mode::Standard)?);
+        assert_eq!("0x4", eval("ctz(48)", Mode::Standard)?);
+        assert_eq!("0x123400", eval("rotl(4660, 8)", Mode::Standard)?);
+        // all 64 bits, as ~ and << have them
+        assert_eq!("0x1", eval("popcount(2^40)", Mode::Standard)?);
+        assert_eq!("0x17", eval("clz(2^40)", Mode::Standard)?);
+        assert_eq!("0x28", eval("ctz(2^40)", Mode::Standard)?);
+        assert_eq!("0x1", eval("rotl(2^40, 24)", Mode::Standard)?);
+        assert_eq!("0x100000000", eval("rotl(2147483648, 1)", Mode::Standard)?);
+        assert_eq!("0x40", eval("popcount(~0)", Mode::Standard)?);
+        // the top bit is the sign of an i64
+        assert_eq!("-0x8000000000000000", eval("rotr(1, 1)", Mode::Standard)?);
+        assert_eq!("0x8000000000000000", eval("rotr(1, 1)", Mode::BigInt)?);
+        assert_eq!("0x1", eval("rotl(2^63, 1)", Mode::BigInt)?);
+        assert_eq!("0x2", eval("rotl(1, -63)", Mode::Standard)?);
+        assert_eq!("0x40", eval("popcount(2^64 - 1)", Mode::BigInt)?);
+        assert!(matches!(
+            Expr::new("clz(2^64)", Mode::BigInt).eval(),
+            Err(ExprError::Type { .. })
+        ));
+        assert!(matches!(
+            Expr::new("popcount(1.5)", Mode::Standard).eval(),
+            Err(ExprError::Type { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_vectors() -> Result<()> {
+        let eval = |src| Expr::new(src, Mode::Standard).eval().map(|v| v.to_string());
+        assert_eq!("{5, 7, 9}", eval("{1, 2, 3} + {4, 5, 6}")?);
+        assert_eq!("{2, 4, 6}", eval("2 * {1, 2, 3}")?);
+        assert_eq!("{-1, 0}", eval("{1, 2} - 2")?);
+        // * is the dot product of vectors and the matrix product of matrices
+        assert_eq!("32", eval("{1, 2, 3} * {4, 5, 6}")?);
+        assert_eq!(
+            "{{19, 22}, {43, 50}}",
+            eval("{{1, 2}, {3, 4}} * {{5, 6}, {7, 8}}")?
+        );
+        assert_eq!("{3, 7}", eval("{{1, 2}, {3, 4}} * {1, 1}")?);
+        assert_eq!("{4, 6}", eval("{1, 1} * {{1, 2}, {3, 4}}")?);
+        assert_eq!("{2, 3}", eval("sqrt({4, 9})")?);
+        assert_eq!("true", eval("{1, 2} == {1, 2} && {1, 2} != {1, 2, 3}")?);
+        assert!(matches!(
+            Expr::new("{1, 2} + {1, 2, 3}", Mode::Standard).eval(),
+            Err(ExprError::Type { .. })
+        ));
+        assert!(matches!(
+            Expr::new("{1, 2} < {3, 4}", Mode::Standard).eval(),
+            Err(ExprError::Type { .. })
+        ));
+
+        let ast = Expr::new("{x, 2 * x} * {{1, 0}, {0, x}}", Mode::Standard).parse()?;
+        assert_eq!("{x, 2 * x} * {{1, 0}, {0, x}}", ast.to_string());
+        let env = Env::from([("x".into(), Value::Int(3))]);
+        let value = ast.eval_with(&env)?;
+        assert_eq!("{3, 18}", value.to_string());
+        assert_eq!(value, CompiledExpr::new(&ast).eval_with(&env)?);
+        assert_eq!(value, ast.compile()(&env)?);
+        let folded = Expr::new("{1, 2} + 1", Mode::Standard).parse()?.simplify();
+        assert_eq!(folded, Ast::from_json(&folded.to_json())?);
+        assert_eq!(
+            r"\begin{bmatrix} 1 & x \\ 0 & 1 \end{bmatrix}",
+            Expr::new("{{1, x}, {0, 1}}", Mode::Standard)
+                .parse()?
+                .to_latex()
+        );
+        assert!(matches!(
+            Expr::new("{1, 2}", Mode::Standard).to_rpn(),
+            Err(ExprError::Unsupported { .. })
+        ));
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_currency() -> Result<()> {
+        load_rates("base = \"USD\"\n\n[rates]\nEUR = 0.5 # test rates\nGBP = 0.8\n")?;
+        let eval = |src| Expr::new(src, Mode::Standard).eval();
+        assert_eq!("200 USD", eval("100 USD + 50 EUR")?.to_string());
+        assert_eq!("true", eval("1 GBP > 1 USD")?.to_string());
+        let Value::Quantity(total) = eval("2 * 20 EUR/h * 3 h")? else {
+            panic!("money is a quantity");
+        };
+        assert_eq!("120 EUR", total.to_string());
+        assert_eq!("240 USD", total.to_currency("USD").unwrap().to_string());
+        assert!(matches!(
+            eval("1 USD + 1 km"),
+            Err(ExprError::IncompatibleUnits { .. })
+        ));
+
+        load_rates(r#"{"base": "EUR", "rates": {"USD": 2}}"#)?;
+        assert_eq!("1.5 EUR", eval("1 EUR + 1 USD")?.to_string());
+        // left out of the new table
+        assert!(eval("1 GBP").is_err());
+        for bad in [
+            r#"{"rates": {"USD": 2}}"#,
+            r#"{"base": "EUR", "rates": {"USD": -2}}"#,
+            r#"{"base": "EUR", "rates": {"km": 2}}"#,
+            "base = \"EUR\"\nUSD = 2",
+        ] {
+            assert!(
+                matches!(load_rates(bad), Err(ExprError::InvalidRates { .. })),
+                "{}",
+                bad
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_precision() -> Result<()> {
+        let format = |src, precision| {
+            Expr::new(src, Mode::Standard)
+                .eval()
+                .map(|v| v.format(Radix::Dec, precision))
+        };
+        assert_eq!("0.1429", format("1 / 7", Precision::Digits(4))?);
+        assert_eq!("10", format("9.99", Precision::Digits(2))?);
+        assert_eq!("120", format("123.0", Precision::Digits(2))?);
+        assert_eq!("0.14", format("1 / 7", Precision::Places(2))?);
+        assert_eq!("3.000", format("3.0", Precision::Places(3))?);
+        // scientific outside 1e-6 to 1e21, unless the places are fixed
+        assert_eq!(
+            "1.2676506002282294e30",
+            format("2.0^100", Precision::Shortest)?
+        );
+        assert_eq!("1.27e30", format("2.0^100", Precision::Digits(3))?);
+        assert_eq!("1e-7", format("1 / 10000000", Precision::Shortest)?);
+        assert_eq!("0.0000001", format("1 / 10000000", Precision::Places(7))?);
+        assert_eq!(
+            "{0.3, 0.7}",
+            format("{1 / 3, 2 / 3}", Precision::Digits(1))?
+        );
+        assert_eq!("1.5 km", format("3 km / 2", Precision::Places(1))?);
+        assert_eq!("7", format("7", Precision::Places(2))?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_render() {
+        let render_err = |src| render(src, &Expr::new(src, Mode::Standard).eval().unwrap_err());
+        let src = "x = 1\ny = 2 sqr(x)";
+        let err = Expr::new(src, Mode::Standard).parse_script().unwrap_err();
+        assert_eq!(
+            "error: expected an operator, `;` or end of input, found `sqr`\n --> 2:7\n  |\n\
+             2 | y = 2 sqr(x)\n  |       ^^^ an operator is missing between `2` and `sqr`\n",
+            render(src, &err)
+        );
+        assert_eq!(
+            "error: unknown function `sqr`\n --> 1:5\n  |\n1 | 1 + sqr(4)\n  |     ^^^ \
+             did you mean `sqrt`?\n",
+            render_err("1 + sqr(4)")
+        );
+        assert_eq!(
+            "error: division by zero\n --> 1:3\n  |\n1 | 1 / 0\n  |   ^ the divisor is zero\n",
+            render_err("1 / 0")
+        );
+        assert_eq!(
+            "error: unexpected character `$`\n --> 1:3\n  |\n1 | 1 $ 2\n  |   ^\n",
+            render_err("1 $ 2")
+        );
+        // the same hint out of an operator and out of a condition
+        for src in ["!2", "1 ? 2 : 3"] {
+            assert!(
+                render_err(src).contains("compare it like `x != 0`"),
+                "{}",
+                src
+            );
+        }
+        let err = Ast::from_json("{}").unwrap_err();
+        assert!(!render("{}", &err).contains("-->"));
+    }
+
+    #[test]
+    fn test_equivalent() -> Result<()> {
+        let parse = |src| Expr::new(src, Mode::Standard).parse();
+        for (a, b) in [
+            ("x + 1", "1 + x"),
+            ("2 * (x + 1)", "x + x + 2"),
+            ("(x + y)^2", "x^2 + 2*x*y + y^2"),
+            ("sin(x)^2 + cos(x)^2", "1"),
+            ("x / 2", "0.5 * x"),
+            ("a && b", "b && a"),
+            ("1 / x", "2 / (2 * x)"),
+        ] {
+            assert!(parse(a)?.equivalent(&parse(b)?), "{} == {}", a, b);
+        }
+        for (a, b) in [
+            ("x + 1", "x + 2"),
+            ("x - y", "y - x"),
+            ("(x + 1)^2", "x^2 + 1"),
+            ("x", "abs(x)"),
+            ("x > 0", "x"),
+        ] {
+            assert!(!parse(a)?.equivalent(&parse(b)?), "{} != {}", a, b);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_canonicalize() -> Result<()> {
+        let canonical = |src| -> Result<String> {
+            Ok(Expr::new(src, Mode::Standard)
+                .parse()?
+                .canonicalize()
+                .to_string())
+        };
+        assert_eq!("3 * x + 3", canonical("x*2 + 3 + x")?);
+        assert_eq!("x + 1", canonical("1 + x")?);
+        assert_eq!("2 * x * y + 1", canonical("y*x + 1 + x*y")?);
+        assert_eq!("x ^ 2 + 2 * x * y + y ^ 2", canonical("(x + y) * (y + x)")?);
+        assert_eq!("0", canonical("x - x")?);
+        assert_eq!("x - 3", canonical("-3 + x")?);
+        assert_eq!("-x + 2", canonical("2 - x")?);
+        assert_eq!("1", canonical("x / x")?);
+        assert_eq!("1 / x ^ 2", canonical("1 / (x * x)")?);
+        // past the sums the operands are only ordered
+        assert_eq!("2 * sin(x + 1)", canonical("2 * sin(1 + x)")?);
+        assert_eq!("a && b", canonical("b && a")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_substitute() -> Result<()> {
+        let parse = |src| Expr::new(src, Mode::Standard).parse();
+        let template = parse("x^2 + 2*x")?;
+        let sub = template.substitute("x", &parse("y + 1")?);
+        assert_eq!("(y + 1) ^ 2 + 2 * (y + 1)", sub.to_string());
+        let env = Env::from([("y".into(), Value::Int(2))]);
+        assert_eq!(Value::Int(15), sub.eval_with(&env)?);
+        // then differentiated in the new variable
+        assert_eq!(Value::Int(8), sub.derivative("y")?.eval_with(&env)?);
+        // other names and ones that only look alike are left alone
+        let sub = parse("x + xy + abs(x)")?.substitute("x", &parse("3")?);
+        assert_eq!("3 + xy + abs(3)", sub.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_double_star_power() -> Result<()> {
+        assert_eq!(Value::Int(1024), eval("2 ** 10")?);
+        // the same precedence and right associativity as ^
+        assert_eq!(Value::Int(512), eval("2 ** 3 ** 2")?);
+        assert_eq!(Value::Int(-9), eval("-3**2")?);
+        assert_eq!(Value::Int(18), eval("2 * 3 ** 2")?);
+        assert_eq!(eval("5 m^2")?, eval("5 m**2")?);
+        assert_eq!(
+            "2 ^ 10",
+            Expr::new("2**10", Mode::Standard).parse()?.to_string()
+        );
+        assert!(matches!(
+            eval("2 *** 3"),
+            Err(ExprError::UnexpectedToken { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_script_each() -> Result<()> {
+        let src = "# side in m\nr = 2\nr^2\n\nr = r + 1; r * 2\n";
+        let script = Expr::new(src, Mode::Standard).parse_script()?;
+        let mut printed = Vec::new();
+        let last = script.eval_each(&mut Env::new(), Ast::eval_with, |v| {
+            printed.push(v.to_string())
+        })?;
+        assert_eq!(vec!["4", "6"], printed);
+        assert_eq!(Value::Int(6), last);
+        Ok(())
+    }
+
+    #[test]
+    fn test_division_by_zero_policy() -> Result<()> {
+        let eval = |src, division_by_zero| {
+            Expr::new(src, Mode::Standard).eval_with_options(&EvalOptions {
+                division_by_zero,
+                ..Default::default()
+            })
+        };
+        assert!(matches!(
+            eval("1 / 0", ZeroDivision::Error),
+            Err(ExprError::DivisionByZero { .. })
+        ));
+        assert_eq!(
+            Value::Float(f64::NEG_INFINITY),
+            eval("-1 / 0", ZeroDivision::Infinity)?
+        );
+        assert_eq!(
+            Value::Float(f64::INFINITY),
+            eval("2.5 // 0", ZeroDivision::Infinity)?
+        );
+        assert!(eval("0 / 0", ZeroDivision::Infinity)?.as_f64().is_nan());
+        assert!(eval("5 % 0", ZeroDivision::Infinity)?.as_f64().is_nan());
+        assert_eq!(Value::Int(i64::MAX), eval("7 / 0", ZeroDivision::Saturate)?);
+        assert_eq!(
+            Value::Int(i64::MIN),
+            eval("-7 // 0", ZeroDivision::Saturate)?
+        );
+        assert_eq!(Value::Int(0), eval("0 / 0", ZeroDivision::Saturate)?);
+        assert_eq!(Value::Int(5), eval("5 % 0", ZeroDivision::Saturate)?);
+        assert_eq!(
+            Value::Float(-f64::MAX),
+            eval("-0.5 / 0", ZeroDivision::Saturate)?
+        );
+        // the rest of the expression goes on with the value
+        assert_eq!(
+            Value::Float(0.0),
+            eval("1 / (1 / 0)", ZeroDivision::Infinity)?
+        );
+        // a zero inside an interval stays an error
+        assert!(eval("1 / [-1, 1]", ZeroDivision::Infinity).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_huge_exponent() -> Result<()> {
+        // 2 ^ 4294967296 once wrapped around to 2 ^ 0
+        for src in [
+            "2 ^ 4294967296",
+            "2 ^ 4294967297",
+            "(-2) ^ 9223372036854775807",
+        ] {
+            assert!(
+                matches!(eval(src), Err(ExprError::Overflow { .. })),
+                "{}",
+                src
+            );
+        }
+        assert_eq!(Value::Int(1), eval("1 ^ 4294967296")?);
+        assert_eq!(Value::Int(0), eval("0 ^ 9223372036854775807")?);
+        assert_eq!(Value::Int(1), eval("(-1) ^ 4294967296")?);
+        assert_eq!(Value::Int(-1), eval("(-1) ^ 4294967297")?);
+        assert_eq!(
+            Some(-1),
+            Expr::new("(-1) ^ 4294967297", Mode::Standard)
+                .eval_as::<i64>()
+                .ok()
+        );
+
+        let big = |src| Expr::new(src, Mode::BigInt).eval();
+        assert_eq!(Value::Big((-1).into()), big("(-1) ^ 99999999999999999999")?);
+        assert_eq!(Value::Big(1.into()), big("(-1) ^ 99999999999999999998")?);
+        assert!(matches!(
+            big("2 ^ 99999999999999999999"),
+            Err(ExprError::Overflow { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_arithmetic_modes() -> Result<()> {
+        let eval = |src: &str, arithmetic| -> Result<Value> {
+            Expr::new(src, Mode::Standard).eval_with_options(&EvalOptions {
+                arithmetic,
+                ..Default::default()
+            })
+        };
+        let max = "9223372036854775807";
+        let min = "(-9223372036854775807 - 1)";
+        for src in [format!("{} + 1", max), format!("-{}", min)] {
+            assert!(matches!(
+                eval(&src, Arithmetic::Checked),
+                Err(ExprError::Overflow { .. })
+            ));
+        }
+        assert_eq!(
+            Value::Int(i64::MIN),
+            eval(&format!("{} + 1", max), Arithmetic::Wrapping)?
+        );
+        assert_eq!(
+            Value::Int(i64::MIN),
+            eval(&format!("-{}", min), Arithmetic::Wrapping)?
+        );
+        assert_eq!(Value::Int(0), eval("2 ^ 64", Arithmetic::Wrapping)?);
+        assert_eq!(
+            Value::Int(-2),
+            eval(&format!("{} * 2", max), Arithmetic::Wrapping)?
+        );
+        assert_eq!(Value::Int(i64::MIN), eval("1 << 63", Arithmetic::Wrapping)?);
+        assert_eq!(
+            Value::Int(i64::MAX),
+            eval(&format!("{} + 1", max), Arithmetic::Saturating)?
+        );
+        assert_eq!(
+            Value::Int(i64::MAX),
+            eval(&format!("-{}", min), Arithmetic::Saturating)?
+        );
+        assert_eq!(
+            Value::Int(i64::MIN),
+            eval("(-3) ^ 41", Arithmetic::Saturating)?
+        );
+        assert_eq!(
+            Value::Int(i64::MAX),
+            eval(&format!("{} // -1", min), Arithmetic::Saturating)?
+        );
+        // exponents past u32::MAX aren't cut down to their low bits
+        assert_eq!(Value::Int(0), eval("2 ^ 4294967296", Arithmetic::Wrapping)?);
+        assert_eq!(
+            Value::Int(i64::MIN),
+            eval("(-3) ^ 4294967297", Arithmetic::Saturating)?
+        );
+        assert_eq!(
+            Value::Int(i64::MAX),
+            eval("(-3) ^ 4294967296", Arithmetic::Saturating)?
+        );
+        // results that fit and other kinds are untouched
+        assert_eq!(Value::Int(7), eval("3 + 4", Arithmetic::Wrapping)?);
+        assert!(eval("exp(1000)", Arithmetic::Saturating).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unicode_operators() -> Result<()> {
+        assert_eq!(Value::Int(42), eval("6 × 7")?);
+        assert_eq!(Value::Int(4), eval("12 ÷ 3")?);
+        assert_eq!(Value::Int(-2), eval("3 − 5")?);
+        assert_eq!(Value::Int(-1), eval("−1")?);
+        assert_eq!(Value::Float(3.0), eval("√9")?);
+        assert_eq!(Value::Float(5.0), eval("√(9 + 16)")?);
+        // like unary minus, tighter than * and looser than ^
+        assert_eq!(Value::Float(6.0), eval("2 × √9")?);
+        assert_eq!(Value::Float(3.0), eval("√3^2")?);
+        assert_eq!(
+            "sqrt(x) * 2",
+            Expr::new("√x×2", Mode::Standard).parse()?.to_string()
+        );
+        assert_eq!("x sqrt 2 *", Expr::new("√x×2", Mode::Standard).to_rpn()?);
+        assert_eq!(
+            "sqrt(9)",
+            Expr::new("9 √", Mode::Standard).parse_rpn()?.to_string()
+        );
+        let err = eval("2 ÷").unwrap_err();
+        assert!(render("2 ÷", &err).contains("after `÷`"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_locale_numbers() -> Result<()> {
+        let eval = |src, locale| Expr::new(src, Mode::Standard).with_locale(locale).eval();
+        assert_eq!(
+            Value::Float(1234.56),
+            eval("1,234.56", Locale::DecimalPoint)?
+        );
+        assert_eq!(
+            Value::Int(1234567),
+            eval("1,234,567", Locale::DecimalPoint)?
+        );
+        // not three digits after the comma, so it separates arguments
+        assert_eq!(Value::Int(23), eval("max(1,23)", Locale::DecimalPoint)?);
+        assert_eq!(
+            Value::Float(1234.56),
+            eval("1.234,56", Locale::DecimalComma)?
+        );
+        assert_eq!(Value::Int(2), eval("max(1,5, 2)", Locale::DecimalComma)?);
+        assert_eq!(Value::Float(1234.56), eval("1.234,56", Locale::Auto)?);
+        assert_eq!(Value::Float(0.125), eval("0.125", Locale::Auto)?);
+        assert_eq!(Value::Int(1234567), eval("1.234.567", Locale::Auto)?);
+        assert!(matches!(
+            eval("1,234", Locale::Auto),
+            Err(ExprError::AmbiguousNumber { .. })
+        ));
+        assert!(matches!(
+            eval("1.23.456,7", Locale::DecimalComma),
+            Err(ExprError::InvalidNumber { .. })
+        ));
+        // plain source is unchanged, 1,5 is two arguments
+        assert_eq!(Value::Int(5), eval("max(1,5)", Locale::Plain)?);
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_native_functions() -> Result<()> {
+        struct Scale(f64);
+
+        impl NativeFn for Scale {
+            fn name(&self) -> &str {
+                "scale"
+            }
+
+            fn arity(&self) -> Arity {
+                Arity::Between(1, 2)
+            }
+
+            fn call(&self, args: &[Value], span: Span) -> Result<Value> {
+                let by = args.get(1).map_or(Ok(1.0), |v| match v {
+                    Value::Text(_) => Err(ExprError::Type {
+                        expected: "a number".into(),
+                        found: v.to_string(),
+                        operation: None,
+                        span,
+                    }),
+                    v => Ok(v.as_f64()),
+                })?;
+                Ok(Value::Float(args[0].as_f64() * self.0 * by))
+            }
+        }
+
+        register_function(Scale(10.0));
+        assert_eq!(Value::Float(30.0), eval("scale(3)")?);
+        assert_eq!(Value::Float(61.0), eval("scale(3, 2) + 1")?);
+        let ast = Expr::new("scale(1) + scale(2)", Mode::Standard).parse()?;
+        assert_eq!(Value::Float(30.0), CompiledExpr::new(&ast).eval()?);
+        assert_eq!(
+            Value::Float(20.0),
+            Expr::new("2 scale", Mode::Standard).parse_rpn()?.eval()?
+        );
+        assert!(matches!(
+            eval("scale(1, 2, 3)"),
+            Err(ExprError::WrongArgCount { .. })
+        ));
+        // factor() gives text, which a builtin would refuse before the call
+        let err = eval("scale(1, factor(12))").unwrap_err();
+        assert_eq!(Span::new(0, 5), err.span());
+        // the code compiled before calls the function registered again
+        register_function(Scale(100.0));
+        assert_eq!(Value::Float(300.0), CompiledExpr::new(&ast).eval()?);
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_expr_cache() -> Result<()> {
+        let cache = ExprCache::new(Mode::Standard);
+        std::thread::scope(|scope| {
+            for x in 0..4 {
+                let cache = &cache;
+                scope.spawn(move || {
+                    let env = Env::from([("x".to_string(), Value::Int(x))]);
+                    assert_eq!(
+                        Value::Int(x * x + 1),
+                        cache.eval("x ^ 2 + 1", &env).unwrap()
+                    );
+                });
+            }
+        });
+        assert_eq!(1, cache.len());
+        let first = cache.get("x ^ 2 + 1")?;
+        assert!(std::sync::Arc::ptr_eq(&first, &cache.get("x ^ 2 + 1")?));
+        // a source that doesn't parse is an error every time and isn't kept
+        assert!(cache.get("1 +").is_err());
+        assert_eq!(1, cache.len());
+        cache.clear();
+        assert!(cache.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_limits() -> Result<()> {
+        let eval = |src: &str, options: EvalOptions| {
+            Expr::new(src, Mode::Standard).eval_with_options(&options)
+        };
+        let ops = |max| EvalOptions {
+            max_ops: Some(max),
+            ..Default::default()
+        };
+        // three additions and a call
+        assert_eq!(Value::Int(4), eval("max(1 + 1 + 1 + 1, 0)", ops(4))?);
+        assert!(matches!(
+            eval("max(1 + 1 + 1 + 1, 0)", ops(3)),
+            Err(ExprError::TooManyOps { max: 3, .. })
+        ));
+        // only the branch taken counts
+        assert_eq!(Value::Int(1), eval("true ? 1 : 1 + 1 + 1 + 1", ops(1))?);
+        // timeouts need a clock
+        #[cfg(feature = "std")]
+        {
+            let options = EvalOptions {
+                timeout: Some(std::time::Duration::ZERO),
+                ..Default::default()
+            };
+            assert!(matches!(
+                eval("1 + 2", options),
+                Err(ExprError::TimedOut { .. })
+            ));
+            assert_eq!(Value::Int(7), eval("7", options)?);
+            let options = EvalOptions {
+                timeout: Some(std::time::Duration::from_secs(60)),
+                ..ops(1000)
+            };
+            assert_eq!(Value::Int(3), eval("1 + 2", options)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_variables() -> Result<()> {
+        let variables = |src| -> Result<Vec<String>> {
+            let ast = Expr::new(src, Mode::Standard).parse()?;
+            Ok(ast.variables().into_iter().collect())
+        };
+        assert_eq!(vec!["x", "y"], variables("y * x + sin(x) ^ 2")?);
+        assert_eq!(vec!["a", "b", "c"], variables("a > 0 ? b : -c")?);
+        assert!(variables("max(1, 2) + 3")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytecode_bytes() -> Result<()> {
+        let env = Env::from([("x".to_string(), Value::Int(4))]);
+        for src in [
+            "x * 2 + 1",
+            "x > 3 && x < 5 ? max(x, 1, 2) : -x",
+            "0.5 + 1/3",
+            "2 km * x",
+            "(x == 4 || x / 0 > 1) ? {1, 2} : {3, 4}",
+            "if(x % 2 == 0, round(x / 3, 2), 0)",
+        ] {
+            let code = CompiledExpr::new(&Expr::new(src, Mode::Rational).parse()?);
+            let bytes = code.to_bytes()?;
+            let read = CompiledExpr::from_bytes(&bytes)?;
+            assert_eq!(code, read, "{}", src);
+            assert_eq!(code.eval_with(&env)?, read.eval_with(&env)?);
+        }
+        let bytes = CompiledExpr::new(&Expr::new("x + 1", Mode::Standard).parse()?).to_bytes()?;
+        // every shorter prefix is refused rather than read past
+        for end in 0..bytes.len() {
+            assert!(matches!(
+                CompiledExpr::from_bytes(&bytes[..end]),
+                Err(ExprError::InvalidBytecode { .. })
+            ));
+        }
+        let mut newer = bytes.clone();
+        newer[4] += 1;
+        assert!(CompiledExpr::from_bytes(&newer).is_err());
+        // no names, no constants and an add with nothing to add
+        let mut add = b"EXPR\x01".to_vec();
+        add.extend([0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 5]);
+        add.extend([0; 8]);
+        assert!(matches!(
+            CompiledExpr::from_bytes(&add),
+            Err(ExprError::InvalidBytecode { .. })
+        ));
+        let mut ops = Operators::new();
+        ops.binary("<>", 5, Assoc::Left, |l, _, _| Ok(l));
+        let ast = Expr::new("1 <> 2", Mode::Standard)
+            .with_operators(&ops)
+            .parse()?;
+        assert!(matches!(
+            CompiledExpr::new(&ast).to_bytes(),
+            Err(ExprError::Unsupported { .. })
+        ));
+        Ok(())
+    }
+
+    #[cfg(feature = "ffi")]
+    #[test]
+    fn test_ffi() {
+        use crate::expr_eval;
+        use std::ffi::{c_char, CStr};
+
+        let mut out = 0.0;
+        let mut err = [1 as c_char; 16];
+        let code = unsafe { expr_eval(c"x = 3; x * 2.5".as_ptr(), &mut out, err.as_mut_ptr(), 16) };
+        assert_eq!((0, 7.5), (code, out));
+        let code = unsafe { expr_eval(c"1 / 0".as_ptr(), &mut out, err.as_mut_ptr(), 16) };
+        assert_eq!((1, 7.5), (code, out));
+        // cut to fit, with the NUL
+        let message = unsafe { CStr::from_ptr(err.as_ptr()) };
+        assert_eq!("division by zer", message.to_str().unwrap());
+        let code = unsafe { expr_eval(c"{1, 2}".as_ptr(), &mut out, core::ptr::null_mut(), 0) };
+        assert_eq!(1, code);
+        let code = unsafe { expr_eval(core::ptr::null(), &mut out, err.as_mut_ptr(), 16) };
+        assert_eq!(2, code);
+    }
+
+    #[test]
+    fn test_percent() -> Result<()> {
+        assert_eq!(Value::Int(220), eval("200 + 10%")?);
+        assert_eq!(Value::Int(180), eval("200 - 10%")?);
+        assert_eq!(Value::Int(30), eval("200 * 15%")?);
+        assert_eq!(Value::Int(500), eval("50 / 10%")?);
+        assert_eq!(Value::Float(0.25), eval("25%")?);
+        assert_eq!(Value::Int(36), eval("(2 + 1) * 12 + 0%")?);
+        // an operand after it makes it the remainder
+        assert_eq!(Value::Int(1), eval("10 % 3")?);
+        assert_eq!(Value::Int(-2), eval("10 % -3")?);
+        assert_eq!(Value::Int(2), eval("max(10 % 4, 1)")?);
+        assert_eq!(Value::Float(0.5), eval("max(50%, 1%)")?);
+        // it ends the right operand of + or -, where a sign after it is the
+        // next operator; the shunting yard doesn't read percentages
+        for parser in &PARSERS[..2] {
+            let eval = |src| parser.parse(&mut Expr::new(src, Mode::Standard))?.eval();
+            assert_eq!(Value::Int(215), eval("200 + 10% - 5")?, "{}", parser.name());
+            assert_eq!(Value::Int(225), eval("200 + 10% + 5")?, "{}", parser.name());
+            assert_eq!(Value::Int(220), eval("100 + 10% * 2")?, "{}", parser.name());
+            assert_eq!(Value::Int(15), eval("200 * 10% - 5")?, "{}", parser.name());
+            assert_eq!(Value::Int(-2), eval("10 % -3")?, "{}", parser.name());
+            assert_eq!(Value::Int(201), eval("200 + 10 % 3")?, "{}", parser.name());
+        }
+        // binding like `*`, the whole remainder or power is the percentage
+        assert_eq!(Value::Float(0.01), eval("10 % 3 %")?);
+        assert_eq!(Value::Float(10.24), eval("2 ^ 10%")?);
+        // in parens it is just a number, and so is a division by 100
+        assert_eq!(Value::Float(200.1), eval("200 + (10%)")?);
+        assert_eq!(Value::Float(200.1), eval("200 + 10 / 100")?);
+        let env = Env::from([("x".to_string(), Value::Int(80))]);
+        let ast = Expr::new("x * 15%", Mode::Standard).parse()?;
+        assert_eq!(Value::Int(12), ast.eval_with(&env)?);
+        assert_eq!(
+            "x * (100 + 5) / 100",
+            Expr::new("x + 5%", Mode::Standard).parse()?.to_string()
+        );
+        assert_eq!(
+            "11/10",
+            Expr::new("1 + 10%", Mode::Rational).eval()?.to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_constants() -> Result<()> {
+        let physics = constants("physics").unwrap();
+        let eval = |src| Expr::new(src, Mode::Standard).parse()?.eval_with(&physics);
+        // mass in kilograms times speed squared gives joules
+        let energy = eval("m_p * c ^ 2")?;
+        assert_eq!("kg*m^2/s^2", energy.to_string().split(' ').nth(1).unwrap());
+        assert!(matches!(
+            eval("c + g0"),
+            Err(ExprError::IncompatibleUnits { .. })
+        ));
+        assert_eq!(Value::Float(6.022_140_76e23), eval("N_A")?);
+        let math = constants("math").unwrap();
+        let ast = Expr::new("golden ^ 2 - golden", Mode::Standard).parse()?;
+        assert!((ast.eval_with(&math)?.as_f64() - 1.0).abs() < 1e-12);
+        assert!(constants("chemistry").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_word_size() -> Result<()> {
+        let out = |src, radix, bits: u32| -> Result<String> {
+            let value = Expr::new(src, Mode::Standard).eval()?;
+            let word = WordSize::from_name(&bits.to_string()).unwrap();
+            Ok(value.format_word(radix, Precision::Shortest, word))
+        };
+        assert_eq!("0xffffffff", out("-1", Radix::Hex, 32)?);
+        assert_eq!("0xff", out("-1", Radix::Hex, 8)?);
+        assert_eq!("0xffffffffffffff00", out("-256", Radix::Hex, 64)?);
+        assert_eq!("0o177776", out("~1", Radix::Oct, 16)?);
+        // bits past the word are dropped, decimal shows the signed word
+        assert_eq!("0x34", out("4660", Radix::Hex, 8)?);
+        assert_eq!("-128", out("128", Radix::Dec, 8)?);
+        assert_eq!("127", out("127", Radix::Dec, 8)?);
+        // anything but an integer is left alone
+        assert_eq!("2.5", out("5 / 2", Radix::Hex, 32)?);
+        assert_eq!("{0xff, 0x1}", out("{-1, 1}", Radix::Hex, 8)?);
+        // only the four sizes name a word, so there is no zero-bit one
+        assert_eq!(Some(64), WordSize::from_name("64").map(WordSize::bits));
+        assert_eq!(None, WordSize::from_name("0"));
+        assert_eq!(None, WordSize::from_name("12"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_placeholders() -> Result<()> {
+        let ast = Expr::new("?1 * ?2 + 3", Mode::Standard).parse()?;
+        assert_eq!("?1 * ?2 + 3", ast.to_string());
+        assert_eq!(
+            Value::Int(9),
+            ast.eval_args(&[Value::Int(2), Value::Int(3)])?
+        );
+        let code = CompiledExpr::new(&ast);
+        assert_eq!(
+            Value::Float(5.5),
+            code.eval_args(&[Value::Float(1.25), Value::Int(2)])?
+        );
+        assert!(matches!(
+            ast.eval_args(&[Value::Int(2)]),
+            Err(ExprError::UnknownVariable { name, .. }) if name == "?2"
+        ));
+        // a ? after an operand is still the conditional
+        let cond = Expr::new("?1 > 0 ?1 : 2", Mode::Standard).parse()?;
+        assert_eq!(Value::Int(1), cond.eval_args(&[Value::Int(5)])?);
+        assert!(Expr::new("? 1", Mode::Standard).parse().is_err());
+        assert!(Expr::new("?0", Mode::Standard).parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_visitor() -> Result<()> {
+        use crate::{Fold, Visitor};
+
+        // calls by name and the depth of the deepest leaf
+        #[derive(Default)]
+        struct Survey {
+            calls: Vec<String>,
+            leaf_depth: usize,
+        }
+
+        impl Visitor for Survey {
+            fn num(&mut self, _: &Value, depth: usize) {
+                self.leaf_depth = self.leaf_depth.max(depth);
+            }
+            fn var(&mut self, _: &str, _: Span, depth: usize) {
+                self.leaf_depth = self.leaf_depth.max(depth);
+            }
+            fn call(&mut self, name: &str, _: usize, _: Span, _: usize) {
+                self.calls.push(name.to_string());
+            }
+        }
+
+        let ast = Expr::new("max(abs(x), 2) + (x > 0 ? 1 : y)", Mode::Standard).parse()?;
+        let mut survey = Survey::default();
+        ast.walk(&mut survey);
+        assert_eq!(vec!["max", "abs"], survey.calls);
+        assert_eq!(3, survey.leaf_depth);
+
+        // the count of nodes, parentheses included
+        struct Size;
+
+        impl Fold for Size {
+            type Output = usize;
+
+            fn num(&mut self, _: &Value) -> usize {
+                1
+            }
+            fn var(&mut self, _: &str, _: Span) -> usize {
+                1
+            }
+            fn unary(&mut self, _: &Token, operand: usize, _: Span) -> usize {
+                operand + 1
+            }
+            fn binary(&mut self, _: &Token, lhs: usize, rhs: usize, _: Span) -> usize {
+                lhs + rhs + 1
+            }
+            fn call(&mut self, _: &str, args: Vec<usize>, _: Span) -> usize {
+                args.into_iter().sum::<usize>() + 1
+            }
+            fn cond(&mut self, cond: usize, then: usize, otherwise: usize, _: Span) -> usize {
+                cond + then + otherwise + 1
+            }
+            fn paren(&mut self, inner: usize) -> usize {
+                inner + 1
+            }
+        }
+
+        assert_eq!(12, ast.fold(&mut Size));
+        assert_eq!(ast.variables().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_promotion() -> Result<()> {
+        let eval = |src, promotion| {
+            let options = EvalOptions {
+                promotion,
+                ..Default::default()
+            };
+            Expr::new(src, Mode::Standard).eval_with_options(&options)
+        };
+        assert_eq!(Value::Float(3.5), eval("2 + 1.5", Promotion::Auto)?);
+        assert!(matches!(
+            eval("2 + 1.5", Promotion::Explicit),
+            Err(ExprError::ImplicitPromotion { value, .. }) if value == "2"
+        ));
+        assert_eq!(
+            Value::Float(3.5),
+            eval("float(2) + 1.5", Promotion::Explicit)?
+        );
+        assert_eq!(Value::Int(3), eval("2 + int(1.5)", Promotion::Explicit)?);
+        assert_eq!(Value::Float(2.5), eval("5 / 2", Promotion::Explicit)?);
+        assert!(eval("5 / 2", Promotion::Error).is_err());
+        assert_eq!(Value::Int(3), eval("6 / 2", Promotion::Error)?);
+        assert_eq!(Value::Float(2.5), eval("float(5) / 2.0", Promotion::Error)?);
+        // the casts themselves
+        assert_eq!(
+            Value::Int(-2),
+            Expr::new("int(-2.9)", Mode::Standard).eval()?
+        );
+        assert!(Expr::new("int(1e30)", Mode::Standard).eval().is_err());
+        assert_eq!(
+            "3",
+            Expr::new("int(7 / 2)", Mode::Rational).eval()?.to_string()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_vm_debug() -> Result<()> {
+        let ast = Expr::new("x > 0 && max(x, 2) < 5", Mode::Standard).parse()?;
+        let env = Env::from([("x".to_string(), Value::Int(3))]);
+        let mut steps = Vec::new();
+        let value = CompiledExpr::new(&ast).eval_debug(&env, |step| steps.push(step.clone()))?;
+        assert_eq!(Value::Bool(true), value);
+        let listing: Vec<&str> = steps.iter().map(|step| step.instr.as_str()).collect();
+        assert_eq!(
+            vec![
+                "load x",
+                "const 0",
+                "gt",
+                "jump_if_false 10",
+                "load x",
+                "const 2",
+                "call max 2",
+                "const 5",
+                "lt",
+                "check_bool",
+            ],
+            listing
+        );
+        assert_eq!(vec![Value::Int(3), Value::Int(2)], steps[5].stack);
+        assert_eq!("  6  call max 2        [3]", steps[6].to_string());
+        // a failing instruction is the last one seen
+        let mut seen = 0;
+        let err = CompiledExpr::new(&Expr::new("1 + 2 / 0", Mode::Standard).parse()?)
+            .eval_debug(&Env::new(), |_| seen += 1);
+        assert!(matches!(err, Err(ExprError::DivisionByZero { .. })));
+        assert_eq!(3, seen);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compound_assign() -> Result<()> {
+        let mut env = Env::new();
+        let script = Expr::new(
+            "x = 10; x += 5; x -= 1; x *= 2 + 1; x /= 7; x",
+            Mode::Standard,
+        )
+        .parse_script()?;
+        assert_eq!(Value::Int(6), script.eval(&mut env)?);
+        assert_eq!(Some(&Value::Int(6)), env.get("x"));
+        // the right side stays whole, and the `/=` is what a zero points at
+        let script = Expr::new("y = 1; y /= 2 - 2", Mode::Standard).parse_script()?;
+        assert!(matches!(
+            script.eval(&mut Env::new()),
+            Err(ExprError::DivisionByZero { span }) if span == Span::new(9, 2)
+        ));
+        assert!(matches!(
+            Expr::new("2 += 1", Mode::Standard).parse_script(),
+            Err(ExprError::InvalidAssignment { .. })
+        ));
+        // an unknown variable can't be updated
+        let script = Expr::new("z += 1", Mode::Standard).parse_script()?;
+        assert!(script.eval(&mut Env::new()).is_err());
+        // nor are they operators inside an expression
+        assert!(Expr::new("1 + (x += 1)", Mode::Standard).parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_for_loop() -> Result<()> {
+        let run = |src: &str| {
+            let mut env = Env::new();
+            Expr::new(src, Mode::Standard)
+                .parse_script()
+                .and_then(|script| script.eval(&mut env).map(|value| (value, env)))
+        };
+        let (value, env) = run("acc = 0; for i in 1..10 { acc = acc + i }")?;
+        assert_eq!(Value::Int(45), value);
+        assert_eq!(Some(&Value::Int(9)), env.get("i"));
+        let (value, _) = run("acc = 0; for i in 1..=10 { acc += i }; acc")?;
+        assert_eq!(Value::Int(55), value);
+        // nested, over several lines, with bounds worked out once
+        let src = "n = 3; fact = 1\nfor i in 1..=n {\n  fact *= i\n  n = 10\n}\nfact";
+        assert_eq!(Value::Int(6), run(src)?.0);
+        let (value, _) = run("t = 0; for i in 0..3 { for j in i..3 { t += 1 } }")?;
+        assert_eq!(Value::Int(6), value);
+        // ranges of other kinds of numbers, and ones that are empty
+        let mut env = Env::new();
+        let script =
+            Expr::new("x = 0; for k in 1..4 { x += k / 2 }", Mode::Rational).parse_script()?;
+        assert_eq!("3", script.eval(&mut env)?.to_string());
+        assert_eq!(Value::Int(0), run("x = 0; for i in 5..5 { x = 1 }; x")?.0);
+        assert!(matches!(
+            run("for i in 5..5 { 1 }"),
+            Err(ExprError::Type { found, .. }) if found == "5..5"
+        ));
+        assert!(run("for i in 1..2.5 { 1 }").is_err());
+        assert!(run("for i in 1..3 { 1 ").is_err());
+        // a decimal number still reads, and `for` alone is still a name
+        assert_eq!(Value::Float(3.5), run("for = 3; for + 0.5")?.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_user_functions() -> Result<()> {
+        let mut env = constants("math").unwrap();
+        let script = Expr::new("fn area(r) = pi * r^2; area(2)", Mode::Standard).parse_script()?;
+        assert_eq!(
+            Value::Float(4.0 * core::f64::consts::PI),
+            script.eval(&mut env)?
+        );
+        let Some(Value::Function(area)) = env.get("area") else {
+            panic!("area is not a function");
+        };
+        assert_eq!("fn area(r) = pi * r ^ 2", area.to_string());
+        // later expressions call it once the parser knows the name
+        assert!(Expr::new("area(1)", Mode::Standard).parse().is_err());
+        let ast = Expr::new("area(1) * 2", Mode::Standard)
+            .with_functions(&env)
+            .parse()?;
+        assert_eq!(Value::Float(core::f64::consts::TAU), ast.eval_with(&env)?);
+        assert_eq!(
+            Value::Float(core::f64::consts::TAU),
+            CompiledExpr::new(&ast).eval_with(&env)?
+        );
+        let bytes = CompiledExpr::new(&ast).to_bytes()?;
+        assert_eq!(
+            Value::Float(core::f64::consts::TAU),
+            CompiledExpr::from_bytes(&bytes)?.eval_with(&env)?
+        );
+        // recursion, until it goes too deep
+        let src = "fn fact(n) = n <= 1 ? 1 : n * fact(n - 1); fact(10)";
+        let script = Expr::new(src, Mode::Standard).parse_script()?;
+        assert_eq!(Value::Int(3628800), script.eval(&mut env)?);
+        let script = Expr::new("fn f(x) = f(x + 1)\nf(1)", Mode::Standard).parse_script()?;
+        assert!(matches!(
+            script.eval(&mut env),
+            Err(ExprError::RecursionLimit { name, span }) if name == "f" && span == Span::new(19, 1)
+        ));
+        // errors inside the body point at the call
+        let ast = Expr::new("fact(1, 2)", Mode::Standard)
+            .with_functions(&env)
+            .parse()?;
+        assert!(matches!(
+            ast.eval_with(&env),
+            Err(ExprError::WrongArgCount { found: 2, .. })
+        ));
+        let script = Expr::new("fn g(x) = x / 0; 1 + g(2)", Mode::Standard).parse_script()?;
+        assert!(matches!(
+            script.eval(&mut env),
+            Err(ExprError::DivisionByZero { span }) if span == Span::new(21, 1)
+        ));
+        // builtins can't be redefined, and a function survives a json round trip
+        assert!(Expr::new("fn max(a, b) = a", Mode::Standard)
+            .parse_script()
+            .is_err());
+        let ast = Ast::from_json(&Ast::Num(env["fact"].clone()).to_json())?;
+        assert_eq!(env["fact"].to_string(), ast.to_string());
+        let env = Env::from([("fact".to_string(), ast.eval_with(&Env::new())?)]);
+        let ast = Expr::new("fact(4)", Mode::Standard)
+            .with_functions(&env)
+            .parse()?;
+        assert_eq!(Value::Int(24), ast.eval_with(&env)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_errors() -> Result<()> {
+        let eval = |src| Expr::new(src, Mode::Standard).eval().unwrap_err();
+        let err = eval("7 + true * 3");
+        assert_eq!(
+            "expected number, found bool at offset 9 (`true * 3`)",
+            err.to_string()
+        );
+        assert!(matches!(
+            &err,
+            ExprError::Type {
+                expected,
+                found,
+                operation: Some(_),
+                ..
+            } if expected == "number" && found == "bool"
+        ));
+        // where there is no operation to show, the type is named all the same
+        for (src, message) in [
+            ("true && 2", "expected bool, found number at offset 5"),
+            ("1 || 2", "expected bool, found number at offset 2"),
+            ("0 && 1 / 0", "expected bool, found number at offset 2"),
+            ("1 ? 2 : 3", "expected bool, found number at offset 2"),
+            ("sqrt(true)", "expected number, found bool at offset 0"),
+            ("max(1, 1 < 2)", "expected number, found bool at offset 0"),
+        ] {
+            let ast = Expr::new(src, Mode::Standard).parse()?;
+            assert_eq!(message, ast.eval().unwrap_err().to_string(), "{}", src);
+            let vm = CompiledExpr::new(&ast).eval().unwrap_err();
+            assert_eq!(message, vm.to_string(), "{}", src);
+        }
+        // a value that doesn't fit is shown
+        assert_eq!(
+            "expected an integer, found `1.5` at offset 0",
+            eval("popcount(1.5)").to_string()
+        );
+        assert_eq!(
+            "expected bool, found number at offset 0 (`!2`)",
+            eval("!2").to_string()
+        );
+        assert_eq!(
+            "expected number, found bool at offset 4 (`1.5 < true`)",
+            eval("1.5 < true").to_string()
+        );
+        // every backend reports the same error
+        let ast = Expr::new("2 - (1 == 1)", Mode::Standard).parse()?;
+        let err = ast.eval().unwrap_err();
+        assert_eq!(
+            "expected number, found bool at offset 2 (`2 - true`)",
+            err.to_string()
+        );
+        let vm = CompiledExpr::new(&ast).eval().unwrap_err();
+        assert_eq!(err.to_string(), vm.to_string());
+        let closure = ast.compile()(&Env::new()).unwrap_err();
+        assert_eq!(err.to_string(), closure.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_number() -> Result<()> {
+        assert_eq!(Value::Int(42), parse_number("42")?);
+        assert_eq!(Value::Int(-1_234), parse_number("-1_234")?);
+        assert_eq!(Value::Float(0.025), parse_number("2.5e-2")?);
+        assert_eq!(Value::Float(3.0), parse_number("3.")?);
+        // too big for an i64 is an overflow, as in an expression
+        assert!(matches!(
+            parse_number("99999999999999999999"),
+            Err(ExprError::Overflow { .. })
+        ));
+        for (src, found) in [("", ""), ("1 2", "2"), ("x", "x"), ("--1", "-"), ("-", "")] {
+            assert!(
+                matches!(
+                    parse_number(src),
+                    Err(ExprError::UnexpectedToken { found: f, .. }) if f == found
+                ),
+                "{}",
+                src
+            );
+        }
+        assert!(matches!(
+            parse_number("1.2.3"),
+            Err(ExprError::InvalidNumber { .. })
+        ));
+
+        // the cells of a csv row in decimal mode add up exactly, as the
+        // literals of the formula do
+        let mode = Mode::Decimal(DEFAULT_DECIMAL_SCALE);
+        let mut env = Env::new();
+        for (name, cell) in [("c1", "0.1"), ("c2", "0.2")] {
+            env.insert(name.into(), Expr::new(cell, mode).parse_number()?);
+        }
+        let sum = Expr::new("c1 + c2", mode).parse()?.eval_with(&env)?;
+        assert_eq!("0.3", sum.to_string());
+        let number = |src, mode| {
+            Expr::new(src, mode)
+                .with_locale(Locale::DecimalComma)
+                .parse_number()
+        };
+        assert_eq!(Value::Float(1.5), number("1,5", Mode::Standard)?);
+        assert_eq!("1/3", number("1/3", Mode::Rational)?.to_string());
+        assert_eq!("-1/3", number("-1/3", Mode::Rational)?.to_string());
+        assert!(matches!(
+            number("1/0", Mode::Rational),
+            Err(ExprError::DivisionByZero { .. })
+        ));
+        assert!(matches!(
+            number("1/3", Mode::Standard),
+            Err(ExprError::UnexpectedToken { found, .. }) if found == "/"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_formatter() -> Result<()> {
+        let eng = Engineering::default();
+        let si = SiPrefix { digits: 3 };
+        for (src, engineering, prefixed) in [
+            ("1500", "1.5e3", "1.5k"),
+            ("-0.000123456", "-123.456e-6", "-123µ"),
+            ("999.9999999", "1e3", "1k"),
+            ("42", "42", "42"),
+            ("0", "0", "0"),
+            ("1e27", "1e27", "1e27"),
+        ] {
+            let value = eval(src)?;
+            assert_eq!(engineering, eng.format(&value), "{}", src);
+            assert_eq!(prefixed, si.format(&value), "{}", src);
+        }
+        assert_eq!("inf", si.format(&Value::Float(f64::INFINITY)));
+        // the numbers inside other values, and everything else as it displays
+        assert_eq!("{2.5e6, 12}", eng.format(&eval("{2500000, 12}")?));
+        assert_eq!("true", eng.format(&eval("1 < 2")?));
+        assert_eq!(
+            "[1.5m, 2m]",
+            si.format(&Value::Interval(Interval::new(0.0015, 0.002).unwrap()))
+        );
+        let hex = |n: &Value| format!("{:x}", n.as_f64() as i64);
+        assert_eq!("{ff, 10}", hex.format(&eval("{255, 16}")?));
+        Ok(())
+    }
+
+    #[test]
+    fn test_expr_limits() -> Result<()> {
+        let limits = ExprLimits {
+            max_tokens: 7,
+            max_depth: 4,
+            max_number_digits: 4,
+        };
+        let parse = |src| Expr::new(src, Mode::BigInt).with_limits(limits).parse();
+        assert_eq!(Value::Big(9999.into()), parse("(9998 + 1)")?.eval()?);
+        // the token past the limit, and nothing after it
+        let err = parse("1 + 2 + 3 + 4 + 5").unwrap_err();
+        assert!(matches!(err, ExprError::TooManyTokens { max: 7, .. }));
+        assert_eq!(Span::new(14, 3), err.span());
+        let errors = Expr::new("1 + 2 + 3 + 4 + 5 $", Mode::Standard)
+            .with_limits(limits)
+            .parse_all()
+            .unwrap_err();
+        assert!(matches!(errors[..], [ExprError::TooManyTokens { .. }]));
+        // separators and the point don't count as digits, the exponent does
+        assert!(parse("1_234").is_ok());
+        assert!(parse("12.34").is_ok());
+        assert!(matches!(
+            parse("12345"),
+            Err(ExprError::TooManyDigits { max: 4, .. })
+        ));
+        assert!(matches!(
+            parse("1.5e1000"),
+            Err(ExprError::TooManyDigits { .. })
+        ));
+        assert!(matches!(parse("((((1))))"), Err(ExprError::TooDeep { .. })));
+        // limits kept through the settings after them
+        let expr = Expr::new("12,345 + 5", Mode::Standard).with_limits(limits);
+        assert!(expr.with_locale(Locale::DecimalPoint).parse().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_backends() -> Result<()> {
+        let corpus = [
+            "1 + 2 * 3 - 4 / 2",
+            "2 ^ 3 ^ 2",
+            "-2 ^ 2 + √16",
+            "(1 + 2) * (3 - (4 - 5))",
+            "10 - 4 - 3 < 5 && !(1 == 2) || false",
+            "1 < 2 ? 3 > 4 ? 5 : 6 : 7",
+            "max(1, min(5, 3), 2) + round(2.345, 2)",
+            "x * (y + 1) % 4 // 2",
+            "~x & 255 | 1 << 3",
+        ];
+        let env: Env = [("x", 7), ("y", 3)]
+            .into_iter()
+            .map(|(name, n)| (name.into(), Value::Int(n)))
+            .collect();
+        for src in corpus {
+            let expected = Expr::new(src, Mode::Standard).parse()?;
+            for parser in PARSERS {
+                let ast = parser.parse(&mut Expr::new(src, Mode::Standard))?;
+                assert_eq!(expected.to_string(), ast.to_string(), "{}", parser.name());
+                assert_eq!(expected.eval_with(&env)?, ast.eval_with(&env)?);
+            }
+        }
+        // only pratt reads vectors and percentages too
+        for src in ["{1, 2} * 200 + 10%", "{?1, ?2}"] {
+            let expected = Expr::new(src, Mode::Standard).parse()?;
+            let ast = Pratt.parse(&mut Expr::new(src, Mode::Standard))?;
+            assert_eq!(expected.to_string(), ast.to_string());
+        }
+        // the same first error from each
+        for src in ["(1 + 2", "1 2", "1 + )", "a ? b", "max(1)", "nope(2)"] {
+            let expected = Expr::new(src, Mode::Standard).parse().unwrap_err();
+            for parser in PARSERS {
+                let err = parser
+                    .parse(&mut Expr::new(src, Mode::Standard))
+                    .unwrap_err();
+                assert_eq!(expected.to_string(), err.to_string(), "{}", parser.name());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_limits() {
+        let nested = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        let negs = format!("{}1", "-".repeat(100_000));
+        let chain = vec!["2"; 100_000].join(" ^ ");
+        let limits = ExprLimits {
+            max_tokens: 7,
+            max_depth: 3,
+            ..Default::default()
+        };
+        for parser in PARSERS {
+            for src in [&nested, &negs, &chain] {
+                let result = parser.parse(&mut Expr::new(src, Mode::Standard));
+                assert!(
+                    matches!(result, Err(ExprError::TooDeep { .. })),
+                    "{}",
+                    parser.name()
+                );
+            }
+            let parse = |src| parser.parse(&mut Expr::new(src, Mode::Standard).with_limits(limits));
+            assert!(parse("1 + (2)").is_ok(), "{}", parser.name());
+            assert!(parse("1 + 2 + 3").is_ok(), "{}", parser.name());
+            for src in ["2 ^ 3 ^ 4 ^ 5", "(((1)))", "1 + (2 + 3)"] {
+                assert!(
+                    matches!(parse(src), Err(ExprError::TooDeep { .. })),
+                    "{} {}",
+                    parser.name(),
+                    src
+                );
+            }
+            assert!(
+                matches!(
+                    parse("max(1, 2, 3, 4)"),
+                    Err(ExprError::TooManyTokens { .. })
+                ),
+                "{}",
+                parser.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_checked() -> Result<()> {
+        let env: Env = [("x".to_string(), Value::Int(6))].into();
+        let corpus = [
+            "x * (x + 1) / 2 - x // 4",
+            "2 ^ 62 * 2",
+            "1 / (x - 6)",
+            "max(x, 2.5) ^ 0.5 + √x",
+            "x > 5 ? {x, 1} * 2 : {0, 0}",
+            "true * x",
+            "y + 1",
+            "200 + 10%",
+        ];
+        for src in corpus {
+            let ast = Expr::new(src, Mode::Standard).parse()?;
+            match ast.eval_checked(&env) {
+                Err(ExprError::BackendsDisagree { .. }) => panic!("{}", src),
+                result => assert_eq!(
+                    format!("{:?}", ast.eval_with(&env)),
+                    format!("{:?}", result)
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_checked_impure() -> Result<()> {
+        // a different number on each call whatever the operand, which no
+        // backend can agree on; an operator of this expression alone, where a
+        // native function would be there for every other test too
+        let count = AtomicI64::new(0);
+        let mut ops = Operators::new();
+        ops.prefix("tick", Token::Power.precedence(), move |_, _| {
+            Ok(Value::Int(count.fetch_add(1, Ordering::Relaxed)))
+        });
+        let err = Expr::new("tick 0 * 10", Mode::Standard)
+            .with_operators(&ops)
+            .parse()?
+            .eval_checked(&Env::new())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ExprError::BackendsDisagree {
+                backend: "the vm",
+                ..
+            }
+        ));
+        assert_eq!(
+            "the vm gives `Int(10)` where the tree walk gives `Int(0)`",
+            err.to_string()
+        );
+        Ok(())
+    }
+}
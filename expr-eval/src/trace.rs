@@ -0,0 +1,120 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt::Display;
+
+use crate::{apply_function, function::call_user, lookup_function, Ast, Env, Result, Token, Value};
+
+/// One reduction made while evaluating: an operation whose operands are
+/// already worked out, and what it gave.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    /// The operation with its operands as values, like `92 + 5`.
+    pub expr: Ast,
+    pub value: Value,
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} → {}", self.expr, self.value)
+    }
+}
+
+impl Ast {
+    /// Evaluate like [`Ast::eval_with`], handing each step to `on_step` in
+    /// the order it is made.
+    ///
+    /// ```
+    /// use expr_eval::{Env, Expr, Mode};
+    ///
+    /// let ast = Expr::new("92 + 5 * 27", Mode::Standard).parse().unwrap();
+    /// let mut steps = Vec::new();
+    /// ast.eval_trace(&Env::new(), |step| steps.push(step.to_string())).unwrap();
+    /// assert_eq!(vec!["5 * 27 → 135", "92 + 135 → 227"], steps);
+    /// ```
+    pub fn eval_trace(&self, env: &Env, mut on_step: impl FnMut(Step)) -> Result<Value> {
+        trace(self, env, &mut on_step)
+    }
+}
+
+fn num(value: &Value) -> Box<Ast> {
+    Box::new(Ast::Num(value.clone()))
+}
+
+// same rules as eval_with; variables and parens are looked through without
+// a step of their own
+fn trace(ast: &Ast, env: &Env, on_step: &mut dyn FnMut(Step)) -> Result<Value> {
+    let (expr, value) = match ast {
+        Ast::Num(_) | Ast::Var { .. } => return ast.eval_with(env),
+        Ast::Paren(inner) => return trace(inner, env, on_step),
+        Ast::Unary { op, operand, span } => {
+            let operand = trace(operand, env, on_step)?;
+            let value = op.compute_unary(operand.clone(), *span)?;
+            let expr = Ast::Unary {
+                op: op.clone(),
+                operand: num(&operand),
+                span: *span,
+            };
+            (expr, value)
+        }
+        // a right side left out by the left is shown as written
+        Ast::BinaryOp { op, lhs, rhs, span } if op.is_logical() => {
+            let l = trace(lhs, env, on_step)?.expect_bool(*span)?;
+            let (rhs, value) = if l == (*op == Token::Or) {
+                (rhs.clone(), l)
+            } else {
+                let r = trace(rhs, env, on_step)?.expect_bool(*span)?;
+                (num(&Value::Bool(r)), r)
+            };
+            let expr = Ast::BinaryOp {
+                op: op.clone(),
+                lhs: num(&Value::Bool(l)),
+                rhs,
+                span: *span,
+            };
+            (expr, Value::Bool(value))
+        }
+        Ast::BinaryOp { op, lhs, rhs, span } => {
+            let (l, r) = (trace(lhs, env, on_step)?, trace(rhs, env, on_step)?);
+            let value = op.compute(l.clone(), r.clone(), *span)?;
+            let expr = Ast::BinaryOp {
+                op: op.clone(),
+                lhs: num(&l),
+                rhs: num(&r),
+                span: *span,
+            };
+            (expr, value)
+        }
+        Ast::Call { name, args, span } => {
+            let args = args.iter().map(|arg| trace(arg, env, on_step));
+            let args = args.collect::<Result<Vec<_>>>()?;
+            // the steps inside a user function aren't traced
+            let value = match lookup_function(name) {
+                Some(func) => apply_function(func, args.clone(), *span)?,
+                None => call_user(name, args.clone(), env, *span)?,
+            };
+            let expr = Ast::Call {
+                name: name.clone(),
+                args: args.iter().cloned().map(Ast::Num).collect(),
+                span: *span,
+            };
+            (expr, value)
+        }
+        // the branch taken makes its own steps
+        Ast::Cond {
+            cond,
+            then,
+            otherwise,
+            span,
+        } => {
+            return if trace(cond, env, on_step)?.expect_bool(*span)? {
+                trace(then, env, on_step)
+            } else {
+                trace(otherwise, env, on_step)
+            };
+        }
+    };
+    on_step(Step {
+        expr,
+        value: value.clone(),
+    });
+    Ok(value)
+}
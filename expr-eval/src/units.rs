@@ -0,0 +1,237 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    fmt::Display,
+    ops::{Div, Mul, Neg},
+};
+
+#[cfg(not(feature = "std"))]
+#[allow(unused_imports)]
+use num_traits::Float;
+
+use crate::currency::{currency, currency_index};
+
+// length, mass, time, money
+const DIMENSIONS: usize = 4;
+const MONEY: usize = 3;
+
+// name, dimension, size in the base unit of that dimension
+const UNITS: &[(&str, usize, f64)] = &[
+    ("m", 0, 1.0),
+    ("km", 0, 1000.0),
+    ("cm", 0, 0.01),
+    ("mm", 0, 0.001),
+    ("g", 1, 0.001),
+    ("kg", 1, 1.0),
+    ("s", 2, 1.0),
+    ("min", 2, 60.0),
+    ("h", 2, 3600.0),
+];
+
+// UNITS first, then the currencies of the loaded rates table
+fn unit_index(name: &str) -> Option<usize> {
+    (UNITS.iter().position(|(n, ..)| *n == name))
+        .or_else(|| currency_index(name).map(|i| UNITS.len() + i))
+}
+
+fn unit(u: usize) -> (&'static str, usize, f64) {
+    match UNITS.get(u) {
+        Some(&unit) => unit,
+        None => {
+            let (name, size) = currency(u - UNITS.len());
+            (name, MONEY, size)
+        }
+    }
+}
+
+pub(crate) fn is_unit(name: &str) -> bool {
+    unit_index(name).is_some()
+}
+
+// one of UNITS, a name no currency can take
+#[cfg(feature = "std")]
+pub(crate) fn is_fixed_unit(name: &str) -> bool {
+    UNITS.iter().any(|(n, ..)| *n == name)
+}
+
+// a number with units, 45 km/h is 45 with km^1 h^-1; a dimension has at most
+// one unit, others of it are converted to the one already there
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    value: f64,
+    // (UNITS index, or past them a currency, exponent), never a zero exponent
+    units: Vec<(usize, i32)>,
+}
+
+impl Quantity {
+    // value in the product of the units raised to their exponents, None for an
+    // unknown unit
+    pub fn new(value: f64, units: &[(&str, i32)]) -> Option<Self> {
+        let mut q = Self::plain(value);
+        for &(name, exp) in units {
+            let unit = Self {
+                value: 1.0,
+                units: vec![(unit_index(name)?, 1)],
+            };
+            q = q * unit.powi(exp);
+        }
+        Some(q)
+    }
+
+    // a number without units
+    pub fn plain(value: f64) -> Self {
+        Self {
+            value,
+            units: Vec::new(),
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    pub fn is_plain(&self) -> bool {
+        self.units.is_empty()
+    }
+
+    // unit names with their exponents
+    pub fn units(&self) -> impl Iterator<Item = (&'static str, i32)> + '_ {
+        self.units.iter().map(|&(u, exp)| (unit(u).0, exp))
+    }
+
+    // written like it is read back: m^2, km/h, m/s^2, or s^-1 without a numerator;
+    // 1 for no units at all
+    pub fn unit(&self) -> String {
+        let power = |u: usize, exp: i32| match exp {
+            1 => unit(u).0.to_string(),
+            _ => format!("{}^{}", unit(u).0, exp),
+        };
+        let mut text: Vec<String> = (self.units.iter())
+            .filter(|(_, exp)| *exp > 0)
+            .map(|&(u, exp)| power(u, exp))
+            .collect();
+        for &(u, exp) in self.units.iter().filter(|(_, exp)| *exp < 0) {
+            if text.is_empty() {
+                text.push(power(u, exp));
+            } else {
+                text.push(format!("/{}", power(u, -exp)));
+            }
+        }
+        match text.join("*").replace("*/", "/") {
+            unit if unit.is_empty() => "1".to_string(),
+            unit => unit,
+        }
+    }
+
+    fn dimensions(&self) -> [i32; DIMENSIONS] {
+        let mut dims = [0; DIMENSIONS];
+        for &(u, exp) in &self.units {
+            dims[unit(u).1] += exp;
+        }
+        dims
+    }
+
+    // other's value in self's units, None unless both measure the same thing
+    fn convert(&self, other: &Self) -> Option<f64> {
+        if self.dimensions() != other.dimensions() {
+            return None;
+        }
+        let factor = other.units.iter().fold(1.0, |acc, &(u, exp)| {
+            let (to, _) = self
+                .units
+                .iter()
+                .find(|(v, _)| unit(*v).1 == unit(u).1)
+                .unwrap();
+            acc * (unit(u).2 / unit(*to).2).powi(exp)
+        });
+        Some(other.value * factor)
+    }
+
+    // the same amount with its money in the currency code, None when the rates
+    // table has no such currency; no money in the units leaves it as it is
+    pub fn to_currency(&self, code: &str) -> Option<Self> {
+        let to = currency_index(code)? + UNITS.len();
+        let mut target = self.clone();
+        for (u, _) in target.units.iter_mut() {
+            if unit(*u).1 == MONEY {
+                *u = to;
+            }
+        }
+        Some(Self {
+            value: target.convert(self)?,
+            units: target.units,
+        })
+    }
+
+    // sum in self's units, None for different dimensions like m + s
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(Self {
+            value: self.value + self.convert(other)?,
+            units: self.units.clone(),
+        })
+    }
+
+    pub fn checked_sub(&self, other: &Self) -> Option<Self> {
+        self.checked_add(&-other.clone())
+    }
+
+    pub fn powi(self, n: i32) -> Self {
+        Self {
+            value: self.value.powi(n),
+            units: (self.units.into_iter())
+                .map(|(u, exp)| (u, exp * n))
+                .filter(|(_, exp)| *exp != 0)
+                .collect(),
+        }
+    }
+}
+
+impl Neg for Quantity {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            value: -self.value,
+            units: self.units,
+        }
+    }
+}
+
+impl Mul for Quantity {
+    type Output = Self;
+
+    fn mul(mut self, other: Self) -> Self {
+        self.value *= other.value;
+        for (u, exp) in other.units {
+            let dim = unit(u).1;
+            match self.units.iter().position(|(v, _)| unit(*v).1 == dim) {
+                Some(i) => {
+                    let (to, acc) = &mut self.units[i];
+                    self.value *= (unit(u).2 / unit(*to).2).powi(exp);
+                    *acc += exp;
+                }
+                None => self.units.push((u, exp)),
+            }
+        }
+        self.units.retain(|(_, exp)| *exp != 0);
+        self
+    }
+}
+
+impl Div for Quantity {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        self * other.powi(-1)
+    }
+}
+
+impl Display for Quantity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} {}", self.value, self.unit())
+    }
+}
@@ -0,0 +1,128 @@
+use alloc::{format, string::ToString, vec::Vec};
+
+use crate::{ExprError, Result, Span, Token, Value};
+
+// {a, b, ...}, the elements already checked to be numbers or vectors
+pub(crate) fn vector(args: Vec<Value>, _span: Span) -> Result<Value> {
+    Ok(Value::Vector(args))
+}
+
+fn shape_mismatch(expected: usize, found: &[Value], span: Span) -> ExprError {
+    ExprError::Type {
+        expected: format!("a vector of length {}", expected),
+        found: Value::Vector(found.to_vec()).to_string(),
+        operation: None,
+        span,
+    }
+}
+
+// element by element, a plain number going with every element; * of two
+// vectors is the matrix product instead
+pub(crate) fn compute_vector(op: &Token, l: Value, r: Value, span: Span) -> Result<Value> {
+    let elems: Result<Vec<Value>> = match (l, r) {
+        (Value::Vector(l), Value::Vector(r)) if *op == Token::Multiply => {
+            return matmul(l, r, span)
+        }
+        (Value::Vector(l), Value::Vector(r)) => {
+            if l.len() != r.len() {
+                return Err(shape_mismatch(l.len(), &r, span));
+            }
+            l.into_iter()
+                .zip(r)
+                .map(|(l, r)| op.compute(l, r, span))
+                .collect()
+        }
+        (Value::Vector(l), r) => l
+            .into_iter()
+            .map(|l| op.compute(l, r.clone(), span))
+            .collect(),
+        (l, Value::Vector(r)) => r
+            .into_iter()
+            .map(|r| op.compute(l.clone(), r, span))
+            .collect(),
+        _ => unreachable!("one side is a vector"),
+    };
+    elems.map(Value::Vector)
+}
+
+// rows of a matrix and whether it was a plain vector, which on the left is one
+// row and on the right one column
+fn rows(v: Vec<Value>, column: bool) -> (Vec<Vec<Value>>, bool) {
+    if v.iter().all(|e| matches!(e, Value::Vector(_))) {
+        let rows = v
+            .into_iter()
+            .map(|e| match e {
+                Value::Vector(row) => row,
+                _ => unreachable!(),
+            })
+            .collect();
+        return (rows, false);
+    }
+    match column {
+        true => (v.into_iter().map(|e| [e].into()).collect(), true),
+        false => ([v].into(), true),
+    }
+}
+
+// rows times columns: vector * vector is the dot product, a matrix times a
+// vector a vector and two matrices a matrix
+fn matmul(l: Vec<Value>, r: Vec<Value>, span: Span) -> Result<Value> {
+    let (l, l_flat) = rows(l, false);
+    let (r, r_flat) = rows(r, true);
+    let inner = r.len();
+    let width = r[0].len();
+    for row in l.iter() {
+        if row.len() != inner {
+            return Err(shape_mismatch(inner, row, span));
+        }
+    }
+    for row in r.iter() {
+        if row.len() != width {
+            return Err(shape_mismatch(width, row, span));
+        }
+    }
+    let mut product = Vec::with_capacity(l.len());
+    for row in &l {
+        let mut out = Vec::with_capacity(width);
+        for j in 0..width {
+            let mut terms = row
+                .iter()
+                .zip(&r)
+                .map(|(x, column)| Token::Multiply.compute(x.clone(), column[j].clone(), span));
+            let mut sum = terms.next().expect("a row is never empty")?;
+            for term in terms {
+                sum = Token::Plus.compute(sum, term?, span)?;
+            }
+            out.push(sum);
+        }
+        product.push(out);
+    }
+    // a vector side gives back a vector, or a number from both
+    let mut product: Vec<Value> = match r_flat {
+        true => product.into_iter().map(|mut row| row.remove(0)).collect(),
+        false => product.into_iter().map(Value::Vector).collect(),
+    };
+    Ok(match l_flat {
+        true => product.remove(0),
+        false => Value::Vector(product),
+    })
+}
+
+// equal when they have the same length and every element is equal
+pub(crate) fn vectors_equal(l: &Value, r: &Value, span: Span) -> Result<bool> {
+    match (l, r) {
+        (Value::Vector(l), Value::Vector(r)) => {
+            if l.len() != r.len() {
+                return Ok(false);
+            }
+            for (l, r) in l.iter().zip(r) {
+                if !vectors_equal(l, r, span)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        (Value::Vector(_), _) | (_, Value::Vector(_)) => Ok(false),
+        _ => Ok(Token::Equal.compare(l, r, span)? == Value::Bool(true)),
+    }
+}
@@ -0,0 +1,173 @@
+use alloc::vec::Vec;
+
+use crate::{Ast, Span, Token, Value};
+
+/// Callbacks for [`Ast::walk`], one per kind of node, each with how many
+/// nodes up the tree it sits. Parentheses are walked through without a
+/// callback of their own. Every method does nothing by default, so a visitor
+/// only writes the ones it cares about and keeps compiling as kinds of nodes
+/// are added.
+pub trait Visitor {
+    fn num(&mut self, _value: &Value, _depth: usize) {}
+    fn var(&mut self, _name: &str, _span: Span, _depth: usize) {}
+    // a unary or binary operator, `arity` being its count of operands
+    fn operator(&mut self, _op: &Token, _arity: usize, _span: Span, _depth: usize) {}
+    fn call(&mut self, _name: &str, _arity: usize, _span: Span, _depth: usize) {}
+    fn cond(&mut self, _span: Span, _depth: usize) {}
+}
+
+/// Combines the results of the children of a node into the result of the
+/// node, for [`Ast::fold`]. Parentheses give the result of what they hold
+/// unless `paren` says otherwise.
+pub trait Fold {
+    type Output;
+
+    fn num(&mut self, value: &Value) -> Self::Output;
+    fn var(&mut self, name: &str, span: Span) -> Self::Output;
+    fn unary(&mut self, op: &Token, operand: Self::Output, span: Span) -> Self::Output;
+    fn binary(
+        &mut self,
+        op: &Token,
+        lhs: Self::Output,
+        rhs: Self::Output,
+        span: Span,
+    ) -> Self::Output;
+    fn call(&mut self, name: &str, args: Vec<Self::Output>, span: Span) -> Self::Output;
+    fn cond(
+        &mut self,
+        cond: Self::Output,
+        then: Self::Output,
+        otherwise: Self::Output,
+        span: Span,
+    ) -> Self::Output;
+
+    fn paren(&mut self, inner: Self::Output) -> Self::Output {
+        inner
+    }
+}
+
+impl Ast {
+    /// Hand every node to `visitor`, each before its children and those
+    /// left to right, for analyses that don't match on the nodes themselves.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Mode, Span, Token, Visitor};
+    ///
+    /// // count of operators, and how deep the tree goes
+    /// #[derive(Default)]
+    /// struct Shape {
+    ///     operators: usize,
+    ///     depth: usize,
+    /// }
+    ///
+    /// impl Visitor for Shape {
+    ///     fn operator(&mut self, _: &Token, _: usize, _: Span, depth: usize) {
+    ///         self.operators += 1;
+    ///         self.depth = self.depth.max(depth);
+    ///     }
+    /// }
+    ///
+    /// let ast = Expr::new("-(1 + 2) * x", Mode::Standard).parse().unwrap();
+    /// let mut shape = Shape::default();
+    /// ast.walk(&mut shape);
+    /// assert_eq!((3, 2), (shape.operators, shape.depth));
+    /// ```
+    pub fn walk(&self, visitor: &mut impl Visitor) {
+        self.walk_at(visitor, 0);
+    }
+
+    fn walk_at(&self, visitor: &mut impl Visitor, depth: usize) {
+        match self {
+            Ast::Num(n) => visitor.num(n, depth),
+            Ast::Var { name, span } => visitor.var(name, *span, depth),
+            Ast::Unary { op, operand, span } => {
+                visitor.operator(op, 1, *span, depth);
+                operand.walk_at(visitor, depth + 1);
+            }
+            Ast::BinaryOp { op, lhs, rhs, span } => {
+                visitor.operator(op, 2, *span, depth);
+                lhs.walk_at(visitor, depth + 1);
+                rhs.walk_at(visitor, depth + 1);
+            }
+            Ast::Paren(inner) => inner.walk_at(visitor, depth),
+            Ast::Call { name, args, span } => {
+                visitor.call(name, args.len(), *span, depth);
+                args.iter().for_each(|arg| arg.walk_at(visitor, depth + 1));
+            }
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                span,
+            } => {
+                visitor.cond(*span, depth);
+                cond.walk_at(visitor, depth + 1);
+                then.walk_at(visitor, depth + 1);
+                otherwise.walk_at(visitor, depth + 1);
+            }
+        }
+    }
+
+    /// Work the tree out bottom up with `folder`, every node from the
+    /// results of its children.
+    ///
+    /// ```
+    /// use expr_eval::{Expr, Fold, Mode, Span, Token, Value};
+    ///
+    /// // whether the expression reads no variables
+    /// struct IsConstant;
+    ///
+    /// impl Fold for IsConstant {
+    ///     type Output = bool;
+    ///
+    ///     fn num(&mut self, _: &Value) -> bool { true }
+    ///     fn var(&mut self, _: &str, _: Span) -> bool { false }
+    ///     fn unary(&mut self, _: &Token, operand: bool, _: Span) -> bool { operand }
+    ///     fn binary(&mut self, _: &Token, lhs: bool, rhs: bool, _: Span) -> bool { lhs && rhs }
+    ///     fn call(&mut self, name: &str, args: Vec<bool>, _: Span) -> bool {
+    ///         name != "rand" && args.into_iter().all(|arg| arg)
+    ///     }
+    ///     fn cond(&mut self, cond: bool, then: bool, otherwise: bool, _: Span) -> bool {
+    ///         cond && then && otherwise
+    ///     }
+    /// }
+    ///
+    /// let parse = |src| Expr::new(src, Mode::Standard).parse().unwrap();
+    /// assert!(parse("max(1, 2) * (3 + 4)").fold(&mut IsConstant));
+    /// assert!(!parse("2 * x").fold(&mut IsConstant));
+    /// ```
+    pub fn fold<F: Fold>(&self, folder: &mut F) -> F::Output {
+        match self {
+            Ast::Num(n) => folder.num(n),
+            Ast::Var { name, span } => folder.var(name, *span),
+            Ast::Unary { op, operand, span } => {
+                let operand = operand.fold(folder);
+                folder.unary(op, operand, *span)
+            }
+            Ast::BinaryOp { op, lhs, rhs, span } => {
+                let lhs = lhs.fold(folder);
+                let rhs = rhs.fold(folder);
+                folder.binary(op, lhs, rhs, *span)
+            }
+            Ast::Paren(inner) => {
+                let inner = inner.fold(folder);
+                folder.paren(inner)
+            }
+            Ast::Call { name, args, span } => {
+                let args = args.iter().map(|arg| arg.fold(folder)).collect();
+                folder.call(name, args, *span)
+            }
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                span,
+            } => {
+                let cond = cond.fold(folder);
+                let then = then.fold(folder);
+                let otherwise = otherwise.fold(folder);
+                folder.cond(cond, then, otherwise, *span)
+            }
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Display;
+
+use crate::{
+    apply_function, function::call_user, function_at, function_index, function_name, placeholders,
+    Ast, Env, ExprError, Result, Span, Token, Value,
+};
+
+// one stack machine instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instr {
+    // push constants[i]
+    Const(usize),
+    // push the variable names[i] from the environment
+    Load(usize),
+    Neg,
+    Not,
+    BitNot,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    FloorDiv,
+    Mod,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    // apply function i, a builtin or a native one, to the top n values, the
+    // last argument on top
+    Call(usize, usize),
+    // apply the user function the environment holds under names[i] to the
+    // top n values
+    CallUser(usize, usize),
+    // apply the registered operator operators[i] to the top one or two values
+    Custom(usize),
+    // keep the boolean on top and jump to the target when it is false, else
+    // drop it and go on; && uses it to skip its right side
+    JumpIfFalse(usize),
+    // same when it is true, for ||
+    JumpIfTrue(usize),
+    // fail unless the top of the stack is a boolean
+    CheckBool,
+    // pop the boolean on top and jump to the target when it is false
+    BranchIfFalse(usize),
+    Jump(usize),
+}
+
+impl Instr {
+    // operator token whose compute implements the instruction
+    fn token(self) -> Option<Token> {
+        match self {
+            Instr::Neg | Instr::Sub => Some(Token::Minus),
+            Instr::Add => Some(Token::Plus),
+            Instr::Mul => Some(Token::Multiply),
+            Instr::Div => Some(Token::Divide),
+            Instr::Pow => Some(Token::Power),
+            Instr::FloorDiv => Some(Token::FloorDiv),
+            Instr::Mod => Some(Token::Mod),
+            Instr::Eq => Some(Token::Equal),
+            Instr::Ne => Some(Token::NotEqual),
+            Instr::Lt => Some(Token::Less),
+            Instr::Le => Some(Token::LessEqual),
+            Instr::Gt => Some(Token::Greater),
+            Instr::Ge => Some(Token::GreaterEqual),
+            Instr::Not => Some(Token::Not),
+            Instr::BitNot => Some(Token::BitNot),
+            Instr::BitAnd => Some(Token::BitAnd),
+            Instr::BitOr => Some(Token::BitOr),
+            Instr::BitXor => Some(Token::BitXor),
+            Instr::Shl => Some(Token::ShiftLeft),
+            Instr::Shr => Some(Token::ShiftRight),
+            _ => None,
+        }
+    }
+
+    fn binary(op: &Token) -> Option<Instr> {
+        match op {
+            Token::Plus => Some(Instr::Add),
+            Token::Minus => Some(Instr::Sub),
+            Token::Multiply => Some(Instr::Mul),
+            Token::Divide => Some(Instr::Div),
+            Token::Power => Some(Instr::Pow),
+            Token::FloorDiv => Some(Instr::FloorDiv),
+            Token::Mod => Some(Instr::Mod),
+            Token::Equal => Some(Instr::Eq),
+            Token::NotEqual => Some(Instr::Ne),
+            Token::Less => Some(Instr::Lt),
+            Token::LessEqual => Some(Instr::Le),
+            Token::Greater => Some(Instr::Gt),
+            Token::GreaterEqual => Some(Instr::Ge),
+            Token::BitAnd => Some(Instr::BitAnd),
+            Token::BitOr => Some(Instr::BitOr),
+            Token::BitXor => Some(Instr::BitXor),
+            Token::ShiftLeft => Some(Instr::Shl),
+            Token::ShiftRight => Some(Instr::Shr),
+            _ => None,
+        }
+    }
+}
+
+// an expression flattened into postfix bytecode, parse once and evaluate many
+// times without walking the tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledExpr {
+    pub(crate) code: Vec<Instr>,
+    // spans[i] is where code[i] came from, for error reporting
+    pub(crate) spans: Vec<Span>,
+    pub(crate) constants: Vec<Value>,
+    pub(crate) names: Vec<String>,
+    // Token::Custom operators the code applies
+    pub(crate) operators: Vec<Token>,
+    // deepest the stack gets, so eval allocates once
+    pub(crate) max_stack: usize,
+}
+
+impl CompiledExpr {
+    pub fn new(ast: &Ast) -> Self {
+        let mut compiled = Self {
+            code: Vec::new(),
+            spans: Vec::new(),
+            constants: Vec::new(),
+            names: Vec::new(),
+            operators: Vec::new(),
+            max_stack: 0,
+        };
+        compiled.emit_tree(ast, 0);
+        compiled
+    }
+
+    fn emit(&mut self, instr: Instr, span: Span) {
+        self.code.push(instr);
+        self.spans.push(span);
+    }
+
+    // instruction applying a registered operator
+    fn operator(&mut self, op: &Token) -> Instr {
+        self.operators.push(op.clone());
+        Instr::Custom(self.operators.len() - 1)
+    }
+
+    // children first, then the node itself; depth is the stack size before
+    fn emit_tree(&mut self, ast: &Ast, depth: usize) {
+        self.max_stack = self.max_stack.max(depth + 1);
+        match ast {
+            Ast::Num(n) => {
+                self.constants.push(n.clone());
+                self.emit(Instr::Const(self.constants.len() - 1), Span::default());
+            }
+            Ast::Var { name, span } => {
+                let index = self.name(name);
+                self.emit(Instr::Load(index), *span);
+            }
+            Ast::Unary { op, operand, span } => {
+                self.emit_tree(operand, depth);
+                let instr = match op {
+                    Token::Not => Instr::Not,
+                    Token::BitNot => Instr::BitNot,
+                    Token::Custom(_) => self.operator(op),
+                    _ => Instr::Neg,
+                };
+                self.emit(instr, *span);
+            }
+            Ast::BinaryOp { .. } => self.emit_chain(ast, depth),
+            Ast::Paren(inner) => self.emit_tree(inner, depth),
+            // cond, branch over then to otherwise, or run then and jump past
+            Ast::Cond {
+                cond,
+                then,
+                otherwise,
+                span,
+            } => {
+                self.emit_tree(cond, depth);
+                let branch = self.code.len();
+                self.emit(Instr::BranchIfFalse(0), *span);
+                self.emit_tree(then, depth);
+                let jump = self.code.len();
+                self.emit(Instr::Jump(0), *span);
+                self.code[branch] = Instr::BranchIfFalse(self.code.len());
+                self.emit_tree(otherwise, depth);
+                self.code[jump] = Instr::Jump(self.code.len());
+            }
+            Ast::Call { name, args, span } => {
+                for (i, arg) in args.iter().enumerate() {
+                    self.emit_tree(arg, depth + i);
+                }
+                // the parser only builds calls to builtins and user functions
+                let instr = match function_index(name) {
+                    Some(index) => Instr::Call(index, args.len()),
+                    None => Instr::CallUser(self.name(name), args.len()),
+                };
+                self.emit(instr, *span);
+            }
+        }
+    }
+
+    // a binary operation and those of its chain one after the other, see
+    // Ast::chain
+    fn emit_chain(&mut self, ast: &Ast, depth: usize) {
+        let (bottom, chain) = ast.chain();
+        self.emit_tree(bottom, depth);
+        for (_, op, rhs, span) in chain {
+            // the left value decides on its own or is dropped for the right one
+            if op.is_logical() {
+                let jump = self.code.len();
+                self.emit(Instr::JumpIfFalse(0), span);
+                self.emit_tree(rhs, depth);
+                self.emit(Instr::CheckBool, span);
+                let end = self.code.len();
+                self.code[jump] = if *op == Token::Or {
+                    Instr::JumpIfTrue(end)
+                } else {
+                    Instr::JumpIfFalse(end)
+                };
+                continue;
+            }
+            self.emit_tree(rhs, depth + 1);
+            let instr = match op {
+                Token::Custom(_) => self.operator(op),
+                _ => Instr::binary(op).expect("binary node holds an operator"),
+            };
+            self.emit(instr, span);
+        }
+    }
+
+    // index of name in names, added the first time it is used
+    fn name(&mut self, name: &str) -> usize {
+        match self.names.iter().position(|n| n == name) {
+            Some(index) => index,
+            None => {
+                self.names.push(name.into());
+                self.names.len() - 1
+            }
+        }
+    }
+
+    // evaluate with no variables bound
+    pub fn eval(&self) -> Result<Value> {
+        self.eval_with(&Env::new())
+    }
+
+    // evaluate with the placeholders `?1`, `?2`, ... bound to args in order
+    pub fn eval_args(&self, args: &[Value]) -> Result<Value> {
+        self.eval_with(&placeholders(args))
+    }
+
+    pub fn eval_with(&self, env: &Env) -> Result<Value> {
+        self.run(env, |_, _, _| {})
+    }
+
+    /// Evaluate like [`CompiledExpr::eval_with`], handing `on_step` each
+    /// instruction as it runs with the stack it leaves, to watch the stack
+    /// machine at work.
+    ///
+    /// ```
+    /// use expr_eval::{CompiledExpr, Env, Expr, Mode};
+    ///
+    /// let ast = Expr::new("2 + 3 * 4", Mode::Standard).parse().unwrap();
+    /// let mut steps = Vec::new();
+    /// CompiledExpr::new(&ast)
+    ///     .eval_debug(&Env::new(), |step| steps.push(step.to_string()))
+    ///     .unwrap();
+    /// assert_eq!("  1  const 3           [2, 3]", steps[1]);
+    /// assert_eq!("  4  add               [14]", steps[4]);
+    /// ```
+    pub fn eval_debug(&self, env: &Env, mut on_step: impl FnMut(&VmStep)) -> Result<Value> {
+        self.run(env, |pc, instr, stack| {
+            on_step(&VmStep {
+                pc,
+                instr: self.describe(instr),
+                stack: stack.to_vec(),
+            })
+        })
+    }
+
+    // the loop of the machine, telling observe about each instruction run
+    fn run(&self, env: &Env, mut observe: impl FnMut(usize, Instr, &[Value])) -> Result<Value> {
+        let mut stack: Vec<Value> = Vec::with_capacity(self.max_stack);
+        let mut pc = 0;
+        while let Some(&instr) = self.code.get(pc) {
+            let span = self.spans[pc];
+            let at = pc;
+            pc += 1;
+            let value = match instr {
+                Instr::Const(i) => Some(self.constants[i].clone()),
+                Instr::Load(i) => Some(env.get(&self.names[i]).cloned().ok_or_else(|| {
+                    ExprError::UnknownVariable {
+                        name: self.names[i].clone(),
+                        span,
+                    }
+                })?),
+                Instr::Neg | Instr::Not | Instr::BitNot => {
+                    let operand = stack.pop().unwrap();
+                    Some(instr.token().unwrap().compute_unary(operand, span)?)
+                }
+                Instr::Custom(i) if self.operators[i].is_prefix() => {
+                    let operand = stack.pop().unwrap();
+                    Some(self.operators[i].compute_unary(operand, span)?)
+                }
+                Instr::Custom(i) => {
+                    let rhs = stack.pop().unwrap();
+                    let lhs = stack.pop().unwrap();
+                    Some(self.operators[i].compute(lhs, rhs, span)?)
+                }
+                Instr::Call(i, n) => {
+                    let args = stack.split_off(stack.len() - n);
+                    Some(apply_function(function_at(i), args, span)?)
+                }
+                Instr::CallUser(i, n) => {
+                    let args = stack.split_off(stack.len() - n);
+                    Some(call_user(&self.names[i], args, env, span)?)
+                }
+                Instr::JumpIfFalse(target) | Instr::JumpIfTrue(target) => {
+                    let cond = stack.last().unwrap().expect_bool(span)?;
+                    if cond == matches!(instr, Instr::JumpIfTrue(_)) {
+                        pc = target;
+                    } else {
+                        stack.pop();
+                    }
+                    None
+                }
+                Instr::BranchIfFalse(target) => {
+                    if !stack.pop().unwrap().expect_bool(span)? {
+                        pc = target;
+                    }
+                    None
+                }
+                Instr::Jump(target) => {
+                    pc = target;
+                    None
+                }
+                Instr::CheckBool => {
+                    stack.last().unwrap().expect_bool(span)?;
+                    None
+                }
+                _ => {
+                    let rhs = stack.pop().unwrap();
+                    let lhs = stack.pop().unwrap();
+                    Some(instr.token().unwrap().compute(lhs, rhs, span)?)
+                }
+            };
+            stack.extend(value);
+            observe(at, instr, &stack);
+        }
+        Ok(stack.pop().expect("compiled expression leaves its value"))
+    }
+
+    // an instruction the way a listing shows it, with its operand by name
+    fn describe(&self, instr: Instr) -> String {
+        match instr {
+            Instr::Const(i) => format!("const {}", self.constants[i]),
+            Instr::Load(i) => format!("load {}", self.names[i]),
+            Instr::Call(i, n) => format!("call {} {}", function_name(i), n),
+            Instr::CallUser(i, n) => format!("call {} {}", self.names[i], n),
+            Instr::Custom(i) => format!("op {}", self.operators[i]),
+            Instr::JumpIfFalse(target) => format!("jump_if_false {}", target),
+            Instr::JumpIfTrue(target) => format!("jump_if_true {}", target),
+            Instr::BranchIfFalse(target) => format!("branch_if_false {}", target),
+            Instr::Jump(target) => format!("jump {}", target),
+            Instr::CheckBool => "check_bool".into(),
+            Instr::BitNot => "bit_not".into(),
+            Instr::BitAnd => "bit_and".into(),
+            Instr::BitOr => "bit_or".into(),
+            Instr::BitXor => "bit_xor".into(),
+            Instr::FloorDiv => "floor_div".into(),
+            _ => format!("{:?}", instr).to_lowercase(),
+        }
+    }
+}
+
+/// One instruction run by [`CompiledExpr::eval_debug`]: where it is in the
+/// code, what it is and the stack it left, the top last.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VmStep {
+    pub pc: usize,
+    pub instr: String,
+    pub stack: Vec<Value>,
+}
+
+impl Display for VmStep {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let stack: Vec<String> = self.stack.iter().map(|v| v.to_string()).collect();
+        write!(
+            f,
+            "{:>3}  {:<16}  [{}]",
+            self.pc,
+            self.instr,
+            stack.join(", ")
+        )
+    }
+}
@@ -0,0 +1,14 @@
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{caret, Env, Expr, Mode};
+
+// entry point of the browser playground: run the statements and give the last
+// value, or the error under a caret line pointing into the source
+#[wasm_bindgen]
+pub fn evaluate(src: &str) -> Result<String, String> {
+    Expr::new(src, Mode::Standard)
+        .parse_script()
+        .and_then(|script| script.eval(&mut Env::new()))
+        .map(|value| value.to_string())
+        .map_err(|err| format!("{}\n{}", caret(src, err.span()), err))
+}